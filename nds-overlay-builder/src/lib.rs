@@ -0,0 +1,275 @@
+//! Builds the overlay table (`y9.bin`) and the file allocation table entries of the
+//! overlays of an ARM9 program, to give to `ndstool`.
+//!
+//! This runs on the host, e.g. in a build script, once the linker laid out the
+//! `.overlayN.*` sections placed by `#[overlay(N)]`. `nds_rs::overlay` reads the tables
+//! back on the console.
+
+#![no_std]
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Size in bytes of an [`OverlayEntry`] in the overlay table
+pub const OVERLAY_ENTRY_SIZE: usize = 32;
+
+/// Size in bytes of a [`FatEntry`] in the file allocation table
+pub const FAT_ENTRY_SIZE: usize = 8;
+
+/// An entry of the overlay table, describing where an overlay goes in RAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OverlayEntry {
+    pub id: u32,
+    /// Where the overlay is loaded
+    pub ram_address: u32,
+    /// Size of the code and data, read from the ROM
+    pub ram_size: u32,
+    /// Size of the zeroed data, after the code and data
+    pub bss_size: u32,
+    /// Start of the array of static initializers, called after loading
+    pub sinit_start: u32,
+    /// End of the array of static initializers
+    pub sinit_end: u32,
+    /// File of the overlay in the [file allocation table](FatEntry)
+    pub file_id: u32,
+    /// Compressed size in the lower 24 bits, and the compressed flag in bit 24
+    pub flags: u32,
+}
+impl OverlayEntry {
+    pub fn to_bytes(&self) -> [u8; OVERLAY_ENTRY_SIZE] {
+        let words = [
+            self.id,
+            self.ram_address,
+            self.ram_size,
+            self.bss_size,
+            self.sinit_start,
+            self.sinit_end,
+            self.file_id,
+            self.flags,
+        ];
+        let mut bytes = [0; OVERLAY_ENTRY_SIZE];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Addresses used by the overlay in RAM, BSS included
+    pub fn ram_range(&self) -> Range<u32> {
+        let end = self
+            .ram_address
+            .saturating_add(self.ram_size)
+            .saturating_add(self.bss_size);
+        self.ram_address..end
+    }
+
+    /// Checks the entry the way the loader of `nds_rs::overlay` does
+    fn is_valid(&self) -> bool {
+        let range = self.ram_range();
+        let fits = self
+            .ram_address
+            .checked_add(self.ram_size)
+            .and_then(|end| end.checked_add(self.bss_size))
+            .is_some();
+        let sinit_aligned = (self.sinit_start | self.sinit_end).is_multiple_of(4);
+        let sinit_inside = self.sinit_start == self.sinit_end
+            || (range.contains(&self.sinit_start) && self.sinit_end <= range.end);
+        fits && sinit_aligned && self.sinit_start <= self.sinit_end && sinit_inside
+    }
+}
+
+/// An entry of the file allocation table, the location of a file in the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FatEntry {
+    pub start: u32,
+    pub end: u32,
+}
+impl FatEntry {
+    pub fn to_bytes(&self) -> [u8; FAT_ENTRY_SIZE] {
+        let mut bytes = [0; FAT_ENTRY_SIZE];
+        bytes[..4].copy_from_slice(&self.start.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.end.to_le_bytes());
+        bytes
+    }
+}
+
+/// Errors when building the tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    /// The entry of this overlay isn't consistent
+    InvalidEntry(u32),
+    /// These two overlays would be loaded in the same addresses
+    /// but they're in different regions
+    Overlapping(u32, u32),
+}
+
+/// An overlay as laid out by the linker, to build the tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlaySpec {
+    /// Overlays with the same region are loaded in the same addresses,
+    /// only one of them can be loaded at a time
+    pub region: u32,
+    pub ram_address: u32,
+    pub ram_size: u32,
+    pub bss_size: u32,
+    /// Addresses of the static initializers array
+    pub sinit: Range<u32>,
+}
+
+/// Builds the overlay table for `specs`, overlay `i` being `specs[i]`.
+///
+/// The files of the overlays get the ids starting at `first_file_id`, and are stored in
+/// the ROM one after the other from `rom_offset`, each one aligned to `rom_align` bytes.
+/// Returns the entries of the overlay table, and the ones to put in the file allocation
+/// table at `first_file_id`.
+///
+/// Fails if two overlays of different regions would use the same addresses.
+pub fn build_overlay_table(
+    specs: &[OverlaySpec],
+    first_file_id: u32,
+    rom_offset: u32,
+    rom_align: u32,
+) -> Result<(Vec<OverlayEntry>, Vec<FatEntry>), BuildError> {
+    let mut entries = Vec::with_capacity(specs.len());
+    let mut fat = Vec::with_capacity(specs.len());
+    let mut offset = rom_offset;
+    for (i, spec) in specs.iter().enumerate() {
+        let id = i as u32;
+        let entry = OverlayEntry {
+            id,
+            ram_address: spec.ram_address,
+            ram_size: spec.ram_size,
+            bss_size: spec.bss_size,
+            sinit_start: spec.sinit.start,
+            sinit_end: spec.sinit.end,
+            file_id: first_file_id + id,
+            flags: 0,
+        };
+        if !entry.is_valid() {
+            return Err(BuildError::InvalidEntry(id));
+        }
+        for (other, other_spec) in entries.iter().zip(specs) {
+            let other: &OverlayEntry = other;
+            if other_spec.region != spec.region && overlaps(&other.ram_range(), &entry.ram_range())
+            {
+                return Err(BuildError::Overlapping(other.id, id));
+            }
+        }
+        offset = offset.next_multiple_of(rom_align.max(1));
+        fat.push(FatEntry {
+            start: offset,
+            end: offset + spec.ram_size,
+        });
+        offset += spec.ram_size;
+        entries.push(entry);
+    }
+    Ok((entries, fat))
+}
+
+/// Encodes an overlay table
+pub fn encode_overlay_table(entries: &[OverlayEntry]) -> Vec<u8> {
+    entries.iter().flat_map(OverlayEntry::to_bytes).collect()
+}
+
+/// Encodes a file allocation table
+pub fn encode_fat(entries: &[FatEntry]) -> Vec<u8> {
+    entries.iter().flat_map(FatEntry::to_bytes).collect()
+}
+
+/// Returns `true` if the ranges share at least one address
+fn overlaps(a: &Range<u32>, b: &Range<u32>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(region: u32, ram_address: u32, ram_size: u32) -> OverlaySpec {
+        OverlaySpec {
+            region,
+            ram_address,
+            ram_size,
+            bss_size: 0x20,
+            sinit: ram_address..ram_address,
+        }
+    }
+
+    #[test]
+    fn tables_are_little_endian() {
+        let entry = OverlayEntry {
+            id: 1,
+            ram_address: 0x0230_0000,
+            ram_size: 0x1000,
+            bss_size: 0x100,
+            sinit_start: 0x0230_0F00,
+            sinit_end: 0x0230_0F08,
+            file_id: 3,
+            flags: 0x0100_0600,
+        };
+        let bytes = encode_overlay_table(&[entry, entry]);
+        assert_eq!(bytes.len(), 2 * OVERLAY_ENTRY_SIZE);
+        assert_eq!(bytes[..8], [1, 0, 0, 0, 0x00, 0x00, 0x30, 0x02]);
+        assert_eq!(bytes[28..32], [0x00, 0x06, 0x00, 0x01]);
+
+        let fat = [FatEntry {
+            start: 0x4000,
+            end: 0x4800,
+        }];
+        assert_eq!(encode_fat(&fat), [0x00, 0x40, 0, 0, 0x00, 0x48, 0, 0]);
+    }
+
+    #[test]
+    fn build_lays_out_files() {
+        let specs = [spec(0, 0x0230_0000, 0x1234), spec(0, 0x0230_0000, 0x100)];
+        let (entries, fat) = build_overlay_table(&specs, 10, 0x8001, 0x200).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].id, entries[0].file_id), (0, 10));
+        assert_eq!((entries[1].id, entries[1].file_id), (1, 11));
+        assert_eq!(entries[0].ram_range(), 0x0230_0000..0x0230_1254);
+        assert_eq!(
+            fat,
+            [
+                FatEntry {
+                    start: 0x8200,
+                    end: 0x9434,
+                },
+                FatEntry {
+                    start: 0x9600,
+                    end: 0x9700,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn build_rejects_invalid_overlays() {
+        let specs = [
+            spec(0, 0x0230_0000, 0x1000),
+            spec(1, 0x0240_0000, 0x1000),
+            spec(2, 0x0230_0800, 0x1000),
+        ];
+        assert_eq!(
+            build_overlay_table(&specs, 0, 0, 4),
+            Err(BuildError::Overlapping(0, 2))
+        );
+
+        // Static initializers not aligned
+        let invalid = OverlaySpec {
+            sinit: 0x0230_0002..0x0230_0004,
+            ..spec(0, 0x0230_0000, 0x10)
+        };
+        assert_eq!(
+            build_overlay_table(&[invalid], 0, 0, 4),
+            Err(BuildError::InvalidEntry(0))
+        );
+
+        // Past the end of the address space
+        let invalid = spec(0, 0xFFFF_FF00, 0x100);
+        assert_eq!(
+            build_overlay_table(&[invalid], 0, 0, 4),
+            Err(BuildError::InvalidEntry(0))
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Implementation of `include_font!`
+
+use std::path::PathBuf;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, LitStr, Token,
+};
+
+mod bdf;
+mod bmfont;
+
+/// A font read from a file
+pub struct Font {
+    pub line_height: usize,
+    pub baseline: usize,
+    pub glyphs: Vec<Glyph>,
+    /// Pairs of characters, and the adjustment of their distance
+    pub kerning: Vec<(char, char, i32)>,
+    /// The files read, to rebuild when they change
+    pub files: Vec<PathBuf>,
+}
+
+/// A character of a [`Font`]
+pub struct Glyph {
+    pub char: char,
+    pub width: usize,
+    pub height: usize,
+    /// Position of the bitmap relative to the pen, on the top of the line
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub advance: i32,
+    /// Line by line
+    pub pixels: Vec<bool>,
+}
+
+pub struct Args {
+    path: LitStr,
+    chars: Option<LitStr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let mut args = Args { path, chars: None };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let option: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match option.to_string().as_str() {
+                "chars" => args.chars = Some(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        option.span(),
+                        "Unknown option. Expected `chars`",
+                    ))
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+pub fn expand(args: Args) -> syn::Result<TokenStream> {
+    let error = |message: String| syn::Error::new(args.path.span(), message);
+
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+    let path = PathBuf::from(root).join(args.path.value());
+    let extension = path.extension().unwrap_or_default();
+    let font = match extension.to_string_lossy().to_lowercase().as_str() {
+        "fnt" | "xml" => bmfont::read(&path),
+        "bdf" => bdf::read(&path),
+        _ => Err("expected a BMFont (`.fnt`) or BDF (`.bdf`) font".into()),
+    }
+    .map_err(|e| error(format!("`{}`: {e}", path.display())))?;
+
+    // Only the chosen characters, and `?` for the others
+    let chars: Option<Vec<char>> = args.chars.map(|chars| chars.value().chars().collect());
+    let keep = |c: char| {
+        chars
+            .as_ref()
+            .is_none_or(|chars| c == '?' || chars.contains(&c))
+    };
+    let mut glyphs: Vec<&Glyph> = font
+        .glyphs
+        .iter()
+        .filter(|glyph| keep(glyph.char))
+        .collect();
+    glyphs.sort_by_key(|glyph| glyph.char);
+    glyphs.dedup_by_key(|glyph| glyph.char);
+    let mut kerning: Vec<_> = font
+        .kerning
+        .iter()
+        .filter(|&&(first, second, amount)| keep(first) && keep(second) && amount != 0)
+        .collect();
+    kerning.sort_by_key(|&&(first, second, _)| (first, second));
+    kerning.dedup_by_key(|&mut &(first, second, _)| (first, second));
+
+    let line_height = u8::try_from(font.line_height).map_err(|_| {
+        error(format!(
+            "The lines are too high ({} pixels)",
+            font.line_height
+        ))
+    })?;
+    let baseline = u8::try_from(font.baseline.min(font.line_height)).unwrap();
+    let mut bitmaps = Vec::new();
+    let mut glyph_tokens = Vec::new();
+    for glyph in &glyphs {
+        let c = glyph.char;
+        let out_of_range = || error(format!("The glyph of `{}` is too big", c.escape_default()));
+        let width = u8::try_from(glyph.width).map_err(|_| out_of_range())?;
+        let height = u8::try_from(glyph.height).map_err(|_| out_of_range())?;
+        let x_offset = i8::try_from(glyph.x_offset).map_err(|_| out_of_range())?;
+        let y_offset = i8::try_from(glyph.y_offset).map_err(|_| out_of_range())?;
+        let advance = u8::try_from(glyph.advance.max(0)).map_err(|_| out_of_range())?;
+        let offset = bitmaps.len() as u32;
+        for line in glyph.pixels.chunks(glyph.width.max(1)) {
+            for byte in line.chunks(8) {
+                let bits = byte.iter().enumerate().filter(|(_, &set)| set);
+                bitmaps.push(bits.fold(0u8, |bits, (x, _)| bits | (0x80 >> x)));
+            }
+        }
+        glyph_tokens.push(quote! {
+            ::nds_rs::font::Glyph {
+                char: #c,
+                width: #width,
+                height: #height,
+                x_offset: #x_offset,
+                y_offset: #y_offset,
+                advance: #advance,
+                offset: #offset,
+            }
+        });
+    }
+    let kerning_tokens = kerning.iter().map(|&&(first, second, amount)| {
+        let amount = amount.clamp(i8::MIN as i32, i8::MAX as i32) as i8;
+        quote! {
+            ::nds_rs::font::Kerning { first: #first, second: #second, amount: #amount }
+        }
+    });
+    let (glyphs_len, kerning_len, bitmaps_len) = (glyphs.len(), kerning.len(), bitmaps.len());
+    let files = font.files.iter().map(|file| file.to_string_lossy());
+
+    Ok(quote! {
+        {
+            // Rebuilds when the font or its pages change
+            #(const _: &[u8] = include_bytes!(#files);)*
+            static GLYPHS: [::nds_rs::font::Glyph; #glyphs_len] = [#(#glyph_tokens),*];
+            static KERNING: [::nds_rs::font::Kerning; #kerning_len] = [#(#kerning_tokens),*];
+            static BITMAPS: [u8; #bitmaps_len] = [#(#bitmaps),*];
+            ::nds_rs::font::Font {
+                line_height: #line_height,
+                baseline: #baseline,
+                glyphs: &GLYPHS,
+                kerning: &KERNING,
+                bitmaps: &BITMAPS,
+            }
+        }
+    })
+}
@@ -0,0 +1,113 @@
+//! Reading of BDF fonts
+
+use std::path::Path;
+
+use super::{Font, Glyph};
+
+/// Parses the numbers after a keyword
+fn numbers<const N: usize>(line: &str, keyword: &str) -> Result<[i32; N], String> {
+    let values: Vec<i32> = line
+        .split_whitespace()
+        .skip(1)
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid `{keyword}`: `{line}`"))?;
+    values
+        .get(..N)
+        .and_then(|values| values.try_into().ok())
+        .ok_or_else(|| format!("invalid `{keyword}`: `{line}`"))
+}
+
+/// Reads a font
+pub fn read(path: &Path) -> Result<Font, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Couldn't read `{}`: {e}", path.display()))?;
+    let mut lines = text.lines().map(str::trim);
+
+    let mut bounding_box = None;
+    let (mut ascent, mut descent) = (None, None);
+    let mut glyphs = Vec::new();
+    while let Some(line) = lines.next() {
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        match keyword {
+            "FONTBOUNDINGBOX" => bounding_box = Some(numbers::<4>(line, keyword)?),
+            "FONT_ASCENT" => ascent = Some(numbers::<1>(line, keyword)?[0]),
+            "FONT_DESCENT" => descent = Some(numbers::<1>(line, keyword)?[0]),
+            "STARTCHAR" => glyphs.push(read_char(&mut lines, bounding_box)?),
+            _ => {}
+        }
+    }
+    let [_, height, _, y_offset] = bounding_box.ok_or("missing `FONTBOUNDINGBOX`")?;
+    let ascent = ascent.unwrap_or(height + y_offset);
+    let descent = descent.unwrap_or(-y_offset);
+
+    Ok(Font {
+        line_height: (ascent + descent).max(0) as usize,
+        baseline: ascent.max(0) as usize,
+        glyphs: glyphs
+            .into_iter()
+            .flatten()
+            .filter_map(|(encoding, bottom, glyph)| {
+                Some(Glyph {
+                    char: char::from_u32(encoding)?,
+                    y_offset: ascent - bottom - glyph.height as i32,
+                    ..glyph
+                })
+            })
+            .collect(),
+        kerning: Vec::new(),
+        files: vec![path.to_owned()],
+    })
+}
+
+/// Reads a character until `ENDCHAR`: its encoding, the offset of the bottom of the
+/// bitmap above the baseline, and its glyph. Characters without an encoding are `None`
+fn read_char<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    bounding_box: Option<[i32; 4]>,
+) -> Result<Option<(u32, i32, Glyph)>, String> {
+    let mut encoding = -1;
+    let mut advance = None;
+    let mut bbx = bounding_box;
+    let mut pixels = Vec::new();
+    let mut in_bitmap = false;
+    for line in lines.by_ref() {
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        match keyword {
+            "ENDCHAR" => {
+                let [width, height, x_offset, bottom] = bbx.ok_or("missing `BBX`")?;
+                let (width, height) = (width.max(0) as usize, height.max(0) as usize);
+                if pixels.len() != width * height {
+                    return Err(format!("invalid bitmap of the character {encoding}"));
+                }
+                let glyph = Glyph {
+                    char: '\0',
+                    width,
+                    height,
+                    x_offset,
+                    y_offset: 0,
+                    advance: advance.unwrap_or(width as i32),
+                    pixels,
+                };
+                return Ok((encoding >= 0).then_some((encoding as u32, bottom, glyph)));
+            }
+            _ if in_bitmap => {
+                let width = bbx.ok_or("missing `BBX`")?[0].max(0) as usize;
+                let bytes = (0..line.len() / 2)
+                    .map(|i| u8::from_str_radix(&line[i * 2..i * 2 + 2], 16))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| format!("invalid bitmap line `{line}`"))?;
+                if bytes.len() * 8 < width {
+                    return Err(format!("the bitmap line `{line}` is too short"));
+                }
+                pixels.extend((0..width).map(|x| bytes[x / 8] & (0x80 >> (x % 8)) != 0));
+            }
+            "ENCODING" => encoding = numbers::<1>(line, keyword)?[0],
+            "DWIDTH" => advance = Some(numbers::<1>(line, keyword)?[0]),
+            "BBX" => bbx = Some(numbers::<4>(line, keyword)?),
+            "BITMAP" => in_bitmap = true,
+            _ => {}
+        }
+    }
+    Err("missing `ENDCHAR`".into())
+}
@@ -0,0 +1,156 @@
+//! Reading of BMFont descriptors, in the text and XML formats
+
+use std::{collections::HashMap, path::Path};
+
+use super::{Font, Glyph};
+use crate::image::decode::{self, Rgba};
+
+/// A tag of the descriptor with its attributes, e.g. `char id=65 x=0 ...`
+type Tag = (String, HashMap<String, String>);
+
+/// Reads a descriptor and the images of its pages
+pub fn read(path: &Path) -> Result<Font, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Couldn't read `{}`: {e}", path.display()))?;
+    let tags = match text.trim_start().starts_with('<') {
+        true => xml_tags(&text)?,
+        false => text_tags(&text)?,
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    let find = |name: &'static str| tags.iter().filter(move |(tag, _)| tag == name);
+    let common = &find("common").next().ok_or("missing `common`")?.1;
+    let mut font = Font {
+        line_height: number(common, "lineHeight")?,
+        baseline: number(common, "base")?,
+        glyphs: Vec::new(),
+        kerning: Vec::new(),
+        files: vec![path.to_owned()],
+    };
+
+    let mut pages = HashMap::new();
+    for (_, page) in find("page") {
+        let id: i32 = number(page, "id")?;
+        let file = dir.join(attribute(page, "file")?);
+        let bytes =
+            std::fs::read(&file).map_err(|e| format!("Couldn't read `{}`: {e}", file.display()))?;
+        let image = decode::decode(&bytes).map_err(|e| format!("`{}`: {e}", file.display()))?;
+        font.files.push(file);
+        pages.insert(id, image);
+    }
+
+    for (_, char) in find("char") {
+        let id: u32 = number(char, "id")?;
+        // Some tools export a glyph -1 for the missing characters
+        let Some(c) = char::from_u32(id) else {
+            continue;
+        };
+        let (x, y): (usize, usize) = (number(char, "x")?, number(char, "y")?);
+        let (width, height): (usize, usize) = (number(char, "width")?, number(char, "height")?);
+        let page: i32 = number(char, "page").unwrap_or(0);
+        let image = pages
+            .get(&page)
+            .ok_or_else(|| format!("the page {page} of `{c}` doesn't exist"))?;
+        if x + width > image.width || y + height > image.height {
+            return Err(format!("the glyph of `{c}` is out of its page"));
+        }
+        let opaque = is_opaque(image);
+        let pixels = (0..height)
+            .flat_map(|row| (0..width).map(move |column| (column, row)))
+            .map(|(column, row)| opaque(image.pixels[(y + row) * image.width + x + column]))
+            .collect();
+        font.glyphs.push(Glyph {
+            char: c,
+            width,
+            height,
+            x_offset: number(char, "xoffset")?,
+            y_offset: number(char, "yoffset")?,
+            advance: number(char, "xadvance")?,
+            pixels,
+        });
+    }
+
+    for (_, kerning) in find("kerning") {
+        let first = char::from_u32(number(kerning, "first")?);
+        let second = char::from_u32(number(kerning, "second")?);
+        if let (Some(first), Some(second)) = (first, second) {
+            font.kerning
+                .push((first, second, number(kerning, "amount")?));
+        }
+    }
+    Ok(font)
+}
+
+/// Whether a pixel is part of a glyph. Pages with transparency have opaque glyphs,
+/// the others light glyphs on a dark background
+fn is_opaque(image: &Rgba) -> fn([u8; 4]) -> bool {
+    match image.pixels.iter().any(|pixel| pixel[3] < 255) {
+        true => |pixel| pixel[3] >= 128,
+        false => |[r, g, b, _]| (r as u32 + g as u32 + b as u32) >= 3 * 128,
+    }
+}
+
+fn attribute<'a>(attributes: &'a HashMap<String, String>, name: &str) -> Result<&'a str, String> {
+    attributes
+        .get(name)
+        .map(String::as_str)
+        .ok_or_else(|| format!("missing `{name}`"))
+}
+
+fn number<T: std::str::FromStr>(
+    attributes: &HashMap<String, String>,
+    name: &str,
+) -> Result<T, String> {
+    let value = attribute(attributes, name)?;
+    value
+        .parse()
+        .map_err(|_| format!("`{name}` isn't a valid number: `{value}`"))
+}
+
+/// The tags of the text format, one per line
+fn text_tags(text: &str) -> Result<Vec<Tag>, String> {
+    if text.starts_with("BMF") {
+        return Err("the binary format isn't supported, export as text or XML".into());
+    }
+    let mut tags = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let (tag, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+        if tag.is_empty() {
+            continue;
+        }
+        let mut attributes = HashMap::new();
+        loop {
+            rest = rest.trim_start();
+            let Some((name, after)) = rest.split_once('=') else {
+                break;
+            };
+            let (value, after) = match after.strip_prefix('"') {
+                Some(quoted) => quoted
+                    .split_once('"')
+                    .ok_or_else(|| format!("unterminated string in `{line}`"))?,
+                None => after.split_once(' ').unwrap_or((after, "")),
+            };
+            attributes.insert(name.trim().to_owned(), value.to_owned());
+            rest = after;
+        }
+        tags.push((tag.to_owned(), attributes));
+    }
+    Ok(tags)
+}
+
+/// The elements of the XML format, whatever their depth
+fn xml_tags(text: &str) -> Result<Vec<Tag>, String> {
+    let document = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+    Ok(document
+        .descendants()
+        .filter(|node| node.is_element())
+        .map(|node| {
+            let attributes = node
+                .attributes()
+                .map(|attribute| (attribute.name().to_owned(), attribute.value().to_owned()))
+                .collect();
+            (node.tag_name().name().to_owned(), attributes)
+        })
+        .collect())
+}
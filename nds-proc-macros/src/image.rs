@@ -0,0 +1,281 @@
+//! Implementation of `include_image!`
+
+use std::path::PathBuf;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    Ident, LitBool, LitInt, LitStr, Token,
+};
+
+pub mod convert;
+pub mod decode;
+
+use convert::{Indexed, Tile};
+
+/// What the image is converted to
+enum Kind {
+    /// Tiles and a map, for tiled backgrounds
+    Tiles,
+    /// A bitmap, for bitmap backgrounds
+    Bitmap,
+    /// Frames of `width`x`height` pixels, for sprites
+    Sprite { width: usize, height: usize },
+}
+
+pub struct Args {
+    path: LitStr,
+    kind: Kind,
+    kind_span: Span,
+    bpp: Option<(usize, Span)>,
+    dedup: bool,
+    flips: bool,
+    transparent: Option<[u8; 3]>,
+    colors: Option<(usize, Span)>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path: LitStr = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let kind_ident: Ident = input.parse()?;
+        let kind = match kind_ident.to_string().as_str() {
+            "tiles" => Kind::Tiles,
+            "bitmap" => Kind::Bitmap,
+            "sprite" => {
+                let content;
+                parenthesized!(content in input);
+                let width: LitInt = content.parse()?;
+                content.parse::<Token![,]>()?;
+                let height: LitInt = content.parse()?;
+                Kind::Sprite {
+                    width: width.base10_parse()?,
+                    height: height.base10_parse()?,
+                }
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    kind_ident.span(),
+                    "Expected `tiles`, `bitmap` or `sprite(width, height)`",
+                ))
+            }
+        };
+        let mut args = Args {
+            path,
+            kind,
+            kind_span: kind_ident.span(),
+            bpp: None,
+            dedup: true,
+            flips: true,
+            transparent: None,
+            colors: None,
+        };
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match name.to_string().as_str() {
+                "bpp" => {
+                    let bpp: LitInt = input.parse()?;
+                    args.bpp = Some((bpp.base10_parse()?, bpp.span()));
+                }
+                "dedup" => args.dedup = input.parse::<LitBool>()?.value,
+                "flips" => args.flips = input.parse::<LitBool>()?.value,
+                "transparent" => {
+                    let color: LitInt = input.parse()?;
+                    let [_, r, g, b] = color.base10_parse::<u32>()?.to_be_bytes();
+                    args.transparent = Some([r, g, b]);
+                }
+                "colors" => {
+                    let colors: LitInt = input.parse()?;
+                    args.colors = Some((colors.base10_parse()?, colors.span()));
+                }
+                _ => return Err(syn::Error::new(
+                    name.span(),
+                    "Unknown option. Expected `bpp`, `dedup`, `flips`, `transparent` or `colors`",
+                )),
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Valid sizes of sprites, in pixels
+pub const SPRITE_SIZES: [(usize, usize); 12] = [
+    (8, 8),
+    (16, 16),
+    (32, 32),
+    (64, 64),
+    (16, 8),
+    (32, 8),
+    (32, 16),
+    (64, 32),
+    (8, 16),
+    (8, 32),
+    (16, 32),
+    (32, 64),
+];
+
+pub fn expand(args: Args) -> syn::Result<TokenStream> {
+    let error = |message: String| syn::Error::new(args.path.span(), message);
+
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+    let path = PathBuf::from(root).join(args.path.value());
+    let bytes = std::fs::read(&path)
+        .map_err(|e| error(format!("Couldn't read `{}`: {e}", path.display())))?;
+    let image = decode::decode(&bytes).map_err(|e| error(format!("`{}`: {e}", path.display())))?;
+    let path = path.to_string_lossy();
+
+    let (default_bpp, allowed) = match args.kind {
+        Kind::Bitmap => (16, &[8, 16][..]),
+        _ => (4, &[4, 8][..]),
+    };
+    let (bpp, bpp_span) = args.bpp.unwrap_or((default_bpp, args.kind_span));
+    if !allowed.contains(&bpp) {
+        return Err(syn::Error::new(
+            bpp_span,
+            format!("Expected a depth of {allowed:?} bits per pixel"),
+        ));
+    }
+    let max_colors = 1 << bpp.min(8);
+    let colors = match args.colors {
+        Some((colors, span)) if colors < 2 || colors > max_colors => {
+            return Err(syn::Error::new(
+                span,
+                format!("Expected between 2 and {max_colors} colors"),
+            ))
+        }
+        Some((colors, _)) => colors,
+        None => max_colors,
+    };
+
+    let body = match args.kind {
+        Kind::Bitmap if bpp == 16 => {
+            let pixels = convert::bitmap16(&image, args.transparent);
+            let (width, height) = (image.width as u16, image.height as u16);
+            let len = pixels.len();
+            quote! {
+                static PIXELS: [u16; #len] = [#(#pixels),*];
+                ::nds_rs::gfx::Bitmap16::new(#width, #height, &PIXELS)
+            }
+        }
+        Kind::Bitmap => {
+            if !image.width.is_multiple_of(2) {
+                return Err(error(format!(
+                    "The width of 8 bits bitmaps ({}) must be even",
+                    image.width
+                )));
+            }
+            let indexed = convert::index(&image, colors, args.transparent);
+            let data = convert::pack_bitmap8(&indexed.indices);
+            let (width, height) = (image.width as u16, image.height as u16);
+            let len = data.len();
+            let palette = palette(&indexed);
+            quote! {
+                static DATA: [u16; #len] = [#(#data),*];
+                #palette
+                ::nds_rs::gfx::BitmapImage8 {
+                    bitmap: ::nds_rs::gfx::Bitmap8::new(#width, #height, &DATA),
+                    palette: ::nds_rs::gfx::Palette::new(&PALETTE),
+                }
+            }
+        }
+        Kind::Tiles => {
+            let indexed = convert::index(&image, colors, args.transparent);
+            let tiles = convert::split_tiles(&indexed).map_err(error)?;
+            let set = match args.dedup {
+                true => convert::dedup_tiles(&tiles, args.flips),
+                false => convert::TileSet {
+                    map: (0..tiles.len() as u16).collect(),
+                    tiles,
+                },
+            };
+            if set.tiles.len() > 1024 {
+                return Err(error(format!(
+                    "The image has {} different tiles, but maps can only use 1024",
+                    set.tiles.len()
+                )));
+            }
+            let map = &set.map;
+            let (width, height) = ((image.width / 8) as u16, (image.height / 8) as u16);
+            let map_len = map.len();
+            let (words, tiles) = tiles_static(&set.tiles, bpp);
+            let palette = palette(&indexed);
+            quote! {
+                #words
+                #palette
+                static MAP: [::nds_rs::sys::background::TileMapEntry16; #map_len] =
+                    [#(::nds_rs::sys::background::TileMapEntry16::from_bits(#map)),*];
+                ::nds_rs::gfx::TiledImage {
+                    tiles: #tiles,
+                    map: ::nds_rs::gfx::TileMap::new(#width, #height, &MAP),
+                    palette: ::nds_rs::gfx::Palette::new(&PALETTE),
+                }
+            }
+        }
+        Kind::Sprite { width, height } => {
+            if !SPRITE_SIZES.contains(&(width, height)) {
+                return Err(syn::Error::new(
+                    args.kind_span,
+                    format!("{width}x{height} isn't a size of sprites"),
+                ));
+            }
+            let indexed = convert::index(&image, colors, args.transparent);
+            let tiles = convert::split_tiles(&indexed).map_err(error)?;
+            let tiles = convert::sprite_order(&tiles, image.width / 8, width / 8, height / 8)
+                .map_err(error)?;
+            let frames = (image.width / width * (image.height / height)) as u16;
+            let (words, tiles) = tiles_static(&tiles, bpp);
+            let palette = palette(&indexed);
+            let (width, height) = (width as u16, height as u16);
+            quote! {
+                #words
+                #palette
+                ::nds_rs::gfx::SpriteSheet {
+                    tiles: #tiles,
+                    palette: ::nds_rs::gfx::Palette::new(&PALETTE),
+                    frame_width: #width,
+                    frame_height: #height,
+                    frames: #frames,
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        {
+            // Rebuilds when the image changes
+            const _: &[u8] = include_bytes!(#path);
+            #body
+        }
+    })
+}
+
+/// The packed tiles in a `WORDS` static, and a `Tiles` using them
+fn tiles_static(tiles: &[Tile], bpp: usize) -> (TokenStream, TokenStream) {
+    let words = convert::pack_tiles(tiles, bpp);
+    let len = words.len();
+    let bpp = match bpp {
+        4 => quote!(::nds_rs::gfx::Bpp::Four),
+        _ => quote!(::nds_rs::gfx::Bpp::Eight),
+    };
+    (
+        quote!(static WORDS: [u32; #len] = [#(#words),*];),
+        quote!(::nds_rs::gfx::Tiles::new(#bpp, &WORDS)),
+    )
+}
+
+/// The palette, in a `PALETTE` static
+fn palette(indexed: &Indexed) -> TokenStream {
+    let colors = &indexed.palette;
+    let len = colors.len();
+    quote! {
+        static PALETTE: [u16; #len] = [#(#colors),*];
+    }
+}
@@ -0,0 +1,446 @@
+//! Conversion of decoded images to the formats of the DS: palettes, tiles, maps and
+//! bitmaps
+
+use std::collections::HashMap;
+
+use super::decode::Rgba;
+
+/// Converts a color to BGR555, without the alpha bit
+pub fn bgr555([r, g, b, _]: [u8; 4]) -> u16 {
+    (r as u16 >> 3) | ((g as u16 >> 3) << 5) | ((b as u16 >> 3) << 10)
+}
+
+/// A pixel is transparent if it's mostly transparent, or has the color key
+pub fn is_transparent(pixel: [u8; 4], key: Option<[u8; 3]>) -> bool {
+    pixel[3] < 128 || key.is_some_and(|key| pixel[..3] == key)
+}
+
+/// An image with a palette of BGR555 colors. Index 0 is transparent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Indexed {
+    pub width: usize,
+    pub height: usize,
+    pub palette: Vec<u16>,
+    pub indices: Vec<u8>,
+}
+
+/// Converts `image` to a palette of at most `max_colors` colors (including the
+/// transparent one), reducing the colors if needed.
+///
+/// The palette of indexed images is kept as is when it fits, and its transparent
+/// pixels all use index 0.
+pub fn index(image: &Rgba, max_colors: usize, key: Option<[u8; 3]>) -> Indexed {
+    if let (Some(palette), Some(indices)) = (&image.palette, &image.indices) {
+        let fits = indices
+            .iter()
+            .all(|&i| (i as usize) < max_colors.min(palette.len()));
+        let transparent_at_0 = image
+            .pixels
+            .iter()
+            .zip(indices)
+            .all(|(&pixel, &i)| !is_transparent(pixel, key) || i == 0);
+        if fits && transparent_at_0 {
+            return Indexed {
+                width: image.width,
+                height: image.height,
+                palette: palette
+                    .iter()
+                    .take(max_colors)
+                    .map(|&c| bgr555(c))
+                    .collect(),
+                indices: indices.clone(),
+            };
+        }
+    }
+
+    // Colors by order of appearance, with their number of pixels
+    let mut counts: Vec<(u16, u32)> = Vec::new();
+    let mut positions = HashMap::new();
+    for &pixel in &image.pixels {
+        if is_transparent(pixel, key) {
+            continue;
+        }
+        let color = bgr555(pixel);
+        let position = *positions.entry(color).or_insert_with(|| {
+            counts.push((color, 0));
+            counts.len() - 1
+        });
+        counts[position].1 += 1;
+    }
+    let background = key.map_or(0, |[r, g, b]| bgr555([r, g, b, 255]));
+    let colors = match counts.len() < max_colors {
+        true => counts.iter().map(|&(color, _)| color).collect(),
+        false => median_cut(&counts, max_colors - 1),
+    };
+    let mut palette = vec![background];
+    palette.extend(&colors);
+
+    let mut nearest = HashMap::new();
+    let indices = image
+        .pixels
+        .iter()
+        .map(|&pixel| {
+            if is_transparent(pixel, key) {
+                return 0;
+            }
+            let color = bgr555(pixel);
+            *nearest
+                .entry(color)
+                .or_insert_with(|| 1 + closest(&colors, color) as u8)
+        })
+        .collect();
+    Indexed {
+        width: image.width,
+        height: image.height,
+        palette,
+        indices,
+    }
+}
+
+fn components(color: u16) -> [i32; 3] {
+    [
+        (color & 0x1F) as i32,
+        ((color >> 5) & 0x1F) as i32,
+        ((color >> 10) & 0x1F) as i32,
+    ]
+}
+
+/// Index of the color of `palette` closest to `color`
+pub fn closest(palette: &[u16], color: u16) -> usize {
+    let target = components(color);
+    let distance = |&c: &u16| {
+        let c = components(c);
+        (0..3).map(|i| (c[i] - target[i]).pow(2)).sum::<i32>()
+    };
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap_or(0)
+}
+
+/// Reduces the colors (with their number of pixels) to at most `max` colors, by
+/// splitting the color space where the colors are the most spread out
+pub fn median_cut(colors: &[(u16, u32)], max: usize) -> Vec<u16> {
+    let mut boxes: Vec<Vec<(u16, u32)>> = vec![colors.to_vec()];
+    while boxes.len() < max {
+        // The box with the widest range of a component
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = (0..3)
+                    .map(|channel| {
+                        let values = b.iter().map(|&(c, _)| components(c)[channel]);
+                        let range = values.clone().max().unwrap() - values.min().unwrap();
+                        (channel, range)
+                    })
+                    .max_by_key(|&(_, range)| range)
+                    .unwrap();
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((i, channel, _)) = widest else {
+            break;
+        };
+        let mut split = boxes.swap_remove(i);
+        split.sort_by_key(|&(c, _)| components(c)[channel]);
+        // Split at the median pixel, keeping a color on each side
+        let total: u64 = split.iter().map(|&(_, n)| n as u64).sum();
+        let mut seen = 0;
+        let mut at = split.len() - 1;
+        for (j, &(_, n)) in split.iter().enumerate() {
+            seen += n as u64;
+            if seen * 2 >= total {
+                at = j + 1;
+                break;
+            }
+        }
+        let at = at.clamp(1, split.len() - 1);
+        let second = split.split_off(at);
+        boxes.push(split);
+        boxes.push(second);
+    }
+    boxes.iter().map(|b| average(b)).collect()
+}
+
+/// Average of colors weighted by their number of pixels
+fn average(colors: &[(u16, u32)]) -> u16 {
+    let total: u64 = colors.iter().map(|&(_, n)| n.max(1) as u64).sum();
+    let mut sums = [0u64; 3];
+    for &(color, n) in colors {
+        let c = components(color);
+        for i in 0..3 {
+            sums[i] += c[i] as u64 * n.max(1) as u64;
+        }
+    }
+    let [r, g, b] = sums.map(|sum| ((sum + total / 2) / total) as u16);
+    r | (g << 5) | (b << 10)
+}
+
+/// The 64 palette indices of an 8x8 tile, line by line
+pub type Tile = [u8; 64];
+
+/// Splits `image` into 8x8 tiles, line by line.
+/// Fails if its size isn't a multiple of 8
+pub fn split_tiles(image: &Indexed) -> Result<Vec<Tile>, String> {
+    if !image.width.is_multiple_of(8) || !image.height.is_multiple_of(8) {
+        return Err(format!(
+            "the size of the image ({}x{}) must be a multiple of 8",
+            image.width, image.height
+        ));
+    }
+    let mut tiles = Vec::new();
+    for ty in 0..image.height / 8 {
+        for tx in 0..image.width / 8 {
+            let mut tile = [0; 64];
+            for y in 0..8 {
+                let start = (ty * 8 + y) * image.width + tx * 8;
+                tile[y * 8..y * 8 + 8].copy_from_slice(&image.indices[start..start + 8]);
+            }
+            tiles.push(tile);
+        }
+    }
+    Ok(tiles)
+}
+
+/// Reorders the tiles of an image `width` tiles wide into sprite frames of
+/// `frame_width`x`frame_height` tiles, each one line by line as the 1D mapping of
+/// sprites expects
+pub fn sprite_order(
+    tiles: &[Tile],
+    width: usize,
+    frame_width: usize,
+    frame_height: usize,
+) -> Result<Vec<Tile>, String> {
+    let height = tiles.len() / width.max(1);
+    if frame_width == 0
+        || frame_height == 0
+        || !width.is_multiple_of(frame_width)
+        || !height.is_multiple_of(frame_height)
+    {
+        return Err(format!(
+            "the image ({width}x{height} tiles) can't be split into frames of {frame_width}x{frame_height} tiles"
+        ));
+    }
+    let mut ordered = Vec::with_capacity(tiles.len());
+    for fy in 0..height / frame_height {
+        for fx in 0..width / frame_width {
+            for y in 0..frame_height {
+                for x in 0..frame_width {
+                    ordered.push(tiles[(fy * frame_height + y) * width + fx * frame_width + x]);
+                }
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+pub fn hflip(tile: &Tile) -> Tile {
+    std::array::from_fn(|i| tile[(i / 8) * 8 + 7 - i % 8])
+}
+
+pub fn vflip(tile: &Tile) -> Tile {
+    std::array::from_fn(|i| tile[(7 - i / 8) * 8 + i % 8])
+}
+
+/// Map entry bits of the flips
+pub const HFLIP: u16 = 1 << 10;
+pub const VFLIP: u16 = 1 << 11;
+
+/// Unique tiles, and for each tile of the image the map entry using them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileSet {
+    pub tiles: Vec<Tile>,
+    pub map: Vec<u16>,
+}
+
+/// Removes the duplicated tiles, and the flipped ones if `flips` is `true`
+pub fn dedup_tiles(tiles: &[Tile], flips: bool) -> TileSet {
+    let mut unique: Vec<Tile> = Vec::new();
+    let mut known: HashMap<Tile, u16> = HashMap::new();
+    let mut map = Vec::with_capacity(tiles.len());
+    for tile in tiles {
+        let mut variants = vec![(*tile, 0)];
+        if flips {
+            let h = hflip(tile);
+            variants.push((h, HFLIP));
+            variants.push((vflip(tile), VFLIP));
+            variants.push((vflip(&h), HFLIP | VFLIP));
+        }
+        let found = variants
+            .iter()
+            .find_map(|(variant, flip)| known.get(variant).map(|&index| index | flip));
+        let entry = found.unwrap_or_else(|| {
+            let index = unique.len() as u16;
+            unique.push(*tile);
+            known.insert(*tile, index);
+            index
+        });
+        map.push(entry);
+    }
+    TileSet { tiles: unique, map }
+}
+
+/// Packs tiles in 4 or 8 bits per pixel, in words
+pub fn pack_tiles(tiles: &[Tile], bpp: usize) -> Vec<u32> {
+    let bytes: Vec<u8> = match bpp {
+        4 => tiles
+            .iter()
+            .flat_map(|tile| tile.chunks_exact(2).map(|p| (p[0] & 0xF) | (p[1] << 4)))
+            .collect(),
+        _ => tiles.iter().flatten().copied().collect(),
+    };
+    words(&bytes)
+}
+
+/// Packs the indices of an 8 bits bitmap, two pixels per halfword
+pub fn pack_bitmap8(indices: &[u8]) -> Vec<u16> {
+    indices
+        .chunks(2)
+        .map(|p| p[0] as u16 | (*p.get(1).unwrap_or(&0) as u16) << 8)
+        .collect()
+}
+
+/// Converts `image` to a 16 bits bitmap, with the alpha bit set on opaque pixels
+pub fn bitmap16(image: &Rgba, key: Option<[u8; 3]>) -> Vec<u16> {
+    image
+        .pixels
+        .iter()
+        .map(|&pixel| match is_transparent(pixel, key) {
+            true => 0,
+            false => bgr555(pixel) | 0x8000,
+        })
+        .collect()
+}
+
+/// Little endian words of `bytes`, padded with zeros
+pub fn words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_le_bytes(word)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::decode::decode;
+    use super::*;
+
+    /// Decodes an image of `tests/fixtures`
+    fn fixture(name: &str) -> Rgba {
+        let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        decode(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    /// BGR555 color of each line of `gradient.bmp`, from blue to red
+    fn gradient(y: u16) -> u16 {
+        (4 * y) | ((31 - 4 * y) << 10)
+    }
+
+    #[test]
+    fn indexed_images_keep_their_palette() {
+        let image = fixture("tiles.png");
+        let indexed = index(&image, 16, None);
+        assert_eq!(indexed.palette, [0x7C1F, 0x001F, 0x03E0, 0x7C00]);
+        assert_eq!(Some(&indexed.indices), image.indices.as_ref());
+
+        // Too many colors, the transparent pixels stay at index 0
+        let reduced = index(&image, 3, None);
+        assert_eq!(reduced.palette.len(), 3);
+        for (&before, &after) in image.indices.as_ref().unwrap().iter().zip(&reduced.indices) {
+            assert_eq!(before == 0, after == 0);
+        }
+    }
+
+    #[test]
+    fn colors_are_indexed_by_appearance() {
+        let image = fixture("gradient.bmp");
+        assert_eq!(image.pixels[0], [0, 0, 255, 255]);
+        let indexed = index(&image, 16, None);
+        let colors: Vec<u16> = (0..8).map(gradient).collect();
+        assert_eq!(indexed.palette[0], 0);
+        assert_eq!(indexed.palette[1..], colors);
+        for (i, &index) in indexed.indices.iter().enumerate() {
+            assert_eq!(index as usize, i / 8 + 1);
+        }
+
+        // The first line has the color key
+        let keyed = index(&image, 16, Some([0, 0, 255]));
+        assert_eq!(keyed.palette[0], 0x7C00);
+        assert_eq!(keyed.palette[1..], colors[1..]);
+        for (i, &index) in keyed.indices.iter().enumerate() {
+            assert_eq!(index as usize, i / 8);
+        }
+    }
+
+    #[test]
+    fn median_cut_splits_the_widest_range() {
+        let indexed = index(&fixture("gradient.bmp"), 3, None);
+        // The lines are split by their blue component, each half averaged
+        assert_eq!(indexed.palette, [0, 22 | (9 << 10), 6 | (25 << 10)]);
+        for (i, &index) in indexed.indices.iter().enumerate() {
+            assert_eq!(index, if i < 32 { 2 } else { 1 });
+        }
+
+        assert_eq!(median_cut(&[(0x001F, 1)], 4), [0x001F]);
+        // Weighted by the number of pixels
+        assert_eq!(median_cut(&[(0, 3), (31, 1)], 1), [8]);
+        assert_eq!(median_cut(&[(0, 3), (31, 1)], 2), [0, 31]);
+    }
+
+    #[test]
+    fn flipped_tiles_are_deduplicated() {
+        let tiles = split_tiles(&index(&fixture("tiles.png"), 16, None)).unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[1], hflip(&tiles[0]));
+
+        let tile = tiles[0];
+        let other = [1; 64];
+        let tiles = [
+            tile,
+            hflip(&tile),
+            vflip(&tile),
+            vflip(&hflip(&tile)),
+            tile,
+            other,
+        ];
+        let flipped = dedup_tiles(&tiles, true);
+        assert_eq!(flipped.tiles, [tile, other]);
+        assert_eq!(flipped.map, [0, HFLIP, VFLIP, HFLIP | VFLIP, 0, 1]);
+
+        let unflipped = dedup_tiles(&tiles, false);
+        assert_eq!(unflipped.tiles.len(), 5);
+        assert_eq!(unflipped.map, [0, 1, 2, 3, 0, 4]);
+    }
+
+    #[test]
+    fn tiles_are_packed() {
+        let tile: Tile = std::array::from_fn(|i| i as u8);
+        let bpp8 = pack_tiles(&[tile], 8);
+        assert_eq!(bpp8.len(), 16);
+        assert_eq!(bpp8[..2], [0x0302_0100, 0x0706_0504]);
+
+        // The low nibble is the first pixel, high bits are dropped
+        let bpp4 = pack_tiles(&[tile, [0xF; 64]], 4);
+        assert_eq!(bpp4.len(), 16);
+        assert_eq!(bpp4[..3], [0x7654_3210, 0xFEDC_BA98, 0x7654_3210]);
+        assert_eq!(bpp4[8..], [0xFFFF_FFFF; 8]);
+    }
+
+    #[test]
+    fn sprites_are_ordered_by_frame() {
+        let tiles: Vec<Tile> = (0..8).map(|i| [i; 64]).collect();
+        let first = |tiles: Vec<Tile>| tiles.iter().map(|tile| tile[0]).collect::<Vec<_>>();
+        let frames = sprite_order(&tiles, 4, 2, 2).unwrap();
+        assert_eq!(first(frames), [0, 1, 4, 5, 2, 3, 6, 7]);
+        let frames = sprite_order(&tiles, 4, 1, 2).unwrap();
+        assert_eq!(first(frames), [0, 4, 1, 5, 2, 6, 3, 7]);
+
+        assert!(sprite_order(&tiles, 4, 3, 1).is_err());
+        assert!(sprite_order(&tiles, 4, 0, 1).is_err());
+    }
+}
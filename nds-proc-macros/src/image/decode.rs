@@ -0,0 +1,194 @@
+//! Decoding of PNG and BMP files to RGBA pixels
+
+/// A decoded image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rgba {
+    pub width: usize,
+    pub height: usize,
+    /// Line by line, from the top
+    pub pixels: Vec<[u8; 4]>,
+    /// Palette of indexed images, whose order is kept when it fits
+    pub palette: Option<Vec<[u8; 4]>>,
+    /// Palette index of each pixel of indexed images
+    pub indices: Option<Vec<u8>>,
+}
+
+/// Decodes a PNG or BMP file, recognized by its signature
+pub fn decode(bytes: &[u8]) -> Result<Rgba, String> {
+    if bytes.starts_with(b"\x89PNG") {
+        decode_png(bytes)
+    } else if bytes.starts_with(b"BM") {
+        decode_bmp(bytes)
+    } else {
+        Err("unknown image format, expected PNG or BMP".into())
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Rgba, String> {
+    let error = |e: png::DecodingError| format!("invalid PNG: {e}");
+    let mut decoder = png::Decoder::new(bytes);
+    // Keep the indices of indexed images, lines with less than 8 bits per sample are
+    // unpacked below
+    decoder.set_transformations(png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(error)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).map_err(error)?;
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let depth = frame.bit_depth as usize;
+    let channels = frame.color_type.samples();
+    let line_size = width * channels;
+    let buffer: Vec<u8> = buffer[..frame.buffer_size()]
+        .chunks_exact(frame.line_size)
+        .flat_map(|line| unpack(line, depth.min(8), line_size))
+        .collect();
+    let info = reader.info();
+
+    let pixels: Vec<[u8; 4]>;
+    let mut palette = None;
+    let mut indices = None;
+    match frame.color_type {
+        png::ColorType::Indexed => {
+            let rgb = info
+                .palette
+                .as_deref()
+                .ok_or("indexed PNG without a palette")?;
+            let alpha = info.trns.as_deref().unwrap_or(&[]);
+            let colors: Vec<[u8; 4]> = rgb
+                .chunks_exact(3)
+                .enumerate()
+                .map(|(i, c)| [c[0], c[1], c[2], alpha.get(i).copied().unwrap_or(255)])
+                .collect();
+            let index = buffer;
+            pixels = index
+                .iter()
+                .map(|&i| colors.get(i as usize).copied().unwrap_or([0; 4]))
+                .collect();
+            palette = Some(colors);
+            indices = Some(index);
+        }
+        color => {
+            // Scale gray levels with less than 8 bits
+            let scale = match color {
+                png::ColorType::Grayscale if depth < 8 => (255 / ((1 << depth) - 1)) as u8,
+                _ => 1,
+            };
+            pixels = buffer
+                .chunks_exact(channels)
+                .map(|p| match *p {
+                    [l] => [l * scale, l * scale, l * scale, 255],
+                    [l, a] => [l, l, l, a],
+                    [r, g, b] => [r, g, b, 255],
+                    [r, g, b, a] => [r, g, b, a],
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+    }
+    Ok(Rgba {
+        width,
+        height,
+        pixels,
+        palette,
+        indices,
+    })
+}
+
+/// Splits a line with `depth` bits per sample (1, 2, 4 or 8) into `len` bytes
+fn unpack(line: &[u8], depth: usize, len: usize) -> Vec<u8> {
+    if depth == 8 {
+        return line[..len].to_vec();
+    }
+    let mask = (1 << depth) - 1;
+    (0..len)
+        .map(|i| {
+            let bit = i * depth;
+            (line[bit / 8] >> (8 - depth - bit % 8)) & mask
+        })
+        .collect()
+}
+
+fn decode_bmp(bytes: &[u8]) -> Result<Rgba, String> {
+    let u16_at = |i: usize| -> Result<u16, String> {
+        let b = bytes.get(i..i + 2).ok_or("truncated BMP")?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |i: usize| -> Result<u32, String> {
+        let b = bytes.get(i..i + 4).ok_or("truncated BMP")?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let data_offset = u32_at(10)? as usize;
+    let header_size = u32_at(14)? as usize;
+    let width = u32_at(18)? as i32;
+    let height = u32_at(22)? as i32;
+    let bpp = u16_at(28)? as usize;
+    let compression = u32_at(30)?;
+    let colors_used = u32_at(46)? as usize;
+    if width <= 0 || height == 0 {
+        return Err("invalid BMP size".into());
+    }
+    // 3 is BI_BITFIELDS, accepted for 32 bits BMPs in the usual BGRA order
+    if compression != 0 && !(compression == 3 && bpp == 32) {
+        return Err("compressed BMPs aren't supported".into());
+    }
+    let (width, top_down) = (width as usize, height < 0);
+    let height = height.unsigned_abs() as usize;
+
+    let mut palette = None;
+    if bpp <= 8 {
+        let count = match colors_used {
+            0 => 1 << bpp,
+            n => n,
+        };
+        let start = 14 + header_size;
+        let table = bytes
+            .get(start..start + count * 4)
+            .ok_or("truncated BMP palette")?;
+        let colors: Vec<[u8; 4]> = table
+            .chunks_exact(4)
+            .map(|c| [c[2], c[1], c[0], 255])
+            .collect();
+        palette = Some(colors);
+    }
+
+    let stride = (width * bpp).div_ceil(32) * 4;
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut indices = palette.as_ref().map(|_| Vec::with_capacity(width * height));
+    for y in 0..height {
+        let line = if top_down { y } else { height - 1 - y };
+        let start = data_offset + line * stride;
+        let row = bytes
+            .get(start..start + stride)
+            .ok_or("truncated BMP pixels")?;
+        for x in 0..width {
+            let pixel = match bpp {
+                1 | 4 | 8 => {
+                    let bit = x * bpp;
+                    let shift = 8 - bpp - bit % 8;
+                    let index = (row[bit / 8] >> shift) & ((1 << bpp) - 1) as u8;
+                    indices.as_mut().unwrap().push(index);
+                    let palette = palette.as_ref().unwrap();
+                    palette.get(index as usize).copied().unwrap_or([0; 4])
+                }
+                24 => {
+                    let p = &row[x * 3..x * 3 + 3];
+                    [p[2], p[1], p[0], 255]
+                }
+                32 => {
+                    let p = &row[x * 4..x * 4 + 4];
+                    // Without bit fields, the 4th byte is unused
+                    let alpha = if compression == 3 { p[3] } else { 255 };
+                    [p[2], p[1], p[0], alpha]
+                }
+                _ => return Err(format!("{bpp} bits BMPs aren't supported")),
+            };
+            pixels.push(pixel);
+        }
+    }
+    Ok(Rgba {
+        width,
+        height,
+        pixels,
+        palette,
+        indices,
+    })
+}
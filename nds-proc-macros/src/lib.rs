@@ -1,10 +1,16 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
-    parse_macro_input, spanned::Spanned, Attribute, FnArg, ItemFn, Meta, MetaNameValue, PatType,
-    ReturnType, Type, Visibility,
+    parse_macro_input, spanned::Spanned, Attribute, FnArg, Item, ItemFn, ItemStatic, LitInt, Meta,
+    MetaNameValue, PatType, ReturnType, StaticMutability, Type, Visibility,
 };
 
+mod font;
+mod image;
+mod names;
+mod sprite;
+mod tiled;
+
 /// Allows only `fn(Hw) -> !`
 fn check_sig(signature: &syn::Signature) -> Result<(), syn::Error> {
     let span = signature.span();
@@ -134,3 +140,418 @@ pub fn entry(_: TokenStream, input: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+/// Forbids `#[link_section = ...]`, and `#[inline(always)]` if `no_inline` is set
+fn check_placement_attr(
+    attrs: &[Attribute],
+    macro_name: &str,
+    no_inline: bool,
+) -> Result<(), syn::Error> {
+    for attr in attrs {
+        match &attr.meta {
+            Meta::NameValue(MetaNameValue { path, .. }) if path.is_ident("link_section") => {
+                return Err(syn::Error::new(
+                    path.span(),
+                    format!("Remove this `link_section`. The section is set by the `{macro_name}` attribute."),
+                ))
+            }
+            Meta::List(list) if no_inline && list.path.is_ident("inline") => {
+                return Err(syn::Error::new(
+                    list.span(),
+                    format!("Remove this `inline`. Inlined code would be copied out of the `{macro_name}` section."),
+                ))
+            }
+            Meta::List(list) if no_inline && list.path.is_ident("instruction_set") => {
+                return Err(syn::Error::new(
+                    list.span(),
+                    format!("Remove this `instruction_set`. Functions marked with `{macro_name}` are always ARM code."),
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Places a function in the ITCM (Instruction Tightly Coupled Memory), 32 KiB of memory
+/// that the ARM9 runs code from without waiting for the bus or the cache.
+///
+/// The function is put in a `.itcm.text.<name>` section, which the BlocksDS linker script
+/// copies to ITCM at startup. It's never inlined, so its code stays in ITCM, and it's always
+/// compiled as ARM code: the ITCM is at 0x01FF8000, and ARM branches reach the whole
+/// main RAM (±32 MiB), so calls from ITCM never need veneers. Calls from Thumb code in main
+/// RAM to ITCM may be out of reach of a Thumb branch; the linker inserts veneers for those.
+///
+/// # Example:
+/// ```rust,no_run
+/// #[itcm]
+/// fn mix_audio(buffer: &mut [i16]) {
+///     /* ... */
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn itcm(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        let args = proc_macro2::TokenStream::from(args);
+        return syn::Error::new(args.span(), "`itcm` doesn't take arguments")
+            .to_compile_error()
+            .into();
+    }
+    let input = parse_macro_input!(input as ItemFn);
+    if let Err(e) = check_placement_attr(&input.attrs, "itcm", true) {
+        return e.to_compile_error().into();
+    }
+    let section = format!(".itcm.text.{}", input.sig.ident);
+    quote! {
+        #[link_section = #section]
+        #[inline(never)]
+        #[instruction_set(arm::a32)]
+        #input
+    }
+    .into()
+}
+
+/// Places a `static` in the DTCM (Data Tightly Coupled Memory), 16 KiB of memory that the
+/// ARM9 reads and writes without waiting for the bus or the cache.
+///
+/// The static is put in a `.dtcm.data.<name>` section, which the BlocksDS linker script
+/// copies to DTCM at startup. The stacks are in DTCM too, so keep these small.
+///
+/// The DMA can't access DTCM: don't use these statics as a DMA source or destination.
+/// See `nds_rs::tcm::DtcmCell` for mutable statics.
+///
+/// # Example:
+/// ```rust,no_run
+/// #[dtcm]
+/// static SINE: [i16; 256] = make_sine_table();
+/// ```
+#[proc_macro_attribute]
+pub fn dtcm(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        let args = proc_macro2::TokenStream::from(args);
+        return syn::Error::new(args.span(), "`dtcm` doesn't take arguments")
+            .to_compile_error()
+            .into();
+    }
+    let input = parse_macro_input!(input as Item);
+    let Item::Static(item) = input else {
+        return syn::Error::new(
+            input.span(),
+            "`dtcm` can only be used on statics. Use `itcm` for functions.",
+        )
+        .to_compile_error()
+        .into();
+    };
+    if let Err(e) = check_placement_attr(&item.attrs, "dtcm", false) {
+        return e.to_compile_error().into();
+    }
+    let section = format!(".dtcm.data.{}", item.ident);
+    quote! {
+        #[link_section = #section]
+        #item
+    }
+    .into()
+}
+
+/// Places a function or a `static` in overlay `N`, code and data loaded from the ROM
+/// when needed (See `nds_rs::overlay`).
+///
+/// The item is put in a `.overlayN.text.<name>` or `.overlayN.data.<name>` section, and
+/// wrapped in an `nds_rs::overlay::OverlayItem` (statics) or `nds_rs::overlay::OverlayFn`
+/// (functions) in the main program, so it can only be used through the handle of the
+/// loaded overlay. Statics can't be `mut`, and functions can't be generic, `async`,
+/// `unsafe`, `extern`, or take `self`.
+///
+/// # Example:
+/// ```rust,no_run
+/// #[overlay(2)]
+/// static LEVEL: [u8; 4096] = *include_bytes!("level2.bin");
+///
+/// #[overlay(2)]
+/// fn spawn_enemies(level: &[u8]) { /* ... */ }
+///
+/// let overlay = unsafe { Overlay::load(2, &mut rom)? };
+/// spawn_enemies.get(&overlay)(LEVEL.get(&overlay));
+/// ```
+#[proc_macro_attribute]
+pub fn overlay(args: TokenStream, input: TokenStream) -> TokenStream {
+    let id = parse_macro_input!(args as LitInt);
+    let id = match id.base10_parse::<u32>() {
+        Ok(id) => id,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    match parse_macro_input!(input as Item) {
+        Item::Static(item) => match overlay_static(id, item) {
+            Ok(tokens) => tokens.into(),
+            Err(e) => e.to_compile_error().into(),
+        },
+        Item::Fn(item) => match overlay_fn(id, item) {
+            Ok(tokens) => tokens.into(),
+            Err(e) => e.to_compile_error().into(),
+        },
+        item => syn::Error::new(
+            item.span(),
+            "`overlay` can only be used on functions and statics",
+        )
+        .to_compile_error()
+        .into(),
+    }
+}
+
+/// Wraps `item` in a static `OverlayItem` holding a reference to it. Only the value is in
+/// the overlay, so the id can be read while the overlay isn't loaded
+fn overlay_static(id: u32, item: ItemStatic) -> Result<proc_macro2::TokenStream, syn::Error> {
+    check_placement_attr(&item.attrs, "overlay", false)?;
+    if let StaticMutability::Mut(mutability) = item.mutability {
+        return Err(syn::Error::new(
+            mutability.span(),
+            "Statics in overlays can't be `mut`",
+        ));
+    }
+    let ItemStatic {
+        attrs,
+        vis,
+        ident,
+        ty,
+        expr,
+        ..
+    } = &item;
+    let section = format!(".overlay{id}.data.{ident}");
+    Ok(quote! {
+        #(#attrs)*
+        #vis static #ident: ::nds_rs::overlay::OverlayItem<#ty> = {
+            #[link_section = #section]
+            static VALUE: #ty = #expr;
+            ::nds_rs::overlay::OverlayItem::new(#id, &VALUE)
+        };
+    })
+}
+
+/// Wraps `item` in a static `OverlayFn` holding a pointer to it
+fn overlay_fn(id: u32, mut item: ItemFn) -> Result<proc_macro2::TokenStream, syn::Error> {
+    check_placement_attr(&item.attrs, "overlay", true)?;
+    let sig = &item.sig;
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "Functions in overlays can't be generic",
+        ));
+    }
+    if let Some(asyncness) = sig.asyncness {
+        return Err(syn::Error::new(
+            asyncness.span(),
+            "Functions in overlays can't be `async`",
+        ));
+    }
+    // The pointer can only be called through `Fn`, which is only implemented
+    // by safe functions of the Rust ABI
+    if let Some(unsafety) = sig.unsafety {
+        return Err(syn::Error::new(
+            unsafety.span(),
+            "Functions in overlays can't be `unsafe`",
+        ));
+    }
+    if let Some(abi) = &sig.abi {
+        return Err(syn::Error::new(
+            abi.span(),
+            "Functions in overlays can't be `extern`",
+        ));
+    }
+    let mut inputs = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            FnArg::Typed(PatType { ty, .. }) => inputs.push(ty.clone()),
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new(
+                    receiver.span(),
+                    "Functions in overlays can't take `self`",
+                ))
+            }
+        }
+    }
+    let output = &sig.output;
+    let fn_type = quote!(fn(#(#inputs),*) #output);
+
+    let vis = std::mem::replace(&mut item.vis, Visibility::Inherited);
+    let ident = &item.sig.ident;
+    let section = format!(".overlay{id}.text.{ident}");
+    let docs = item
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .cloned()
+        .collect::<Vec<_>>();
+    item.attrs.retain(|attr| !attr.path().is_ident("doc"));
+    Ok(quote! {
+        #(#docs)*
+        #[allow(non_upper_case_globals)]
+        #vis static #ident: ::nds_rs::overlay::OverlayFn<#fn_type> = {
+            #[link_section = #section]
+            #[inline(never)]
+            #item
+            ::nds_rs::overlay::OverlayFn::new(#id, #ident as #fn_type)
+        };
+    })
+}
+
+/// Converts a PNG or BMP file at build time to data ready for the 2D engines.
+///
+/// The path is relative to the `Cargo.toml` of the crate. Depending on the second
+/// argument, the macro evaluates to:
+/// - `tiles`: a `nds_rs::gfx::TiledImage`, with 8x8 tiles, a map and a palette.
+///   Duplicated tiles are only stored once, and so are flipped tiles unless
+///   `flips = false` (`dedup = false` keeps all the tiles).
+/// - `bitmap`: a `nds_rs::gfx::Bitmap16`, or with `bpp = 8` a
+///   `nds_rs::gfx::BitmapImage8` with a palette.
+/// - `sprite(width, height)`: a `nds_rs::gfx::SpriteSheet`, the image being split
+///   into frames of `width`x`height` pixels, in the order of the 1D mapping.
+///
+/// Options:
+/// - `bpp = 4 | 8 | 16`: bits per pixel, 4 by default for tiles and sprites, 16 for
+///   bitmaps.
+/// - `colors = N`: the maximum number of colors of the palette, including the
+///   transparent color at index 0. Images with more colors are reduced.
+/// - `transparent = 0xRRGGBB`: a color treated as transparent, in addition to
+///   transparent pixels.
+///
+/// The palette of indexed images is kept when it fits.
+///
+/// # Example:
+/// ```rust,no_run
+/// static LEVEL: TiledImage = include_image!("gfx/level.png", tiles, bpp = 8);
+/// static HERO: SpriteSheet = include_image!("gfx/hero.png", sprite(16, 32), transparent = 0xFF00FF);
+/// static TITLE: Bitmap16 = include_image!("gfx/title.bmp", bitmap);
+/// ```
+#[proc_macro]
+pub fn include_image(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as image::Args);
+    match image::expand(args) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Converts a map made with [Tiled](https://www.mapeditor.org) (`.tmx` or `.json`) at
+/// build time, into a module holding its tiles, palette, backgrounds and objects.
+///
+/// The module has:
+/// - `MAP`: a `nds_rs::gfx::TiledMap`, with a `nds_rs::gfx::MapLayer` per tile layer.
+///   The top layer goes to BG0, the one below to BG1, and so on; an `int` property
+///   `bg` on a layer chooses its background. The layers are laid out in screen blocks,
+///   ready to be copied to the map base of a text background.
+/// - `Class`: an enum with a variant per class of object, e.g. `Class::BigEnemy` for
+///   `big_enemy`.
+/// - a static of `nds_rs::gfx::Spawn<Class>` per object layer, e.g. `ENEMIES` for the
+///   layer `enemies`, with the objects and their properties.
+///
+/// The tilesets must be based on an image, with tiles whose size is a multiple of 8.
+/// With `bpp = 4` (the default), each 8x8 tile gets one of 16 palettes, chosen
+/// automatically, and can have 15 colors. `bpp = 8` shares a palette of 255 colors.
+///
+/// The map is rejected if it doesn't fit in the hardware: more than 4 tile layers,
+/// more than 64x64 tiles, more than 1024 different 8x8 tiles, or rotated tiles.
+///
+/// # Example:
+/// ```rust,no_run
+/// include_tiled!(mod level1 = "maps/level1.tmx");
+///
+/// level1::MAP.tiles.load_bg(Engine::Main, 1, 0);
+/// level1::MAP.palette.load_bg(Engine::Main, 0);
+/// for layer in level1::MAP.layers {
+///     layer.load_bg(Engine::Main, layer.bg as usize * 4);
+/// }
+/// for spawn in &level1::ENEMIES {
+///     match spawn.class {
+///         Some(level1::Class::Bat) => { /* ... */ }
+///         _ => {}
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn include_tiled(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as tiled::Args);
+    match tiled::expand(args) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Converts a sprite at build time, into a module holding its tiles, palette, OBJs and
+/// animations. The sprite can be bigger than an OBJ: it's split into OBJs of
+/// 64x64 pixels and smaller, the ones that stay transparent in all the frames being
+/// dropped.
+///
+/// The path leads to either:
+/// - the JSON data of a sheet exported by [Aseprite](https://www.aseprite.org), as a
+///   hash or an array. Trimmed frames are supported, the durations of the frames are
+///   converted to video frames, and each tag becomes an animation with its direction
+///   and repeats.
+/// - a PNG or BMP image, split into frames of `frame(width, height)` pixels line by
+///   line (the whole image by default), shown for `duration = N` video frames each (8
+///   by default).
+///
+/// The module has:
+/// - `SPRITE`: a `nds_rs::sprite::MetaSprite` with the frames. Identical frames share
+///   their tiles.
+/// - a static of `nds_rs::sprite::Animation` per tag, e.g. `RUN_LEFT` for the tag
+///   `run left`, or `ALL` with all the frames when there are no tags.
+///
+/// Options:
+/// - `bpp = 4 | 8`: bits per pixel, 4 by default.
+/// - `colors = N`: the maximum number of colors of the palette shared by the frames,
+///   including the transparent color at index 0.
+/// - `transparent = 0xRRGGBB`: a color treated as transparent, in addition to
+///   transparent pixels.
+///
+/// The tiles must fit in the 32 KiB the OBJs can use with the 1D mapping with a
+/// boundary of 32 bytes.
+///
+/// # Example:
+/// ```rust,no_run
+/// include_sprite!(mod hero = "gfx/hero.json");
+/// include_sprite!(mod coin = "gfx/coin.png", frame(16, 16), duration = 6);
+///
+/// hero::SPRITE.tiles.load_sprite(Engine::Main, 0);
+/// hero::SPRITE.palette.load_sprite(Engine::Main, 0);
+/// let mut animation = SpriteAnimation::new(&hero::SPRITE, &hero::WALK, 0);
+/// loop {
+///     animation.tick();
+///     animation.place(&mut objs, x, y);
+///     // ...
+/// }
+/// ```
+#[proc_macro]
+pub fn include_sprite(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as sprite::Args);
+    match sprite::expand(args) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Converts a bitmap font at build time to a `nds_rs::font::Font`, for variable width
+/// text.
+///
+/// The path is relative to the `Cargo.toml` of the crate, and leads to either:
+/// - a [BMFont](https://www.angelcode.com/products/bmfont/) descriptor (`.fnt`), in the
+///   text or XML format, with the PNG or BMP images of its pages. The glyphs are the
+///   opaque pixels of the pages, or their light pixels if the pages have no
+///   transparency. The kerning pairs are kept.
+/// - a BDF font (`.bdf`).
+///
+/// The glyphs keep one bit per pixel. `chars = "..."` only keeps the listed characters
+/// (and `?`, drawn for the missing ones), to make big fonts smaller.
+///
+/// # Example:
+/// ```rust,no_run
+/// static FONT: Font = include_font!("fonts/ui.fnt");
+/// static DIGITS: Font = include_font!("fonts/big.bdf", chars = "0123456789:");
+/// ```
+#[proc_macro]
+pub fn include_font(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as font::Args);
+    match font::expand(args) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,25 @@
+//! Names of the items generated from the names of assets
+
+use syn::Ident;
+
+/// The words of `name`, split on anything that isn't a letter or a digit
+fn words(name: &str) -> impl Iterator<Item = &str> {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+pub fn camel_case(name: &str) -> String {
+    words(name)
+        .map(|word| word[..1].to_uppercase() + &word[1..])
+        .collect()
+}
+
+pub fn screaming_snake_case(name: &str) -> String {
+    let words: Vec<_> = words(name).map(str::to_uppercase).collect();
+    words.join("_")
+}
+
+/// `name` as an identifier, if it's a valid one
+pub fn identifier(name: &str) -> Option<Ident> {
+    syn::parse_str(name).ok()
+}
@@ -0,0 +1,347 @@
+//! Implementation of `include_sprite!`
+
+use std::path::PathBuf;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    Ident, LitInt, LitStr, Token, Visibility,
+};
+
+use crate::{
+    image::{convert, decode},
+    names::{identifier, screaming_snake_case},
+};
+
+mod aseprite;
+mod layout;
+
+use layout::Rect;
+
+pub struct Args {
+    vis: Visibility,
+    name: Ident,
+    path: LitStr,
+    frame: Option<(usize, usize, Span)>,
+    duration: Option<(u16, Span)>,
+    bpp: Option<(usize, Span)>,
+    transparent: Option<[u8; 3]>,
+    colors: Option<(usize, Span)>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        input.parse::<Token![mod]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let path = input.parse()?;
+        let mut args = Args {
+            vis,
+            name,
+            path,
+            frame: None,
+            duration: None,
+            bpp: None,
+            transparent: None,
+            colors: None,
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let option: Ident = input.parse()?;
+            if option == "frame" {
+                let content;
+                parenthesized!(content in input);
+                let width: LitInt = content.parse()?;
+                content.parse::<Token![,]>()?;
+                let height: LitInt = content.parse()?;
+                args.frame = Some((width.base10_parse()?, height.base10_parse()?, option.span()));
+                continue;
+            }
+            input.parse::<Token![=]>()?;
+            match option.to_string().as_str() {
+                "duration" => {
+                    let duration: LitInt = input.parse()?;
+                    args.duration = Some((duration.base10_parse()?, duration.span()));
+                }
+                "bpp" => {
+                    let bpp: LitInt = input.parse()?;
+                    args.bpp = Some((bpp.base10_parse()?, bpp.span()));
+                }
+                "transparent" => {
+                    let color: LitInt = input.parse()?;
+                    let [_, r, g, b] = color.base10_parse::<u32>()?.to_be_bytes();
+                    args.transparent = Some([r, g, b]);
+                }
+                "colors" => {
+                    let colors: LitInt = input.parse()?;
+                    args.colors = Some((colors.base10_parse()?, colors.span()));
+                }
+                _ => return Err(syn::Error::new(
+                    option.span(),
+                    "Unknown option. Expected `frame`, `duration`, `bpp`, `transparent` or `colors`",
+                )),
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// An animation: its name, frames with their duration in video frames, and repeats
+struct Timeline {
+    name: String,
+    frames: Vec<(usize, u16)>,
+    repeat: u16,
+}
+
+/// Converts milliseconds to video frames, at 60 frames per second
+fn video_frames(milliseconds: u32) -> u16 {
+    ((milliseconds as f64 * 60.0 / 1000.0).round() as u16).max(1)
+}
+
+pub fn expand(args: Args) -> syn::Result<TokenStream> {
+    let error = |message: String| syn::Error::new(args.path.span(), message);
+
+    let (bpp, bpp_span) = args.bpp.unwrap_or((4, args.name.span()));
+    if bpp != 4 && bpp != 8 {
+        return Err(syn::Error::new(
+            bpp_span,
+            "Expected a depth of 4 or 8 bits per pixel",
+        ));
+    }
+    let max_colors = 1 << bpp;
+    let colors = match args.colors {
+        Some((colors, span)) if colors < 2 || colors > max_colors => {
+            return Err(syn::Error::new(
+                span,
+                format!("Expected between 2 and {max_colors} colors"),
+            ))
+        }
+        Some((colors, _)) => colors,
+        None => max_colors,
+    };
+
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+    let path = PathBuf::from(root).join(args.path.value());
+    let read_image = |path: &PathBuf| {
+        let bytes = std::fs::read(path)
+            .map_err(|e| error(format!("Couldn't read `{}`: {e}", path.display())))?;
+        decode::decode(&bytes).map_err(|e| error(format!("`{}`: {e}", path.display())))
+    };
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+    let (files, frames, timelines) = if is_json {
+        if let Some((.., span)) = args.frame {
+            return Err(syn::Error::new(
+                span,
+                "The frames of Aseprite sheets are in their JSON data",
+            ));
+        }
+        if let Some((_, span)) = args.duration {
+            return Err(syn::Error::new(
+                span,
+                "The durations of Aseprite sheets are in their JSON data",
+            ));
+        }
+        let sheet =
+            aseprite::read(&path).map_err(|e| error(format!("`{}`: {e}", path.display())))?;
+        let image = read_image(&sheet.image)?;
+        let mut frames = Vec::new();
+        for frame in &sheet.frames {
+            let src = Rect {
+                x: frame.x,
+                y: frame.y,
+                width: frame.width,
+                height: frame.height,
+            };
+            let (width, height) = (frame.source_width, frame.source_height);
+            let cropped = layout::crop(&image, src, width, height, frame.offset_x, frame.offset_y)
+                .map_err(|e| error(format!("`{}`: {e}", sheet.image.display())))?;
+            frames.push(cropped);
+        }
+        let duration = |frame: usize| video_frames(sheet.frames[frame].duration);
+        let timelines = match sheet.tags.is_empty() {
+            true => vec![Timeline {
+                name: "all".into(),
+                frames: (0..frames.len()).map(|i| (i, duration(i))).collect(),
+                repeat: 0,
+            }],
+            false => sheet
+                .tags
+                .iter()
+                .map(|tag| Timeline {
+                    name: tag.name.clone(),
+                    frames: tag
+                        .timeline()
+                        .into_iter()
+                        .map(|i| (i, duration(i)))
+                        .collect(),
+                    repeat: tag.repeat,
+                })
+                .collect(),
+        };
+        (vec![path.clone(), sheet.image], frames, timelines)
+    } else {
+        let image = read_image(&path)?;
+        let (width, height, span) =
+            args.frame
+                .unwrap_or((image.width, image.height, args.name.span()));
+        if width == 0
+            || height == 0
+            || !image.width.is_multiple_of(width)
+            || !image.height.is_multiple_of(height)
+        {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "The image ({}x{}) can't be split into frames of {width}x{height} pixels",
+                    image.width, image.height
+                ),
+            ));
+        }
+        let mut frames = Vec::new();
+        for y in (0..image.height).step_by(height) {
+            for x in (0..image.width).step_by(width) {
+                let src = Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                };
+                frames.push(layout::crop(&image, src, width, height, 0, 0).map_err(error)?);
+            }
+        }
+        let duration = args.duration.map_or(8, |(duration, _)| duration.max(1));
+        let timelines = vec![Timeline {
+            name: "all".into(),
+            frames: (0..frames.len()).map(|i| (i, duration)).collect(),
+            repeat: 0,
+        }];
+        (vec![path.clone()], frames, timelines)
+    };
+
+    let output = layout::convert(&frames, bpp, colors, args.transparent)
+        .map_err(|e| error(format!("`{}`: {e}", path.display())))?;
+
+    let files = files.iter().map(|file| file.to_string_lossy());
+    let words = convert::pack_tiles(&output.tiles, bpp);
+    let words_len = words.len();
+    let palette = &output.palette;
+    let palette_len = palette.len();
+    let bpp = match bpp {
+        4 => quote!(::nds_rs::gfx::Bpp::Four),
+        _ => quote!(::nds_rs::gfx::Bpp::Eight),
+    };
+    let pieces = output.pieces.iter().map(|piece| {
+        let (x, y) = (piece.rect.x as i16, piece.rect.y as i16);
+        let (shape, size) = shape_size(piece.rect.width, piece.rect.height);
+        let tile = piece.tile as u16;
+        quote! {
+            ::nds_rs::sprite::Piece {
+                x: #x,
+                y: #y,
+                shape: ::nds_rs::sprite::Shape::#shape,
+                size: ::nds_rs::sprite::Size::#size,
+                tile: #tile,
+            }
+        }
+    });
+    let pieces_len = output.pieces.len();
+    let offsets = output.frames.iter().map(|&offset| offset as u16);
+    let frames_len = output.frames.len();
+
+    let mut names = [
+        "SPRITE",
+        "WORDS",
+        "PALETTE",
+        "PIECES",
+        "FRAMES",
+        "ANIMATIONS",
+    ]
+    .map(String::from)
+    .to_vec();
+    let mut animations = Vec::new();
+    let mut idents = Vec::new();
+    for timeline in &timelines {
+        let name = &timeline.name;
+        let ident = identifier(&screaming_snake_case(name))
+            .ok_or_else(|| error(format!("The tag `{name}` can't be the name of a static")))?;
+        if names.contains(&ident.to_string()) {
+            return Err(error(format!(
+                "The tag `{name}` is named like another static (`{ident}`)"
+            )));
+        }
+        names.push(ident.to_string());
+        let frames = timeline.frames.iter().map(|&(frame, duration)| {
+            let frame = frame as u16;
+            quote!(::nds_rs::sprite::AnimationFrame { frame: #frame, duration: #duration })
+        });
+        let repeat = timeline.repeat;
+        let doc = format!("The animation `{name}`");
+        animations.push(quote! {
+            #[doc = #doc]
+            pub static #ident: ::nds_rs::sprite::Animation = ::nds_rs::sprite::Animation {
+                name: #name,
+                frames: &[#(#frames),*],
+                repeat: #repeat,
+            };
+        });
+        idents.push(ident);
+    }
+    let animations_len = idents.len();
+    let (width, height) = (output.width as u16, output.height as u16);
+
+    let (vis, name) = (&args.vis, &args.name);
+    let doc = format!("Converted from `{}`", args.path.value());
+    Ok(quote! {
+        #[doc = #doc]
+        #vis mod #name {
+            // Rebuilds when the sheet or its image change
+            #(const _: &[u8] = include_bytes!(#files);)*
+
+            static WORDS: [u32; #words_len] = [#(#words),*];
+            static PALETTE: [u16; #palette_len] = [#(#palette),*];
+            static PIECES: [::nds_rs::sprite::Piece; #pieces_len] = [#(#pieces),*];
+            static FRAMES: [u16; #frames_len] = [#(#offsets),*];
+            static ANIMATIONS: [&::nds_rs::sprite::Animation; #animations_len] = [#(&#idents),*];
+
+            pub static SPRITE: ::nds_rs::sprite::MetaSprite = ::nds_rs::sprite::MetaSprite {
+                tiles: ::nds_rs::gfx::Tiles::new(#bpp, &WORDS),
+                palette: ::nds_rs::gfx::Palette::new(&PALETTE),
+                width: #width,
+                height: #height,
+                pieces: &PIECES,
+                frames: &FRAMES,
+                animations: &ANIMATIONS,
+            };
+
+            #(#animations)*
+        }
+    })
+}
+
+/// The variants of `Shape` and `Size` of an OBJ of `width`x`height` pixels
+fn shape_size(width: usize, height: usize) -> (Ident, Ident) {
+    let shape = match width.cmp(&height) {
+        std::cmp::Ordering::Equal => "ShapeSquare",
+        std::cmp::Ordering::Greater => "ShapeWide",
+        std::cmp::Ordering::Less => "ShapeTall",
+    };
+    let size = match (width.max(height), width.min(height)) {
+        (8, 8) | (16, 8) => "SizeSmall",
+        (16, 16) | (32, 8) => "SizeMed",
+        (32, 32) | (32, 16) => "SizeBig",
+        _ => "SizeMax",
+    };
+    (
+        Ident::new(shape, Span::call_site()),
+        Ident::new(size, Span::call_site()),
+    )
+}
@@ -0,0 +1,178 @@
+//! Reading of the JSON data exported by Aseprite with its sprite sheets
+
+use std::path::{Path, PathBuf};
+
+use serde_json::Value as Json;
+
+/// A sprite sheet and its data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    pub image: PathBuf,
+    pub frames: Vec<Frame>,
+    pub tags: Vec<Tag>,
+}
+
+/// A frame, maybe trimmed, and where it is in the sheet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Rectangle of the sheet holding the frame
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// Position of this rectangle in the untrimmed frame
+    pub offset_x: usize,
+    pub offset_y: usize,
+    /// Size of the untrimmed frame
+    pub source_width: usize,
+    pub source_height: usize,
+    /// Duration in milliseconds
+    pub duration: u32,
+}
+
+/// A tag, naming an animation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub direction: Direction,
+    /// Number of times the animation plays, 0 to loop forever
+    pub repeat: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+impl Tag {
+    /// The frames played by the tag, in order
+    pub fn timeline(&self) -> Vec<usize> {
+        let forward = self.from..=self.to;
+        let inner = self.from + 1..self.to;
+        match self.direction {
+            Direction::Forward => forward.collect(),
+            Direction::Reverse => forward.rev().collect(),
+            Direction::PingPong => forward.chain(inner.rev()).collect(),
+            Direction::PingPongReverse => forward.rev().chain(inner).collect(),
+        }
+    }
+}
+
+fn field<'a>(value: &'a Json, name: &str) -> Result<&'a Json, String> {
+    value.get(name).ok_or_else(|| format!("missing `{name}`"))
+}
+
+fn usize_field(value: &Json, name: &str) -> Result<usize, String> {
+    field(value, name)?
+        .as_u64()
+        .map(|n| n as usize)
+        .ok_or_else(|| format!("`{name}` isn't a positive integer"))
+}
+
+/// Reads the data of a sheet, exported as a hash or as an array
+pub fn read(path: &Path) -> Result<Sheet, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Couldn't read `{}`: {e}", path.display()))?;
+    let root: Json = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    let meta = field(&root, "meta")?;
+    let image = field(meta, "image")?
+        .as_str()
+        .ok_or("`image` isn't a string")?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    // The hash keeps the order of the file, which is the order of the frames
+    let frames: Vec<&Json> = match field(&root, "frames")? {
+        Json::Object(frames) => frames.values().collect(),
+        Json::Array(frames) => frames.iter().collect(),
+        _ => return Err("`frames` isn't an object or an array".into()),
+    };
+    if frames.is_empty() {
+        return Err("the sheet has no frames".into());
+    }
+    let frames = frames
+        .into_iter()
+        .map(frame)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tags = Vec::new();
+    for tag in meta
+        .get("frameTags")
+        .and_then(Json::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let name = field(tag, "name")?.as_str().unwrap_or_default().to_owned();
+        let (from, to) = (usize_field(tag, "from")?, usize_field(tag, "to")?);
+        if from > to || to >= frames.len() {
+            return Err(format!("the frames of the tag `{name}` don't exist"));
+        }
+        let direction = match tag.get("direction").and_then(Json::as_str) {
+            None | Some("forward") => Direction::Forward,
+            Some("reverse") => Direction::Reverse,
+            Some("pingpong") => Direction::PingPong,
+            Some("pingpong_reverse") => Direction::PingPongReverse,
+            Some(direction) => {
+                return Err(format!(
+                    "unknown direction `{direction}` of the tag `{name}`"
+                ))
+            }
+        };
+        // A string since Aseprite 1.3
+        let repeat = match tag.get("repeat") {
+            None => Some(0),
+            Some(Json::String(repeat)) => repeat.parse().ok(),
+            Some(repeat) => repeat.as_u64().and_then(|n| n.try_into().ok()),
+        }
+        .ok_or_else(|| format!("invalid `repeat` of the tag `{name}`"))?;
+        tags.push(Tag {
+            name,
+            from,
+            to,
+            direction,
+            repeat,
+        });
+    }
+
+    Ok(Sheet {
+        image: dir.join(image),
+        frames,
+        tags,
+    })
+}
+
+fn frame(frame: &Json) -> Result<Frame, String> {
+    if frame.get("rotated").and_then(Json::as_bool) == Some(true) {
+        return Err("rotated frames aren't supported".into());
+    }
+    let rect = field(frame, "frame")?;
+    let (width, height) = (usize_field(rect, "w")?, usize_field(rect, "h")?);
+    let (offset_x, offset_y, source_width, source_height) =
+        match (frame.get("spriteSourceSize"), frame.get("sourceSize")) {
+            (Some(trim), Some(source)) => (
+                usize_field(trim, "x")?,
+                usize_field(trim, "y")?,
+                usize_field(source, "w")?,
+                usize_field(source, "h")?,
+            ),
+            _ => (0, 0, width, height),
+        };
+    if offset_x + width > source_width || offset_y + height > source_height {
+        return Err("a trimmed frame is out of its frame".into());
+    }
+    Ok(Frame {
+        x: usize_field(rect, "x")?,
+        y: usize_field(rect, "y")?,
+        width,
+        height,
+        offset_x,
+        offset_y,
+        source_width,
+        source_height,
+        duration: frame.get("duration").and_then(Json::as_u64).unwrap_or(100) as u32,
+    })
+}
@@ -0,0 +1,256 @@
+//! Layout of the frames of a sprite in OBJs and tiles
+
+use std::collections::HashMap;
+
+use crate::image::{
+    convert::{self, Tile},
+    decode::Rgba,
+    SPRITE_SIZES,
+};
+
+/// A rectangle of pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// An OBJ showing a part of every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Piece {
+    pub rect: Rect,
+    /// First tile relative to the frame, in units of 32 bytes
+    pub tile: usize,
+}
+
+/// The frames laid out in OBJs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    /// Tiles of the different frames, frame after frame
+    pub tiles: Vec<Tile>,
+    pub palette: Vec<u16>,
+    /// Size of the frames in pixels, a multiple of 8
+    pub width: usize,
+    pub height: usize,
+    pub pieces: Vec<Piece>,
+    /// First tile of each frame, in units of 32 bytes
+    pub frames: Vec<usize>,
+}
+
+/// Copies the rectangle `src` of `image` to (`x`, `y`) on a transparent canvas of
+/// `width`x`height` pixels
+pub fn crop(
+    image: &Rgba,
+    src: Rect,
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+) -> Result<Rgba, String> {
+    if src.x + src.width > image.width || src.y + src.height > image.height {
+        return Err(format!(
+            "the frame at ({}, {}) is out of the image",
+            src.x, src.y
+        ));
+    }
+    let mut pixels = vec![[0; 4]; width * height];
+    let mut indices = image.indices.as_ref().map(|_| vec![0; width * height]);
+    for line in 0..src.height {
+        let from = (src.y + line) * image.width + src.x;
+        let to = (y + line) * width + x;
+        pixels[to..to + src.width].copy_from_slice(&image.pixels[from..from + src.width]);
+        if let (Some(indices), Some(source)) = (&mut indices, &image.indices) {
+            indices[to..to + src.width].copy_from_slice(&source[from..from + src.width]);
+        }
+    }
+    Ok(Rgba {
+        width,
+        height,
+        pixels,
+        palette: image.palette.clone(),
+        indices,
+    })
+}
+
+/// Splits a length in pixels, a multiple of 8, into lengths of OBJs, the largest first
+fn segments(length: usize) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < length {
+        let size = [64, 32, 16, 8]
+            .into_iter()
+            .find(|&size| size <= length - start)
+            .unwrap();
+        segments.push((start, size));
+        start += size;
+    }
+    segments
+}
+
+/// Splits a rectangle in the sizes of OBJs. Only 64x8, 64x16 and their transposes
+/// aren't sizes, and are halved
+fn split(rect: Rect, pieces: &mut Vec<Rect>) {
+    if SPRITE_SIZES.contains(&(rect.width, rect.height)) {
+        pieces.push(rect);
+    } else if rect.width > rect.height {
+        let width = rect.width / 2;
+        split(Rect { width, ..rect }, pieces);
+        split(
+            Rect {
+                x: rect.x + width,
+                width,
+                ..rect
+            },
+            pieces,
+        );
+    } else {
+        let height = rect.height / 2;
+        split(Rect { height, ..rect }, pieces);
+        split(
+            Rect {
+                y: rect.y + height,
+                height,
+                ..rect
+            },
+            pieces,
+        );
+    }
+}
+
+/// Converts frames of the same size to tiles with a shared palette of at most
+/// `colors` colors, and OBJs covering their opaque parts.
+///
+/// Identical frames share their tiles.
+pub fn convert(
+    frames: &[Rgba],
+    bpp: usize,
+    colors: usize,
+    key: Option<[u8; 3]>,
+) -> Result<Output, String> {
+    let (source_width, source_height) = (frames[0].width, frames[0].height);
+    if frames
+        .iter()
+        .any(|frame| (frame.width, frame.height) != (source_width, source_height))
+    {
+        return Err("the frames don't have the same size".into());
+    }
+    let (width, height) = (
+        source_width.next_multiple_of(8),
+        source_height.next_multiple_of(8),
+    );
+
+    // All the frames one below the other, to share the palette
+    let keep_indices = frames
+        .iter()
+        .all(|frame| frame.indices.is_some() && frame.palette == frames[0].palette);
+    let mut stacked = Rgba {
+        width,
+        height: height * frames.len(),
+        pixels: Vec::new(),
+        palette: frames[0].palette.clone().filter(|_| keep_indices),
+        indices: keep_indices.then(Vec::new),
+    };
+    for frame in frames {
+        let padded = crop(
+            frame,
+            Rect {
+                x: 0,
+                y: 0,
+                width: source_width,
+                height: source_height,
+            },
+            width,
+            height,
+            0,
+            0,
+        )?;
+        stacked.pixels.extend(padded.pixels);
+        if let (Some(indices), Some(padded)) = (&mut stacked.indices, padded.indices) {
+            indices.extend(padded);
+        }
+    }
+    let indexed = convert::index(&stacked, colors, key);
+    let frame_indices: Vec<&[u8]> = indexed.indices.chunks_exact(width * height).collect();
+
+    // The OBJs with opaque pixels in at least one frame
+    let mut rects = Vec::new();
+    for &(y, rect_height) in &segments(height) {
+        for &(x, rect_width) in &segments(width) {
+            let rect = Rect {
+                x,
+                y,
+                width: rect_width,
+                height: rect_height,
+            };
+            split(rect, &mut rects);
+        }
+    }
+    rects.retain(|rect| {
+        frame_indices.iter().any(|indices| {
+            (rect.y..rect.y + rect.height).any(|y| {
+                let start = y * width + rect.x;
+                indices[start..start + rect.width].iter().any(|&i| i != 0)
+            })
+        })
+    });
+    if rects.len() > 128 {
+        return Err(format!(
+            "the sprite needs {} OBJs, but there are only 128",
+            rects.len()
+        ));
+    }
+
+    let units_per_tile = bpp / 4;
+    let mut pieces = Vec::new();
+    let mut frame_units = 0;
+    for &rect in &rects {
+        pieces.push(Piece {
+            rect,
+            tile: frame_units,
+        });
+        frame_units += rect.width / 8 * (rect.height / 8) * units_per_tile;
+    }
+
+    let mut tiles = Vec::new();
+    let mut offsets = Vec::new();
+    let mut known: HashMap<Vec<Tile>, usize> = HashMap::new();
+    for indices in &frame_indices {
+        let mut frame_tiles = Vec::new();
+        for rect in &rects {
+            for ty in (rect.y..rect.y + rect.height).step_by(8) {
+                for tx in (rect.x..rect.x + rect.width).step_by(8) {
+                    let mut tile = [0; 64];
+                    for y in 0..8 {
+                        let start = (ty + y) * width + tx;
+                        tile[y * 8..y * 8 + 8].copy_from_slice(&indices[start..start + 8]);
+                    }
+                    frame_tiles.push(tile);
+                }
+            }
+        }
+        let offset = *known.entry(frame_tiles.clone()).or_insert_with(|| {
+            let offset = tiles.len() * units_per_tile;
+            tiles.extend(frame_tiles);
+            offset
+        });
+        offsets.push(offset);
+    }
+    let units = tiles.len() * units_per_tile;
+    if units > 1024 {
+        return Err(format!(
+            "the frames need {} KiB of tiles, but sprites can only use 32 KiB",
+            units * 32 / 1024
+        ));
+    }
+
+    Ok(Output {
+        tiles,
+        palette: indexed.palette,
+        width,
+        height,
+        pieces,
+        frames: offsets,
+    })
+}
@@ -0,0 +1,260 @@
+//! Implementation of `include_tiled!`
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, LitInt, LitStr, Token, Visibility,
+};
+
+use crate::{
+    image::convert,
+    names::{camel_case, identifier, screaming_snake_case},
+};
+
+mod layout;
+mod parse;
+
+use parse::{Layer, Object, Property, Value};
+
+pub struct Args {
+    vis: Visibility,
+    name: Ident,
+    path: LitStr,
+    bpp: Option<(usize, Span)>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        input.parse::<Token![mod]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let path = input.parse()?;
+        let mut args = Args {
+            vis,
+            name,
+            path,
+            bpp: None,
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let option: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match option.to_string().as_str() {
+                "bpp" => {
+                    let bpp: LitInt = input.parse()?;
+                    args.bpp = Some((bpp.base10_parse()?, bpp.span()));
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        option.span(),
+                        "Unknown option. Expected `bpp`",
+                    ))
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+pub fn expand(args: Args) -> syn::Result<TokenStream> {
+    let error = |message: String| syn::Error::new(args.path.span(), message);
+
+    let (bpp, bpp_span) = args.bpp.unwrap_or((4, args.name.span()));
+    if bpp != 4 && bpp != 8 {
+        return Err(syn::Error::new(
+            bpp_span,
+            "Expected a depth of 4 or 8 bits per pixel",
+        ));
+    }
+
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+    let path = PathBuf::from(root).join(args.path.value());
+    let map = parse::read_map(&path).map_err(|e| error(format!("`{}`: {e}", path.display())))?;
+    let mut images = Vec::new();
+    for tileset in &map.tilesets {
+        let bytes = std::fs::read(&tileset.image)
+            .map_err(|e| error(format!("Couldn't read `{}`: {e}", tileset.image.display())))?;
+        let image = crate::image::decode::decode(&bytes)
+            .map_err(|e| error(format!("`{}`: {e}", tileset.image.display())))?;
+        images.push(image);
+    }
+    let output = layout::convert(&map, &images, bpp)
+        .map_err(|e| error(format!("`{}`: {e}", path.display())))?;
+
+    let files = map.files.iter().map(|file| file.to_string_lossy());
+    let words = convert::pack_tiles(&output.tiles, bpp);
+    let words_len = words.len();
+    let palette = &output.palette;
+    let palette_len = palette.len();
+    let bpp = match bpp {
+        4 => quote!(::nds_rs::gfx::Bpp::Four),
+        _ => quote!(::nds_rs::gfx::Bpp::Eight),
+    };
+    let size = match (output.columns, output.rows) {
+        (32, 32) => quote!(TextSmall),
+        (64, 32) => quote!(TextWide),
+        (32, 64) => quote!(TextTall),
+        _ => quote!(TextBig),
+    };
+
+    let mut layers = Vec::new();
+    let mut layer_statics = Vec::new();
+    for layer in &output.layers {
+        let entries_name = format_ident!("BG{}_ENTRIES", layer.bg);
+        let entries = &layer.entries;
+        let len = entries.len();
+        layer_statics.push(quote! {
+            static #entries_name: [::nds_rs::sys::background::TileMapEntry16; #len] =
+                [#(::nds_rs::sys::background::TileMapEntry16::from_bits(#entries)),*];
+        });
+        let (name, bg) = (&layer.name, format_ident!("Layer{}", layer.bg));
+        layers.push(quote! {
+            ::nds_rs::gfx::MapLayer {
+                name: #name,
+                bg: ::nds_rs::sys::background::Layer::#bg,
+                size: ::nds_rs::sys::background::BgSize::#size,
+                entries: &#entries_name,
+            }
+        });
+    }
+    let layers_len = layers.len();
+    let (width, height) = (output.width as u16, output.height as u16);
+
+    // The classes of all the objects, as variants of `Class`
+    let classes: BTreeSet<&str> = map
+        .layers
+        .iter()
+        .flat_map(|layer| match layer {
+            Layer::Objects { objects, .. } => objects.as_slice(),
+            Layer::Tiles { .. } => &[],
+        })
+        .map(|object| object.class.as_str())
+        .filter(|class| !class.is_empty())
+        .collect();
+    let mut variants = Vec::new();
+    for class in &classes {
+        let variant = identifier(&camel_case(class)).ok_or_else(|| {
+            error(format!(
+                "The class `{class}` can't be the name of a variant"
+            ))
+        })?;
+        if variants.contains(&variant) {
+            return Err(error(format!("Two classes are named like `{variant}`")));
+        }
+        variants.push(variant);
+    }
+
+    let mut spawn_tables = Vec::new();
+    let mut names = vec!["MAP".to_owned()];
+    for layer in &map.layers {
+        let Layer::Objects { name, objects } = layer else {
+            continue;
+        };
+        let ident = identifier(&screaming_snake_case(name)).ok_or_else(|| {
+            error(format!(
+                "The object layer `{name}` can't be the name of a static"
+            ))
+        })?;
+        if names.contains(&ident.to_string()) {
+            return Err(error(format!(
+                "The object layer `{name}` is named like another static (`{ident}`)"
+            )));
+        }
+        names.push(ident.to_string());
+        let spawns = objects
+            .iter()
+            .map(|object| spawn(object, &classes, &variants));
+        let len = objects.len();
+        let doc = format!("Objects of the layer `{name}`");
+        spawn_tables.push(quote! {
+            #[doc = #doc]
+            pub static #ident: [::nds_rs::gfx::Spawn<Class>; #len] = [#(#spawns),*];
+        });
+    }
+
+    let (vis, name) = (&args.vis, &args.name);
+    let doc = format!("Converted from `{}`", args.path.value());
+    Ok(quote! {
+        #[doc = #doc]
+        #vis mod #name {
+            // Rebuilds when the map, the tilesets or their images change
+            #(const _: &[u8] = include_bytes!(#files);)*
+
+            /// Classes of the objects
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub enum Class {
+                #(#variants),*
+            }
+
+            static WORDS: [u32; #words_len] = [#(#words),*];
+            static PALETTE: [u16; #palette_len] = [#(#palette),*];
+            #(#layer_statics)*
+            static LAYERS: [::nds_rs::gfx::MapLayer; #layers_len] = [#(#layers),*];
+
+            pub static MAP: ::nds_rs::gfx::TiledMap = ::nds_rs::gfx::TiledMap {
+                tiles: ::nds_rs::gfx::Tiles::new(#bpp, &WORDS),
+                palette: ::nds_rs::gfx::Palette::new(&PALETTE),
+                width: #width,
+                height: #height,
+                layers: &LAYERS,
+            };
+
+            #(#spawn_tables)*
+        }
+    })
+}
+
+fn spawn(object: &Object, classes: &BTreeSet<&str>, variants: &[Ident]) -> TokenStream {
+    let class = match classes.iter().position(|&class| class == object.class) {
+        Some(i) => {
+            let variant = &variants[i];
+            quote!(Some(Class::#variant))
+        }
+        None => quote!(None),
+    };
+    let (id, name) = (object.id, &object.name);
+    let (x, y) = (object.x.round() as i32, object.y.round() as i32);
+    let (width, height) = (object.width.round() as u32, object.height.round() as u32);
+    let properties = object.properties.iter().map(property);
+    quote! {
+        ::nds_rs::gfx::Spawn {
+            id: #id,
+            name: #name,
+            class: #class,
+            x: #x,
+            y: #y,
+            width: #width,
+            height: #height,
+            properties: &[#(#properties),*],
+        }
+    }
+}
+
+fn property(property: &Property) -> TokenStream {
+    let value = match &property.value {
+        Value::Bool(b) => quote!(Bool(#b)),
+        Value::Int(i) => {
+            let i = *i as i32;
+            quote!(Int(#i))
+        }
+        Value::Float(f) => {
+            let f = *f as f32;
+            quote!(Float(#f))
+        }
+        Value::String(s) => quote!(String(#s)),
+    };
+    let name = &property.name;
+    quote! {
+        ::nds_rs::gfx::Property {
+            name: #name,
+            value: ::nds_rs::gfx::PropertyValue::#value,
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Conversion of Tiled maps to tiles, palettes and screen blocks
+
+use std::collections::BTreeSet;
+
+use super::parse::{self, Layer, Map, Tileset, Value};
+use crate::image::{
+    convert::{self, Tile, HFLIP, VFLIP},
+    decode::Rgba,
+};
+
+/// Size of a screen block in tiles
+pub const BLOCK: usize = 32;
+/// Width or height of the biggest text backgrounds in tiles
+pub const MAX_SIZE: usize = 64;
+/// Tiles a map entry can refer to
+pub const MAX_TILES: usize = 1024;
+
+/// A tile layer laid out in screen blocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgLayer {
+    pub name: String,
+    /// The background, from 0 to 3
+    pub bg: u8,
+    /// Map entries, screen block by screen block
+    pub entries: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub tiles: Vec<Tile>,
+    pub palette: Vec<u16>,
+    /// Size of the map in 8x8 tiles
+    pub width: usize,
+    pub height: usize,
+    /// Size of the backgrounds in tiles: 32 or 64
+    pub columns: usize,
+    pub rows: usize,
+    pub layers: Vec<BgLayer>,
+}
+
+/// An 8x8 tile of a tileset, where the tile of Tiled it's part of comes from
+struct Source {
+    tileset: usize,
+    id: usize,
+    pixels: [[u8; 4]; 64],
+}
+
+/// Converts the tile layers of `map`, whose tilesets have the images `images`, to
+/// tiles in `bpp` bits per pixel.
+///
+/// With 4 bits per pixel each tile gets one of 16 palettes of 15 colors (and the
+/// transparent one), shared by the tiles with the fewest added colors.
+pub fn convert(map: &Map, images: &[Rgba], bpp: usize) -> Result<Output, String> {
+    let tile_layers: Vec<_> = map
+        .layers
+        .iter()
+        .filter_map(|layer| match layer {
+            Layer::Tiles {
+                name,
+                cells,
+                properties,
+            } => Some((name, cells, properties)),
+            Layer::Objects { .. } => None,
+        })
+        .collect();
+    if tile_layers.len() > 4 {
+        return Err(format!(
+            "the map has {} tile layers, but the DS only has 4 backgrounds",
+            tile_layers.len()
+        ));
+    }
+    if !map.tile_width.is_multiple_of(8) || !map.tile_height.is_multiple_of(8) {
+        return Err(format!(
+            "the size of the tiles of the map ({}x{}) must be a multiple of 8",
+            map.tile_width, map.tile_height
+        ));
+    }
+    for tileset in &map.tilesets {
+        if (tileset.tile_width, tileset.tile_height) != (map.tile_width, map.tile_height) {
+            return Err(format!(
+                "the tiles of the tileset `{}` ({}x{}) must have the size of the tiles of the map ({}x{})",
+                tileset.name, tileset.tile_width, tileset.tile_height, map.tile_width, map.tile_height
+            ));
+        }
+    }
+    let (sub_width, sub_height) = (map.tile_width / 8, map.tile_height / 8);
+    let (width, height) = (map.width * sub_width, map.height * sub_height);
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(format!(
+            "the map is {width}x{height} tiles of 8x8 pixels, but backgrounds are at most {MAX_SIZE}x{MAX_SIZE}"
+        ));
+    }
+
+    // The 8x8 tiles of the tiles of Tiled used by the map, the first one being
+    // transparent for empty cells
+    let gids: Vec<u32> = tile_layers
+        .iter()
+        .flat_map(|(_, cells, _)| cells.iter().map(|&cell| cell & parse::GID_MASK))
+        .filter(|&gid| gid != 0)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let mut sources = vec![Source {
+        tileset: 0,
+        id: 0,
+        pixels: [[0; 4]; 64],
+    }];
+    for &gid in &gids {
+        let (index, tileset) = map
+            .tilesets
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, tileset)| tileset.first_gid <= gid)
+            .ok_or_else(|| format!("the tile {gid} isn't in a tileset"))?;
+        let id = (gid - tileset.first_gid) as usize;
+        if id >= tileset.tile_count {
+            return Err(format!("the tile {gid} isn't in a tileset"));
+        }
+        for pixels in split(tileset, &images[index], id)? {
+            sources.push(Source {
+                tileset: index,
+                id,
+                pixels,
+            });
+        }
+    }
+
+    let Indexed {
+        tiles,
+        banks,
+        palette,
+    } = match bpp {
+        4 => index_banked(map, &sources)?,
+        _ => index_shared(&sources),
+    };
+    let set = convert::dedup_tiles(&tiles, true);
+    if set.tiles.len() > MAX_TILES {
+        return Err(format!(
+            "the map uses {} different 8x8 tiles, but maps can only use {MAX_TILES}",
+            set.tiles.len()
+        ));
+    }
+    let entries: Vec<u16> = set
+        .map
+        .iter()
+        .zip(&banks)
+        .map(|(&entry, &bank)| entry | (bank as u16) << 12)
+        .collect();
+
+    let bgs = assign_backgrounds(&tile_layers)?;
+    let (columns, rows) = (size(width), size(height));
+    let mut layers = Vec::new();
+    for ((name, cells, _), bg) in tile_layers.iter().zip(bgs) {
+        if cells.len() != map.width * map.height {
+            return Err(format!(
+                "the layer `{name}` doesn't have the size of the map"
+            ));
+        }
+        let mut layer = vec![0; width * height];
+        for (i, &cell) in cells.iter().enumerate() {
+            let gid = cell & parse::GID_MASK;
+            if gid == 0 {
+                continue;
+            }
+            if cell & parse::FLIPPED_DIAGONALLY != 0 {
+                return Err(format!(
+                    "the layer `{name}` has rotated tiles, but the DS can only flip them"
+                ));
+            }
+            let hflip = cell & parse::FLIPPED_HORIZONTALLY != 0;
+            let vflip = cell & parse::FLIPPED_VERTICALLY != 0;
+            let first = 1 + gids.binary_search(&gid).unwrap() * sub_width * sub_height;
+            let (x, y) = ((i % map.width) * sub_width, (i / map.width) * sub_height);
+            for sy in 0..sub_height {
+                for sx in 0..sub_width {
+                    let from_x = if hflip { sub_width - 1 - sx } else { sx };
+                    let from_y = if vflip { sub_height - 1 - sy } else { sy };
+                    let mut entry = entries[first + from_y * sub_width + from_x];
+                    // The empty tile stays as is
+                    if hflip && entry != 0 {
+                        entry ^= HFLIP;
+                    }
+                    if vflip && entry != 0 {
+                        entry ^= VFLIP;
+                    }
+                    layer[(y + sy) * width + x + sx] = entry;
+                }
+            }
+        }
+        layers.push(BgLayer {
+            name: name.to_string(),
+            bg,
+            entries: screen_blocks(&layer, width, columns, rows),
+        });
+    }
+
+    Ok(Output {
+        tiles: set.tiles,
+        palette,
+        width,
+        height,
+        columns,
+        rows,
+        layers,
+    })
+}
+
+/// The 8x8 tiles of the tile `id` of `tileset`, line by line. Pixels of the
+/// transparent color of the tileset are made transparent
+fn split(tileset: &Tileset, image: &Rgba, id: usize) -> Result<Vec<[[u8; 4]; 64]>, String> {
+    let columns = tileset.columns.max(1);
+    let left = tileset.margin + (id % columns) * (tileset.tile_width + tileset.spacing);
+    let top = tileset.margin + (id / columns) * (tileset.tile_height + tileset.spacing);
+    if left + tileset.tile_width > image.width || top + tileset.tile_height > image.height {
+        return Err(format!(
+            "the tile {id} of the tileset `{}` is out of its image",
+            tileset.name
+        ));
+    }
+    let mut tiles = Vec::new();
+    for ty in 0..tileset.tile_height / 8 {
+        for tx in 0..tileset.tile_width / 8 {
+            tiles.push(std::array::from_fn(|i| {
+                let (x, y) = (left + tx * 8 + i % 8, top + ty * 8 + i / 8);
+                let pixel = image.pixels[y * image.width + x];
+                match convert::is_transparent(pixel, tileset.transparent) {
+                    true => [0; 4],
+                    false => pixel,
+                }
+            }));
+        }
+    }
+    Ok(tiles)
+}
+
+/// Tiles with the palette of each one
+struct Indexed {
+    tiles: Vec<Tile>,
+    /// Palette of 16 colors of each tile, always 0 with 8 bits per pixel
+    banks: Vec<u8>,
+    palette: Vec<u16>,
+}
+
+/// Indexes the tiles with a palette of 256 colors
+fn index_shared(sources: &[Source]) -> Indexed {
+    let strip = Rgba {
+        width: 8,
+        height: sources.len() * 8,
+        pixels: sources.iter().flat_map(|s| s.pixels).collect(),
+        palette: None,
+        indices: None,
+    };
+    let indexed = convert::index(&strip, 256, None);
+    let tiles = convert::split_tiles(&indexed).expect("the strip is 8 pixels wide");
+    Indexed {
+        tiles,
+        banks: vec![0; sources.len()],
+        palette: indexed.palette,
+    }
+}
+
+/// Indexes the tiles with up to 16 palettes of 16 colors
+fn index_banked(map: &Map, sources: &[Source]) -> Result<Indexed, String> {
+    let colors: Vec<BTreeSet<u16>> = sources
+        .iter()
+        .map(|source| {
+            let opaque = source.pixels.iter().filter(|p| p[3] != 0);
+            opaque.map(|&p| convert::bgr555(p)).collect()
+        })
+        .collect();
+    for (source, colors) in sources.iter().zip(&colors) {
+        if colors.len() > 15 {
+            return Err(format!(
+                "an 8x8 part of the tile {} of the tileset `{}` has {} colors, but tiles with 4 bits per pixel can only have 15. Use `bpp = 8`",
+                source.id,
+                map.tilesets[source.tileset].name,
+                colors.len()
+            ));
+        }
+    }
+    let (banks, bank_of) = pack_banks(&colors)?;
+
+    let tiles = sources
+        .iter()
+        .zip(&bank_of)
+        .map(|(source, &bank)| {
+            let bank = &banks[bank as usize];
+            source.pixels.map(|p| match p[3] {
+                0 => 0,
+                _ => 1 + bank.iter().position(|&c| c == convert::bgr555(p)).unwrap() as u8,
+            })
+        })
+        .collect();
+    let mut palette = Vec::new();
+    for bank in &banks {
+        palette.push(0);
+        palette.extend(bank);
+        palette.resize(palette.len().next_multiple_of(16), 0);
+    }
+    Ok(Indexed {
+        tiles,
+        banks: bank_of,
+        palette,
+    })
+}
+
+/// Groups the color sets into palettes of 15 colors, putting each set (the biggest
+/// first) in the palette it adds the fewest colors to.
+/// Returns the palettes, and the palette of each set
+pub fn pack_banks(sets: &[BTreeSet<u16>]) -> Result<(Vec<Vec<u16>>, Vec<u8>), String> {
+    let mut order: Vec<usize> = (0..sets.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sets[i].len()));
+    let mut banks: Vec<BTreeSet<u16>> = Vec::new();
+    let mut bank_of = vec![0; sets.len()];
+    for i in order {
+        let best = banks
+            .iter()
+            .enumerate()
+            .map(|(b, bank)| (b, sets[i].difference(bank).count(), bank.len()))
+            .filter(|&(_, added, len)| len + added <= 15)
+            .min_by_key(|&(_, added, _)| added);
+        let bank = match best {
+            Some((b, _, _)) => b,
+            None => {
+                banks.push(BTreeSet::new());
+                banks.len() - 1
+            }
+        };
+        banks[bank].extend(&sets[i]);
+        bank_of[i] = bank as u8;
+    }
+    if banks.len() > 16 {
+        return Err(format!(
+            "the tiles need {} palettes of 15 colors, but there are only 16. Use `bpp = 8`",
+            banks.len()
+        ));
+    }
+    if banks.is_empty() {
+        banks.push(BTreeSet::new());
+    }
+    let banks = banks.into_iter().map(|b| b.into_iter().collect()).collect();
+    Ok((banks, bank_of))
+}
+
+/// Chooses the background of each tile layer (given from the bottom to the top): the
+/// top one is BG0, which is drawn above the others at the same priority. An `int`
+/// property `bg` chooses another one
+fn assign_backgrounds(
+    layers: &[(&String, &Vec<u32>, &Vec<parse::Property>)],
+) -> Result<Vec<u8>, String> {
+    let mut bgs = Vec::new();
+    for (i, (name, _, properties)) in layers.iter().enumerate() {
+        let property = properties.iter().find(|p| p.name == "bg");
+        let bg = match property.map(|p| &p.value) {
+            Some(Value::Int(bg @ 0..=3)) => *bg as u8,
+            Some(_) => {
+                return Err(format!(
+                    "the `bg` property of the layer `{name}` must be an int from 0 to 3"
+                ))
+            }
+            None => (layers.len() - 1 - i) as u8,
+        };
+        if let Some(j) = bgs.iter().position(|&other| other == bg) {
+            return Err(format!(
+                "the layers `{}` and `{name}` both use BG{bg}",
+                layers[j].0
+            ));
+        }
+        bgs.push(bg);
+    }
+    Ok(bgs)
+}
+
+/// Size of the background fitting `tiles` tiles
+fn size(tiles: usize) -> usize {
+    if tiles <= BLOCK {
+        BLOCK
+    } else {
+        MAX_SIZE
+    }
+}
+
+/// Lays out the entries of a map `width` tiles wide in screen blocks of 32x32 tiles,
+/// for a background of `columns`x`rows` tiles. The blocks are placed line by line
+pub fn screen_blocks(entries: &[u16], width: usize, columns: usize, rows: usize) -> Vec<u16> {
+    let mut blocks = vec![0; columns * rows];
+    let blocks_wide = columns / BLOCK;
+    for (y, line) in entries.chunks_exact(width).enumerate() {
+        for (x, &entry) in line.iter().enumerate() {
+            let block = (y / BLOCK) * blocks_wide + x / BLOCK;
+            blocks[block * BLOCK * BLOCK + (y % BLOCK) * BLOCK + x % BLOCK] = entry;
+        }
+    }
+    blocks
+}
@@ -0,0 +1,525 @@
+//! Reading of Tiled maps and tilesets, in the TMX (XML) and JSON formats
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value as Json;
+
+/// Bits of the global tile IDs of the cells
+pub const FLIPPED_HORIZONTALLY: u32 = 1 << 31;
+pub const FLIPPED_VERTICALLY: u32 = 1 << 30;
+pub const FLIPPED_DIAGONALLY: u32 = 1 << 29;
+pub const ROTATED_HEXAGONAL: u32 = 1 << 28;
+pub const GID_MASK: u32 =
+    !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    /// Size in tiles of the map
+    pub width: usize,
+    pub height: usize,
+    /// Size in pixels of the tiles
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub tilesets: Vec<Tileset>,
+    /// From the bottom to the top
+    pub layers: Vec<Layer>,
+    /// Files read, to rebuild when they change
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tileset {
+    pub first_gid: u32,
+    pub name: String,
+    pub tile_width: usize,
+    pub tile_height: usize,
+    pub tile_count: usize,
+    pub columns: usize,
+    pub margin: usize,
+    pub spacing: usize,
+    pub image: PathBuf,
+    /// Color of the image treated as transparent
+    pub transparent: Option<[u8; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Layer {
+    Tiles {
+        name: String,
+        /// Global tile IDs with their flip flags, line by line
+        cells: Vec<u32>,
+        properties: Vec<Property>,
+    },
+    Objects {
+        name: String,
+        objects: Vec<Object>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl Value {
+    /// Converts the text of a property of type `kind` (`string` when missing)
+    fn parse(kind: &str, text: &str) -> Result<Self, String> {
+        let error = || format!("invalid {kind} property `{text}`");
+        Ok(match kind {
+            "bool" => Self::Bool(text == "true"),
+            "int" | "object" => Self::Int(text.parse().map_err(|_| error())?),
+            "float" => Self::Float(text.parse().map_err(|_| error())?),
+            _ => Self::String(text.into()),
+        })
+    }
+}
+
+/// Reads a map, recognized by its extension
+pub fn read_map(path: &Path) -> Result<Map, String> {
+    let text = read_text(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut map = match extension(path).as_str() {
+        "tmx" => tmx::map(&text, dir)?,
+        "json" | "tmj" => json::map(&text, dir)?,
+        _ => return Err(format!("`{}` isn't a TMX or JSON map", path.display())),
+    };
+    map.files.insert(0, path.to_owned());
+    Ok(map)
+}
+
+/// Reads an external tileset, recognized by its extension
+fn read_tileset(path: &Path, first_gid: u32) -> Result<Tileset, String> {
+    let text = read_text(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    match extension(path).as_str() {
+        "tsx" => {
+            let document = roxmltree::Document::parse(&text)
+                .map_err(|e| format!("`{}`: {e}", path.display()))?;
+            tmx::tileset(document.root_element(), first_gid, dir)
+        }
+        "json" | "tsj" => {
+            let tileset: Json =
+                serde_json::from_str(&text).map_err(|e| format!("`{}`: {e}", path.display()))?;
+            json::tileset(&tileset, first_gid, dir)
+        }
+        _ => Err(format!("`{}` isn't a TSX or JSON tileset", path.display())),
+    }
+}
+
+fn read_text(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Couldn't read `{}`: {e}", path.display()))
+}
+
+fn extension(path: &Path) -> String {
+    let extension = path.extension().unwrap_or_default();
+    extension.to_string_lossy().to_lowercase()
+}
+
+/// Parses `#RRGGBB`, `RRGGBB` or `#AARRGGBB`
+fn color(text: &str) -> Result<[u8; 3], String> {
+    let digits = text.trim_start_matches('#');
+    let value = u32::from_str_radix(digits, 16).map_err(|_| format!("invalid color `{text}`"))?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Ok([r, g, b])
+}
+
+/// Decodes the cells of a layer encoded in base64, maybe compressed
+fn decode_cells(data: &str, compression: &str) -> Result<Vec<u32>, String> {
+    let bytes = base64(data)?;
+    let bytes = match compression {
+        "" => bytes,
+        "zlib" | "gzip" => {
+            let mut inflated = Vec::new();
+            let result = match compression {
+                "zlib" => flate2::read::ZlibDecoder::new(&bytes[..]).read_to_end(&mut inflated),
+                _ => flate2::read::GzDecoder::new(&bytes[..]).read_to_end(&mut inflated),
+            };
+            result.map_err(|e| format!("invalid {compression} layer data: {e}"))?;
+            inflated
+        }
+        _ => {
+            return Err(format!(
+                "{compression} compressed layers aren't supported, use zlib, gzip or CSV"
+            ))
+        }
+    };
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn base64(text: &str) -> Result<Vec<u8>, String> {
+    let digit = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let digits = text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+        .map(|c| digit(c).ok_or("invalid base64 layer data"))
+        .collect::<Result<Vec<u8>, _>>()?;
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &d)| bits | (d as u32) << (18 - 6 * i));
+        bytes.extend(&bits.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(bytes)
+}
+
+fn parse_cells_csv(text: &str) -> Result<Vec<u32>, String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|cell| !cell.is_empty())
+        .map(|cell| cell.parse().map_err(|_| format!("invalid cell `{cell}`")))
+        .collect()
+}
+
+mod tmx {
+    use super::*;
+    use roxmltree::Node;
+
+    fn attribute<T: std::str::FromStr>(node: Node, name: &str) -> Result<T, String> {
+        let value = node
+            .attribute(name)
+            .ok_or_else(|| format!("`<{}>` without `{name}`", node.tag_name().name()))?;
+        value.parse().map_err(|_| {
+            format!(
+                "invalid `{name}` in `<{}>`: `{value}`",
+                node.tag_name().name()
+            )
+        })
+    }
+
+    fn attribute_or<T: std::str::FromStr>(node: Node, name: &str, default: T) -> Result<T, String> {
+        match node.attribute(name) {
+            Some(_) => attribute(node, name),
+            None => Ok(default),
+        }
+    }
+
+    fn children<'a, 'i>(node: Node<'a, 'i>, tag: &'a str) -> impl Iterator<Item = Node<'a, 'i>> {
+        node.children().filter(move |child| child.has_tag_name(tag))
+    }
+
+    pub fn map(text: &str, dir: &Path) -> Result<Map, String> {
+        let document = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+        let root = document.root_element();
+        if root.attribute("infinite") == Some("1") {
+            return Err("infinite maps aren't supported".into());
+        }
+        let mut map = Map {
+            width: attribute(root, "width")?,
+            height: attribute(root, "height")?,
+            tile_width: attribute(root, "tilewidth")?,
+            tile_height: attribute(root, "tileheight")?,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            files: Vec::new(),
+        };
+        for node in children(root, "tileset") {
+            let first_gid = attribute(node, "firstgid")?;
+            let tileset = match node.attribute("source") {
+                Some(source) => {
+                    let path = dir.join(source);
+                    map.files.push(path.clone());
+                    read_tileset(&path, first_gid)?
+                }
+                None => tileset(node, first_gid, dir)?,
+            };
+            map.files.push(tileset.image.clone());
+            map.tilesets.push(tileset);
+        }
+        layers(root, &mut map.layers)?;
+        Ok(map)
+    }
+
+    /// Reads the layers of `node`, flattening the groups
+    fn layers(node: Node, layers: &mut Vec<Layer>) -> Result<(), String> {
+        for child in node.children() {
+            match child.tag_name().name() {
+                "layer" => layers.push(tile_layer(child)?),
+                "objectgroup" => layers.push(Layer::Objects {
+                    name: child.attribute("name").unwrap_or_default().into(),
+                    objects: children(child, "object")
+                        .map(object)
+                        .collect::<Result<_, _>>()?,
+                }),
+                "group" => self::layers(child, layers)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn tile_layer(node: Node) -> Result<Layer, String> {
+        let data = children(node, "data")
+            .next()
+            .ok_or("`<layer>` without `<data>`")?;
+        let text = data.text().unwrap_or_default();
+        let cells = match data.attribute("encoding") {
+            Some("csv") => parse_cells_csv(text)?,
+            Some("base64") => {
+                decode_cells(text, data.attribute("compression").unwrap_or_default())?
+            }
+            Some(encoding) => return Err(format!("unknown layer encoding `{encoding}`")),
+            None => children(data, "tile")
+                .map(|tile| attribute_or(tile, "gid", 0))
+                .collect::<Result<_, _>>()?,
+        };
+        if children(data, "chunk").next().is_some() {
+            return Err("infinite maps aren't supported".into());
+        }
+        Ok(Layer::Tiles {
+            name: node.attribute("name").unwrap_or_default().into(),
+            cells,
+            properties: properties(node)?,
+        })
+    }
+
+    fn object(node: Node) -> Result<Object, String> {
+        let class = node.attribute("class").or(node.attribute("type"));
+        Ok(Object {
+            id: attribute_or(node, "id", 0)?,
+            name: node.attribute("name").unwrap_or_default().into(),
+            class: class.unwrap_or_default().into(),
+            x: attribute_or(node, "x", 0.0)?,
+            y: attribute_or(node, "y", 0.0)?,
+            width: attribute_or(node, "width", 0.0)?,
+            height: attribute_or(node, "height", 0.0)?,
+            properties: properties(node)?,
+        })
+    }
+
+    fn properties(node: Node) -> Result<Vec<Property>, String> {
+        let Some(properties) = children(node, "properties").next() else {
+            return Ok(Vec::new());
+        };
+        children(properties, "property")
+            .filter(|p| p.attribute("type") != Some("class"))
+            .map(|p| {
+                // Multiline strings are in the text of the element
+                let text = p.attribute("value").or(p.text()).unwrap_or_default();
+                Ok(Property {
+                    name: attribute(p, "name")?,
+                    value: Value::parse(p.attribute("type").unwrap_or("string"), text)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn tileset(node: Node, first_gid: u32, dir: &Path) -> Result<Tileset, String> {
+        let name: String = node.attribute("name").unwrap_or_default().into();
+        let image = children(node, "image")
+            .next()
+            .ok_or_else(|| format!("the tileset `{name}` isn't based on a single image"))?;
+        Ok(Tileset {
+            first_gid,
+            tile_width: attribute(node, "tilewidth")?,
+            tile_height: attribute(node, "tileheight")?,
+            tile_count: attribute(node, "tilecount")?,
+            columns: attribute(node, "columns")?,
+            margin: attribute_or(node, "margin", 0)?,
+            spacing: attribute_or(node, "spacing", 0)?,
+            image: dir.join(attribute::<String>(image, "source")?),
+            transparent: image.attribute("trans").map(color).transpose()?,
+            name,
+        })
+    }
+}
+
+mod json {
+    use super::*;
+
+    fn field<'a>(value: &'a Json, name: &str) -> Result<&'a Json, String> {
+        value.get(name).ok_or_else(|| format!("missing `{name}`"))
+    }
+
+    fn usize_field(value: &Json, name: &str) -> Result<usize, String> {
+        field(value, name)?
+            .as_u64()
+            .map(|n| n as usize)
+            .ok_or_else(|| format!("`{name}` isn't a positive integer"))
+    }
+
+    fn usize_or(value: &Json, name: &str, default: usize) -> Result<usize, String> {
+        match value.get(name) {
+            Some(_) => usize_field(value, name),
+            None => Ok(default),
+        }
+    }
+
+    fn str_or<'a>(value: &'a Json, name: &str) -> &'a str {
+        value.get(name).and_then(Json::as_str).unwrap_or_default()
+    }
+
+    fn f64_or(value: &Json, name: &str) -> f64 {
+        value.get(name).and_then(Json::as_f64).unwrap_or_default()
+    }
+
+    pub fn map(text: &str, dir: &Path) -> Result<Map, String> {
+        let root: Json = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if root.get("infinite").and_then(Json::as_bool) == Some(true) {
+            return Err("infinite maps aren't supported".into());
+        }
+        let mut map = Map {
+            width: usize_field(&root, "width")?,
+            height: usize_field(&root, "height")?,
+            tile_width: usize_field(&root, "tilewidth")?,
+            tile_height: usize_field(&root, "tileheight")?,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            files: Vec::new(),
+        };
+        for tileset in field(&root, "tilesets")?.as_array().into_iter().flatten() {
+            let first_gid = usize_field(tileset, "firstgid")? as u32;
+            let tileset = match tileset.get("source").and_then(Json::as_str) {
+                Some(source) => {
+                    let path = dir.join(source);
+                    map.files.push(path.clone());
+                    read_tileset(&path, first_gid)?
+                }
+                None => self::tileset(tileset, first_gid, dir)?,
+            };
+            map.files.push(tileset.image.clone());
+            map.tilesets.push(tileset);
+        }
+        layers(field(&root, "layers")?, &mut map.layers)?;
+        Ok(map)
+    }
+
+    /// Reads the layers of `array`, flattening the groups
+    fn layers(array: &Json, layers: &mut Vec<Layer>) -> Result<(), String> {
+        for layer in array.as_array().into_iter().flatten() {
+            let name = str_or(layer, "name").to_owned();
+            match str_or(layer, "type") {
+                "tilelayer" => {
+                    if layer.get("chunks").is_some() {
+                        return Err("infinite maps aren't supported".into());
+                    }
+                    let data = field(layer, "data")?;
+                    let cells = match data.as_str() {
+                        Some(text) => decode_cells(text, str_or(layer, "compression"))?,
+                        None => data
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .map(|cell| cell.as_u64().map(|gid| gid as u32).ok_or("invalid cell"))
+                            .collect::<Result<_, _>>()?,
+                    };
+                    let properties = properties(layer)?;
+                    layers.push(Layer::Tiles {
+                        name,
+                        cells,
+                        properties,
+                    });
+                }
+                "objectgroup" => {
+                    let objects = field(layer, "objects")?.as_array().into_iter().flatten();
+                    layers.push(Layer::Objects {
+                        name,
+                        objects: objects.map(object).collect::<Result<_, _>>()?,
+                    });
+                }
+                "group" => self::layers(field(layer, "layers")?, layers)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn object(object: &Json) -> Result<Object, String> {
+        let class = match str_or(object, "class") {
+            "" => str_or(object, "type"),
+            class => class,
+        };
+        Ok(Object {
+            id: usize_or(object, "id", 0)? as u32,
+            name: str_or(object, "name").into(),
+            class: class.into(),
+            x: f64_or(object, "x"),
+            y: f64_or(object, "y"),
+            width: f64_or(object, "width"),
+            height: f64_or(object, "height"),
+            properties: properties(object)?,
+        })
+    }
+
+    fn properties(value: &Json) -> Result<Vec<Property>, String> {
+        let properties = value.get("properties").and_then(Json::as_array);
+        properties
+            .into_iter()
+            .flatten()
+            .filter(|p| str_or(p, "type") != "class")
+            .map(|p| {
+                let name = str_or(p, "name").to_owned();
+                let value = match field(p, "value")? {
+                    Json::Bool(b) => Value::Bool(*b),
+                    Json::Number(n) if str_or(p, "type") == "float" || n.is_f64() => {
+                        Value::Float(n.as_f64().unwrap_or_default())
+                    }
+                    Json::Number(n) => Value::Int(n.as_i64().ok_or("invalid integer property")?),
+                    Json::String(s) => Value::String(s.clone()),
+                    _ => return Err(format!("unsupported type of the property `{name}`")),
+                };
+                Ok(Property { name, value })
+            })
+            .collect()
+    }
+
+    pub fn tileset(tileset: &Json, first_gid: u32, dir: &Path) -> Result<Tileset, String> {
+        let name = str_or(tileset, "name").to_owned();
+        let Some(image) = tileset.get("image").and_then(Json::as_str) else {
+            return Err(format!(
+                "the tileset `{name}` isn't based on a single image"
+            ));
+        };
+        let transparent = match str_or(tileset, "transparentcolor") {
+            "" => None,
+            text => Some(color(text)?),
+        };
+        Ok(Tileset {
+            first_gid,
+            tile_width: usize_field(tileset, "tilewidth")?,
+            tile_height: usize_field(tileset, "tileheight")?,
+            tile_count: usize_field(tileset, "tilecount")?,
+            columns: usize_field(tileset, "columns")?,
+            margin: usize_or(tileset, "margin", 0)?,
+            spacing: usize_or(tileset, "spacing", 0)?,
+            image: dir.join(image),
+            transparent,
+            name,
+        })
+    }
+}
@@ -0,0 +1,75 @@
+//! Compares the ways of filling a bitmap through `embedded-graphics`, and finds the
+//! span length where filling with a DMA channel gets faster than the CPU, which
+//! `DMA_MIN_LEN` in `src/embedded_graphics/fill.rs` is based on.
+//!
+//! Run it in NO$GBA: the cycles of each section are printed to its debugger console.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, format, vec};
+
+use embedded_graphics_core::{pixelcolor::Bgr555, prelude::*, primitives::Rectangle};
+use nds_rs::{
+    embedded_graphics::{Framebuffer, GraphicsTarget},
+    entry, profile,
+    sys::video::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    Hw,
+};
+
+/// Lengths of the spans filled by the CPU and the DMA, in halfwords
+const SPANS: [usize; 10] = [4, 8, 16, 24, 32, 48, 64, 128, 256, 1024];
+
+fn framebuffer() -> Box<Framebuffer> {
+    let pixels = (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
+    // Built on the heap, a whole framebuffer doesn't fit in the stack
+    vec![0; pixels].into_boxed_slice().try_into().unwrap()
+}
+
+#[entry]
+pub fn main(mut hw: Hw) -> ! {
+    let mut target = framebuffer();
+    let mut buffer = framebuffer();
+    let color = Bgr555::new(31, 0, 15);
+
+    {
+        let mut graphics = GraphicsTarget::new(&mut target, &mut buffer, &mut hw.dma.ch3);
+        let screen = Rectangle::new(Point::zero(), graphics.size());
+        // Lines shorter than the screen can't be filled in a single transfer
+        let column = Rectangle::new(Point::new(100, 0), Size::new(16, SCREEN_HEIGHT));
+        for (name, area) in [("screen", screen), ("column", column)] {
+            let label = format!("draw_iter {name}");
+            profile!(&label, {
+                let pixels = area.points().map(|point| Pixel(point, color));
+                graphics.draw_iter(pixels).unwrap();
+            });
+            let label = format!("fill_contiguous {name}");
+            profile!(&label, {
+                let colors = core::iter::repeat(color);
+                graphics.fill_contiguous(&area, colors).unwrap();
+            });
+            let label = format!("fill_solid {name}");
+            profile!(&label, {
+                graphics.fill_solid(&area, color).unwrap();
+            });
+        }
+    }
+
+    for len in SPANS {
+        let span = &mut buffer[..len];
+        let label = format!("cpu fill {len}");
+        profile!(&label, {
+            span.fill(0x7FFF);
+        });
+        let label = format!("dma fill {len}");
+        profile!(&label, {
+            hw.dma.ch3.fill(0x7FFF, span);
+        });
+    }
+
+    loop {
+        unsafe { nds_rs::sys::interrupts::swiWaitForVBlank() };
+    }
+}
@@ -0,0 +1,95 @@
+//! Hardware of the ARM7, with the `arm7` feature
+//!
+//! With the `arm7` feature, nds-rs builds programs for the ARM7 instead of the ARM9,
+//! linking libnds7. The ARM9 modules (video, cache...) aren't available, and these
+//! ones are: the [`sound`] channels, the [`spi`] bus and the devices behind it
+//! ([`power`] management and [`touch`] screen), and the real time clock ([`rtc`]).
+//! The [`dma`](crate::dma) channels and the keys of [`input`](crate::input) are
+//! available on both processors.
+//!
+//! The program is built for the `armv4t-nintendo-ds-newlibeabi` target, and its entry point
+//! is marked with [`#[entry]`](crate::entry) like on the ARM9, taking the ARM7 [`Hw`]:
+//! ```rust,no_run
+//! #[entry]
+//! fn main(mut hw: Hw) -> ! {
+//!     hw.sound.enable(127);
+//!     let channel = FifoChannel::<u32>::new(8).unwrap();
+//!     Executor::new().block_on(async {
+//!         loop {
+//!             let request = channel.recv().await;
+//!             /* ... */
+//!         }
+//!     })
+//! }
+//! ```
+//!
+//! Before the entry point, the runtime initializes the interrupts and the FIFO system, and
+//! answers the requests libnds9 makes to the ARM7 (See [`init`]).
+
+use nds_sys::arm7::*;
+use spin::Mutex;
+
+pub mod power;
+pub mod rtc;
+pub mod sound;
+pub mod spi;
+pub mod touch;
+
+use crate::dma::Dma;
+use rtc::Rtc;
+use sound::Sound;
+use spi::Spi;
+
+/// Sets up libnds for the ARM7: the interrupts, the FIFO system, the answers to the
+/// system requests of the ARM9, and the user settings. Called by the runtime before
+/// the entry point
+pub(crate) unsafe fn init() {
+    readUserSettings();
+    irqInit();
+    fifoInit();
+    installSystemFIFO();
+}
+
+/// Returns `true` while the lid is closed
+pub fn is_lid_closed() -> bool {
+    let keys = KeyXyBits::from_bits_retain(unsafe { REG_KEYXY.read_volatile() });
+    keys.contains(KeyXyBits::LID_CLOSED)
+}
+
+#[no_mangle]
+pub static __HW: Mutex<Option<Hw>> = Mutex::new(Some(unsafe { Hw::new() }));
+
+/// The hardware of the ARM7.
+///
+/// Like the ARM9 [`Hw`](crate::Hw), it's passed to the entry point, or taken with
+/// [`take`](Hw::take).
+#[non_exhaustive]
+pub struct Hw {
+    pub spi: Spi,
+    pub sound: Sound,
+    pub rtc: Rtc,
+    pub dma: Dma,
+}
+impl Drop for Hw {
+    fn drop(&mut self) {
+        unsafe {
+            *__HW.lock() = Some(Hw::new());
+        }
+    }
+}
+impl Hw {
+    pub(crate) const unsafe fn new() -> Self {
+        Self {
+            spi: Spi::new(),
+            sound: Sound::new(),
+            rtc: Rtc::new(),
+            dma: Dma::new(),
+        }
+    }
+
+    #[inline]
+    pub fn take() -> Option<Self> {
+        let mut this = __HW.lock();
+        this.take()
+    }
+}
@@ -0,0 +1,73 @@
+//! Power management: backlights, sound amplifier, power LED, battery and power off
+
+use nds_sys::arm7::power::{registers::POWCNT2, *};
+
+use super::spi::{Spi, SpiBaud, SpiDevice};
+
+pub use nds_sys::arm7::power::{PmControl, PowerFlags2};
+
+/// Reads register `reg` of the power management chip
+pub fn read_register(spi: &mut Spi, reg: u8) -> u8 {
+    let mut data = [reg | PM_READ, 0];
+    spi.transfer(SpiDevice::Power, SpiBaud::Mhz1, &mut data);
+    data[1]
+}
+
+/// Writes `value` to register `reg` of the power management chip
+pub fn write_register(spi: &mut Spi, reg: u8, value: u8) {
+    let mut data = [reg & !PM_READ, value];
+    spi.transfer(SpiDevice::Power, SpiBaud::Mhz1, &mut data);
+}
+
+pub fn control(spi: &mut Spi) -> PmControl {
+    PmControl::from_bits_retain(read_register(spi, PM_CONTROL_REG))
+}
+
+pub fn set_control(spi: &mut Spi, control: PmControl) {
+    write_register(spi, PM_CONTROL_REG, control.bits());
+}
+
+/// Sets or clears `flags` in the control register, keeping the others
+pub fn update_control(spi: &mut Spi, flags: PmControl, enabled: bool) {
+    let mut current = control(spi);
+    current.set(flags, enabled);
+    set_control(spi, current);
+}
+
+/// Turns the backlight of each screen on or off
+pub fn set_backlights(spi: &mut Spi, top: bool, bottom: bool) {
+    let mut current = control(spi);
+    current.set(PmControl::BACKLIGHT_TOP, top);
+    current.set(PmControl::BACKLIGHT_BOTTOM, bottom);
+    set_control(spi, current);
+}
+
+/// Turns the speakers on (amplifier on, not muted) or off
+pub fn set_speakers(spi: &mut Spi, enabled: bool) {
+    let mut current = control(spi);
+    current.set(PmControl::SOUND_AMP, enabled);
+    current.set(PmControl::SOUND_MUTE, !enabled);
+    set_control(spi, current);
+}
+
+/// Returns `true` when the battery is low (the power LED is red)
+pub fn is_battery_low(spi: &mut Spi) -> bool {
+    read_register(spi, PM_BATTERY_REG) & 1 != 0
+}
+
+/// Turns the console off
+pub fn power_off(spi: &mut Spi) -> ! {
+    update_control(spi, PmControl::SYSTEM_POWER_OFF, true);
+    loop {
+        crate::interrupts::swi_wait_for_v_blank();
+    }
+}
+
+/// Powers the sound or wireless hardware on or off
+pub fn set_powered(flags: PowerFlags2, powered: bool) {
+    critical_section::with(|_| unsafe {
+        let mut current = PowerFlags2::from_bits_retain(POWCNT2.read_volatile());
+        current.set(flags, powered);
+        POWCNT2.write_volatile(current.bits());
+    });
+}
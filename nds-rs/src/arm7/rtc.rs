@@ -0,0 +1,176 @@
+//! The real time clock
+//!
+//! The clock is read and set with [`Rtc`]. It stores the date and time in BCD, which
+//! [`DateTime::from_bcd`] and [`DateTime::to_bcd`] convert.
+
+use nds_sys::arm7::{
+    rtc::{registers::REG_RTC, *},
+    swiDelay,
+};
+
+/// Wait between the changes of the clock signal
+const DELAY: u32 = 48;
+
+/// A date and time of the real time clock, between 2000 and 2099
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DateTime {
+    pub year: u16,
+    /// From 1 to 12
+    pub month: u8,
+    /// From 1 to 31
+    pub day: u8,
+    /// From 0 to 6, the meaning is up to the program (usually 0 is Sunday)
+    pub weekday: u8,
+    /// From 0 to 23
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+impl DateTime {
+    /// Decodes the 7 bytes of the date and time command: year, month, day, weekday, hour,
+    /// minute and second, in BCD. Returns `None` if a value is out of range
+    pub fn from_bcd(bytes: &[u8; 7], is_24h: bool) -> Option<Self> {
+        let [year, month, day, weekday, hour, minute, second] = *bytes;
+        let pm = hour & RTC_HOUR_PM != 0;
+        let mut hour = from_bcd(hour & 0x3F)?;
+        if !is_24h {
+            // 12 hours mode: 0 to 11, with a flag after noon
+            if hour >= 12 {
+                return None;
+            }
+            if pm {
+                hour += 12;
+            }
+        }
+        let date_time = Self {
+            year: 2000 + from_bcd(year)? as u16,
+            month: from_bcd(month & 0x1F)?,
+            day: from_bcd(day & 0x3F)?,
+            weekday: weekday & 0x07,
+            hour,
+            minute: from_bcd(minute & 0x7F)?,
+            second: from_bcd(second & 0x7F)?,
+        };
+        date_time.is_valid().then_some(date_time)
+    }
+
+    /// Encodes the date and time for the 24 hours mode. Returns `None` if it's invalid
+    pub fn to_bcd(&self) -> Option<[u8; 7]> {
+        if !self.is_valid() {
+            return None;
+        }
+        Some([
+            to_bcd((self.year - 2000) as u8),
+            to_bcd(self.month),
+            to_bcd(self.day),
+            self.weekday,
+            to_bcd(self.hour),
+            to_bcd(self.minute),
+            to_bcd(self.second),
+        ])
+    }
+
+    /// Checks the ranges of the values. The day isn't checked against the month
+    pub fn is_valid(&self) -> bool {
+        (2000..2100).contains(&self.year)
+            && (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.weekday < 7
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+/// Decodes a BCD byte, or returns `None` if a digit is above 9
+pub const fn from_bcd(bcd: u8) -> Option<u8> {
+    let (high, low) = (bcd >> 4, bcd & 0xF);
+    if high > 9 || low > 9 {
+        None
+    } else {
+        Some(high * 10 + low)
+    }
+}
+
+/// Encodes a value below 100 in BCD
+pub const fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+/// The real time clock. Part of the ARM7 [`Hw`](super::Hw)
+pub struct Rtc {
+    _private: (),
+}
+impl Rtc {
+    pub(crate) const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Sends `command` (`RTC_CMD_*`, and [`RTC_READ`] to read) followed by `write`,
+    /// then reads `read`
+    pub fn transaction(&mut self, command: u8, write: &[u8], read: &mut [u8]) {
+        let select = RtcBits::SELECT | RtcBits::SELECT_OUT;
+        let clock = RtcBits::CLOCK | RtcBits::CLOCK_OUT;
+        let set = |bits: RtcBits| unsafe {
+            REG_RTC.write_volatile(bits.bits());
+            swiDelay(DELAY);
+        };
+        let send_bit = |bit: bool| {
+            let data = RtcBits::DATA_OUT | RtcBits::from_bits_retain(bit as u8);
+            set(select | RtcBits::CLOCK_OUT | data);
+            set(select | clock | data);
+        };
+
+        critical_section::with(|_| {
+            set(RtcBits::SELECT_OUT | clock | RtcBits::DATA_OUT | RtcBits::DATA);
+            set(select | clock | RtcBits::DATA_OUT | RtcBits::DATA);
+            // The command is sent from the highest bit, the data from the lowest
+            let command = RTC_COMMAND | command;
+            (0..8)
+                .rev()
+                .for_each(|bit| send_bit(command & (1 << bit) != 0));
+            for byte in write {
+                (0..8).for_each(|bit| send_bit(byte & (1 << bit) != 0));
+            }
+            for byte in read.iter_mut() {
+                *byte = 0;
+                for bit in 0..8 {
+                    set(select | RtcBits::CLOCK_OUT);
+                    set(select | clock);
+                    if unsafe { REG_RTC.read_volatile() } & RtcBits::DATA.bits() != 0 {
+                        *byte |= 1 << bit;
+                    }
+                }
+            }
+            set(RtcBits::SELECT_OUT | clock);
+        });
+    }
+
+    /// Returns `true` if the clock is in the 24 hours mode
+    pub fn is_24h(&mut self) -> bool {
+        let mut status = [0];
+        self.transaction(RTC_CMD_STATUS_1 | RTC_READ, &[], &mut status);
+        status[0] & RTC_STATUS_24H != 0
+    }
+
+    /// Reads the date and time, or returns `None` if the clock holds an invalid value
+    pub fn date_time(&mut self) -> Option<DateTime> {
+        let is_24h = self.is_24h();
+        let mut bytes = [0; 7];
+        self.transaction(RTC_CMD_DATE_TIME | RTC_READ, &[], &mut bytes);
+        DateTime::from_bcd(&bytes, is_24h)
+    }
+
+    /// Sets the date and time, switching the clock to the 24 hours mode.
+    /// Returns `false` if `date_time` is invalid
+    pub fn set_date_time(&mut self, date_time: &DateTime) -> bool {
+        let Some(bytes) = date_time.to_bcd() else {
+            return false;
+        };
+        let mut status = [0];
+        self.transaction(RTC_CMD_STATUS_1 | RTC_READ, &[], &mut status);
+        self.transaction(RTC_CMD_STATUS_1, &[status[0] | RTC_STATUS_24H], &mut []);
+        self.transaction(RTC_CMD_DATE_TIME, &bytes, &mut []);
+        true
+    }
+}
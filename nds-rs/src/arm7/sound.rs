@@ -0,0 +1,185 @@
+//! The 16 sound channels
+//!
+//! Each channel plays samples from memory (PCM or IMA-ADPCM). Channels 8 to 13 can
+//! also play square waves, and channels 14 and 15 noise ([`SoundChannel::play_psg`]).
+//! The ARM7 has to [`enable`](Sound::enable) the sound and the speakers first
+//! (See [`power::set_speakers`](super::power::set_speakers)).
+
+use nds_sys::arm7::{
+    power::PowerFlags2,
+    sound::{registers::*, *},
+};
+
+pub use nds_sys::arm7::sound::{SoundFlags, SOUND_CHANNELS, SOUND_CLOCK};
+
+/// Encoding of the samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundFormat {
+    Pcm8,
+    Pcm16,
+    /// IMA-ADPCM, with a 4-byte header
+    Adpcm,
+}
+
+/// What happens at the end of the samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Stops
+    OneShot,
+    /// Starts again from this offset in the samples, in bytes (a multiple of 4)
+    Loop { start: usize },
+}
+
+/// Samples to play on a channel
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// The samples, aligned to 4 bytes
+    pub data: &'static [u8],
+    pub format: SoundFormat,
+    /// Samples per second
+    pub rate: u32,
+    pub repeat: Repeat,
+}
+
+/// Value of a channel timer for `rate` samples per second
+pub const fn timer_for_rate(rate: u32) -> u16 {
+    let period = match rate {
+        0 => 0x10000,
+        rate => SOUND_CLOCK / rate,
+    };
+    let period = if period < 1 {
+        1
+    } else if period > 0x10000 {
+        0x10000
+    } else {
+        period
+    };
+    (0x10000 - period) as u16
+}
+
+/// Control bits of a channel: `volume` and `pan` from 0 to 127 (64 is the center)
+pub fn control_bits(volume: u8, pan: u8, format: SoundFlags, repeat: SoundFlags) -> SoundFlags {
+    let volume = SoundFlags::from_bits_retain(volume.min(127) as u32);
+    let pan = SoundFlags::from_bits_retain((pan.min(127) as u32) << 16);
+    SoundFlags::ENABLE | volume | pan | format | repeat
+}
+
+/// The sound hardware. Part of the ARM7 [`Hw`](super::Hw)
+pub struct Sound {
+    _private: (),
+}
+impl Sound {
+    pub(crate) const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Powers the sound hardware on and sets the master volume, from 0 to 127
+    pub fn enable(&mut self, volume: u8) {
+        super::power::set_powered(PowerFlags2::SOUND, true);
+        let control = SoundControl::ENABLE | SoundControl::from_bits_retain(volume.min(127) as u16);
+        unsafe {
+            REG_SOUNDCNT.write_volatile(control.bits());
+            REG_SOUNDBIAS.write_volatile(0x200);
+        }
+    }
+
+    /// Stops every channel and powers the sound hardware off
+    pub fn disable(&mut self) {
+        (0..SOUND_CHANNELS).for_each(|n| self.channel(n).stop());
+        unsafe {
+            REG_SOUNDCNT.write_volatile(0);
+        }
+        super::power::set_powered(PowerFlags2::SOUND, false);
+    }
+
+    /// Returns channel `n`. Panics if `n` isn't lower than [`SOUND_CHANNELS`]
+    pub fn channel(&mut self, n: usize) -> SoundChannel<'_> {
+        assert!(
+            n < SOUND_CHANNELS,
+            "There are only {SOUND_CHANNELS} sound channels"
+        );
+        SoundChannel { n, _sound: self }
+    }
+
+    /// Returns the first channel that isn't playing
+    pub fn free_channel(&mut self) -> Option<SoundChannel<'_>> {
+        let n = (0..SOUND_CHANNELS).find(|&n| !is_playing(n))?;
+        Some(self.channel(n))
+    }
+}
+
+fn is_playing(n: usize) -> bool {
+    let control = unsafe { sound_cr(n).read_volatile() };
+    control & SoundFlags::ENABLE.bits() != 0
+}
+
+/// One of the sound channels
+pub struct SoundChannel<'s> {
+    n: usize,
+    _sound: &'s mut Sound,
+}
+impl SoundChannel<'_> {
+    pub fn index(&self) -> usize {
+        self.n
+    }
+
+    /// Plays `sample`, with `volume` and `pan` from 0 to 127 (64 is the center)
+    pub fn play(&mut self, sample: &Sample, volume: u8, pan: u8) {
+        let format = match sample.format {
+            SoundFormat::Pcm8 => SoundFlags::FORMAT_PCM8,
+            SoundFormat::Pcm16 => SoundFlags::FORMAT_PCM16,
+            SoundFormat::Adpcm => SoundFlags::FORMAT_ADPCM,
+        };
+        let (repeat, loop_start) = match sample.repeat {
+            Repeat::OneShot => (SoundFlags::ONE_SHOT, 0),
+            Repeat::Loop { start } => (SoundFlags::REPEAT, start.min(sample.data.len())),
+        };
+        let n = self.n;
+        self.stop();
+        unsafe {
+            sound_sad(n).write_volatile(sample.data.as_ptr() as u32);
+            sound_tmr(n).write_volatile(timer_for_rate(sample.rate));
+            sound_pnt(n).write_volatile((loop_start / 4) as u16);
+            sound_len(n).write_volatile(((sample.data.len() - loop_start) / 4) as u32);
+            sound_cr(n).write_volatile(control_bits(volume, pan, format, repeat).bits());
+        }
+    }
+
+    /// Plays a square wave (channels 8 to 13) with a duty of `(duty + 1) / 8`, or noise
+    /// (channels 14 and 15), at `frequency` Hz
+    pub fn play_psg(&mut self, frequency: u32, duty: u8, volume: u8, pan: u8) {
+        let duty = SoundFlags::from_bits_retain((duty.min(7) as u32) << 24);
+        let n = self.n;
+        self.stop();
+        unsafe {
+            // A period of the square wave is 8 steps
+            sound_tmr(n).write_volatile(timer_for_rate(frequency.saturating_mul(8)));
+            let control = control_bits(volume, pan, SoundFlags::FORMAT_PSG, SoundFlags::REPEAT);
+            sound_cr(n).write_volatile((control | duty).bits());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        unsafe {
+            sound_cr(self.n).write_volatile(0);
+        }
+    }
+
+    /// Returns `false` once a one-shot sample ended
+    pub fn is_playing(&self) -> bool {
+        is_playing(self.n)
+    }
+
+    /// Changes the volume and panning while playing
+    pub fn set_volume(&mut self, volume: u8, pan: u8) {
+        critical_section::with(|_| unsafe {
+            let control = SoundFlags::from_bits_retain(sound_cr(self.n).read_volatile());
+            if control.contains(SoundFlags::ENABLE) {
+                let kept = control - SoundFlags::VOLUME_MASK - SoundFlags::PAN_MASK;
+                let volume = SoundFlags::from_bits_retain(volume.min(127) as u32);
+                let pan = SoundFlags::from_bits_retain((pan.min(127) as u32) << 16);
+                sound_cr(self.n).write_volatile((kept | volume | pan).bits());
+            }
+        });
+    }
+}
@@ -0,0 +1,82 @@
+//! The SPI bus, shared by the power management chip, the firmware and the touch screen
+
+use nds_sys::arm7::spi::{registers::*, SpiFlags};
+
+/// Devices on the SPI bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiDevice {
+    Power,
+    Firmware,
+    Touch,
+}
+impl SpiDevice {
+    const fn flags(self) -> SpiFlags {
+        match self {
+            Self::Power => SpiFlags::DEVICE_POWER,
+            Self::Firmware => SpiFlags::DEVICE_FIRMWARE,
+            Self::Touch => SpiFlags::DEVICE_TOUCH,
+        }
+    }
+}
+
+/// Transfer speed of the SPI bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiBaud {
+    Mhz4,
+    Mhz2,
+    Mhz1,
+    Khz512,
+}
+impl SpiBaud {
+    const fn flags(self) -> SpiFlags {
+        match self {
+            Self::Mhz4 => SpiFlags::BAUD_4MHZ,
+            Self::Mhz2 => SpiFlags::BAUD_2MHZ,
+            Self::Mhz1 => SpiFlags::BAUD_1MHZ,
+            Self::Khz512 => SpiFlags::BAUD_512KHZ,
+        }
+    }
+}
+
+/// The SPI bus. Part of the ARM7 [`Hw`](super::Hw)
+pub struct Spi {
+    _private: (),
+}
+impl Spi {
+    pub(crate) const unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Sends `data` to `device` as a single command, replacing each byte with the one
+    /// received at the same time.
+    ///
+    /// Interrupts are disabled during the transfer, since libnds also uses the bus
+    /// from its handlers (e.g. to answer the power requests of the ARM9).
+    pub fn transfer(&mut self, device: SpiDevice, baud: SpiBaud, data: &mut [u8]) {
+        let flags = SpiFlags::ENABLE | device.flags() | baud.flags();
+        let last = data.len().saturating_sub(1);
+        critical_section::with(|_| {
+            for (i, byte) in data.iter_mut().enumerate() {
+                // The device stays selected until the last byte
+                let flags = match i == last {
+                    true => flags,
+                    false => flags | SpiFlags::HOLD,
+                };
+                unsafe {
+                    wait_busy();
+                    REG_SPICNT.write_volatile(flags.bits());
+                    REG_SPIDATA.write_volatile(*byte as u16);
+                    wait_busy();
+                    *byte = REG_SPIDATA.read_volatile() as u8;
+                }
+            }
+            unsafe {
+                REG_SPICNT.write_volatile(0);
+            }
+        });
+    }
+}
+
+fn wait_busy() {
+    while unsafe { REG_SPICNT.read_volatile() } & SpiFlags::BUSY.bits() != 0 {}
+}
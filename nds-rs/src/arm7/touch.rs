@@ -0,0 +1,141 @@
+//! The touch screen controller
+//!
+//! [`read`] measures the position in the units of the controller, and a [`Calibration`]
+//! converts it to pixels. The calibration of the user is in the firmware settings
+//! (See [`Calibration::from_user_settings`]).
+
+use nds_sys::arm7::{touch::*, KeyXyBits, REG_KEYXY};
+
+use super::spi::{Spi, SpiBaud, SpiDevice};
+
+/// Returns `true` while the screen is touched
+pub fn is_touched() -> bool {
+    let keys = KeyXyBits::from_bits_retain(unsafe { REG_KEYXY.read_volatile() });
+    !keys.contains(KeyXyBits::PEN_UP)
+}
+
+/// Measures `channel` (one of the `TSC_MEASURE_*` commands), returning a 12-bit value
+pub fn read_channel(spi: &mut Spi, channel: u8) -> u16 {
+    let mut data = [channel | TSC_START, 0, 0];
+    spi.transfer(SpiDevice::Touch, SpiBaud::Mhz2, &mut data);
+    decode_sample(data[1], data[2])
+}
+
+/// The 12-bit result of a measure, from the two bytes received after the command
+pub const fn decode_sample(high: u8, low: u8) -> u16 {
+    ((((high as u16) << 8) | low as u16) >> 3) & 0xFFF
+}
+
+/// A touch, in the units of the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RawTouch {
+    pub x: u16,
+    pub y: u16,
+    pub z1: u16,
+    pub z2: u16,
+}
+impl RawTouch {
+    /// Resistance of the touch, lower when pressing harder. `None` if it can't be
+    /// measured (`z1` is 0)
+    pub fn resistance(&self) -> Option<u32> {
+        if self.z1 == 0 {
+            return None;
+        }
+        let (x, z1, z2) = (self.x as u32, self.z1 as u32, self.z2 as u32);
+        Some(x * z2.saturating_sub(z1) / z1 / 16)
+    }
+}
+
+/// Samples taken for each coordinate, the median is kept
+const SAMPLES: usize = 5;
+
+/// Returns the median of `samples`
+pub fn median<const N: usize>(mut samples: [u16; N]) -> u16 {
+    samples.sort_unstable();
+    samples[N / 2]
+}
+
+/// Measures the touch, or returns `None` if the screen isn't touched.
+///
+/// Each coordinate is measured several times to filter out noise.
+pub fn read(spi: &mut Spi) -> Option<RawTouch> {
+    if !is_touched() {
+        return None;
+    }
+    let mut sample =
+        |channel| median::<SAMPLES>(core::array::from_fn(|_| read_channel(spi, channel)));
+    let touch = RawTouch {
+        x: sample(TSC_MEASURE_X),
+        y: sample(TSC_MEASURE_Y),
+        z1: sample(TSC_MEASURE_Z1),
+        z2: sample(TSC_MEASURE_Z2),
+    };
+    // The pen could have been lifted while measuring
+    is_touched().then_some(touch)
+}
+
+/// Two points touched during the calibration of the touch screen, in the units of the
+/// controller and in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    pub raw_x1: u16,
+    pub raw_y1: u16,
+    pub pixel_x1: u8,
+    pub pixel_y1: u8,
+    pub raw_x2: u16,
+    pub raw_y2: u16,
+    pub pixel_x2: u8,
+    pub pixel_y2: u8,
+}
+impl Calibration {
+    /// Parses the calibration stored in the user settings of the firmware, at offset 0x58
+    pub fn from_bytes(bytes: &[u8; 12]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        Self {
+            raw_x1: u16_at(0),
+            raw_y1: u16_at(2),
+            pixel_x1: bytes[4],
+            pixel_y1: bytes[5],
+            raw_x2: u16_at(6),
+            raw_y2: u16_at(8),
+            pixel_x2: bytes[10],
+            pixel_y2: bytes[11],
+        }
+    }
+
+    /// Reads the calibration of the user settings, copied to main RAM at startup
+    pub fn from_user_settings() -> Self {
+        const PERSONAL_DATA: usize = 0x02FF_FC80;
+        const CALIBRATION: usize = PERSONAL_DATA + 0x58;
+        Self::from_bytes(unsafe { &*(CALIBRATION as *const [u8; 12]) })
+    }
+
+    /// Converts a touch to pixels, clamped to the screen
+    pub fn to_pixels(&self, touch: &RawTouch) -> (u8, u8) {
+        let x = scale(
+            touch.x,
+            self.raw_x1,
+            self.raw_x2,
+            self.pixel_x1,
+            self.pixel_x2,
+        );
+        let y = scale(
+            touch.y,
+            self.raw_y1,
+            self.raw_y2,
+            self.pixel_y1,
+            self.pixel_y2,
+        );
+        (x.clamp(0, 255) as u8, y.clamp(0, 191) as u8)
+    }
+}
+
+/// Maps `raw` linearly from `raw1..raw2` to `pixel1..pixel2`
+fn scale(raw: u16, raw1: u16, raw2: u16, pixel1: u8, pixel2: u8) -> i32 {
+    let raw_span = raw2 as i32 - raw1 as i32;
+    if raw_span == 0 {
+        return pixel1 as i32;
+    }
+    let pixel_span = pixel2 as i32 - pixel1 as i32;
+    pixel1 as i32 + (raw as i32 - raw1 as i32) * pixel_span / raw_span
+}
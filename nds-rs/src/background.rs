@@ -1,3 +1,4 @@
+mod double_buffer;
 mod graphics_mode;
 
 #[cfg(feature = "embedded-graphics-core")]
@@ -5,17 +6,19 @@ use embedded_graphics_core::prelude::*;
 
 use core::convert::Infallible;
 
-pub use graphics_mode::*;
+#[cfg(feature = "embedded-graphics-core")]
+use crate::embedded_graphics::{clip, fill_halfwords, PaletteIndex};
+use crate::{cache::dc_flush_slice, dma::DmaChannel};
 
-extern "C" {
-    fn CP15_CleanAndFlushDCacheRange(start: *mut core::ffi::c_void, size: usize);
-}
+pub use double_buffer::*;
+pub use graphics_mode::*;
 
 pub struct RenderTargetBitmap {
     // TODO: change lifetime?
     framebuffer: &'static mut [u16],
     width: u32,
     height: u32,
+    dma: Option<DmaChannel>,
 }
 
 impl RenderTargetBitmap {
@@ -24,9 +27,23 @@ impl RenderTargetBitmap {
             framebuffer,
             width,
             height,
+            dma: None,
+        }
+    }
+
+    /// Uses `dma` to fill rectangles and clear the bitmap
+    pub fn with_dma(self, dma: DmaChannel) -> Self {
+        Self {
+            dma: Some(dma),
+            ..self
         }
     }
 
+    /// Gives back the channel given to [`with_dma`](Self::with_dma)
+    pub fn take_dma(&mut self) -> Option<DmaChannel> {
+        self.dma.take()
+    }
+
     #[inline]
     pub fn put_pixel(&mut self, x: u32, y: u32, color: u16) -> Option<()> {
         let offset = self.width.checked_mul(y)?.checked_add(x)? as usize;
@@ -36,12 +53,22 @@ impl RenderTargetBitmap {
         Some(())
     }
 
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The pixels, line by line
+    pub fn framebuffer(&mut self) -> &mut [u16] {
+        self.framebuffer
+    }
+
     pub fn flush_cache(&mut self) {
         unsafe {
-            CP15_CleanAndFlushDCacheRange(
-                self.framebuffer.as_mut_ptr() as _,
-                self.framebuffer.len(),
-            );
+            dc_flush_slice(self.framebuffer);
         }
     }
 }
@@ -70,4 +97,220 @@ impl DrawTarget for RenderTargetBitmap {
 
         Ok(())
     }
+
+    fn fill_contiguous<I>(
+        &mut self,
+        area: &embedded_graphics_core::primitives::Rectangle,
+        colors: I,
+    ) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        // Only the bounds of the area are checked when it's inside the bitmap
+        match clip(area, self.size()) {
+            Some((x, y, width, height)) if Size::new(width as u32, height as u32) == area.size => {
+                let stride = self.width as usize;
+                let mut colors = colors.into_iter();
+                let lines = self.framebuffer[y * stride..].chunks_exact_mut(stride);
+                for line in lines.take(height) {
+                    for (pixel, color) in line[x..x + width].iter_mut().zip(&mut colors) {
+                        *pixel = u16::from_le_bytes(color.to_le_bytes()) | 0x8000;
+                    }
+                }
+                Ok(())
+            }
+            _ => self.draw_iter(
+                area.points()
+                    .zip(colors)
+                    .map(|(pos, color)| Pixel(pos, color)),
+            ),
+        }
+    }
+
+    fn fill_solid(
+        &mut self,
+        area: &embedded_graphics_core::primitives::Rectangle,
+        color: Self::Color,
+    ) -> Result<(), Self::Error> {
+        if let Some(rect) = clip(area, self.size()) {
+            let color = u16::from_le_bytes(color.to_le_bytes()) | 0x8000;
+            let stride = self.width as usize;
+            fill_halfwords(self.framebuffer, stride, rect, color, self.dma.as_mut());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let color = u16::from_le_bytes(color.to_le_bytes()) | 0x8000;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let rect = (0, 0, width, height);
+        fill_halfwords(self.framebuffer, width, rect, color, self.dma.as_mut());
+        Ok(())
+    }
+}
+
+/// A bitmap of 8 bits palette indices, two pixels per halfword since VRAM can't be
+/// written byte by byte
+pub struct RenderTargetBitmap8 {
+    framebuffer: &'static mut [u16],
+    width: u32,
+    height: u32,
+    dma: Option<DmaChannel>,
+}
+
+impl RenderTargetBitmap8 {
+    pub(crate) const fn new(framebuffer: &'static mut [u16], width: u32, height: u32) -> Self {
+        Self {
+            framebuffer,
+            width,
+            height,
+            dma: None,
+        }
+    }
+
+    /// Uses `dma` to fill rectangles and clear the bitmap
+    pub fn with_dma(self, dma: DmaChannel) -> Self {
+        Self {
+            dma: Some(dma),
+            ..self
+        }
+    }
+
+    /// Gives back the channel given to [`with_dma`](Self::with_dma)
+    pub fn take_dma(&mut self) -> Option<DmaChannel> {
+        self.dma.take()
+    }
+
+    #[inline]
+    pub fn put_pixel(&mut self, x: u32, y: u32, index: u8) -> Option<()> {
+        if x >= self.width {
+            return None;
+        }
+        let offset = self.width.checked_mul(y)?.checked_add(x)? as usize;
+        let pair = self.framebuffer.get_mut(offset / 2)?;
+        let shift = (offset % 2) * 8;
+        *pair = (*pair & !(0xFF << shift)) | ((index as u16) << shift);
+
+        Some(())
+    }
+
+    /// The palette index of a pixel
+    pub fn pixel(&self, x: u32, y: u32) -> Option<u8> {
+        if x >= self.width {
+            return None;
+        }
+        let offset = self.width.checked_mul(y)?.checked_add(x)? as usize;
+        let pair = self.framebuffer.get(offset / 2)?;
+        Some((pair >> ((offset % 2) * 8)) as u8)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The pairs of pixels, line by line, the left pixel in the low byte
+    pub fn framebuffer(&mut self) -> &mut [u16] {
+        self.framebuffer
+    }
+
+    pub fn flush_cache(&mut self) {
+        unsafe {
+            dc_flush_slice(self.framebuffer);
+        }
+    }
+}
+#[cfg(feature = "embedded-graphics-core")]
+impl OriginDimensions for RenderTargetBitmap8 {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+#[cfg(feature = "embedded-graphics-core")]
+impl DrawTarget for RenderTargetBitmap8 {
+    type Color = PaletteIndex;
+
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(pos, color) in pixels {
+            if self.bounding_box().contains(pos) {
+                self.put_pixel(pos.x as u32, pos.y as u32, color.0);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(
+        &mut self,
+        area: &embedded_graphics_core::primitives::Rectangle,
+        colors: I,
+    ) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        // Only the bounds of the area are checked when it's inside the bitmap
+        match clip(area, self.size()) {
+            Some((x, y, width, height)) if Size::new(width as u32, height as u32) == area.size => {
+                let stride = self.width as usize;
+                let mut colors = colors.into_iter();
+                for line in y..y + height {
+                    let start = line * stride + x;
+                    for (offset, color) in (start..start + width).zip(&mut colors) {
+                        let pair = &mut self.framebuffer[offset / 2];
+                        let shift = (offset % 2) * 8;
+                        *pair = (*pair & !(0xFF << shift)) | ((color.0 as u16) << shift);
+                    }
+                }
+                Ok(())
+            }
+            _ => self.draw_iter(
+                area.points()
+                    .zip(colors)
+                    .map(|(pos, color)| Pixel(pos, color)),
+            ),
+        }
+    }
+
+    fn fill_solid(
+        &mut self,
+        area: &embedded_graphics_core::primitives::Rectangle,
+        color: Self::Color,
+    ) -> Result<(), Self::Error> {
+        let Some((x, y, width, height)) = clip(area, self.size()) else {
+            return Ok(());
+        };
+        // The pixels sharing a halfword with a pixel out of the area
+        let (start, end) = (x.next_multiple_of(2), (x + width) & !1);
+        for line in y..y + height {
+            if x % 2 == 1 {
+                self.put_pixel(x as u32, line as u32, color.0);
+            }
+            if end < x + width && end >= start {
+                self.put_pixel(end as u32, line as u32, color.0);
+            }
+        }
+        if end > start {
+            let pair = u16::from_le_bytes([color.0, color.0]);
+            let rect = (start / 2, y, (end - start) / 2, height);
+            let stride = self.width as usize / 2;
+            fill_halfwords(self.framebuffer, stride, rect, pair, self.dma.as_mut());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let pair = u16::from_le_bytes([color.0, color.0]);
+        let (stride, height) = (self.width as usize / 2, self.height as usize);
+        let rect = (0, 0, stride, height);
+        fill_halfwords(self.framebuffer, stride, rect, pair, self.dma.as_mut());
+        Ok(())
+    }
 }
@@ -0,0 +1,237 @@
+use nds_sys::{
+    background::{
+        registers::{BG3CNT, DB_BG3CNT},
+        BackgroundControl,
+    },
+    video::{
+        DispCntFlags, DisplayMode, REG_DISPCNT, SCREEN_HEIGHT, SCREEN_WIDTH, VRAM_A, VRAM_A_CR,
+        VRAM_B, VRAM_B_CR, VRAM_C, VRAM_C_CR, VRAM_D, VRAM_D_CR, VRAM_ENABLE,
+    },
+};
+
+use super::{RenderTargetBitmap, RenderTargetBitmap8};
+use crate::{dma::DmaChannel, interrupts::swi_wait_for_v_blank, private::Sealed, video::Engine};
+
+/// A bitmap a [`DoubleBuffer`] can draw on
+pub trait BitmapPage: Sealed {
+    /// Whether the pixels are palette indices, like the pixels of
+    /// [`BitmapLayer::new_paletted`](super::BitmapLayer::new_paletted)
+    const PALETTED: bool;
+
+    #[doc(hidden)]
+    fn page(framebuffer: &'static mut [u16], width: u32, height: u32) -> Self;
+    #[doc(hidden)]
+    fn flush(&mut self);
+    #[doc(hidden)]
+    fn dma(&mut self) -> &mut Option<DmaChannel>;
+}
+impl BitmapPage for RenderTargetBitmap {
+    const PALETTED: bool = false;
+
+    fn page(framebuffer: &'static mut [u16], width: u32, height: u32) -> Self {
+        Self::new(framebuffer, width, height)
+    }
+    fn flush(&mut self) {
+        self.flush_cache();
+    }
+    fn dma(&mut self) -> &mut Option<DmaChannel> {
+        &mut self.dma
+    }
+}
+impl BitmapPage for RenderTargetBitmap8 {
+    const PALETTED: bool = true;
+
+    fn page(framebuffer: &'static mut [u16], width: u32, height: u32) -> Self {
+        Self::new(framebuffer, width, height)
+    }
+    fn flush(&mut self) {
+        self.flush_cache();
+    }
+    fn dma(&mut self) -> &mut Option<DmaChannel> {
+        &mut self.dma
+    }
+}
+
+/// A VRAM bank the main engine can show in LCD mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LcdBank {
+    A,
+    B,
+    C,
+    D,
+}
+impl LcdBank {
+    const fn control(self) -> *mut u8 {
+        match self {
+            LcdBank::A => VRAM_A_CR,
+            LcdBank::B => VRAM_B_CR,
+            LcdBank::C => VRAM_C_CR,
+            LcdBank::D => VRAM_D_CR,
+        }
+    }
+    const fn pixels(self) -> *mut u16 {
+        match self {
+            LcdBank::A => VRAM_A,
+            LcdBank::B => VRAM_B,
+            LcdBank::C => VRAM_C,
+            LcdBank::D => VRAM_D,
+        }
+    }
+    const fn display_mode(self) -> DisplayMode {
+        match self {
+            LcdBank::A => DisplayMode::VramA,
+            LcdBank::B => DisplayMode::VramB,
+            LcdBank::C => DisplayMode::VramC,
+            LcdBank::D => DisplayMode::VramD,
+        }
+    }
+}
+
+/// How the pages are shown
+#[derive(Debug, Clone, Copy)]
+enum Pages {
+    /// Two bitmaps of the 3rd layer, swapped by changing its map base
+    MapBase {
+        control: *mut u16,
+        front: u16,
+        back: u16,
+    },
+    /// Two banks shown by the main engine in LCD mode
+    Lcd { front: LcdBank, back: LcdBank },
+}
+
+/// A bitmap with two pages: the front page is shown while drawing on the back page,
+/// until [`flip`](Self::flip) swaps them. Nothing is copied.
+///
+/// The back page is a `DrawTarget` with the `embedded-graphics-core` feature, see
+/// [`back`](Self::back). Its cache is flushed before it's shown.
+pub struct DoubleBuffer<T: BitmapPage> {
+    front: T,
+    back: T,
+    pages: Pages,
+}
+impl<T: BitmapPage> DoubleBuffer<T> {
+    /// Pages of the 3rd layer at the map bases `front` and `back` (16 KiB each)
+    pub(crate) fn map_base(
+        engine: Engine,
+        graphics_base: *mut u16,
+        (width, height): (u32, u32),
+        front: u16,
+        back: u16,
+    ) -> Self {
+        const BLOCK_SIZE: usize = 0x4000;
+        let (control, vram_size) = match engine {
+            Engine::Main => (BG3CNT, 512 * 1024),
+            Engine::Sub => (DB_BG3CNT, 128 * 1024),
+        };
+        let page_size = (width * height) as usize * if T::PALETTED { 1 } else { 2 };
+        let blocks = page_size.div_ceil(BLOCK_SIZE);
+        assert!(front.abs_diff(back) as usize >= blocks, "the pages overlap");
+        assert!(
+            (front.max(back) as usize) * BLOCK_SIZE + page_size <= vram_size,
+            "the pages don't fit"
+        );
+        let page = |base: u16| unsafe {
+            let data = graphics_base.byte_add(base as usize * BLOCK_SIZE);
+            T::page(
+                core::slice::from_raw_parts_mut(data, page_size / 2),
+                width,
+                height,
+            )
+        };
+
+        Self {
+            front: page(front),
+            back: page(back),
+            pages: Pages::MapBase {
+                control,
+                front,
+                back,
+            },
+        }
+    }
+
+    /// Gives `dma` to the back page to fill rectangles and clear it
+    pub fn with_dma(mut self, dma: DmaChannel) -> Self {
+        *self.back.dma() = Some(dma);
+        self
+    }
+
+    /// The page being drawn on
+    pub fn back(&mut self) -> &mut T {
+        &mut self.back
+    }
+
+    /// The page being shown. Drawing on it shows up right away, with tearing
+    pub fn front(&mut self) -> &mut T {
+        &mut self.front
+    }
+
+    /// Waits for the next VBlank and shows the back page. The page that was shown
+    /// becomes the back page, with its content of two flips ago
+    pub fn flip(&mut self) {
+        self.back.flush();
+        swi_wait_for_v_blank();
+        self.show_back();
+    }
+
+    /// Shows the back page right away. To call during VBlank, for example after
+    /// [`next_frame`](crate::executor::next_frame)
+    pub fn present(&mut self) {
+        self.back.flush();
+        self.show_back();
+    }
+
+    fn show_back(&mut self) {
+        match &mut self.pages {
+            Pages::MapBase {
+                control,
+                front,
+                back,
+            } => unsafe {
+                let mut flags = BackgroundControl::from_bits_retain(control.read_volatile());
+                flags.set_map_base(*back);
+                control.write_volatile(flags.bits());
+                core::mem::swap(front, back);
+            },
+            Pages::Lcd { front, back } => unsafe {
+                let flags = DispCntFlags::from_bits_retain(REG_DISPCNT.read_volatile());
+                let flags = flags.with_display_mode(back.display_mode());
+                REG_DISPCNT.write_volatile(flags.bits());
+                core::mem::swap(front, back);
+            },
+        }
+        core::mem::swap(&mut self.front, &mut self.back);
+        // The channel stays with the back page
+        *self.back.dma() = self.front.dma().take();
+    }
+}
+impl DoubleBuffer<RenderTargetBitmap> {
+    /// Pages in two VRAM banks shown by the main engine in LCD mode, which replaces
+    /// the graphics mode. Maps both banks to LCD and shows `front`
+    ///
+    /// # Safety
+    /// The pages borrow the banks for the whole program: while the buffer or its pages
+    /// are alive, `front` and `back` must not be used by anything else, such as another
+    /// buffer, a layer or a remapping of the banks.
+    pub unsafe fn lcd(front: LcdBank, back: LcdBank) -> Self {
+        assert!(front != back, "the pages overlap");
+        let len = (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
+        let page = |bank: LcdBank| unsafe {
+            bank.control().write_volatile(VRAM_ENABLE);
+            let framebuffer = core::slice::from_raw_parts_mut(bank.pixels(), len);
+            RenderTargetBitmap::new(framebuffer, SCREEN_WIDTH, SCREEN_HEIGHT)
+        };
+        let this = Self {
+            front: page(front),
+            back: page(back),
+            pages: Pages::Lcd { front, back },
+        };
+        unsafe {
+            let flags = DispCntFlags::from_bits_retain(REG_DISPCNT.read_volatile());
+            let flags = flags.with_display_mode(front.display_mode());
+            REG_DISPCNT.write_volatile(flags.bits());
+        }
+        this
+    }
+}
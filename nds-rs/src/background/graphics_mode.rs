@@ -3,17 +3,22 @@ use nds_sys::{
     video::{DispCntFlags, DisplayMode, BG_GFX, BG_GFX_SUB, REG_DISPCNT, REG_DISPCNT_SUB},
 };
 
-use crate::{private::Sealed, video::Video};
+use crate::{
+    private::Sealed,
+    video::{Engine, Video},
+    window::Windows,
+};
 
-use super::RenderTargetBitmap;
+use super::{BitmapPage, DoubleBuffer, RenderTargetBitmap, RenderTargetBitmap8};
 
 pub trait GraphicsModeSettings: Sealed {
     unsafe fn map_base(&self) -> *mut u16;
     unsafe fn tile_base(&self) -> *mut u16;
     unsafe fn graphics_base(&self) -> *mut u16;
+    fn engine(&self) -> Engine;
 }
 
-pub struct MainGraphicsModeSettings(DispCntFlags);
+pub struct MainGraphicsModeSettings(DispCntFlags, Windows);
 impl MainGraphicsModeSettings {
     pub fn new(enabled: (bool, bool, bool, bool), _: u32, _: u32) -> Self {
         let mut flags = DispCntFlags::empty();
@@ -22,7 +27,12 @@ impl MainGraphicsModeSettings {
         flags.set(DispCntFlags::BG2, enabled.2);
         flags.set(DispCntFlags::BG3, enabled.3);
 
-        Self(flags)
+        Self(flags, Windows::new())
+    }
+
+    /// Sets the windows to configure when the mode is applied
+    pub fn with_windows(self, windows: Windows) -> Self {
+        Self(self.0, windows)
     }
 }
 impl GraphicsModeSettings for MainGraphicsModeSettings {
@@ -41,9 +51,13 @@ impl GraphicsModeSettings for MainGraphicsModeSettings {
     unsafe fn graphics_base(&self) -> *mut u16 {
         BG_GFX
     }
+
+    fn engine(&self) -> Engine {
+        Engine::Main
+    }
 }
 
-pub struct SubGraphicsModeSettings(DispCntFlags);
+pub struct SubGraphicsModeSettings(DispCntFlags, Windows);
 impl SubGraphicsModeSettings {
     pub fn new(enabled: (bool, bool, bool, bool)) -> Self {
         let mut flags = DispCntFlags::empty();
@@ -52,7 +66,12 @@ impl SubGraphicsModeSettings {
         flags.set(DispCntFlags::BG2, enabled.2);
         flags.set(DispCntFlags::BG3, enabled.3);
 
-        Self(flags)
+        Self(flags, Windows::new())
+    }
+
+    /// Sets the windows to configure when the mode is applied
+    pub fn with_windows(self, windows: Windows) -> Self {
+        Self(self.0, windows)
     }
 }
 impl GraphicsModeSettings for SubGraphicsModeSettings {
@@ -67,6 +86,10 @@ impl GraphicsModeSettings for SubGraphicsModeSettings {
     unsafe fn graphics_base(&self) -> *mut u16 {
         BG_GFX_SUB
     }
+
+    fn engine(&self) -> Engine {
+        Engine::Sub
+    }
 }
 
 pub type MainGraphicsMode<L2, L3> = GraphicsMode<L2, L3, MainGraphicsModeSettings>;
@@ -104,12 +127,14 @@ where
             .mode_settings
             .0
             .with_display_mode(display_mode)
-            .union(DispCntFlags::from_bits_retain(Self::MODE));
+            .union(DispCntFlags::from_bits_retain(Self::MODE))
+            .union(self.mode_settings.1.display_flags());
 
         let bg0 = /* self.flags0() */ BackgroundControl::empty();
         let bg1 = /* self.flags1() */ BackgroundControl::empty();
         let bg2 = self.flags2();
         let bg3 = self.flags3();
+        self.mode_settings.1.write_registers(Engine::Main);
         unsafe {
             REG_DISPCNT.write_volatile(control_flags.bits());
             BG0CNT.write_volatile(bg0.bits());
@@ -146,12 +171,14 @@ where
             .mode_settings
             .0
             .with_display_mode(display_mode)
-            .union(DispCntFlags::from_bits_retain(Self::MODE));
+            .union(DispCntFlags::from_bits_retain(Self::MODE))
+            .union(self.mode_settings.1.display_flags());
 
         let bg0 = /* self.flags0() */ BackgroundControl::empty();
         let bg1 = /* self.flags1() */ BackgroundControl::empty();
         let bg2 = self.flags2();
         let bg3 = self.flags3();
+        self.mode_settings.1.write_registers(Engine::Sub);
         unsafe {
             REG_DISPCNT_SUB.write_volatile(control_flags.bits());
             DB_BG0CNT.write_volatile(bg0.bits());
@@ -189,11 +216,49 @@ where
             core::slice::from_raw_parts_mut(data, len as usize)
         };
 
-        RenderTargetBitmap {
-            framebuffer,
-            width,
-            height,
-        }
+        RenderTargetBitmap::new(framebuffer, width, height)
+    }
+
+    /// The bitmap of a layer created with [`BitmapLayer::new_paletted`]
+    pub fn layer3_framebuffer8(&self) -> RenderTargetBitmap8 {
+        assert!(self.layer3.is_paletted(), "the layer has direct colors");
+        let (width, height) = self.layer3.size();
+        // Two pixels per halfword
+        let len = width * height / 2;
+        let framebuffer = unsafe {
+            let data = self
+                .mode_settings
+                .graphics_base()
+                .add(self.layer3.gfx_block());
+            core::slice::from_raw_parts_mut(data, len as usize)
+        };
+
+        RenderTargetBitmap8::new(framebuffer, width, height)
+    }
+
+    /// Two pages for the 3rd layer: the bitmap at its map base, shown first, and the
+    /// one at the map base `back` (16 KiB each). The banks of VRAM under both must be
+    /// mapped to the backgrounds of the engine.
+    ///
+    /// `T` is [`RenderTargetBitmap`] for direct colors or [`RenderTargetBitmap8`] for a
+    /// layer created with [`BitmapLayer::new_paletted`]
+    ///
+    /// # Safety
+    /// The pages borrow their VRAM for the whole program: while the buffer or its pages
+    /// are alive, that memory must not be used by anything else, such as another buffer,
+    /// [`layer3_framebuffer`](Self::layer3_framebuffer) or a remapping of the banks.
+    pub unsafe fn layer3_double_buffer<T: BitmapPage>(&self, back: u16) -> DoubleBuffer<T> {
+        assert!(
+            self.layer3.is_paletted() == T::PALETTED,
+            "the pages don't match the colors of the layer"
+        );
+        DoubleBuffer::map_base(
+            self.mode_settings.engine(),
+            unsafe { self.mode_settings.graphics_base() },
+            self.layer3.size(),
+            self.layer3.flags.map_base(),
+            back,
+        )
     }
 }
 
@@ -216,6 +281,29 @@ impl BitmapLayer {
             transformation: Transformation::IDENTITY,
         }
     }
+    /// Creates a new bitmap layer of 8 bits palette indices with a size of 256x256 pixels
+    pub const fn new_paletted() -> Self {
+        Self {
+            flags: BackgroundControl::FULLCOLOR.with_size(BgSize::BitmapMedium),
+            transformation: Transformation::IDENTITY,
+        }
+    }
+    /// Creates a new bitmap layer of 8 bits palette indices with a size of 512x512 pixels
+    pub const fn new_paletted_big() -> Self {
+        Self {
+            flags: BackgroundControl::FULLCOLOR.with_size(BgSize::BitmapBig),
+            transformation: Transformation::IDENTITY,
+        }
+    }
+    /// Puts the bitmap at the map base `map_base`, in blocks of 16 KiB
+    pub const fn with_map_base(mut self, map_base: u16) -> Self {
+        self.flags.set_map_base(map_base);
+        self
+    }
+    /// Whether the pixels are palette indices rather than direct colors
+    pub const fn is_paletted(&self) -> bool {
+        !self.flags.contains(BackgroundControl::BITMAP)
+    }
     pub fn size(&self) -> (u32, u32) {
         match self.flags.size_value() {
             0 => (128, 128),
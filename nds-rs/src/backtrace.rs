@@ -0,0 +1,107 @@
+//! Best-effort stack traces, following the chain of frame pointers
+//!
+//! Only works when the code is compiled with frame pointers
+//! (`-C force-frame-pointers=yes`). Each function then stores the frame pointer (`r11`)
+//! of its caller and its return address (`lr`) next to each other:
+//!
+//! ```text
+//! fp + 4: return address
+//! fp:     frame pointer of the caller
+//! ```
+//!
+//! Frames compiled without frame pointers (libnds, the C library...) are skipped or
+//! end the trace early. The walk stops at the first pointer outside of RAM,
+//! so a corrupted stack can't make it fault.
+//!
+//! The addresses can be turned into function names with
+//! `arm-none-eabi-addr2line -f -e game.elf <address>`.
+
+use core::{arch::asm, ops::Range};
+
+/// Maximum amount of frames returned by [`Frames`]
+pub const MAX_FRAMES: usize = 32;
+
+/// Memory where stacks can be: main RAM (with its DSi extension), and the data TCM where
+/// the runtime mapped it
+#[cfg(not(feature = "arm7"))]
+pub fn stack_ranges() -> [Range<usize>; 2] {
+    [0x0200_0000..0x0300_0000, crate::tcm::dtcm_range()]
+}
+
+/// Memory where stacks can be: main RAM (with its DSi extension), and the WRAM of the ARM7
+#[cfg(feature = "arm7")]
+pub fn stack_ranges() -> [Range<usize>; 2] {
+    [0x0200_0000..0x0300_0000, 0x0300_0000..0x0400_0000]
+}
+
+/// Returns `true` if `address` is a word aligned address where a stack can be
+pub fn is_stack_address(address: usize) -> bool {
+    address & 3 == 0
+        && stack_ranges()
+            .iter()
+            .any(|range| range.contains(&address) && range.contains(&(address + 3)))
+}
+
+/// Reads the word at `address`, if it's in [`stack_ranges`]
+pub fn read_stack_word(address: usize) -> Option<usize> {
+    if is_stack_address(address) {
+        // SAFETY: the address is aligned and in RAM
+        Some(unsafe { (address as *const usize).read_volatile() })
+    } else {
+        None
+    }
+}
+
+/// Frame pointer of the calling function
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe {
+        asm!("mov {}, r11", out(reg) fp, options(nomem, nostack, preserves_flags));
+    }
+    fp
+}
+
+/// Return addresses of the calling function and its callers, innermost first
+#[inline(always)]
+pub fn capture() -> Frames<fn(usize) -> Option<usize>> {
+    walk(frame_pointer(), read_stack_word)
+}
+
+/// Walks the frame chain starting at `fp`, using `read` to read the stack.
+/// `read` returns `None` for addresses that can't be read, which ends the walk.
+pub fn walk<F: Fn(usize) -> Option<usize>>(fp: usize, read: F) -> Frames<F> {
+    Frames { fp, read, count: 0 }
+}
+
+/// Iterator over return addresses, see [`walk`]
+pub struct Frames<F> {
+    fp: usize,
+    read: F,
+    count: usize,
+}
+impl<F: Fn(usize) -> Option<usize>> Iterator for Frames<F> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.fp == 0 || self.count >= MAX_FRAMES {
+            return None;
+        }
+        let caller_fp = (self.read)(self.fp);
+        let return_address = (self.read)(self.fp.wrapping_add(4));
+        let (Some(caller_fp), Some(return_address)) = (caller_fp, return_address) else {
+            self.fp = 0;
+            return None;
+        };
+        // Stacks grow downwards, so callers have higher frame pointers.
+        // Anything else means the chain is broken
+        self.fp = if caller_fp > self.fp { caller_fp } else { 0 };
+        if return_address == 0 {
+            self.fp = 0;
+            return None;
+        }
+        self.count += 1;
+        // Clear the Thumb bit
+        Some(return_address & !1)
+    }
+}
@@ -1,5 +1,13 @@
 use core::mem::size_of_val;
 
+extern "C" {
+    fn CP15_CleanAndFlushDCache();
+    fn CP15_CleanAndFlushDCacheRange(base: *const core::ffi::c_void, size: usize);
+    fn CP15_FlushDCacheRange(base: *const core::ffi::c_void, size: usize);
+    fn CP15_InvalidateICache();
+    fn CP15_InvalidateICacheRange(base: *const core::ffi::c_void, size: usize);
+}
+
 /// Flushes the data cache to memory.
 ///
 /// # Safety
@@ -10,7 +18,7 @@ use core::mem::size_of_val;
 /// See also [`dc_invalidate_all`], which is the opposite operation.
 #[inline]
 pub unsafe fn dc_flush_all() {
-    todo!()
+    CP15_CleanAndFlushDCache();
 }
 
 /// Flushes the data cache to memory for the given slice.
@@ -23,7 +31,7 @@ pub unsafe fn dc_flush_all() {
 /// See also [`dc_invalidate_slice`], which is the opposite operation.
 #[inline]
 pub unsafe fn dc_flush_slice<T>(slice: &[T]) {
-    todo!()
+    CP15_CleanAndFlushDCacheRange(slice.as_ptr() as _, size_of_val(slice));
 }
 
 /// Flushes the data cache to memory for the given array.
@@ -36,7 +44,7 @@ pub unsafe fn dc_flush_slice<T>(slice: &[T]) {
 /// See also [`dc_invalidate_array`], which is the opposite operation.
 #[inline]
 pub unsafe fn dc_flush_array<const N: usize, T>(array: &[T; N]) {
-    todo!()
+    dc_flush_slice(array.as_slice());
 }
 
 /// Invalidates the data cache for the given slice.
@@ -49,7 +57,7 @@ pub unsafe fn dc_flush_array<const N: usize, T>(array: &[T; N]) {
 /// See also [`dc_flush_slice`], which is the opposite operation.
 #[inline]
 pub unsafe fn dc_invalidate_slice<T>(slice: &[T]) {
-    todo!()
+    CP15_FlushDCacheRange(slice.as_ptr() as _, size_of_val(slice));
 }
 
 /// Invalidates the data cache for the given array.
@@ -62,5 +70,26 @@ pub unsafe fn dc_invalidate_slice<T>(slice: &[T]) {
 /// See also [`dc_flush_slice`], which is the opposite operation.
 #[inline]
 pub unsafe fn dc_invalidate_array<const N: usize, T>(array: &[T; N]) {
-    todo!()
+    dc_invalidate_slice(array.as_slice());
+}
+
+/// Invalidates the instruction cache.
+///
+/// # Safety
+///
+/// Code written to memory (e.g. when loading an overlay) is only run after the data
+/// cache was flushed and the instruction cache was invalidated.
+#[inline]
+pub unsafe fn ic_invalidate_all() {
+    CP15_InvalidateICache();
+}
+
+/// Invalidates the instruction cache for the given slice.
+///
+/// # Safety
+///
+/// See [`ic_invalidate_all`].
+#[inline]
+pub unsafe fn ic_invalidate_slice<T>(slice: &[T]) {
+    CP15_InvalidateICacheRange(slice.as_ptr() as _, size_of_val(slice));
 }
@@ -0,0 +1,408 @@
+//! The inner and outer cameras of the DSi
+//!
+//! A [`Camera`] streams frames into two buffers with an NDMA channel: the camera
+//! writes to one while the program reads the other (See [`FrameSwap`]).
+//! ```rust,no_run
+//! let channels = hw.ndma.channels()?;
+//! let mut camera = Camera::new(&mut channels.ch1, Facing::Outer, Resolution::Preview, Format::Yuv422)?;
+//! camera.start();
+//! loop {
+//!     let frame = camera.next_frame().await;
+//!     frame.copy_to(&mut framebuffer);
+//! }
+//! ```
+//! Frames in [`Format::Yuv422`] are converted by the CPU when copied (See [`convert_yuv422`]),
+//! the camera can also convert them itself with [`Format::Bgr555`].
+//!
+//! The cameras are only available in DSi mode, [`Camera::new`] returns
+//! [`CameraError::Unsupported`] otherwise.
+
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use nds_sys::camera::{registers::REG_CAM_CNT, *};
+
+use crate::{
+    background::RenderTargetBitmap,
+    cache::{dc_flush_slice, dc_invalidate_slice},
+    dsi::{self, Capabilities},
+    executor::{next_frame, NextFrame},
+    ndma::NdmaChannel,
+};
+
+mod convert;
+mod frames;
+pub use convert::*;
+pub use frames::*;
+
+pub use nds_sys::camera::CameraControl;
+
+/// One of the two cameras
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facing {
+    /// Facing the player
+    Inner,
+    Outer,
+}
+impl Facing {
+    const fn id(self) -> u32 {
+        match self {
+            Self::Inner => CAMERA_INNER,
+            Self::Outer => CAMERA_OUTER,
+        }
+    }
+}
+
+/// Size of the frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// 256x192, the size of a screen
+    Preview,
+    /// 640x480
+    Capture,
+}
+impl Resolution {
+    pub const fn width(self) -> usize {
+        match self {
+            Self::Preview => 256,
+            Self::Capture => 640,
+        }
+    }
+
+    pub const fn height(self) -> usize {
+        match self {
+            Self::Preview => 192,
+            Self::Capture => 480,
+        }
+    }
+
+    pub const fn pixels(self) -> usize {
+        self.width() * self.height()
+    }
+
+    const fn capture_mode(self) -> u8 {
+        match self {
+            Self::Preview => MCUREG_APT_SEQ_CMD_PREVIEW,
+            Self::Capture => MCUREG_APT_SEQ_CMD_CAPTURE,
+        }
+    }
+}
+
+/// Encoding of the pixels sent by the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Two pixels per word, see [`yuyv_to_bgr555`]
+    Yuv422,
+    /// Converted by the camera, ready for a bitmap layer
+    Bgr555,
+}
+
+/// Errors of the cameras
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraError {
+    /// Not in DSi mode
+    Unsupported,
+    /// The cameras couldn't be initialized
+    Init,
+    /// The camera couldn't be selected
+    Select,
+}
+impl From<dsi::Unsupported> for CameraError {
+    fn from(_: dsi::Unsupported) -> Self {
+        Self::Unsupported
+    }
+}
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => f.write_str("cameras need DSi mode"),
+            Self::Init => f.write_str("couldn't initialize the cameras"),
+            Self::Select => f.write_str("couldn't select the camera"),
+        }
+    }
+}
+
+/// A camera streaming frames into two buffers.
+///
+/// Holds the NDMA channel used to receive the pixels. Dropping it stops the transfer
+/// and powers the cameras off.
+pub struct Camera<'c> {
+    ndma: &'c mut NdmaChannel,
+    facing: Facing,
+    resolution: Resolution,
+    format: Format,
+    /// Two frames, aligned to the lines of the data cache
+    buffers: [FrameBuffer; 2],
+    swap: FrameSwap,
+    running: bool,
+}
+impl<'c> Camera<'c> {
+    /// Initializes the cameras and selects `facing`
+    pub fn new(
+        ndma: &'c mut NdmaChannel,
+        facing: Facing,
+        resolution: Resolution,
+        format: Format,
+    ) -> Result<Self, CameraError> {
+        dsi::require(Capabilities::CAMERA | Capabilities::NDMA)?;
+        if !unsafe { cameraInit() } {
+            return Err(CameraError::Init);
+        }
+        let words = resolution.pixels() / 2;
+        let mut camera = Self {
+            ndma,
+            facing,
+            resolution,
+            format,
+            buffers: [FrameBuffer::new(words), FrameBuffer::new(words)],
+            swap: FrameSwap::new(),
+            running: false,
+        };
+        camera.select(facing)?;
+        Ok(camera)
+    }
+
+    pub fn facing(&self) -> Facing {
+        self.facing
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Switches to the other camera, restarting the stream if it was running
+    pub fn select(&mut self, facing: Facing) -> Result<(), CameraError> {
+        self.restart_with(|camera| {
+            if !unsafe { cameraSelect(facing.id()) } {
+                return Err(CameraError::Select);
+            }
+            camera.facing = facing;
+            Ok(())
+        })
+    }
+
+    /// Changes the size of the frames, restarting the stream if it was running.
+    /// The buffers are reallocated, and the previous frames dropped
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        let _ = self.restart_with(|camera| {
+            if camera.resolution != resolution {
+                let words = resolution.pixels() / 2;
+                camera.buffers = [FrameBuffer::new(words), FrameBuffer::new(words)];
+                camera.swap = FrameSwap::new();
+                camera.resolution = resolution;
+            }
+            Ok(())
+        });
+    }
+
+    /// Changes the encoding of the pixels, restarting the stream if it was running
+    pub fn set_format(&mut self, format: Format) {
+        let _ = self.restart_with(|camera| {
+            camera.format = format;
+            Ok(())
+        });
+    }
+
+    /// Stops the stream while running `change`, and restarts it
+    fn restart_with(
+        &mut self,
+        change: impl FnOnce(&mut Self) -> Result<(), CameraError>,
+    ) -> Result<(), CameraError> {
+        let running = self.running;
+        self.stop();
+        let result = change(self);
+        if running {
+            self.start();
+        }
+        result
+    }
+
+    /// Starts streaming frames
+    pub fn start(&mut self) {
+        if self.running {
+            return;
+        }
+        self.running = true;
+        self.start_frame();
+    }
+
+    /// Stops streaming frames. The last complete frame can still be read
+    pub fn stop(&mut self) {
+        if !self.running {
+            return;
+        }
+        unsafe {
+            cameraStopTransfer();
+        }
+        self.swap.abort_frame();
+        self.running = false;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn start_frame(&mut self) {
+        let target = self.swap.start_frame();
+        let buffer = &mut self.buffers[target];
+        unsafe {
+            // Write back dirty lines first, so evicting them later doesn't overwrite
+            // the frame the NDMA writes to main RAM
+            dc_flush_slice(buffer.lines());
+            // Set before the transfer starts, so the whole frame is in the same format
+            let mut control = CameraControl::from_bits_retain(REG_CAM_CNT.read_volatile());
+            control.set(CameraControl::FORMAT_RGB, self.format == Format::Bgr555);
+            REG_CAM_CNT.write_volatile(control.bits());
+            let channel = self.ndma.channel() as u8;
+            cameraStartTransfer(
+                buffer.words_mut().as_mut_ptr().cast(),
+                self.resolution.capture_mode(),
+                channel,
+            );
+        }
+    }
+
+    /// Checks if the camera finished a frame, and starts the next one.
+    /// Returns `true` when a new frame is ready
+    pub fn poll(&mut self) -> bool {
+        if !self.running || unsafe { cameraTransferActive() } {
+            return false;
+        }
+        self.swap.finish_frame();
+        self.start_frame();
+        true
+    }
+
+    /// Returns the last complete frame, if it wasn't read yet
+    pub fn try_frame(&mut self) -> Option<Frame<'_>> {
+        self.poll();
+        let index = self.swap.acquire()?;
+        let buffer = &self.buffers[index];
+        // The NDMA wrote to main RAM behind the cache. The buffer is made of whole
+        // lines, so no other data is discarded
+        unsafe {
+            dc_invalidate_slice(buffer.lines());
+        }
+        let words = buffer.words();
+        Some(Frame {
+            words,
+            resolution: self.resolution,
+            format: self.format,
+            swap: &mut self.swap,
+        })
+    }
+
+    /// Waits for a new frame, checking once per frame
+    pub fn next_frame(&mut self) -> NextCameraFrame<'_, 'c> {
+        NextCameraFrame {
+            camera: Some(self),
+            frame: None,
+        }
+    }
+
+    /// Frames completed and frames dropped since the stream started
+    pub fn stats(&self) -> (u32, u32) {
+        (self.swap.frames(), self.swap.dropped())
+    }
+}
+impl Drop for Camera<'_> {
+    fn drop(&mut self) {
+        self.stop();
+        unsafe {
+            cameraDeinit();
+        }
+    }
+}
+
+/// A complete frame of a [`Camera`]. The camera doesn't write to it until it's dropped
+pub struct Frame<'f> {
+    words: &'f [u32],
+    resolution: Resolution,
+    format: Format,
+    swap: &'f mut FrameSwap,
+}
+impl Frame<'_> {
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The pixels, two per word
+    pub fn words(&self) -> &[u32] {
+        self.words
+    }
+
+    /// The pixels in BGR555, or `None` if the frame is in YUV422
+    pub fn bgr555(&self) -> Option<&[u16]> {
+        match self.format {
+            Format::Bgr555 => Some(unsafe {
+                core::slice::from_raw_parts(self.words.as_ptr().cast(), self.words.len() * 2)
+            }),
+            Format::Yuv422 => None,
+        }
+    }
+
+    /// Copies the frame to `target` in BGR555, converting it if needed. The frame is
+    /// cropped to the size of `target`
+    pub fn copy_to(&self, target: &mut RenderTargetBitmap) {
+        let width = self.resolution.width();
+        let target_width = target.width() as usize;
+        let copy_width = width.min(target_width);
+        match self.bgr555() {
+            Some(pixels) => {
+                let lines = pixels.chunks_exact(width);
+                for (src, dst) in lines.zip(target.framebuffer().chunks_exact_mut(target_width)) {
+                    for (dst, src) in dst[..copy_width].iter_mut().zip(src) {
+                        *dst = *src | 0x8000;
+                    }
+                }
+            }
+            None => convert_yuv422_rect(self.words, width, target.framebuffer(), target_width),
+        }
+    }
+}
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        self.swap.release();
+    }
+}
+
+/// Future returned by [`Camera::next_frame`]
+#[must_use = "futures do nothing unless awaited"]
+pub struct NextCameraFrame<'a, 'c> {
+    camera: Option<&'a mut Camera<'c>>,
+    frame: Option<NextFrame>,
+}
+impl<'a> Future for NextCameraFrame<'a, '_> {
+    type Output = Frame<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            if let Some(frame) = &mut this.frame {
+                match Pin::new(frame).poll(cx) {
+                    Poll::Ready(_) => this.frame = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            let camera = this.camera.as_mut().expect("polled after completion");
+            camera.poll();
+            if camera.swap.ready().is_some() {
+                let camera = this.camera.take().unwrap();
+                return Poll::Ready(camera.try_frame().unwrap());
+            }
+            this.frame = Some(next_frame());
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Conversion of the YUV422 pixels of the cameras to BGR555
+
+/// Converts a pixel to BGR555, with the alpha bit set for bitmap layers.
+///
+/// Uses the full range BT.601 coefficients (JPEG), in 8.8 fixed point.
+pub const fn yuv_to_bgr555(y: u8, u: u8, v: u8) -> u16 {
+    let y = (y as i32) << 8;
+    let u = u as i32 - 128;
+    let v = v as i32 - 128;
+    let r = channel(y + 359 * v);
+    let g = channel(y - 88 * u - 183 * v);
+    let b = channel(y + 454 * u);
+    0x8000 | (b << 10) | (g << 5) | r
+}
+
+/// Clamps an 8.8 fixed point value to 0..=255, and keeps the 5 highest bits
+const fn channel(value: i32) -> u16 {
+    let value = value >> 8;
+    let value = if value < 0 {
+        0
+    } else if value > 255 {
+        255
+    } else {
+        value
+    };
+    (value >> 3) as u16
+}
+
+/// Converts two pixels sent by the camera in a word (Y0, U, Y1, V from the lowest
+/// byte) to BGR555. Both pixels share the same U and V
+pub const fn yuyv_to_bgr555(word: u32) -> [u16; 2] {
+    let [y0, u, y1, v] = word.to_le_bytes();
+    [yuv_to_bgr555(y0, u, v), yuv_to_bgr555(y1, u, v)]
+}
+
+/// Converts the YUV422 pixels of `src` to BGR555 in `dst`, two pixels per word.
+/// Stops at the end of the shortest one
+pub fn convert_yuv422(src: &[u32], dst: &mut [u16]) {
+    for (word, pixels) in src.iter().zip(dst.chunks_mut(2)) {
+        // The last chunk has a single pixel when `dst` has an odd length
+        let len = pixels.len();
+        pixels.copy_from_slice(&yuyv_to_bgr555(*word)[..len]);
+    }
+}
+
+/// Converts the `src_width` pixels wide YUV422 frame `src` to BGR555 in `dst`, which is
+/// `dst_width` pixels wide, e.g. to copy a frame to a bitmap layer.
+/// The frame is cropped to `dst`
+pub fn convert_yuv422_rect(src: &[u32], src_width: usize, dst: &mut [u16], dst_width: usize) {
+    if src_width < 2 || dst_width == 0 {
+        return;
+    }
+    let width = src_width.min(dst_width);
+    let lines = src
+        .chunks_exact(src_width / 2)
+        .zip(dst.chunks_exact_mut(dst_width));
+    for (src, dst) in lines {
+        convert_yuv422(src, &mut dst[..width]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_colors() {
+        // Black, white and grey have no chroma
+        assert_eq!(yuv_to_bgr555(0, 128, 128), 0x8000);
+        assert_eq!(yuv_to_bgr555(255, 128, 128), 0xFFFF);
+        assert_eq!(yuv_to_bgr555(128, 128, 128), 0xC210);
+        // Full red, green and blue in BT.601 full range
+        assert_eq!(yuv_to_bgr555(76, 85, 255), 0x801F);
+        assert_eq!(yuv_to_bgr555(150, 44, 21), 0x83E0);
+        assert_eq!(yuv_to_bgr555(29, 255, 107), 0xFC00);
+    }
+
+    #[test]
+    fn channels_are_clamped() {
+        assert_eq!(yuv_to_bgr555(255, 255, 255), 0xFDFF);
+        assert_eq!(yuv_to_bgr555(0, 0, 0), 0x8000 | (16 << 5));
+    }
+
+    #[test]
+    fn words_hold_two_pixels() {
+        assert_eq!(yuyv_to_bgr555(0x80FF_8000), [0x8000, 0xFFFF]);
+
+        let mut dst = [0; 3];
+        convert_yuv422(&[0x80FF_8000, 0x8000_80FF], &mut dst);
+        assert_eq!(dst, [0x8000, 0xFFFF, 0xFFFF]);
+    }
+
+    #[test]
+    fn rects_are_cropped() {
+        // 4x2 frame, a black and a white line
+        let src = [0x8000_8000, 0x8000_8000, 0x80FF_80FF, 0x80FF_80FF];
+
+        let mut dst = [0; 6];
+        convert_yuv422_rect(&src, 4, &mut dst, 3);
+        assert_eq!(dst, [0x8000, 0x8000, 0x8000, 0xFFFF, 0xFFFF, 0xFFFF]);
+
+        let mut dst = [0; 12];
+        convert_yuv422_rect(&src, 4, &mut dst, 6);
+        assert_eq!(dst[..4], [0x8000; 4]);
+        assert_eq!(dst[4..6], [0; 2]);
+        assert_eq!(dst[6..10], [0xFFFF; 4]);
+        assert_eq!(dst[10..], [0; 2]);
+    }
+}
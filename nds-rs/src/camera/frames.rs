@@ -0,0 +1,217 @@
+//! The two frame buffers of a camera, and their bookkeeping
+
+extern crate alloc;
+use alloc::{vec, vec::Vec};
+
+/// Size in bytes of a line of the data cache
+const CACHE_LINE_SIZE: usize = 32;
+
+/// A line of the data cache, in words
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(32))]
+pub(crate) struct CacheLine([u32; CACHE_LINE_SIZE / 4]);
+
+/// The buffer of a frame, made of whole lines of the data cache. Invalidating it after
+/// the NDMA wrote to it can't discard data of the allocations around it
+pub(crate) struct FrameBuffer {
+    lines: Vec<CacheLine>,
+    words: usize,
+}
+impl FrameBuffer {
+    pub(crate) fn new(words: usize) -> Self {
+        let lines = vec![CacheLine([0; CACHE_LINE_SIZE / 4]); words.div_ceil(CACHE_LINE_SIZE / 4)];
+        Self { lines, words }
+    }
+
+    /// The whole cache lines of the buffer, to flush or invalidate
+    pub(crate) fn lines(&self) -> &[CacheLine] {
+        &self.lines
+    }
+
+    pub(crate) fn words(&self) -> &[u32] {
+        unsafe { core::slice::from_raw_parts(self.lines.as_ptr().cast(), self.words) }
+    }
+
+    pub(crate) fn words_mut(&mut self) -> &mut [u32] {
+        unsafe { core::slice::from_raw_parts_mut(self.lines.as_mut_ptr().cast(), self.words) }
+    }
+}
+
+/// Tracks which of two buffers the camera writes to, which one holds the last
+/// complete frame, and which one the program is reading.
+///
+/// The camera never writes to the buffer being read. When the program reads slower
+/// than the camera, the unread frame is overwritten by the next one, and counted as
+/// [`dropped`](Self::dropped).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FrameSwap {
+    writing: Option<usize>,
+    ready: Option<usize>,
+    reading: Option<usize>,
+    frames: u32,
+    dropped: u32,
+}
+impl FrameSwap {
+    pub const fn new() -> Self {
+        Self {
+            writing: None,
+            ready: None,
+            reading: None,
+            frames: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Chooses the buffer for the next frame, and returns its index
+    pub fn start_frame(&mut self) -> usize {
+        let target = match (self.reading, self.ready) {
+            (Some(reading), _) => 1 - reading,
+            (None, Some(ready)) => 1 - ready,
+            (None, None) => self.writing.map_or(0, |writing| 1 - writing),
+        };
+        if self.ready == Some(target) {
+            self.ready = None;
+            self.dropped += 1;
+        }
+        self.writing = Some(target);
+        target
+    }
+
+    /// Marks the buffer being written as holding a complete frame.
+    /// Returns its index, or `None` if no frame was started
+    pub fn finish_frame(&mut self) -> Option<usize> {
+        let done = self.writing.take()?;
+        if self.ready.is_some() {
+            self.dropped += 1;
+        }
+        self.ready = Some(done);
+        self.frames += 1;
+        Some(done)
+    }
+
+    /// Stops writing to the buffer, without completing the frame
+    pub fn abort_frame(&mut self) {
+        self.writing = None;
+    }
+
+    /// Takes the last complete frame to read it, returning its index. The previous
+    /// frame being read is released
+    pub fn acquire(&mut self) -> Option<usize> {
+        let ready = self.ready.take()?;
+        self.reading = Some(ready);
+        Some(ready)
+    }
+
+    /// Releases the frame being read, the camera can write to it again
+    pub fn release(&mut self) {
+        self.reading = None;
+    }
+
+    /// The buffer the camera is writing to
+    pub fn writing(&self) -> Option<usize> {
+        self.writing
+    }
+
+    /// The buffer holding the last complete frame, not read yet
+    pub fn ready(&self) -> Option<usize> {
+        self.ready
+    }
+
+    /// The buffer being read
+    pub fn reading(&self) -> Option<usize> {
+        self.reading
+    }
+
+    /// Frames completed so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Frames overwritten before being read
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_line_aligned() {
+        for words in [0, 1, 8, 9, 256 * 192 / 2, 640 * 480 / 2] {
+            let mut buffer = FrameBuffer::new(words);
+            assert_eq!(buffer.words().len(), words);
+            assert_eq!(buffer.words_mut().len(), words);
+            assert!(buffer.words().iter().all(|&word| word == 0));
+            let lines = buffer.lines();
+            assert_eq!(lines.as_ptr() as usize % CACHE_LINE_SIZE, 0);
+            assert_eq!(buffer.words().as_ptr().cast(), lines.as_ptr());
+            assert!(size_of_val(lines) >= words * 4);
+            assert_eq!(size_of_val(lines) % CACHE_LINE_SIZE, 0);
+        }
+    }
+
+    #[test]
+    fn frames_alternate_buffers() {
+        let mut swap = FrameSwap::new();
+        assert_eq!(swap.acquire(), None);
+        assert_eq!(swap.start_frame(), 0);
+        assert_eq!(swap.finish_frame(), Some(0));
+        assert_eq!(swap.start_frame(), 1);
+        assert_eq!(swap.acquire(), Some(0));
+        assert_eq!(swap.finish_frame(), Some(1));
+        swap.release();
+        assert_eq!(swap.start_frame(), 0);
+        assert_eq!(swap.acquire(), Some(1));
+        assert_eq!((swap.frames(), swap.dropped()), (2, 0));
+    }
+
+    #[test]
+    fn buffer_being_read_isnt_written() {
+        let mut swap = FrameSwap::new();
+        swap.start_frame();
+        swap.finish_frame();
+        assert_eq!(swap.acquire(), Some(0));
+        for frame in 1..4 {
+            assert_eq!(swap.start_frame(), 1);
+            assert_eq!(swap.finish_frame(), Some(1));
+            assert_eq!(swap.reading(), Some(0));
+            assert_eq!(swap.frames(), frame + 1);
+        }
+        // The unread frames were overwritten
+        assert_eq!(swap.dropped(), 2);
+        swap.release();
+        assert_eq!(swap.acquire(), Some(1));
+    }
+
+    #[test]
+    fn unread_frames_are_dropped() {
+        let mut swap = FrameSwap::new();
+        swap.start_frame();
+        swap.finish_frame();
+        // Finishing a frame while the other one is still unread
+        assert_eq!(swap.start_frame(), 1);
+        assert_eq!(swap.finish_frame(), Some(1));
+        assert_eq!(swap.dropped(), 1);
+        assert_eq!(swap.ready(), Some(1));
+        // Starting a frame in the unread buffer
+        assert_eq!(swap.start_frame(), 0);
+        assert_eq!(swap.ready(), Some(1));
+        swap.acquire();
+        assert_eq!(swap.start_frame(), 0);
+        assert_eq!((swap.frames(), swap.dropped()), (2, 1));
+    }
+
+    #[test]
+    fn aborted_frames_arent_finished() {
+        let mut swap = FrameSwap::new();
+        assert_eq!(swap.finish_frame(), None);
+        swap.start_frame();
+        swap.abort_frame();
+        assert_eq!(swap.writing(), None);
+        assert_eq!(swap.finish_frame(), None);
+        assert_eq!(swap.frames(), 0);
+        assert_eq!(swap, FrameSwap::default());
+    }
+}
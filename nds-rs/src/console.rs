@@ -0,0 +1,491 @@
+//! Text consoles on tiled background layers
+//!
+//! A [`Console`] draws text on a 256x256 text layer of either engine. Any number of
+//! consoles can exist at the same time, each one with its own layer, window, cursor and color.
+//! Text is written using [`core::fmt::Write`]:
+//!
+//! ```rust,no_run
+//! let mut console = Console::new(&mut hw.video, ConsoleLayer::new(Engine::Sub, Layer::Layer0));
+//! writeln!(console, "Hello \x1b[32mworld\x1b[39m!").unwrap();
+//! ```
+//!
+//! Rendering is done in Rust, but the state is kept in a libnds [`PrintConsole`],
+//! so a console can also be selected as the target of the C standard output
+//! (See [`Console::select`]).
+//!
+//! The following escape sequences are supported (`ESC` is `\x1b`):
+//!
+//! - `ESC[<row>;<column>H`: moves the cursor (1-based, relative to the window)
+//! - `ESC[<n>A`, `ESC[<n>B`, `ESC[<n>C`, `ESC[<n>D`: moves the cursor up, down, forward and back
+//! - `ESC[s`, `ESC[u`: saves and restores the cursor position
+//! - `ESC[2J`: clears the window (`0J` and `1J` clear after and before the cursor)
+//! - `ESC[2K`: clears the line (`0K` and `1K` clear after and before the cursor)
+//! - `ESC[<n>m`: `0` resets the color, `1` selects bright colors, `22` normal ones,
+//!   `30` to `37` select a [`Color`], `39` the default one and `90` to `97` a bright [`Color`]
+//!
+//! Characters outside of ASCII are drawn as `?`.
+
+extern crate alloc;
+use alloc::boxed::Box;
+use core::{ffi::c_int, fmt, mem::MaybeUninit, ptr};
+
+use nds_sys::{
+    background::{BgSize, BgType, Layer},
+    console::*,
+    video::{BG_PALETTE, BG_PALETTE_SUB},
+};
+
+pub use nds_sys::console::ConsoleFont;
+
+use crate::video::{Engine, Video};
+
+mod escape;
+use escape::{Action, ClearMode, Parser};
+
+/// Size of the console layers, in tiles
+const MAP_WIDTH: u8 = 32;
+/// Visible rows of the console layers
+const MAP_HEIGHT: u8 = 24;
+
+/// Text colors. Each one selects a palette of the layer, see [`PALETTE`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Color {
+    Black = 0,
+    Red = 1,
+    Green = 2,
+    Yellow = 3,
+    Blue = 4,
+    Magenta = 5,
+    Cyan = 6,
+    White = 7,
+}
+
+const fn rgb(r: u16, g: u16, b: u16) -> u16 {
+    r | (g << 5) | (b << 10)
+}
+
+/// Colors loaded for fonts converted from 1bpp: color `n` goes to entry 15
+/// of palette `n`, bright colors follow normal ones.
+pub const PALETTE: [u16; 16] = [
+    rgb(0, 0, 0),
+    rgb(20, 0, 0),
+    rgb(0, 20, 0),
+    rgb(20, 20, 0),
+    rgb(0, 0, 20),
+    rgb(20, 0, 20),
+    rgb(0, 20, 20),
+    rgb(24, 24, 24),
+    rgb(12, 12, 12),
+    rgb(31, 8, 8),
+    rgb(8, 31, 8),
+    rgb(31, 31, 8),
+    rgb(8, 8, 31),
+    rgb(31, 8, 31),
+    rgb(8, 31, 31),
+    rgb(31, 31, 31),
+];
+
+/// Where a console is drawn
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ConsoleLayer {
+    pub engine: Engine,
+    pub layer: Layer,
+    /// Map base of the layer, in blocks of 2 KiB
+    pub map_base: u8,
+    /// Tile base of the layer (where the font is loaded), in blocks of 16 KiB
+    pub tile_base: u8,
+}
+impl ConsoleLayer {
+    /// A layer with the map at base 31 and the font at base 0 (the libnds defaults).
+    /// Consoles on the same engine need different bases.
+    pub const fn new(engine: Engine, layer: Layer) -> Self {
+        Self {
+            engine,
+            layer,
+            map_base: 31,
+            tile_base: 0,
+        }
+    }
+
+    pub const fn with_map_base(mut self, map_base: u8) -> Self {
+        self.map_base = map_base;
+        self
+    }
+
+    pub const fn with_tile_base(mut self, tile_base: u8) -> Self {
+        self.tile_base = tile_base;
+        self
+    }
+}
+
+/// A text console on a background layer. See the [module documentation](self)
+pub struct Console {
+    /// A leaked `Box`, or static storage for the panic screen, so the pointer given to
+    /// libnds stays valid
+    inner: &'static mut PrintConsole,
+    /// Whether `inner` is a leaked `Box`, freed on drop
+    boxed: bool,
+    engine: Engine,
+    parser: Parser,
+    saved_cursor: (c_int, c_int),
+    color: Color,
+    bright: bool,
+}
+// SAFETY: the pointers in `PrintConsole` point to VRAM and to the font,
+// which aren't tied to any thread
+unsafe impl Send for Console {}
+
+impl Console {
+    /// Creates a console using the default font.
+    ///
+    /// The layer is set up as a 4bpp text layer, but the display mode, the layer enable bit
+    /// and the VRAM banks must be configured separately.
+    /// The palettes of the engine are overwritten with [`PALETTE`].
+    ///
+    /// The console becomes the target of the C standard output, see [`Console::select`].
+    pub fn new(_: &mut Video, layer: ConsoleLayer) -> Self {
+        unsafe { Self::init(layer, None) }
+    }
+
+    /// Creates a console using a custom font, see [`Console::new`].
+    /// The font graphics are copied to VRAM, so `font` doesn't need to outlive the console.
+    pub fn with_font(_: &mut Video, layer: ConsoleLayer, font: &ConsoleFont) -> Self {
+        unsafe { Self::init(layer, Some(font)) }
+    }
+
+    /// Creates a console on the first layer of the sub engine, using VRAM bank C.
+    /// Meant for quick prototyping: it changes the display mode of the sub engine.
+    pub fn demo(_: &mut Video) -> Self {
+        unsafe { Self::demo_unchecked() }
+    }
+
+    /// Same as [`Console::demo`], without requiring access to the video hardware
+    pub(crate) unsafe fn demo_unchecked() -> Self {
+        let mut inner = Box::new(ptr::read(consoleDemoInit()));
+        consoleSelect(&mut *inner);
+        Self::from_raw(inner, Engine::Sub)
+    }
+
+    /// Same as [`Console::demo_unchecked`], keeping the state in `storage` instead of
+    /// allocating it, for when the heap can't be trusted
+    pub(crate) unsafe fn demo_in(storage: &'static mut MaybeUninit<PrintConsole>) -> Self {
+        let inner = storage.write(ptr::read(consoleDemoInit()));
+        consoleSelect(inner);
+        Self::from_parts(inner, false, Engine::Sub)
+    }
+
+    unsafe fn init(layer: ConsoleLayer, font: Option<&ConsoleFont>) -> Self {
+        let mut inner = Box::new(ptr::read(consoleGetDefault()));
+        let r#type = match font {
+            Some(font) if font.bpp == 8 => BgType::Text8,
+            _ => BgType::Text4,
+        };
+        consoleInit(
+            &mut *inner,
+            layer.layer as c_int,
+            r#type,
+            BgSize::TextSmall,
+            layer.map_base as c_int,
+            layer.tile_base as c_int,
+            layer.engine == Engine::Main,
+            font.is_none(),
+        );
+        if let Some(font) = font {
+            // libnds only reads the font, but takes it as mutable
+            let mut font = ptr::read(font);
+            consoleSetFont(&mut *inner, &mut font);
+        }
+        let mut console = Self::from_raw(inner, layer.engine);
+        console.clear();
+        console
+    }
+
+    unsafe fn from_raw(inner: Box<PrintConsole>, engine: Engine) -> Self {
+        Self::from_parts(Box::leak(inner), true, engine)
+    }
+
+    unsafe fn from_parts(inner: &'static mut PrintConsole, boxed: bool, engine: Engine) -> Self {
+        let mut console = Self {
+            inner,
+            boxed,
+            engine,
+            parser: Parser::new(),
+            saved_cursor: (0, 0),
+            color: Color::White,
+            bright: false,
+        };
+        console.load_palette();
+        console.update_palette();
+        console
+    }
+
+    /// Makes this console the target of the C standard output (`printf` and such).
+    /// Dropping the console makes libnds go back to its default console.
+    pub fn select(&mut self) {
+        unsafe {
+            consoleSelect(&mut *self.inner);
+        }
+    }
+
+    /// Restricts the console to a rectangle of the layer, in tiles.
+    /// The rectangle is clipped to the screen, and the cursor goes back to its top left corner.
+    pub fn set_window(&mut self, x: u8, y: u8, width: u8, height: u8) {
+        let x = x.min(MAP_WIDTH - 1);
+        let y = y.min(MAP_HEIGHT - 1);
+        let width = width.clamp(1, MAP_WIDTH - x);
+        let height = height.clamp(1, MAP_HEIGHT - y);
+        unsafe {
+            consoleSetWindow(
+                &mut *self.inner,
+                x as c_int,
+                y as c_int,
+                width as c_int,
+                height as c_int,
+            );
+        }
+        self.set_cursor(0, 0);
+    }
+
+    /// Returns the window as `(x, y, width, height)`, in tiles
+    pub fn window(&self) -> (u8, u8, u8, u8) {
+        let c = &self.inner;
+        (
+            c.window_x as u8,
+            c.window_y as u8,
+            c.window_width as u8,
+            c.window_height as u8,
+        )
+    }
+
+    /// Moves the cursor, relative to the window. Clamped to the window
+    pub fn set_cursor(&mut self, column: u8, row: u8) {
+        self.move_to(column as c_int, row as c_int);
+    }
+
+    /// Returns the position of the cursor as `(column, row)`, relative to the window
+    pub fn cursor(&self) -> (u8, u8) {
+        (self.inner.cursor_x as u8, self.inner.cursor_y as u8)
+    }
+
+    /// Selects the color of the next characters
+    pub fn set_color(&mut self, color: Color, bright: bool) {
+        self.color = color;
+        self.bright = bright;
+        self.update_palette();
+    }
+
+    /// Clears the window and moves the cursor to its top left corner
+    pub fn clear(&mut self) {
+        self.clear_screen(ClearMode::All);
+    }
+
+    /// Writes raw bytes, escape sequences included
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.feed(byte);
+        }
+    }
+
+    fn feed(&mut self, byte: u8) {
+        if let Some(action) = self.parser.feed(byte) {
+            self.apply(action);
+            while let Some(action) = self.parser.pending() {
+                self.apply(action);
+            }
+        }
+    }
+
+    fn apply(&mut self, action: Action) {
+        let (x, y) = (self.inner.cursor_x, self.inner.cursor_y);
+        match action {
+            Action::Print(c) => {
+                if x >= self.inner.window_width {
+                    self.inner.cursor_x = 0;
+                    self.line_feed();
+                }
+                let (x, y) = (self.inner.cursor_x, self.inner.cursor_y);
+                self.put(x, y, c);
+                self.inner.cursor_x += 1;
+            }
+            Action::NewLine => {
+                self.inner.cursor_x = 0;
+                self.line_feed();
+            }
+            Action::CarriageReturn => self.inner.cursor_x = 0,
+            Action::Tab => {
+                let tab = self.inner.tab_size.max(1);
+                self.inner.cursor_x = ((x / tab + 1) * tab).min(self.inner.window_width);
+            }
+            Action::Backspace => {
+                if x > 0 {
+                    self.inner.cursor_x -= 1;
+                    self.put(x - 1, y, b' ');
+                }
+            }
+            Action::MoveTo { row, column } => {
+                let column = column.map_or(x, |c| c as c_int);
+                let row = row.map_or(y, |r| r as c_int);
+                self.move_to(column, row);
+            }
+            Action::MoveBy { rows, columns } => {
+                self.move_to(x + columns as c_int, y + rows as c_int);
+            }
+            Action::SaveCursor => self.saved_cursor = (x, y),
+            Action::RestoreCursor => {
+                let (x, y) = self.saved_cursor;
+                self.move_to(x, y);
+            }
+            Action::ClearScreen(mode) => self.clear_screen(mode),
+            Action::ClearLine(mode) => {
+                let width = self.inner.window_width;
+                match mode {
+                    ClearMode::ToEnd => self.clear_cells(x, width, y),
+                    ClearMode::ToStart => self.clear_cells(0, x + 1, y),
+                    ClearMode::All => self.clear_cells(0, width, y),
+                }
+            }
+            Action::Graphics(param) => {
+                match param {
+                    0 => (self.color, self.bright) = (Color::White, false),
+                    1 => self.bright = true,
+                    2 | 22 => self.bright = false,
+                    30..=37 => self.color = color_of(param - 30),
+                    39 => self.color = Color::White,
+                    90..=97 => (self.color, self.bright) = (color_of(param - 90), true),
+                    _ => {}
+                }
+                self.update_palette();
+            }
+        }
+    }
+
+    fn move_to(&mut self, column: c_int, row: c_int) {
+        let c = &mut self.inner;
+        c.cursor_x = column.clamp(0, c.window_width - 1);
+        c.cursor_y = row.clamp(0, c.window_height - 1);
+    }
+
+    /// Moves the cursor to the next row, scrolling the window if needed
+    fn line_feed(&mut self) {
+        if self.inner.cursor_y + 1 < self.inner.window_height {
+            self.inner.cursor_y += 1;
+            return;
+        }
+        let (width, height) = (self.inner.window_width, self.inner.window_height);
+        for y in 1..height {
+            for x in 0..width {
+                unsafe {
+                    let value = self.cell(x, y).read_volatile();
+                    self.cell(x, y - 1).write_volatile(value);
+                }
+            }
+        }
+        self.clear_cells(0, width, height - 1);
+    }
+
+    fn clear_screen(&mut self, mode: ClearMode) {
+        let (x, y) = (self.inner.cursor_x, self.inner.cursor_y);
+        let (width, height) = (self.inner.window_width, self.inner.window_height);
+        let rows = match mode {
+            ClearMode::ToEnd => {
+                self.clear_cells(x, width, y);
+                y + 1..height
+            }
+            ClearMode::ToStart => {
+                self.clear_cells(0, x + 1, y);
+                0..y
+            }
+            ClearMode::All => {
+                self.move_to(0, 0);
+                0..height
+            }
+        };
+        for row in rows {
+            self.clear_cells(0, width, row);
+        }
+    }
+
+    /// Clears the columns `from..to` of `row`
+    fn clear_cells(&mut self, from: c_int, to: c_int, row: c_int) {
+        for x in from..to.min(self.inner.window_width) {
+            self.put(x, row, b' ');
+        }
+    }
+
+    /// Draws `c` with the current color, relative to the window
+    fn put(&mut self, x: c_int, y: c_int, c: u8) {
+        let font = &self.inner.font;
+        let index = (c as u16).wrapping_sub(font.ascii_offset);
+        let index = if index < font.num_chars { index } else { 0 };
+        let value = self.inner.font_char_offset.wrapping_add(index) | self.inner.font_cur_pal;
+        unsafe {
+            self.cell(x, y).write_volatile(value);
+        }
+    }
+
+    /// Map entry at `(x, y)`, relative to the window
+    fn cell(&self, x: c_int, y: c_int) -> *mut u16 {
+        let c = &self.inner;
+        let offset = (c.window_y + y) * c.console_width + c.window_x + x;
+        unsafe { c.font_bg_map.add(offset as usize) }
+    }
+
+    /// Stores the palette of the current color where libnds expects it
+    fn update_palette(&mut self) {
+        let palette = self.color as u16 + if self.bright { 8 } else { 0 };
+        self.inner.font_cur_pal = palette << 12;
+    }
+
+    /// Loads [`PALETTE`] if the font is converted from 1bpp
+    unsafe fn load_palette(&mut self) {
+        let font = &self.inner.font;
+        if font.bpp != 4 || !font.convert_single_color {
+            return;
+        }
+        let palette = match self.engine {
+            Engine::Main => BG_PALETTE,
+            Engine::Sub => BG_PALETTE_SUB,
+        };
+        for (i, &color) in PALETTE.iter().enumerate() {
+            palette.add(i * 16 + 15).write_volatile(color);
+        }
+    }
+}
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.feed(if c.is_ascii() { c as u8 } else { b'?' });
+        }
+        Ok(())
+    }
+}
+impl Drop for Console {
+    fn drop(&mut self) {
+        unsafe {
+            let this: *mut PrintConsole = &mut *self.inner;
+            let previous = consoleSelect(this);
+            if previous != this {
+                consoleSelect(previous);
+            } else {
+                consoleSelect(consoleGetDefault());
+            }
+            if self.boxed {
+                drop(Box::from_raw(this));
+            }
+        }
+    }
+}
+
+const fn color_of(index: u16) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::White,
+    }
+}
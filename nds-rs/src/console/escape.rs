@@ -0,0 +1,215 @@
+/// Maximum amount of numeric parameters kept for a control sequence,
+/// extra ones are ignored
+const MAX_PARAMS: usize = 4;
+
+/// What a byte written to a [`Console`](super::Console) asks it to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    /// Draw a character at the cursor and move it forward
+    Print(u8),
+    /// `\n`
+    NewLine,
+    /// `\r`
+    CarriageReturn,
+    /// `\t`
+    Tab,
+    /// `\x08`
+    Backspace,
+    /// `ESC[<row>;<column>H`, 0-based. `None` keeps the current value
+    MoveTo {
+        row: Option<u16>,
+        column: Option<u16>,
+    },
+    /// `ESC[<n>A`, `ESC[<n>B`, `ESC[<n>C` and `ESC[<n>D`
+    MoveBy { rows: i16, columns: i16 },
+    /// `ESC[s`
+    SaveCursor,
+    /// `ESC[u`
+    RestoreCursor,
+    /// `ESC[<n>J`
+    ClearScreen(ClearMode),
+    /// `ESC[<n>K`
+    ClearLine(ClearMode),
+    /// `ESC[<n>m`, one action per parameter
+    Graphics(u16),
+}
+
+/// Part of the screen or line cleared by [`Action::ClearScreen`] and [`Action::ClearLine`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClearMode {
+    /// From the cursor to the end
+    ToEnd,
+    /// From the start to the cursor
+    ToStart,
+    /// Everything, the cursor goes back home when clearing the screen
+    All,
+}
+impl ClearMode {
+    const fn from_param(param: u16) -> Option<Self> {
+        match param {
+            0 => Some(Self::ToEnd),
+            1 => Some(Self::ToStart),
+            2 => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// After `ESC`
+    Escape,
+    /// After `ESC[`
+    Csi,
+}
+
+/// Decodes the bytes written to a console, one at a time.
+///
+/// Supports the subset of ANSI escape sequences understood by the libnds console.
+/// Unknown and malformed sequences are dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct Parser {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    count: usize,
+    /// Index of the parameter being parsed in [`Parser::params`]
+    current: Option<usize>,
+    /// Graphics parameters still to be returned by [`Parser::pending`]
+    pending: usize,
+}
+impl Parser {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            count: 0,
+            current: None,
+            pending: 0,
+        }
+    }
+
+    /// Feeds a byte to the parser. Returns the resulting action, if any.
+    ///
+    /// A graphics sequence with several parameters (`ESC[1;31m`) returns the
+    /// first one, the rest must be collected with [`Parser::pending`].
+    pub(crate) fn feed(&mut self, byte: u8) -> Option<Action> {
+        match self.state {
+            State::Ground => match byte {
+                0x1B => {
+                    self.state = State::Escape;
+                    None
+                }
+                b'\n' => Some(Action::NewLine),
+                b'\r' => Some(Action::CarriageReturn),
+                b'\t' => Some(Action::Tab),
+                0x08 => Some(Action::Backspace),
+                0x20..=0x7E => Some(Action::Print(byte)),
+                // Other control characters (NUL included) are ignored
+                _ => None,
+            },
+            State::Escape => {
+                self.state = State::Ground;
+                if byte == b'[' {
+                    self.state = State::Csi;
+                    self.params = [0; MAX_PARAMS];
+                    self.count = 0;
+                    self.current = None;
+                }
+                None
+            }
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    let index = *self.current.get_or_insert(self.count);
+                    if index < MAX_PARAMS {
+                        let digit = (byte - b'0') as u16;
+                        self.params[index] =
+                            self.params[index].saturating_mul(10).saturating_add(digit);
+                    }
+                    None
+                }
+                b';' => {
+                    // An empty parameter counts as a 0
+                    self.count += 1;
+                    self.current = None;
+                    None
+                }
+                0x40..=0x7E => {
+                    if self.current.is_some() || self.count > 0 {
+                        self.count += 1;
+                    }
+                    self.state = State::Ground;
+                    self.dispatch(byte)
+                }
+                // Intermediate bytes and unexpected control characters
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the remaining parameters of the last graphics sequence
+    pub(crate) fn pending(&mut self) -> Option<Action> {
+        if self.pending == 0 {
+            return None;
+        }
+        let index = self.count.min(MAX_PARAMS) - self.pending;
+        self.pending -= 1;
+        Some(Action::Graphics(self.params[index]))
+    }
+
+    /// Parameter `index`, or `default` if it's missing or 0
+    fn param_or(&self, index: usize, default: u16) -> u16 {
+        match self.params.get(index) {
+            Some(&value) if index < self.count && value != 0 => value,
+            _ => default,
+        }
+    }
+
+    fn dispatch(&mut self, command: u8) -> Option<Action> {
+        let n = self.param_or(0, 1).min(i16::MAX as u16) as i16;
+        match command {
+            b'A' => Some(Action::MoveBy {
+                rows: -n,
+                columns: 0,
+            }),
+            b'B' => Some(Action::MoveBy {
+                rows: n,
+                columns: 0,
+            }),
+            b'C' => Some(Action::MoveBy {
+                rows: 0,
+                columns: n,
+            }),
+            b'D' => Some(Action::MoveBy {
+                rows: 0,
+                columns: -n,
+            }),
+            b'H' | b'f' => Some(Action::MoveTo {
+                row: Some(self.param_or(0, 1) - 1),
+                column: Some(self.param_or(1, 1) - 1),
+            }),
+            b'G' => Some(Action::MoveTo {
+                row: None,
+                column: Some(self.param_or(0, 1) - 1),
+            }),
+            b'd' => Some(Action::MoveTo {
+                row: Some(self.param_or(0, 1) - 1),
+                column: None,
+            }),
+            b's' => Some(Action::SaveCursor),
+            b'u' => Some(Action::RestoreCursor),
+            b'J' => ClearMode::from_param(self.params[0]).map(Action::ClearScreen),
+            b'K' => ClearMode::from_param(self.params[0]).map(Action::ClearLine),
+            b'm' => {
+                let count = self.count.min(MAX_PARAMS);
+                if count == 0 {
+                    // `ESC[m` is the same as `ESC[0m`
+                    return Some(Action::Graphics(0));
+                }
+                self.pending = count - 1;
+                Some(Action::Graphics(self.params[0]))
+            }
+            _ => None,
+        }
+    }
+}
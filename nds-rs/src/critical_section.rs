@@ -22,7 +22,9 @@ critical_section::set_impl!(CriticalSection);
 
 unsafe impl critical_section::Impl for CriticalSection {
     unsafe fn acquire() -> RawRestoreState {
-        read_ime()
+        let enabled = read_ime();
+        disable_ime();
+        enabled
     }
 
     unsafe fn release(restore_state: RawRestoreState) {
@@ -5,9 +5,13 @@ use core::{
 use nds_sys::debug::registers;
 use spin::Mutex;
 extern crate alloc;
+#[cfg(not(feature = "arm7"))]
+use crate::console::Console;
 use core::arch::asm;
 
+mod profile;
 mod safe_chunks_iter;
+pub use profile::*;
 use safe_chunks_iter::SafeChunksIter;
 
 #[macro_export]
@@ -27,11 +31,15 @@ macro_rules! println {
 enum Logger {
     None,
     NoCash,
+    #[cfg(not(feature = "arm7"))]
     Tty,
 }
 
 /// This is the logging target that will be used by the `print!` and `println!` macros.
 static LOGGER: Mutex<Logger> = Mutex::new(Logger::None);
+/// Console used when the logger is [`Logger::Tty`]
+#[cfg(not(feature = "arm7"))]
+static TTY: Mutex<Option<Console>> = Mutex::new(None);
 
 /// Makes the `print!` and `println!` macros write to the NO$GBA debugger console.
 /// Only on output can be used as default, so calling this function will override
@@ -65,31 +73,41 @@ pub fn log_to_nocash() -> bool {
     }
 }
 
-/// Makes the `print!` and `println!` macros write to the stdout on the bottom screen.
+/// Makes the `print!` and `println!` macros write to a text console on the bottom screen.
 /// Only on output can be used as default, so calling this function will override
 /// any previous logger.
 ///
-/// Returns `true` if it could change the display mode to text, `false` otherwise.
+/// The console is created with [`Console::demo`] the first time, unless one was
+/// given to [`log_to_console`].
 ///
 /// ## See also
 /// - [`log_to_nocash`]
-pub fn log_to_tty() -> bool {
-    todo!("Write methods are not implemented yet!");
-
-    extern "C" {
-        #[link_name = "consoleDemoInit"]
-        fn console_demo_init() -> *const core::ffi::c_void;
-    }
-
+/// - [`log_to_console`]
+#[cfg(not(feature = "arm7"))]
+pub fn log_to_tty() {
     let mut logger_lock = LOGGER.lock();
-    // TODO: don't unconditionaly call console_demo_init?
-    unsafe {
-        console_demo_init();
+    let mut tty = TTY.lock();
+    if tty.is_none() {
+        // SAFETY: the user asked for the bottom screen to be taken over
+        *tty = Some(unsafe { Console::demo_unchecked() });
     }
     *logger_lock = Logger::Tty;
+}
 
-    // TODO: check if the console was initialized
-    true
+/// Makes the `print!` and `println!` macros write to `console`.
+/// Only on output can be used as default, so calling this function will override
+/// any previous logger.
+///
+/// Returns the console previously used by the `print!` macros, if any.
+///
+/// ## See also
+/// - [`log_to_tty`]
+#[cfg(not(feature = "arm7"))]
+pub fn log_to_console(console: Console) -> Option<Console> {
+    let mut logger_lock = LOGGER.lock();
+    let previous = TTY.lock().replace(console);
+    *logger_lock = Logger::Tty;
+    previous
 }
 
 /// Passes a formatable string to the configured debugger output.
@@ -109,6 +127,7 @@ pub fn _print(args: Arguments) {
             Logger::NoCash => {
                 NoCash.write_cstr_param(cstr);
             }
+            #[cfg(not(feature = "arm7"))]
             Logger::Tty => {
                 Tty.write_cstr(cstr);
             }
@@ -119,6 +138,7 @@ pub fn _print(args: Arguments) {
             Logger::NoCash => {
                 write!(NoCash, "{args}").unwrap();
             }
+            #[cfg(not(feature = "arm7"))]
             Logger::Tty => {
                 write!(Tty, "{args}").unwrap();
             }
@@ -166,11 +186,71 @@ impl NoCash {
         self.write_cstr_param(cstr);
     }
 
-    /// Returns a string identifying the emulator that the ROM is running on.
-    /// Only NO$GBA supports this, so don't rely on it
+    /// Returns a string identifying the emulator that the ROM is running on,
+    /// or `None` on hardware and emulators without the debug interface.
+    ///
+    /// See [`Emulator::detect`] to know which emulator it is.
     pub fn get_emu_id() -> Option<&'static str> {
-        let emu_id = unsafe { core::slice::from_raw_parts(registers::EMU_ID_PTR, 16) };
-        core::str::from_utf8(emu_id).ok()
+        let emu_id = unsafe { &*(registers::EMU_ID_PTR as *const [u8; 16]) };
+        parse_emu_id(emu_id)
+    }
+
+    /// Stops the emulator like a breakpoint set in the debugger, even on release builds.
+    /// Does nothing on hardware.
+    ///
+    /// See [`breakpoint`] and [`dbg_breakpoint!`] for breakpoints that are removed
+    /// on release builds.
+    #[inline(always)]
+    pub fn breakpoint() {
+        unsafe {
+            asm!("mov r11,r11", options(nomem, nostack, preserves_flags));
+        }
+    }
+}
+
+/// Parses the ID read from [`registers::EMU_ID_PTR`]: printable ASCII, padded with
+/// spaces or null bytes. The register reads as zeroes on hardware.
+fn parse_emu_id(id: &[u8; 16]) -> Option<&str> {
+    let len = id
+        .iter()
+        .rposition(|&b| b != 0 && b != b' ')
+        .map_or(0, |last| last + 1);
+    let id = &id[..len];
+    if id.is_empty() || !id.iter().all(|b| (0x20..0x7F).contains(b)) {
+        return None;
+    }
+    core::str::from_utf8(id).ok()
+}
+
+/// Emulators implementing the NO$GBA debug interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emulator {
+    /// NO$GBA, which reports an ID like `no$gba v2.8d`
+    NoCash,
+    /// melonDS, which reports an ID starting with `melonDS`
+    MelonDs,
+    /// Any other emulator with a debug ID
+    Other,
+}
+impl Emulator {
+    /// Identifies an emulator from the ID returned by [`NoCash::get_emu_id`]
+    pub fn from_id(id: &str) -> Self {
+        let starts_with = |prefix: &str| {
+            id.get(..prefix.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+        };
+        if starts_with("no$gba") {
+            Self::NoCash
+        } else if starts_with("melonds") {
+            Self::MelonDs
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Returns the emulator the ROM is running on, or `None` when running on hardware
+    pub fn detect() -> Option<Self> {
+        NoCash::get_emu_id().map(Self::from_id)
     }
 }
 impl Write for NoCash {
@@ -215,16 +295,27 @@ impl Write for NoCash {
     }
 }
 
+/// Writes to the console used by the `print!` macros. See [`log_to_tty`] and [`log_to_console`].
+///
+/// Writing does nothing if no console has been set up.
 #[derive(Clone, Copy)]
+#[cfg(not(feature = "arm7"))]
 pub struct Tty;
+#[cfg(not(feature = "arm7"))]
 impl Tty {
     pub fn write_cstr(&mut self, cstr: &CStr) {
-        todo!()
+        if let Some(console) = TTY.lock().as_mut() {
+            console.write_bytes(cstr.to_bytes());
+        }
     }
 }
+#[cfg(not(feature = "arm7"))]
 impl Write for Tty {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        todo!()
+        match TTY.lock().as_mut() {
+            Some(console) => console.write_str(s),
+            None => Ok(()),
+        }
     }
 }
 
@@ -262,11 +353,11 @@ pub const fn breakpoint() {}
 #[macro_export]
 macro_rules! dbg_breakpoint {
     () => {
-        dbg_breakpoint!("Hit breakpoint");
+        $crate::dbg_breakpoint!("Hit breakpoint");
     };
     ($($arg:tt)*) => {
         if cfg!(debug_assertions) {
-            println!("[{}:{}] {}", file!(), line!(), format_args!($($arg)*));
+            $crate::println!("[{}:{}] {}", file!(), line!(), format_args!($($arg)*));
             $crate::debug::breakpoint();
         }
     }
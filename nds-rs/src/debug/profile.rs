@@ -0,0 +1,57 @@
+use core::fmt::Write;
+
+use super::NoCash;
+
+/// Measures the CPU cycles spent between its creation and its drop, using the
+/// `%zeroclks%` and `%lastclks%` specifiers of the NO$GBA debugger.
+/// The count is printed to the debugger console when the scope is dropped.
+///
+/// The emulator has a single cycle counter, so scopes can't be nested: an inner scope
+/// resets the counter of the outer one. When not running in an emulator, scopes do nothing.
+///
+/// Prefer the [`profile!`](crate::profile!) macro.
+pub struct ProfileScope<'a> {
+    name: &'a str,
+    enabled: bool,
+}
+impl<'a> ProfileScope<'a> {
+    /// Resets the cycle counter of the emulator and starts measuring
+    #[inline]
+    pub fn start(name: &'a str) -> Self {
+        let enabled = NoCash::get_emu_id().is_some();
+        if enabled {
+            NoCash.write_cstr_param(c"%zeroclks%");
+        }
+        Self { name, enabled }
+    }
+}
+impl Drop for ProfileScope<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.enabled {
+            NoCash.write_cstr_param(c"%lastclks%");
+            let _ = writeln!(NoCash, " cycles: {}", self.name);
+        }
+    }
+}
+
+/// Counts the CPU cycles spent running a block in the NO$GBA debugger,
+/// and prints them to its console, followed by the name of the section.
+/// See [`ProfileScope`].
+///
+/// ```rust,no_run
+/// let state = nds_rs::profile!("update", { update(state) });
+///
+/// // Or until the end of the current scope
+/// let _scope = nds_rs::profile!("draw");
+/// ```
+#[macro_export]
+macro_rules! profile {
+    ($name:expr) => {
+        $crate::debug::ProfileScope::start($name)
+    };
+    ($name:expr, $body:block) => {{
+        let _scope = $crate::debug::ProfileScope::start($name);
+        $body
+    }};
+}
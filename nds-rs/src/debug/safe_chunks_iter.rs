@@ -1,10 +1,31 @@
+/// Splits a string in chunks of at most `B` bytes, without splitting characters
+/// or NO$GBA `%...%` specifiers.
+///
+/// A specifier is always a chunk of its own. A `%` that isn't closed within `B` bytes
+/// isn't a specifier, and is kept as text.
 pub(crate) struct SafeChunksIter<'s, const B: usize> {
-    remaining: &'s str
+    remaining: &'s str,
 }
 impl<'s, const B: usize> SafeChunksIter<'s, B> {
+    /// Chunks must be able to hold any character
+    const MIN_SIZE: () = assert!(B >= 4, "chunks must be at least 4 bytes long");
+
     pub(crate) const fn new(remaining: &'s str) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::MIN_SIZE;
         Self { remaining }
     }
+
+    /// Largest char boundary of the remaining string not greater than `index`
+    fn floor_char_boundary(&self, index: usize) -> usize {
+        if index >= self.remaining.len() {
+            return self.remaining.len();
+        }
+        (0..=index)
+            .rev()
+            .find(|&i| self.remaining.is_char_boundary(i))
+            .unwrap_or(0)
+    }
 }
 impl<'s, const B: usize> Iterator for SafeChunksIter<'s, B> {
     type Item = &'s str;
@@ -13,26 +34,70 @@ impl<'s, const B: usize> Iterator for SafeChunksIter<'s, B> {
         if self.remaining.is_empty() {
             return None;
         }
-        let split_point;
-
-        match self.remaining.find("%") {
-            Some(0) => {
-                let closing_marker = self.remaining[1..]
-                    .find("%")
-                    .map(|i| i + 2)
-                    .unwrap_or(self.remaining.len());
-                split_point = closing_marker;
-                debug_assert!(split_point < B);
-            }
-            Some(next_marker) => {
-                split_point = next_marker.min(B);
-            }
-            None => {
-                split_point = self.remaining.len().min(B);
+        // position of the next `%` after the first byte.
+        // Bytes of multi-byte characters are never `%`, so there's no need to decode them
+        let next_marker = self.remaining.as_bytes()[1..]
+            .iter()
+            .position(|&b| b == b'%')
+            .map(|i| i + 1);
+
+        let split_point = match next_marker {
+            // a specifier, including both markers
+            Some(closing) if self.remaining.starts_with('%') && closing < B => closing + 1,
+            // text until the next specifier
+            Some(next_marker) if !self.remaining.starts_with('%') => {
+                self.floor_char_boundary(next_marker.min(B))
             }
-        }
+            // text with an unclosed `%`, or a too long specifier
+            _ => self.floor_char_boundary(B),
+        };
         let (section, the_rest) = self.remaining.split_at(split_point);
         self.remaining = the_rest;
         Some(section)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn chunks<const B: usize>(s: &str) -> Vec<&str> {
+        SafeChunksIter::<B>::new(s).collect()
+    }
+
+    #[test]
+    fn specifier_is_its_own_chunk() {
+        assert_eq!(chunks::<8>("abc%r0%def"), ["abc", "%r0%", "def"]);
+        assert_eq!(chunks::<8>("%r0%%r1%"), ["%r0%", "%r1%"]);
+    }
+
+    #[test]
+    fn specifier_crossing_chunk_boundary() {
+        // The text before it is cut short instead of the specifier
+        assert_eq!(chunks::<8>("abcdef%frame%"), ["abcdef", "%frame%"]);
+        // Too long to be a specifier: kept as text
+        assert_eq!(chunks::<8>("%totallytext%"), ["%totally", "text", "%"]);
+    }
+
+    #[test]
+    fn unterminated_marker_is_text() {
+        assert_eq!(chunks::<8>("100%"), ["100", "%"]);
+        assert_eq!(chunks::<4>("%abcdef"), ["%abc", "def"]);
+    }
+
+    #[test]
+    fn exact_size_chunks() {
+        assert_eq!(chunks::<4>("abcdefgh"), ["abcd", "efgh"]);
+        assert_eq!(chunks::<4>("%ab%"), ["%ab%"]);
+        assert_eq!(chunks::<4>("%abc%"), ["%abc", "%"]);
+        assert_eq!(chunks::<4>(""), [] as [&str; 0]);
+    }
+
+    #[test]
+    fn characters_are_not_split() {
+        assert_eq!(chunks::<4>("aé€"), ["aé", "€"]);
+        assert_eq!(chunks::<4>("😀😀"), ["😀", "😀"]);
+    }
+}
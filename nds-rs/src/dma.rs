@@ -7,89 +7,517 @@
 //! In case an operation is being processed, and a new one with a higher priority
 //! is received, the one with the lowest priority will be put on hold and the other will be fulfilled.
 //! **4 cycles** must be waited after issuing a request before cancelling/overwriting it;
-//! (See the first 4 lines of [`wait_for`]); failing to do so may lead to a lock up (See point 1 below)
+//! (See the first 4 lines of [`is_busy`]); failing to do so may lead to a lock up (See point 1 below)
 //!
+//! Each channel is represented by a [`DmaChannel`], taken from [`Hw::dma`](crate::Hw).
+//! [`copy`](DmaChannel::copy), [`fill`](DmaChannel::fill) and
+//! [`copy_within`](DmaChannel::copy_within) return once the operation is done.
+//! To keep the CPU busy meanwhile, [`start_copy`](DmaChannel::start_copy),
+//! [`start_fill`](DmaChannel::start_fill) and
+//! [`start_copy_within`](DmaChannel::start_copy_within) return immediatly with a
+//! [`Transfer`], which borrows both the channel and the buffers until the operation is
+//! done. Use [`Transfer::is_done`], [`Transfer::wait`] or `.await` it.
 //!
-//! All functions here are NOT synchronous (except for [`wait_for`]), they return immediatly.
-//! Use [`is_busy`] and [`wait_for`] to check and wait for a channel to be available
+//! Channels can also be programmed to copy a value per scanline on each HBlank
+//! (See [`DmaChannel::hblank`] and [`ScanlineTable`]), for raster effects such as
+//! wavy backgrounds, gradients or per-line perspective.
+//!
+//! With the `arm7` feature, the channels of the ARM7 are part of its
+//! [`Hw`](crate::arm7::Hw). They can't start on HBlank, and have no fill registers:
+//! [`fill`](DmaChannel::fill) copies the value from the [`DmaChannel`] instead.
 //! # Warning
 //! The DMA can violate any safety guaranty in Rust, since it can be used to overwrite any memory segment
 //! using any value. It can be challenging to properly **and safely** use this hardware, even with this API.
 //! When developing, one should keep in mind the following things:
 //!  1. There is a delay of 2 cycles after issuing a request (Writing [`Flags::ENABLED`]) and the DMA actually starting.
-//! **Don't touch the channel during that period**, it _will_ lock up.
+//!     **Don't touch the channel during that period**, it _will_ lock up.
 //!  2. Toggling OFF the bit [`ENABLED`](Flags::ENABLED) will halt the DMA immediatly.
-//! This is **not recommended** unless the channel was programmed to [autorepeat](Flags::REPEAT).
-//! In any case, **wait at least 4 cycles after starting before halting the channel**.
+//!     This is **not recommended** unless the channel was programmed to [autorepeat](Flags::REPEAT).
+//!     In any case, **wait at least 4 cycles after starting before halting the channel**.
 //!  3. [Wait](wait_for) until the channel has finished before issuing a command, otherwise the current operation will be overwritten
-//! (in the best case, see points 1 and 2 for the worst case).
-//! ([`copy`](copy) and [`fill`](fill) are the only non-`unsafe` functions here, and automatically call [wait_for])
+//!     (in the best case, see points 1 and 2 for the worst case).
+//!     A [`Transfer`] holds a mutable borrow of its [`DmaChannel`], so the borrow checker enforces this
+//!     as long as the [`Transfer`] isn't leaked. Dropping a [`Transfer`] waits for it to finish.
 //!  4. _The hardware itself_ doesn't have access to the CPU cache, therefore, data in the stack (such as local variables) may not be available yet
-//! in main memory. Static variables and [`Box`](alloc::boxed::Box)ed values are ok, flushing the cache is also an option. ([copy<T>()](copy) and [fill<T>()](fill)
-//! take care of this issue by themselves)
+//!     in main memory. The methods of [`DmaChannel`] flush the source and destination from the cache before starting.
+//!  5. Leaking a [`Transfer`] (e.g. with [`core::mem::forget`], or by leaking a future awaiting it) releases the
+//!     borrows while the DMA may still be running. That's why the functions returning one are `unsafe`.
 
-use crate::interrupts::swi_intr_wait;
-use core::arch::asm;
-use core::mem::size_of;
+use core::{
+    arch::asm,
+    future::Future,
+    marker::PhantomData,
+    mem::size_of,
+    ops::Range,
+    pin::Pin,
+    task::{Context, Poll},
+};
 use nds_sys::{
     dma::{calc_cr, calc_registers, Flags},
     interrupts,
 };
 
+#[cfg(not(feature = "arm7"))]
+use crate::cache::dc_flush_slice;
+use crate::executor::irq;
+
 pub use nds_sys::dma::Channel;
 
-/// Checks if the specified [`Channel`] is busy
-pub fn is_busy(ch: Channel) -> bool {
+#[cfg(not(feature = "arm7"))]
+mod hblank;
+#[cfg(not(feature = "arm7"))]
+pub use hblank::*;
+
+/// The ARM7 has no data cache, the DMA always sees the memory written by the CPU
+#[cfg(feature = "arm7")]
+fn dc_flush_slice<T>(_: &[T]) {}
+
+/// Waits the 4 cycles required between starting a channel and touching it again
+#[inline(always)]
+fn settle() {
     unsafe {
         asm!("nop");
         asm!("nop");
         asm!("nop");
         asm!("nop");
     }
+}
+
+/// Checks if the specified [`Channel`] is busy
+pub fn is_busy(ch: Channel) -> bool {
+    settle();
     let cr = calc_cr(ch);
     let flags = Flags::from_bits_retain(unsafe { cr.read_volatile() });
     (flags & Flags::ENABLED).bits() != 0
 }
 
 /// Hangs until the specified [`Channel`] becomes available.
-/// This function uses interrupts, so extra care should be taken
-/// to make sure interrupts [`DMA0`](interrupts::Flags::DMA0), [`DMA1`](interrupts::Flags::DMA1),
-/// [`DMA2`](interrupts::Flags::DMA2) and [`DMA3`](interrupts::Flags::DMA3) are enabled ([`irq_enable`](crate::interrupts::irq_enable)).
-/// On debug builds, this function panics if the required interrupt is not enabled, but on release this may hang for ever
+/// Polls the channel, so it doesn't depend on interrupts being enabled.
+/// Channels programmed to [autorepeat](Flags::REPEAT) never become available.
 pub fn wait_for(ch: Channel) {
-    unsafe {
-        asm!("nop");
-        asm!("nop");
-        asm!("nop");
-        asm!("nop");
-    }
-    if !is_busy(ch) {
-        return;
-    }
-    let irq = match ch {
+    while is_busy(ch) {}
+}
+
+const fn irq_of(ch: Channel) -> interrupts::Flags {
+    match ch {
         Channel::Ch0 => interrupts::Flags::DMA0,
         Channel::Ch1 => interrupts::Flags::DMA1,
         Channel::Ch2 => interrupts::Flags::DMA2,
         Channel::Ch3 => interrupts::Flags::DMA3,
-    };
-    if cfg!(debug_assertions) {
-        let ok;
-        let ime = unsafe { interrupts::REG_IME.read_volatile() };
-        if ime == 1 {
-            let ie = unsafe { interrupts::Flags::from_bits(interrupts::REG_IE.read_volatile()) };
-            if let Some(ie) = ie {
-                ok = (ie & irq).bits() != 0;
-            } else {
-                ok = false;
-            }
+    }
+}
+
+/// All four DMA channels. Part of [`Hw`](crate::Hw).
+#[non_exhaustive]
+pub struct Dma {
+    /// Highest priority
+    pub ch0: DmaChannel,
+    pub ch1: DmaChannel,
+    pub ch2: DmaChannel,
+    /// Lowest priority
+    pub ch3: DmaChannel,
+}
+impl Dma {
+    pub(crate) const unsafe fn new() -> Self {
+        Self {
+            ch0: DmaChannel::new(Channel::Ch0),
+            ch1: DmaChannel::new(Channel::Ch1),
+            ch2: DmaChannel::new(Channel::Ch2),
+            ch3: DmaChannel::new(Channel::Ch3),
+        }
+    }
+}
+
+/// Exclusive handle to a DMA [`Channel`].
+///
+/// Only one [`Transfer`] can be running on a channel at any time,
+/// since starting one borrows the channel mutably.
+pub struct DmaChannel {
+    ch: Channel,
+    /// Source of [`start_fill`](Self::start_fill), the ARM7 has no fill registers
+    #[cfg(feature = "arm7")]
+    fill: u32,
+}
+impl DmaChannel {
+    pub(crate) const unsafe fn new(ch: Channel) -> Self {
+        Self {
+            ch,
+            #[cfg(feature = "arm7")]
+            fill: 0,
+        }
+    }
+
+    /// The channel this handle controls
+    pub fn channel(&self) -> Channel {
+        self.ch
+    }
+
+    /// Checks if the channel is busy.
+    /// Only returns `true` if a [`Transfer`] was leaked or if the channel
+    /// was programmed through the `unsafe` functions of this module.
+    pub fn is_busy(&self) -> bool {
+        is_busy(self.ch)
+    }
+
+    /// Programs the channel to move data from `src` to `dst`, using `flags`
+    /// (which must include the length and [`Flags::ENABLE`]).
+    ///
+    /// # Safety
+    /// The caller must make sure that the transfer described by `flags` only reads from
+    /// and writes to memory that outlives the returned [`Transfer`], and that the
+    /// memory has been flushed from the cache if needed.
+    pub unsafe fn start<'t>(
+        &'t mut self,
+        src: *const usize,
+        dst: *mut usize,
+        flags: Flags,
+    ) -> Transfer<'t> {
+        // The previous transfer (if any) was already waited for when it was dropped,
+        // but channels can also be started with the `unsafe` functions
+        wait_for(self.ch);
+        let (src_cr, dst_cr, cr, _) = calc_registers(self.ch);
+        src_cr.write_volatile(src);
+        dst_cr.write_volatile(dst);
+        cr.write_volatile(flags.bits());
+        Transfer {
+            channel: self,
+            _buffers: PhantomData,
+        }
+    }
+
+    /// Copies `src` into `dst`, and waits until it's done.
+    /// Panics if `size_of::<T>()` is neither 2 nor 4, or if there are more than
+    /// [`MAX_LEN`] elements to copy.
+    /// In case `src.len() != dst.len()` then only `min(src.len(), dst.len())` elements will be copied.
+    pub fn copy<T>(&mut self, src: &[T], dst: &mut [T])
+    where
+        T: Sized + Copy,
+    {
+        unsafe { self.start_copy(src, dst) }.wait()
+    }
+
+    /// Starts copying `src` into `dst`, see [`copy`](Self::copy).
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked, directly or through a future
+    /// awaiting it: it has to be dropped, waited for or awaited until done.
+    pub unsafe fn start_copy<'t, T>(&'t mut self, src: &'t [T], dst: &'t mut [T]) -> Transfer<'t>
+    where
+        T: Sized + Copy,
+    {
+        let len = src.len().min(dst.len());
+        if len == 0 {
+            return self.start_noop();
+        }
+        let flags =
+            Flags::ENABLE | Flags::START_IMM | Flags::INC_SRC | Flags::INC_DST | Flags::INT_REQ;
+        let flags = flags | unit_flags::<T>("copy") | count_flags(len);
+        dc_flush_slice(&src[..len]);
+        dc_flush_slice(&dst[..len]);
+        self.start(src.as_ptr() as _, dst.as_mut_ptr() as _, flags)
+    }
+
+    /// Copies the elements of `buffer` in range `src` to `buffer`, starting at `dest`,
+    /// and waits until it's done.
+    /// Works like [`slice::copy_within`], overlapping ranges included.
+    /// Panics if `size_of::<T>()` is neither 2 nor 4, if the ranges are out of bounds or
+    /// longer than [`MAX_LEN`].
+    pub fn copy_within<T>(&mut self, buffer: &mut [T], src: Range<usize>, dest: usize)
+    where
+        T: Sized + Copy,
+    {
+        unsafe { self.start_copy_within(buffer, src, dest) }.wait()
+    }
+
+    /// Starts copying the elements of `buffer` in range `src` to `dest`, see
+    /// [`copy_within`](Self::copy_within).
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked, see [`start_copy`](Self::start_copy)
+    pub unsafe fn start_copy_within<'t, T>(
+        &'t mut self,
+        buffer: &'t mut [T],
+        src: Range<usize>,
+        dest: usize,
+    ) -> Transfer<'t>
+    where
+        T: Sized + Copy,
+    {
+        assert!(src.start <= src.end, "src start is greater than src end");
+        assert!(src.end <= buffer.len(), "src is out of bounds");
+        let len = src.end - src.start;
+        assert!(dest <= buffer.len() - len, "dest is out of bounds");
+        if len == 0 {
+            return self.start_noop();
+        }
+        self.copy_within_unchecked(buffer, src.start, dest, len)
+    }
+
+    /// Copies `len` elements from `src`. Starts copying at `from`, and copies to `to`.
+    /// Panics if `size_of::<T>()` is neither 2 nor 4, or if `len` is 0 or greater than
+    /// [`MAX_LEN`]; but doesn't do any bounds check
+    ///
+    /// # Safety
+    /// `from + len` and `to + len` must not be greater than `src.len()`, and the returned
+    /// [`Transfer`] must not be leaked
+    pub unsafe fn copy_within_unchecked<'t, T>(
+        &'t mut self,
+        src: &'t mut [T],
+        from: usize,
+        to: usize,
+        len: usize,
+    ) -> Transfer<'t>
+    where
+        T: Sized + Copy,
+    {
+        assert!(len > 0, "a count of 0 copies the maximum length");
+        dc_flush_slice(src);
+        let ptr = src.as_mut_ptr();
+        let unit = unit_flags::<T>("copy_within") | count_flags(len);
+        // When the destination is after the source the ranges may overlap,
+        // so copy backwards, starting from the last element
+        if to > from {
+            let flags = Flags::ENABLE | Flags::DEC_SRC | Flags::DEC_DST | Flags::INT_REQ | unit;
+            let last = len - 1;
+            self.start(ptr.add(from + last) as _, ptr.add(to + last) as _, flags)
         } else {
-            ok = false;
+            let flags = Flags::ENABLE | Flags::INC_SRC | Flags::INC_DST | Flags::INT_REQ | unit;
+            self.start(ptr.add(from) as _, ptr.add(to) as _, flags)
+        }
+    }
+
+    /// Fills `dst` by copying `value`, and waits until it's done.
+    /// Panics if `size_of::<T>()` is neither 2 nor 4, or if `dst` is longer than [`MAX_LEN`].
+    pub fn fill<T>(&mut self, value: T, dst: &mut [T])
+    where
+        T: Sized + Copy,
+    {
+        unsafe { self.start_fill(value, dst) }.wait()
+    }
+
+    /// Starts filling `dst` with `value`, see [`fill`](Self::fill).
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked, see [`start_copy`](Self::start_copy)
+    pub unsafe fn start_fill<'t, T>(&'t mut self, value: T, dst: &'t mut [T]) -> Transfer<'t>
+    where
+        T: Sized + Copy,
+    {
+        if dst.is_empty() {
+            return self.start_noop();
         }
-        if !ok {
-            panic!("wait_for requires DMA interrupts to be enabled");
+        let flags = Flags::ENABLE | Flags::FIX_SRC | Flags::INT_REQ;
+        let flags = flags | unit_flags::<T>("fill") | count_flags(dst.len());
+        let value_conv: u32 = match size_of::<T>() {
+            4 => core::mem::transmute_copy(&value),
+            _ => (core::mem::transmute_copy::<_, u16>(&value)) as u32,
+        };
+        dc_flush_slice(dst);
+        // Writing the value is fine, the channel isn't running
+        wait_for(self.ch);
+        #[cfg(not(feature = "arm7"))]
+        let fill_cr = {
+            let fill_cr = calc_registers(self.ch).3;
+            fill_cr.write_volatile(value_conv);
+            fill_cr
+        };
+        // The value lives in the channel, which the transfer borrows until it's done
+        #[cfg(feature = "arm7")]
+        let fill_cr = {
+            self.fill = value_conv;
+            core::ptr::addr_of!(self.fill)
+        };
+        self.start(fill_cr as _, dst.as_mut_ptr() as _, flags)
+    }
+
+    /// Returns a [`Transfer`] that is already done, for empty operations
+    pub(crate) unsafe fn start_noop(&mut self) -> Transfer<'_> {
+        Transfer {
+            channel: self,
+            _buffers: PhantomData,
         }
     }
-    swi_intr_wait(irq, false);
+}
+
+/// Most units a single operation can move, the size of the count field of the channels
+#[cfg(not(feature = "arm7"))]
+pub const MAX_LEN: usize = 0x1F_FFFF;
+/// Most units a single operation can move. The count field of the ARM7 channels is
+/// 14 bits long, except for channel 3 where it's 16 bits long
+#[cfg(feature = "arm7")]
+pub const MAX_LEN: usize = 0x3FFF;
+
+/// Returns the count field of the flags for `len` units.
+/// Panics if `len` doesn't fit, a count of 0 meaning the maximum length to the hardware
+pub(crate) fn count_flags(len: usize) -> Flags {
+    assert!(
+        (1..=MAX_LEN).contains(&len),
+        "DMA length {len} is out of range"
+    );
+    Flags::from_bits_retain(len as u32)
+}
+
+/// Returns the flags for the transfer unit of `T`.
+/// Panics if `size_of::<T>()` is neither 2 nor 4.
+fn unit_flags<T>(op: &str) -> Flags {
+    match size_of::<T>() {
+        4 => Flags::WORDS,
+        2 => Flags::HALFWORDS,
+        _ => panic!("Can only run {op}<T>() if T is either 2 or 4 bytes"),
+    }
+}
+
+/// An operation running on a [`DmaChannel`].
+///
+/// Borrows the channel and the buffers involved until the operation is done.
+/// Dropping it waits until the channel is available.
+#[must_use = "dropping a `Transfer` blocks until it's done"]
+pub struct Transfer<'t> {
+    channel: &'t mut DmaChannel,
+    _buffers: PhantomData<&'t mut [u8]>,
+}
+impl Transfer<'_> {
+    /// Returns `true` once the operation has finished
+    pub fn is_done(&self) -> bool {
+        !self.channel.is_busy()
+    }
+
+    /// Blocks until the operation has finished
+    pub fn wait(self) {
+        // Dropping waits
+    }
+
+    /// The channel running this operation
+    pub fn channel(&self) -> Channel {
+        self.channel.ch
+    }
+}
+impl Drop for Transfer<'_> {
+    fn drop(&mut self) {
+        wait_for(self.channel.ch);
+    }
+}
+/// Resolves once the operation has finished. Uses the DMA interrupt of the channel
+/// to wake the task, installing the handler and enabling the interrupt when polled.
+impl Future for Transfer<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_done() {
+            return Poll::Ready(());
+        }
+        irq::listen(irq_of(self.channel.ch), cx.waker());
+        // The transfer could have finished before the waker was registered
+        if self.is_done() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Operations shared by the legacy [`DmaChannel`] and the DSi
+/// [`NdmaChannel`](crate::ndma::NdmaChannel), to write code generic over both engines.
+///
+/// See [`AnyChannel`](crate::ndma::AnyChannel) to use the NDMA when available, and the
+/// legacy DMA otherwise.
+pub trait DmaEngine {
+    type Transfer<'t>: DmaTransfer
+    where
+        Self: 't;
+
+    /// Copies `src` into `dst` and waits until it's done, see [`DmaChannel::copy`]
+    fn copy<T: Copy>(&mut self, src: &[T], dst: &mut [T]) {
+        unsafe { self.start_copy(src, dst) }.wait()
+    }
+
+    /// Copies the elements of `buffer` in range `src` to `dest` and waits until it's done,
+    /// see [`DmaChannel::copy_within`]
+    fn copy_within<T: Copy>(&mut self, buffer: &mut [T], src: Range<usize>, dest: usize) {
+        unsafe { self.start_copy_within(buffer, src, dest) }.wait()
+    }
+
+    /// Fills `dst` with `value` and waits until it's done, see [`DmaChannel::fill`]
+    fn fill<T: Copy>(&mut self, value: T, dst: &mut [T]) {
+        unsafe { self.start_fill(value, dst) }.wait()
+    }
+
+    /// Starts copying `src` into `dst`, see [`DmaChannel::start_copy`]
+    ///
+    /// # Safety
+    /// The returned transfer must not be leaked, directly or through a future awaiting it.
+    unsafe fn start_copy<'t, T: Copy>(
+        &'t mut self,
+        src: &'t [T],
+        dst: &'t mut [T],
+    ) -> Self::Transfer<'t>;
+
+    /// Starts copying the elements of `buffer` in range `src` to `dest`,
+    /// see [`DmaChannel::start_copy_within`]
+    ///
+    /// # Safety
+    /// The returned transfer must not be leaked, see [`start_copy`](Self::start_copy)
+    unsafe fn start_copy_within<'t, T: Copy>(
+        &'t mut self,
+        buffer: &'t mut [T],
+        src: Range<usize>,
+        dest: usize,
+    ) -> Self::Transfer<'t>;
+
+    /// Starts filling `dst` with `value`, see [`DmaChannel::start_fill`]
+    ///
+    /// # Safety
+    /// The returned transfer must not be leaked, see [`start_copy`](Self::start_copy)
+    unsafe fn start_fill<'t, T: Copy>(
+        &'t mut self,
+        value: T,
+        dst: &'t mut [T],
+    ) -> Self::Transfer<'t>;
+}
+
+/// An operation started by a [`DmaEngine`]. Dropping it waits until it's done
+pub trait DmaTransfer: Future<Output = ()> {
+    /// Returns `true` once the operation has finished
+    fn is_done(&self) -> bool;
+
+    /// Blocks until the operation has finished
+    fn wait(self)
+    where
+        Self: Sized,
+    {
+        // Dropping waits
+    }
+}
+
+impl DmaEngine for DmaChannel {
+    type Transfer<'t> = Transfer<'t>;
+
+    unsafe fn start_copy<'t, T: Copy>(
+        &'t mut self,
+        src: &'t [T],
+        dst: &'t mut [T],
+    ) -> Transfer<'t> {
+        DmaChannel::start_copy(self, src, dst)
+    }
+
+    unsafe fn start_copy_within<'t, T: Copy>(
+        &'t mut self,
+        buffer: &'t mut [T],
+        src: Range<usize>,
+        dest: usize,
+    ) -> Transfer<'t> {
+        DmaChannel::start_copy_within(self, buffer, src, dest)
+    }
+
+    unsafe fn start_fill<'t, T: Copy>(&'t mut self, value: T, dst: &'t mut [T]) -> Transfer<'t> {
+        DmaChannel::start_fill(self, value, dst)
+    }
+}
+
+impl DmaTransfer for Transfer<'_> {
+    fn is_done(&self) -> bool {
+        Transfer::is_done(self)
+    }
 }
 
 /// Fills `dst` with `len` words of `src`.
@@ -135,6 +563,7 @@ pub unsafe fn copy_half_words(ch: Channel, src: *const u16, dst: *mut u16, len:
 ///  - only [`Copy`] values are being copied,
 ///  - [`dst`, `dst + len`) is valid **and** won't overwrite unrelated data,
 ///  - and the channel is free, since [`fill_words`] will overwrite it's settings
+#[cfg(not(feature = "arm7"))]
 pub unsafe fn fill_words(ch: Channel, value: u32, dst: *mut u32, len: usize) {
     let (src_cr, dst_cr, cr, fill_cr) = calc_registers(ch);
     fill_cr.write_volatile(value as u32);
@@ -153,6 +582,7 @@ pub unsafe fn fill_words(ch: Channel, value: u32, dst: *mut u32, len: usize) {
 ///  - only [`Copy`] values are being copied,
 ///  - [`dst`, `dst + len`) is valid **and** won't overwrite unrelated data,
 ///  - and the channel is free, since [`fill_half_words`] will overwrite it's settings
+#[cfg(not(feature = "arm7"))]
 pub unsafe fn fill_half_words(ch: Channel, value: u16, dst: *mut u16, len: usize) {
     let (src_cr, dst_cr, cr, fill_cr) = calc_registers(ch);
     fill_cr.write_volatile(value as u32);
@@ -161,114 +591,3 @@ pub unsafe fn fill_half_words(ch: Channel, value: u16, dst: *mut u16, len: usize
     let flags = (Flags::ENABLE | Flags::FIX_SRC).bits() | (len as u32);
     cr.write_volatile(flags);
 }
-
-/// Copies `src` into `dst` using DMA channel 3.
-/// Calls [`wait_for`] on [`Channel::Ch3`](Channel 3), so if interrupt [`DMA3`](interrupts::Flags::DMA3) is disabled a panic may occur.
-/// Panics if `size_of::<T>()` is neither 2 nor 4.
-/// In case `src.len() != dst.len()` then only `min(src.len(), dst.len())` elements will be copied.
-pub fn copy<T>(src: &[T], dst: &mut [T])
-where
-    T: Sized + Copy,
-{
-    wait_for(Channel::Ch3);
-    let (src_cr, dst_cr, cr, _) = calc_registers(Channel::Ch3);
-    let mut flags: Flags =
-        Flags::ENABLE | Flags::START_IMM | Flags::INC_SRC | Flags::INC_DST | Flags::INT_REQ;
-    match size_of::<T>() {
-        4 => {
-            flags |= Flags::WORDS;
-        }
-        2 => {
-            flags |= Flags::HALFWORDS;
-        }
-        _ => {
-            panic!("Can only run copy<T>() if T is either 2 or 4 bytes");
-        }
-    }
-    let len = src.len().min(dst.len()) as u32;
-    let flags: u32 = flags.bits() | len;
-
-    unsafe {
-        src_cr.write_volatile(src.as_ptr() as *const usize);
-        dst_cr.write_volatile(dst.as_mut_ptr() as *mut usize);
-        cr.write_volatile(flags);
-    }
-}
-
-/// Copies `len` elements from `src`. Starts copying at `from`, and copies to `to`.
-/// Hangs if the channel is busy.
-/// Panics if `size_of::<T>()` is neither 2 nor 4; but doesn't do any bounds check
-pub unsafe fn copy_within_unchecked<T>(src: &mut [T], from: usize, to: usize, len: usize)
-where
-    T: Sized + Copy,
-{
-    wait_for(Channel::Ch3);
-    let (src_cr, dst_cr, cr, _) = calc_registers(Channel::Ch3);
-    let mut flags: Flags = Flags::ENABLE;
-    match size_of::<T>() {
-        4 => {
-            flags |= Flags::WORDS;
-        }
-        2 => {
-            flags |= Flags::HALFWORDS;
-        }
-        _ => {
-            panic!("Can only run copy<T>() if size_of::<T>() is either 2 or 4 bytes");
-        }
-    }
-    let flags: u32 = flags.bits() | len as u32;
-    let src = src.as_mut_ptr() as *mut T;
-    src_cr.write_volatile(src.add(from) as *const usize);
-    dst_cr.write_volatile(src.add(to) as *mut usize);
-    cr.write_volatile(flags);
-}
-
-// /// Copies `len` elements from `src`. Starts copying at `from`, and copies to `to`.
-// /// Hangs if the channel is busy.
-// /// Panics if `size_of::<T>()` is neither 2 nor 4.
-// pub fn copy_within<T>(src: &mut [T], from: usize, to: usize, len: usize) -> Result<(), ()>
-// where
-//     T: Sized + Copy,
-// {
-//     // Out-of-bounds read || Out-of-bounds write
-//     if src.len() < from + len || src.len() < to + len {
-//         return Err(());
-//     }
-//     //debug_assert!(to <= from || to > from+len,"The segments to be copied overlap");
-//     unsafe { copy_within_unchecked::<T>(src, from, to, len) }
-//     return Ok(());
-// }
-
-/// Fills `dst` by copying `value` using DMA channel 3.
-/// Copies `src` into `dst` using DMA channel 3.
-/// Calls [`wait_for`] on [`Channel::Ch3`](Channel 3), so if interrupt [`DMA3`](interrupts::Flags::DMA3) is disabled a panic may occur.
-/// Panics if `size_of::<T>()` is neither 2 nor 4.
-pub fn fill<T>(value: T, dst: &mut [T])
-where
-    T: Sized + Copy,
-{
-    wait_for(Channel::Ch3);
-    let (src_cr, dst_cr, cr, fill_cr) = calc_registers(Channel::Ch3);
-    let mut flags: Flags = Flags::ENABLE | Flags::FIX_SRC | Flags::INT_REQ;
-    let value_conv: u32;
-    match size_of::<T>() {
-        4 => {
-            flags |= Flags::WORDS;
-            value_conv = unsafe { core::mem::transmute_copy(&value) };
-        }
-        2 => {
-            flags |= Flags::HALFWORDS;
-            value_conv = unsafe { core::mem::transmute_copy::<_, u16>(&value) } as u32;
-        }
-        _ => {
-            panic!("Can only run fill<T>() if T is either 2 or 4 bytes");
-        }
-    }
-    let flags: u32 = flags.bits() | dst.len() as u32;
-    unsafe {
-        fill_cr.write_volatile(value_conv);
-        src_cr.write_volatile(fill_cr as *const usize);
-        dst_cr.write_volatile(dst.as_mut_ptr() as *mut usize);
-        cr.write_volatile(flags);
-    }
-}
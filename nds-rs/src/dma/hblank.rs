@@ -0,0 +1,254 @@
+use core::{
+    mem::size_of,
+    ops::{Deref, DerefMut},
+};
+
+use nds_sys::{
+    dma::{calc_cr, Flags},
+    video::SCREEN_HEIGHT,
+};
+
+use super::{settle, wait_for, DmaChannel};
+use crate::cache::dc_flush_slice;
+
+/// Amount of visible scanlines, one entry of a [`ScanlineTable`] each
+pub const SCANLINES: usize = SCREEN_HEIGHT as usize;
+
+/// One value per visible scanline, to be streamed into a register by [`HBlankDma`].
+///
+/// Derefs to a slice of [`SCANLINES`] elements, where index `n` is the value
+/// the register will hold while line `n` is drawn.
+///
+/// # Example
+/// ```rust,no_run
+/// // Horizontal offsets for a wavy background
+/// let table = ScanlineTable::from_fn(|line| WAVE[line % WAVE.len()]);
+/// ```
+#[repr(C, align(4))]
+#[derive(Clone)]
+pub struct ScanlineTable<T> {
+    /// The extra entry is read by the DMA on the HBlank of the last line,
+    /// it mirrors the first one. See [`HBlankDma::vblank`]
+    entries: [T; SCANLINES + 1],
+}
+impl<T: Copy> ScanlineTable<T> {
+    /// Creates a table where every line has the same value
+    pub const fn splat(value: T) -> Self {
+        Self {
+            entries: [value; SCANLINES + 1],
+        }
+    }
+
+    /// Creates a table by calling `f` with the number of each line
+    pub fn from_fn(mut f: impl FnMut(usize) -> T) -> Self {
+        let first = f(0);
+        let entries = core::array::from_fn(|line| match line {
+            0 | SCANLINES => first,
+            _ => f(line),
+        });
+        Self { entries }
+    }
+
+    /// Calls `f` with the number and the value of each line, so it can be modified
+    pub fn update(&mut self, mut f: impl FnMut(usize, &mut T)) {
+        for (line, value) in self.iter_mut().enumerate() {
+            f(line, value);
+        }
+    }
+
+    /// Makes the sentinel entry mirror the first line again
+    fn sync_sentinel(&mut self) {
+        self.entries[SCANLINES] = self.entries[0];
+    }
+}
+impl ScanlineTable<u16> {
+    /// Creates a vertical gradient between two BGR555 colors, `top` for the first
+    /// line and `bottom` for the last one. Useful for the backdrop color.
+    pub fn gradient(top: u16, bottom: u16) -> Self {
+        const CHANNEL: u16 = 0b11111;
+        const LAST: i32 = SCANLINES as i32 - 1;
+        let lerp = |shift: u32, line: usize| {
+            let from = ((top >> shift) & CHANNEL) as i32;
+            let to = ((bottom >> shift) & CHANNEL) as i32;
+            let value = from + (to - from) * line as i32 / LAST;
+            (value as u16 & CHANNEL) << shift
+        };
+        Self::from_fn(|line| lerp(0, line) | lerp(5, line) | lerp(10, line))
+    }
+}
+impl<T> Deref for ScanlineTable<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries[..SCANLINES]
+    }
+}
+impl<T> DerefMut for ScanlineTable<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.entries[..SCANLINES]
+    }
+}
+
+/// A channel streaming a [`ScanlineTable`] into a register, one entry per HBlank.
+///
+/// Created with [`DmaChannel::hblank`]. [`vblank`](Self::vblank) must be called once
+/// per frame, during VBlank, to rewind the table. Dropping it stops the channel.
+pub struct HBlankDma<'t, T: Copy> {
+    channel: &'t mut DmaChannel,
+    table: &'t mut ScanlineTable<T>,
+    register: *mut T,
+}
+impl<T: Copy> HBlankDma<'_, T> {
+    /// The table being streamed. Changes are picked up on the next [`vblank`](Self::vblank)
+    pub fn table(&self) -> &ScanlineTable<T> {
+        self.table
+    }
+
+    /// The table being streamed. Changes are picked up on the next [`vblank`](Self::vblank)
+    pub fn table_mut(&mut self) -> &mut ScanlineTable<T> {
+        self.table
+    }
+
+    /// Rewinds the table. Call it once per frame during VBlank (e.g. right after
+    /// [`swi_wait_for_v_blank`](crate::interrupts::swi_wait_for_v_blank)).
+    ///
+    /// Writes the value of the first line, flushes the table from the cache and
+    /// restarts the channel, which then copies the entry of line `n + 1`
+    /// on the HBlank of line `n`.
+    pub fn vblank(&mut self) {
+        self.stop();
+        self.table.sync_sentinel();
+        let (unit, count) = unit_of::<T>();
+        let dst_mode = if count == 1 {
+            Flags::FIX_DST
+        } else {
+            Flags::INC_REL_DST
+        };
+        let flags = Flags::ENABLE
+            | Flags::START_AT_HBLANK
+            | Flags::REPEAT
+            | Flags::INC_SRC
+            | dst_mode
+            | unit
+            | Flags::from_bits_retain(count);
+        unsafe {
+            self.register.write_volatile(self.table.entries[0]);
+            dc_flush_slice(&self.table.entries);
+            let (src_cr, dst_cr, cr, _) = nds_sys::dma::calc_registers(self.channel.ch);
+            src_cr.write_volatile(self.table.entries[1..].as_ptr() as _);
+            dst_cr.write_volatile(self.register as _);
+            cr.write_volatile(flags.bits());
+        }
+    }
+
+    /// Halts the channel. It's started again by [`vblank`](Self::vblank)
+    pub fn stop(&mut self) {
+        // Repeating channels are allowed to be halted, as long as they
+        // aren't touched in the first cycles after being started
+        settle();
+        unsafe {
+            calc_cr(self.channel.ch).write_volatile(0);
+        }
+        wait_for(self.channel.ch);
+    }
+}
+impl<T: Copy> Drop for HBlankDma<'_, T> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Returns the transfer unit and amount of units that make a `T`.
+/// Panics if `T` can't be copied with 16 or 32 bit units.
+fn unit_of<T>() -> (Flags, u32) {
+    match size_of::<T>() {
+        2 => (Flags::HALFWORDS, 1),
+        n if n % 4 == 0 && n > 0 => (Flags::WORDS, (n / 4) as u32),
+        _ => panic!("HBlank DMA needs entries of 2 bytes or a multiple of 4 bytes"),
+    }
+}
+
+impl DmaChannel {
+    /// Streams `table` into `register`, one entry per scanline, using HBlank DMA.
+    /// Entries bigger than 4 bytes are copied to consecutive registers
+    /// starting at `register` (e.g. the 4 affine parameters of a background).
+    ///
+    /// The channel is started right away, see [`HBlankDma::vblank`].
+    /// Panics if `T` is neither 2 bytes long nor a multiple of 4 bytes.
+    ///
+    /// # Safety
+    /// `register` must be a memory mapped register (or a range of them) where
+    /// writing any value of `table` is valid.
+    ///
+    /// The returned [`HBlankDma`] must be dropped, not leaked (e.g. with
+    /// [`mem::forget`](core::mem::forget)): the channel keeps repeating after the
+    /// borrow of `table` ends, reading whatever takes the place of the table.
+    pub unsafe fn hblank<'t, T: Copy>(
+        &'t mut self,
+        table: &'t mut ScanlineTable<T>,
+        register: *mut T,
+    ) -> HBlankDma<'t, T> {
+        wait_for(self.ch);
+        let mut hdma = HBlankDma {
+            channel: self,
+            table,
+            register,
+        };
+        hdma.vblank();
+        hdma
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentinel_mirrors_first_line() {
+        let mut table = ScanlineTable::from_fn(|line| line as u16 + 7);
+        assert_eq!(table.entries[SCANLINES], 7);
+        assert_eq!(table[SCANLINES - 1], SCANLINES as u16 + 6);
+
+        table[0] = 42;
+        table.update(|line, value| *value += (line == 1) as u16);
+        assert_eq!(table.entries[SCANLINES], 7);
+        table.sync_sentinel();
+        assert_eq!(table.entries[SCANLINES], 42);
+        assert_eq!(table[1], 9);
+    }
+
+    #[test]
+    fn gradient_endpoints() {
+        let (top, bottom) = (0x7C1F, 0x03E0);
+        let table = ScanlineTable::gradient(top, bottom);
+        assert_eq!(table[0], top);
+        assert_eq!(table[SCANLINES - 1], bottom);
+        assert_eq!(table.entries[SCANLINES], top);
+    }
+
+    #[test]
+    fn gradient_interpolates_each_channel() {
+        // Red goes up, green stays, blue goes down
+        let table = ScanlineTable::gradient(0x7C00 | 4 << 5, 0x001F | 4 << 5);
+        let channels = |color: u16| (color & 0x1F, color >> 5 & 0x1F, color >> 10);
+        let mut previous = channels(table[0]);
+        for &color in table.iter() {
+            let (r, g, b) = channels(color);
+            assert!(r >= previous.0 && b <= previous.2);
+            assert_eq!(g, 4);
+            previous = (r, g, b);
+        }
+        // Halfway down, each channel is halfway through its range
+        assert_eq!(channels(table[SCANLINES / 2 - 1]), (15, 4, 16));
+    }
+
+    #[test]
+    fn deref_hides_the_sentinel() {
+        let mut table = ScanlineTable::splat(0u32);
+        assert_eq!(table.len(), SCANLINES);
+        assert_eq!(SCANLINES, 192);
+        assert_eq!(table.iter_mut().count(), 192);
+        table.update(|_, value| *value = 1);
+        assert_eq!(table.entries[SCANLINES], 0);
+    }
+}
@@ -0,0 +1,178 @@
+//! Detection of the console model and of the DSi hardware
+//!
+//! A DSi (or a 3DS) runs programs either in DS mode, where it behaves like a DS, or
+//! in DSi mode with 16 MB of RAM, a faster CPU and new hardware. The new hardware is
+//! enabled by the SCFG registers, which the loader may lock.
+//!
+//! APIs that need DSi hardware check the [`Capabilities`] and return
+//! `Err(Unsupported)` when it's missing:
+//! ```rust,no_run
+//! let previous = hw.system.set_cpu_speed(CpuSpeed::Mhz133)?;
+//! ```
+
+use core::fmt;
+
+use nds_sys::dsi::{registers::*, *};
+use portable_atomic::{AtomicU8, Ordering};
+
+pub use nds_sys::dsi::{ScfgClock, ScfgExt};
+
+/// The hardware needed by an API isn't available on this console or in this mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsupported;
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("not supported on this hardware")
+    }
+}
+
+/// The console running the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// The original DS, or the iQue DS
+    Ds,
+    /// The DS Lite, or the iQue DS Lite
+    DsLite,
+    /// The DSi, in DS or DSi mode. The 3DS runs DS software as a DSi, and is reported
+    /// as one
+    Dsi,
+    /// Unknown console type in the firmware header
+    Unknown(u8),
+}
+impl Model {
+    /// The model matching the console type of the firmware header
+    pub const fn from_console_type(console_type: u8) -> Self {
+        match console_type {
+            CONSOLE_TYPE_DS | CONSOLE_TYPE_IQUE_DS => Self::Ds,
+            CONSOLE_TYPE_DS_LITE | CONSOLE_TYPE_IQUE_DS_LITE => Self::DsLite,
+            CONSOLE_TYPE_DSI => Self::Dsi,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// Speed of the ARM9
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuSpeed {
+    Mhz67,
+    /// Only in DSi mode
+    Mhz133,
+}
+
+bitflags::bitflags! {
+    /// Hardware available to the program
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Capabilities: u16 {
+        /// Running in DSi mode
+        const DSI_MODE = 1 << 0;
+        /// The SCFG registers are unlocked, e.g. to switch the [`CpuSpeed`]
+        const SCFG = 1 << 1;
+        /// 16 MB of main RAM
+        const EXTENDED_RAM = 1 << 2;
+        /// The new DMA channels
+        const NDMA = 1 << 3;
+        /// The new shared WRAM
+        const NEW_WRAM = 1 << 4;
+        /// The cameras (ARM9 only)
+        const CAMERA = 1 << 5;
+        /// The DSP (ARM9 only)
+        const DSP = 1 << 6;
+    }
+}
+impl Capabilities {
+    /// Hardware enabled by the launcher in DSi mode, assumed when the SCFG registers
+    /// are locked
+    pub const DSI_DEFAULT: Self = Self::DSI_MODE
+        .union(Self::EXTENDED_RAM)
+        .union(Self::NDMA)
+        .union(Self::NEW_WRAM)
+        .union(Self::CAMERA)
+        .union(Self::DSP);
+
+    /// Capabilities from the mode and the value of `REG_SCFG_EXT`, as seen by the ARM9
+    pub fn from_scfg(dsi_mode: bool, ext: ScfgExt) -> Self {
+        if !dsi_mode {
+            return Self::empty();
+        }
+        if !ext.contains(ScfgExt::SCFG_ACCESS) {
+            return Self::DSI_DEFAULT;
+        }
+        let mut capabilities = Self::DSI_MODE | Self::SCFG;
+        let ram_limit = (ext.bits() & ScfgExt::RAM_LIMIT.bits()) >> 14;
+        capabilities.set(Self::EXTENDED_RAM, ram_limit >= 2);
+        capabilities.set(Self::NDMA, ext.contains(ScfgExt::NDMA));
+        capabilities.set(Self::NEW_WRAM, ext.contains(ScfgExt::NEW_WRAM));
+        capabilities.set(Self::CAMERA, ext.contains(ScfgExt::CAMERA));
+        capabilities.set(Self::DSP, ext.contains(ScfgExt::DSP));
+        capabilities
+    }
+
+    /// Returns `Err(Unsupported)` unless all of `needed` are available
+    pub fn require(self, needed: Self) -> Result<(), Unsupported> {
+        match self.contains(needed) {
+            true => Ok(()),
+            false => Err(Unsupported),
+        }
+    }
+}
+
+/// Returns `true` when running in DSi mode
+pub fn is_dsi_mode() -> bool {
+    unsafe { core::ptr::addr_of!(__dsimode).read_volatile() }
+}
+
+/// The console type of the firmware header, 0 until it's read
+static CONSOLE_TYPE: AtomicU8 = AtomicU8::new(0);
+
+/// Returns the console model.
+///
+/// In DS mode the model is read from the firmware (through the ARM7 on the ARM9)
+/// the first time, a DSi in DS mode is still reported as a [`Model::Dsi`].
+pub fn model() -> Model {
+    if is_dsi_mode() {
+        return Model::Dsi;
+    }
+    let mut console_type = CONSOLE_TYPE.load(Ordering::Relaxed);
+    if console_type == 0 {
+        let mut byte = CONSOLE_TYPE_DS;
+        unsafe {
+            if readFirmware(FIRMWARE_CONSOLE_TYPE, (&raw mut byte).cast(), 1) != 0 {
+                byte = CONSOLE_TYPE_DS;
+            }
+        }
+        console_type = byte;
+        CONSOLE_TYPE.store(console_type, Ordering::Relaxed);
+    }
+    Model::from_console_type(console_type)
+}
+
+/// Reads `REG_SCFG_EXT`, or returns `None` outside of DSi mode
+pub fn scfg_ext() -> Option<ScfgExt> {
+    is_dsi_mode().then(|| ScfgExt::from_bits_retain(unsafe { REG_SCFG_EXT.read_volatile() }))
+}
+
+/// Reads `REG_SCFG_CLK`, or returns `None` outside of DSi mode
+pub fn scfg_clock() -> Option<ScfgClock> {
+    is_dsi_mode().then(|| ScfgClock::from_bits_retain(unsafe { REG_SCFG_CLK.read_volatile() }))
+}
+
+/// Reads `REG_SCFG_ROM`, or returns `None` outside of DSi mode
+pub fn scfg_rom() -> Option<u16> {
+    is_dsi_mode().then(|| unsafe { REG_SCFG_ROM.read_volatile() })
+}
+
+/// Returns the hardware available to the program
+pub fn capabilities() -> Capabilities {
+    let capabilities =
+        Capabilities::from_scfg(is_dsi_mode(), scfg_ext().unwrap_or(ScfgExt::empty()));
+    // The ARM7 has other devices at the bits of the cameras and the DSP
+    match cfg!(feature = "arm7") {
+        true => capabilities - Capabilities::CAMERA - Capabilities::DSP,
+        false => capabilities,
+    }
+}
+
+/// Returns `Err(Unsupported)` unless all of `needed` are available
+pub fn require(needed: Capabilities) -> Result<(), Unsupported> {
+    capabilities().require(needed)
+}
@@ -0,0 +1,251 @@
+//! Color special effects of the 2D engines
+//!
+//! Each engine can apply one [`Effect`] at a time: alpha blending between two sets of
+//! layers, or fading some layers to white or black. Sprites in
+//! [`TRANSLUCENT`](nds_sys::sprite::Attr0::TRANSLUCENT) mode are always alpha blended
+//! with the second targets, whatever the selected effect is.
+//!
+//! On top of that, the master brightness fades the whole output of an engine,
+//! which is what [`Fade`] and [`Video::fade`] use for scene transitions.
+//!
+//! Effects can be limited to some areas of the screen using [windows](crate::window).
+
+use nds_sys::effects::*;
+
+pub use nds_sys::effects::BlendTargets;
+
+use crate::{
+    interrupts::swi_wait_for_v_blank,
+    video::{Engine, Video},
+};
+
+/// Maximum value of the blending and brightness coefficients
+pub const MAX_COEFFICIENT: u8 = 16;
+
+/// A color special effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// No effect. Semi-transparent OBJs don't blend either, since they need second
+    /// targets: use [`Effect::Alpha`] for them
+    None,
+    /// `first * first_weight / 16 + second * second_weight / 16`.
+    /// Only applies where a first target pixel is right on top of a second target one
+    Alpha {
+        first: BlendTargets,
+        second: BlendTargets,
+        first_weight: u8,
+        second_weight: u8,
+    },
+    /// Moves the colors of `targets` towards white by `amount / 16`
+    FadeToWhite { targets: BlendTargets, amount: u8 },
+    /// Moves the colors of `targets` towards black by `amount / 16`
+    FadeToBlack { targets: BlendTargets, amount: u8 },
+}
+impl Effect {
+    /// Values of `[BLDCNT, BLDALPHA, BLDY]` for this effect.
+    /// Coefficients are clamped to [`MAX_COEFFICIENT`].
+    pub const fn registers(&self) -> [u16; 3] {
+        const fn coefficient(value: u8) -> u16 {
+            if value > MAX_COEFFICIENT {
+                MAX_COEFFICIENT as u16
+            } else {
+                value as u16
+            }
+        }
+        match *self {
+            Effect::None => [BlendMode::None as u16, 0, 0],
+            Effect::Alpha {
+                first,
+                second,
+                first_weight,
+                second_weight,
+            } => [
+                BlendMode::Alpha as u16 | first.bits() as u16 | (second.bits() as u16) << 8,
+                coefficient(first_weight) | coefficient(second_weight) << 8,
+                0,
+            ],
+            Effect::FadeToWhite { targets, amount } => [
+                BlendMode::Brighten as u16 | targets.bits() as u16,
+                0,
+                coefficient(amount),
+            ],
+            Effect::FadeToBlack { targets, amount } => [
+                BlendMode::Darken as u16 | targets.bits() as u16,
+                0,
+                coefficient(amount),
+            ],
+        }
+    }
+}
+
+/// Size of the mosaic "pixels", in pixels. Valid sizes are `1..=16`, 1 disables the effect.
+///
+/// Only layers with [`MOSAIC`](nds_sys::background::BackgroundControl::MOSAIC) set and
+/// sprites with the mosaic attribute are affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mosaic {
+    /// Horizontal and vertical size for backgrounds
+    pub bg: (u8, u8),
+    /// Horizontal and vertical size for sprites
+    pub obj: (u8, u8),
+}
+impl Mosaic {
+    /// Mosaic disabled
+    pub const NONE: Mosaic = Mosaic {
+        bg: (1, 1),
+        obj: (1, 1),
+    };
+
+    /// Value of the `MOSAIC` register. Sizes are clamped to `1..=16`
+    pub const fn bits(&self) -> u16 {
+        const fn size(value: u8) -> u16 {
+            let value = if value == 0 {
+                1
+            } else if value > 16 {
+                16
+            } else {
+                value
+            };
+            (value - 1) as u16
+        }
+        size(self.bg.0) | size(self.bg.1) << 4 | size(self.obj.0) << 8 | size(self.obj.1) << 12
+    }
+}
+
+/// Value of the master brightness register for `brightness`, which goes from
+/// `-16` (black) to `16` (white). `0` leaves the colors untouched.
+pub const fn master_brightness_bits(brightness: i8) -> u16 {
+    let factor = brightness.unsigned_abs();
+    let factor = if factor > MAX_COEFFICIENT {
+        MAX_COEFFICIENT
+    } else {
+        factor
+    } as u16;
+    match brightness {
+        0 => MasterBrightMode::Disabled as u16,
+        1.. => MasterBrightMode::Up as u16 | factor,
+        _ => MasterBrightMode::Down as u16 | factor,
+    }
+}
+
+/// Master brightness levels for a transition of `frames` frames.
+///
+/// Iterates over the brightness of each frame, from right after `from` until `to` (included).
+///
+/// # Example
+/// ```rust,no_run
+/// // Fade the top screen to black in half a second
+/// hw.video.fade(Engine::Main, Fade::new(0, -16, 30));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fade {
+    from: i8,
+    to: i8,
+    frames: u16,
+    frame: u16,
+}
+impl Fade {
+    /// A transition from `from` to `to` brightness (See [`master_brightness_bits`]).
+    /// If `frames` is 0, the transition jumps straight to `to`.
+    pub const fn new(from: i8, to: i8, frames: u16) -> Self {
+        Self {
+            from,
+            to,
+            frames,
+            frame: 0,
+        }
+    }
+
+    /// Fade from normal colors to black
+    pub const fn out_to_black(frames: u16) -> Self {
+        Self::new(0, -(MAX_COEFFICIENT as i8), frames)
+    }
+
+    /// Fade from black to normal colors
+    pub const fn in_from_black(frames: u16) -> Self {
+        Self::new(-(MAX_COEFFICIENT as i8), 0, frames)
+    }
+
+    /// Fade from normal colors to white
+    pub const fn out_to_white(frames: u16) -> Self {
+        Self::new(0, MAX_COEFFICIENT as i8, frames)
+    }
+
+    /// Fade from white to normal colors
+    pub const fn in_from_white(frames: u16) -> Self {
+        Self::new(MAX_COEFFICIENT as i8, 0, frames)
+    }
+
+    /// Returns `true` once the last level has been returned
+    pub fn is_done(&self) -> bool {
+        self.frame >= self.frames.max(1)
+    }
+}
+impl Iterator for Fade {
+    type Item = i8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done() {
+            return None;
+        }
+        self.frame += 1;
+        if self.frame >= self.frames {
+            return Some(self.to);
+        }
+        let delta = (self.to as i32 - self.from as i32) * self.frame as i32 / self.frames as i32;
+        Some((self.from as i32 + delta) as i8)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.frames.max(1) - self.frame.min(self.frames.max(1))) as usize;
+        (left, Some(left))
+    }
+}
+impl ExactSizeIterator for Fade {}
+
+/// Returns `[BLDCNT, BLDALPHA, BLDY, MOSAIC, MASTER_BRIGHT]` for `engine`
+const fn registers(engine: Engine) -> [*mut u16; 5] {
+    match engine {
+        Engine::Main => [BLDCNT, BLDALPHA, BLDY, MOSAIC, MASTER_BRIGHT],
+        Engine::Sub => [DB_BLDCNT, DB_BLDALPHA, DB_BLDY, DB_MOSAIC, DB_MASTER_BRIGHT],
+    }
+}
+
+impl Video {
+    /// Selects the color special effect of `engine`
+    pub fn set_effect(&mut self, engine: Engine, effect: &Effect) {
+        let [bldcnt, bldalpha, bldy, ..] = registers(engine);
+        let [cnt, alpha, y] = effect.registers();
+        unsafe {
+            bldcnt.write_volatile(cnt);
+            bldalpha.write_volatile(alpha);
+            bldy.write_volatile(y);
+        }
+    }
+
+    /// Sets the size of the mosaic effect of `engine`
+    pub fn set_mosaic(&mut self, engine: Engine, mosaic: &Mosaic) {
+        let [.., mosaic_cr, _] = registers(engine);
+        unsafe {
+            mosaic_cr.write_volatile(mosaic.bits());
+        }
+    }
+
+    /// Sets the master brightness of `engine`, from `-16` (black) to `16` (white).
+    /// It affects the whole output of the engine, 3D included.
+    pub fn set_master_brightness(&mut self, engine: Engine, brightness: i8) {
+        let [.., master_bright] = registers(engine);
+        unsafe {
+            master_bright.write_volatile(master_brightness_bits(brightness));
+        }
+    }
+
+    /// Plays `fade` on the master brightness of `engine`, one level per frame.
+    /// Blocks until the transition is over.
+    pub fn fade(&mut self, engine: Engine, fade: Fade) {
+        for brightness in fade {
+            swi_wait_for_v_blank();
+            self.set_master_brightness(engine, brightness);
+        }
+    }
+}
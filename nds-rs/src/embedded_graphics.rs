@@ -1,7 +1,11 @@
 use nds_sys::video::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
+pub use color::PaletteIndex;
+pub(crate) use fill::{clip, fill_halfwords};
 pub use target::GraphicsTarget;
 
+mod color;
+mod fill;
 mod target;
 
 const PIXELS: usize = (SCREEN_WIDTH * SCREEN_HEIGHT) as _;
@@ -0,0 +1,28 @@
+use embedded_graphics_core::pixelcolor::{
+    raw::{RawData, RawU8},
+    PixelColor,
+};
+
+/// An index in a palette of 256 colors, the color of 8 bits bitmaps and of tiles.
+/// Index 0 is transparent
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PaletteIndex(pub u8);
+
+impl PixelColor for PaletteIndex {
+    type Raw = RawU8;
+}
+impl From<RawU8> for PaletteIndex {
+    fn from(raw: RawU8) -> Self {
+        Self(raw.into_inner())
+    }
+}
+impl From<PaletteIndex> for RawU8 {
+    fn from(color: PaletteIndex) -> Self {
+        RawU8::new(color.0)
+    }
+}
+impl From<u8> for PaletteIndex {
+    fn from(index: u8) -> Self {
+        Self(index)
+    }
+}
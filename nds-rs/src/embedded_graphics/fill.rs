@@ -0,0 +1,47 @@
+use embedded_graphics_core::{prelude::*, primitives::Rectangle};
+
+use crate::dma::DmaChannel;
+
+/// Spans shorter than this many halfwords are filled by the CPU, faster than
+/// programming a DMA channel. `examples/fill_benchmark.rs` prints the cycles of both
+/// for spans of increasing length, to find where the DMA gets faster
+const DMA_MIN_LEN: usize = 32;
+
+/// Fills the halfwords of a rectangle of a buffer `stride` halfwords wide, from
+/// (`x`, `y`) to `width`x`height`. The rectangle must be in the buffer.
+///
+/// Uses `dma` for long spans, and a single transfer when the lines are whole.
+pub(crate) fn fill_halfwords(
+    buffer: &mut [u16],
+    stride: usize,
+    (x, y, width, height): (usize, usize, usize, usize),
+    value: u16,
+    dma: Option<&mut DmaChannel>,
+) {
+    let fill = |span: &mut [u16], dma: Option<&mut DmaChannel>| match dma {
+        Some(dma) if span.len() >= DMA_MIN_LEN => dma.fill(value, span),
+        _ => span.fill(value),
+    };
+    if width == stride {
+        fill(&mut buffer[y * stride..(y + height) * stride], dma);
+        return;
+    }
+    let mut dma = dma;
+    for line in buffer[y * stride..].chunks_exact_mut(stride).take(height) {
+        fill(&mut line[x..x + width], dma.as_deref_mut());
+    }
+}
+
+/// The part of `area` inside a target of size `size`, as (x, y, width, height).
+/// `None` if nothing is inside
+pub(crate) fn clip(area: &Rectangle, size: Size) -> Option<(usize, usize, usize, usize)> {
+    let area = area.intersection(&Rectangle::new(Point::zero(), size));
+    let bottom_right = area.bottom_right()?;
+    let top_left = area.top_left;
+    Some((
+        top_left.x as usize,
+        top_left.y as usize,
+        (bottom_right.x - top_left.x + 1) as usize,
+        (bottom_right.y - top_left.y + 1) as usize,
+    ))
+}
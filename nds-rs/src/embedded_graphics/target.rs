@@ -1,42 +1,50 @@
 use embedded_graphics_core::{
     pixelcolor::IntoStorage,
     prelude::{DrawTarget, OriginDimensions, Pixel, Point, Size},
+    primitives::Rectangle,
 };
-use nds_sys::{
-    dma::Channel,
-    video::{SCREEN_HEIGHT, SCREEN_WIDTH},
-};
+use nds_sys::video::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
-use crate::{cache::dc_flush_array, dma};
+use crate::dma::DmaChannel;
 
-use super::Framebuffer;
+use super::{clip, fill_halfwords, Framebuffer};
 
 /// A struct that implements [`DrawTarget`] and [`OriginDimensions`] from
 /// [`embedded-graphics`](https://docs.rs/embedded-graphics/latest/embedded_graphics/index.html).
 ///
 /// It can be used to draw to either a layer (Running in modes 3, 4 or 5)
-pub struct GraphicsTarget<'t, 'b> {
+///
+/// Drawing happens on `buffer`, which is copied to `target` using `dma` on [`flush`](Self::flush).
+/// A [`DoubleBuffer`](crate::background::DoubleBuffer) avoids the copy by flipping
+/// between two pages of VRAM.
+pub struct GraphicsTarget<'t, 'b, 'd> {
     target: &'t mut Framebuffer,
     buffer: &'b mut Framebuffer,
+    dma: &'d mut DmaChannel,
 }
-impl<'t, 'b> GraphicsTarget<'t, 'b> {
-    pub fn new(target: &'t mut Framebuffer, buffer: &'b mut Framebuffer) -> Self {
-        Self { target, buffer }
+impl<'t, 'b, 'd> GraphicsTarget<'t, 'b, 'd> {
+    pub fn new(
+        target: &'t mut Framebuffer,
+        buffer: &'b mut Framebuffer,
+        dma: &'d mut DmaChannel,
+    ) -> Self {
+        Self {
+            target,
+            buffer,
+            dma,
+        }
     }
 
     pub fn flush(&mut self) {
-        unsafe {
-            dc_flush_array(self.buffer);
-        }
-        dma::copy(self.buffer, self.target)
+        self.dma.copy(self.buffer, self.target);
     }
 }
-impl OriginDimensions for GraphicsTarget<'_, '_> {
+impl OriginDimensions for GraphicsTarget<'_, '_, '_> {
     fn size(&self) -> embedded_graphics_core::prelude::Size {
         Size::new(SCREEN_WIDTH, SCREEN_HEIGHT)
     }
 }
-impl DrawTarget for GraphicsTarget<'_, '_> {
+impl DrawTarget for GraphicsTarget<'_, '_, '_> {
     type Color = embedded_graphics_core::pixelcolor::Bgr555;
 
     type Error = core::convert::Infallible;
@@ -58,12 +66,16 @@ impl DrawTarget for GraphicsTarget<'_, '_> {
         Ok(())
     }
 
-    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
-        dma::fill(color.into_storage(), self.buffer);
-        dma::wait_for(Channel::Ch3);
-        unsafe {
-            dc_flush_array(self.buffer);
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        if let Some(rect) = clip(area, self.size()) {
+            let (stride, color) = (SCREEN_WIDTH as usize, color.into_storage());
+            fill_halfwords(self.buffer, stride, rect, color, Some(self.dma));
         }
         Ok(())
     }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.dma.fill(color.into_storage(), self.buffer);
+        Ok(())
+    }
 }
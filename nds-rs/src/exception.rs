@@ -0,0 +1,267 @@
+//! CPU exceptions of the ARM9
+//!
+//! Without a handler, data aborts (e.g. writing through a bad pointer), prefetch
+//! aborts (jumping to a bad address) and undefined instructions just hang.
+//! [`install`] makes them show the [panic screen](crate::panic_screen) instead, along with
+//! an [`ExceptionInfo`] describing what happened. A hook can be set to handle them first
+//! (e.g. to save the game or log the error).
+//!
+//! The ARM9 doesn't report the address of a data abort, it is found by decoding
+//! the faulting instruction. See [`decode_arm`] and [`decode_thumb`].
+//!
+//! Accesses to the first page of memory don't fault by default, since the instruction
+//! TCM is mirrored there. [`crate::mpu::protect_null_page`] makes them fault, which
+//! turns null pointer dereferences into data aborts.
+
+use core::{
+    arch::asm,
+    fmt::{self, Display},
+    ptr::addr_of,
+};
+
+use nds_sys::exception::{exceptionRegisters, setExceptionHandler, EXCEPTION_SPSR};
+use spin::Mutex;
+
+use crate::{
+    mpu,
+    panic_screen::{self, Registers, Report},
+};
+
+mod decode;
+pub use decode::*;
+
+/// Modes of the CPU, from the low bits of the program status register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CpuMode {
+    User = 0x10,
+    Fiq = 0x11,
+    Irq = 0x12,
+    Supervisor = 0x13,
+    Abort = 0x17,
+    Undefined = 0x1B,
+    System = 0x1F,
+}
+impl CpuMode {
+    /// Mode from a program status register. Returns `None` for reserved modes
+    pub const fn from_psr(psr: u32) -> Option<Self> {
+        Some(match psr & 0x1F {
+            0x10 => Self::User,
+            0x11 => Self::Fiq,
+            0x12 => Self::Irq,
+            0x13 => Self::Supervisor,
+            0x17 => Self::Abort,
+            0x1B => Self::Undefined,
+            0x1F => Self::System,
+            _ => return None,
+        })
+    }
+}
+
+/// Thumb state bit of the program status registers
+pub const PSR_THUMB: u32 = 1 << 5;
+/// Carry flag of the program status registers
+pub const PSR_CARRY: u32 = 1 << 29;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionKind {
+    /// A load or store to an address that can't be accessed
+    DataAbort,
+    /// Code was executed from an address that can't be accessed
+    PrefetchAbort,
+    UndefinedInstruction,
+}
+impl ExceptionKind {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::DataAbort => "data abort",
+            Self::PrefetchAbort => "prefetch abort",
+            Self::UndefinedInstruction => "undefined instruction",
+        }
+    }
+}
+
+/// What the CPU was doing when an exception happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionInfo {
+    pub kind: ExceptionKind,
+    /// Mode of the code that caused the exception, `None` if it's invalid
+    pub mode: Option<CpuMode>,
+    /// The code was running in Thumb state
+    pub thumb: bool,
+    /// Registers of the code that caused the exception. `pc` is the address of
+    /// the faulting instruction and `cpsr` its status register
+    pub registers: Registers,
+    /// The faulting instruction, if it could be read
+    pub instruction: Option<u32>,
+    /// The faulting instruction, if it accesses memory
+    pub access: Option<MemoryAccess>,
+    /// The address that couldn't be accessed, for aborts.
+    /// For data aborts, it's only known if the instruction could be decoded
+    pub address: Option<u32>,
+}
+impl ExceptionInfo {
+    /// Builds the information of an exception from the state saved by the CPU and libnds.
+    ///
+    /// `exception_mode` is the mode the exception was taken in (Abort or Undefined),
+    /// `spsr` the status register of the faulting code and `registers` its registers,
+    /// with `r15` holding the return address of the exception. `read` reads code memory.
+    pub fn new(
+        exception_mode: CpuMode,
+        spsr: u32,
+        mut registers: [u32; 16],
+        read: impl Fn(u32, bool) -> Option<u32>,
+    ) -> Self {
+        let thumb = spsr & PSR_THUMB != 0;
+        let return_address = registers[15];
+        let kind = match exception_mode {
+            // Both aborts return 4 bytes after the faulting instruction would have
+            // been fetched. If that address can't be read, the fetch itself failed
+            CpuMode::Abort if read(return_address.wrapping_sub(4), thumb).is_none() => {
+                ExceptionKind::PrefetchAbort
+            }
+            CpuMode::Abort => ExceptionKind::DataAbort,
+            _ => ExceptionKind::UndefinedInstruction,
+        };
+        let pc = match (kind, thumb) {
+            (ExceptionKind::DataAbort, _) => return_address.wrapping_sub(8),
+            (ExceptionKind::PrefetchAbort, _) => return_address.wrapping_sub(4),
+            (ExceptionKind::UndefinedInstruction, true) => return_address.wrapping_sub(2),
+            (ExceptionKind::UndefinedInstruction, false) => return_address.wrapping_sub(4),
+        };
+        let instruction = match kind {
+            ExceptionKind::PrefetchAbort => None,
+            _ => read(pc, thumb),
+        };
+        let access = match (kind, instruction) {
+            (ExceptionKind::DataAbort, Some(opcode)) if thumb => decode_thumb(opcode as u16),
+            (ExceptionKind::DataAbort, Some(opcode)) => decode_arm(opcode),
+            _ => None,
+        };
+        let address = match kind {
+            ExceptionKind::DataAbort => access.map(|access| {
+                let pc_value = if thumb {
+                    pc.wrapping_add(4) & !3
+                } else {
+                    pc.wrapping_add(8)
+                };
+                access.address(&registers, pc_value, spsr & PSR_CARRY != 0)
+            }),
+            ExceptionKind::PrefetchAbort => Some(pc),
+            ExceptionKind::UndefinedInstruction => None,
+        };
+        registers[15] = pc;
+        Self {
+            kind,
+            mode: CpuMode::from_psr(spsr),
+            thumb,
+            registers: Registers {
+                r: registers,
+                cpsr: spsr,
+            },
+            instruction,
+            access,
+            address,
+        }
+    }
+
+    /// Reads the state saved by libnds. Only meaningful inside an exception handler
+    ///
+    /// # Safety
+    /// Must be called from the handler set with `setExceptionHandler`.
+    pub unsafe fn capture() -> Self {
+        let cpsr: u32;
+        asm!("mrs {}, cpsr", out(reg) cpsr, options(nomem, nostack, preserves_flags));
+        let mode = CpuMode::from_psr(cpsr).unwrap_or(CpuMode::Undefined);
+        let registers = addr_of!(exceptionRegisters).read_volatile();
+        Self::new(mode, EXCEPTION_SPSR.read_volatile(), registers, read_code)
+    }
+
+    /// Address of the faulting instruction
+    pub fn pc(&self) -> u32 {
+        self.registers.r[15]
+    }
+}
+impl Display for ExceptionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.thumb { "Thumb" } else { "ARM" };
+        write!(f, "{} at {:08X} ({state}", self.kind.name(), self.pc())?;
+        match self.mode {
+            Some(mode) => write!(f, ", {mode:?} mode)")?,
+            None => write!(f, ")")?,
+        }
+        if let Some(instruction) = self.instruction {
+            write!(f, "\ninstruction: {instruction:08X}")?;
+        }
+        match (self.address, self.access) {
+            (Some(address), Some(access)) => {
+                let op = if access.load { "reading" } else { "writing" };
+                write!(f, "\n{op} {:?} at {address:08X}", access.size)
+            }
+            (Some(address), None) => write!(f, "\naddress: {address:08X}"),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// Memory code can be read from: ITCM and its mirrors, main RAM, shared WRAM and the BIOS.
+/// Only the parts in a protection region are mapped, see [`read_code`]
+const CODE_RANGES: [core::ops::Range<u32>; 4] = [
+    0x0100_0000..0x0200_0000,
+    0x0200_0000..0x0300_0000,
+    0x0300_0000..0x0400_0000,
+    0xFFFF_0000..0xFFFF_8000,
+];
+
+/// Reads an instruction at `address`, if it's in [`CODE_RANGES`] and in an enabled
+/// protection region. Memory outside of every region isn't mapped (e.g. main RAM past
+/// 4 MiB in DS mode): reading it would abort again inside the handler, which would then
+/// never return
+fn read_code(address: u32, thumb: bool) -> Option<u32> {
+    let align = if thumb { 1 } else { 3 };
+    let mapped = (0..mpu::REGIONS)
+        .map(mpu::region)
+        .any(|region| region.enabled && region.contains(address));
+    if address & align != 0 || !CODE_RANGES.iter().any(|r| r.contains(&address)) || !mapped {
+        return None;
+    }
+    unsafe {
+        Some(if thumb {
+            (address as *const u16).read_volatile() as u32
+        } else {
+            (address as *const u32).read_volatile()
+        })
+    }
+}
+
+/// Function called with the information of an exception, before the panic screen is shown.
+/// It can reset or power off the console instead of returning.
+pub type Hook = fn(&ExceptionInfo);
+
+static HOOK: Mutex<Option<Hook>> = Mutex::new(None);
+
+/// Installs the exception handler, calling `hook` (if any) when an exception happens
+/// and then showing the panic screen
+pub fn install(hook: Option<Hook>) {
+    *HOOK.lock() = hook;
+    unsafe {
+        setExceptionHandler(Some(handler));
+    }
+}
+
+unsafe extern "C" fn handler() {
+    let info = ExceptionInfo::capture();
+    // The lock could be held by the code that faulted
+    let hook = HOOK.try_lock().and_then(|hook| *hook);
+    if let Some(hook) = hook {
+        hook(&info);
+    }
+    // No allocation nor file: the exception may have happened while holding their locks
+    panic_screen::show_without_file(&Report {
+        kind: info.kind.name(),
+        message: Some(&info),
+        location: None,
+        registers: Some(&info.registers),
+        frame_pointer: info.registers.r[11] as usize,
+    })
+}
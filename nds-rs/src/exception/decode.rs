@@ -0,0 +1,505 @@
+//! Decoding of the load and store instructions, to find the address of a data abort.
+//! The ARM946E-S doesn't report it, so it has to be computed from the instruction
+//! and the registers.
+
+/// Amount of data moved by a [`MemoryAccess`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessSize {
+    Byte,
+    Halfword,
+    Word,
+    Doubleword,
+    /// `LDM`/`STM`, `PUSH`/`POP`: the amount of registers
+    Multiple(u8),
+}
+
+/// Shift applied to a register offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shift {
+    Lsl,
+    Lsr,
+    Asr,
+    Ror,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Offset {
+    Immediate(u32),
+    /// `rm` shifted by `amount`, as encoded (an amount of 0 has special meanings
+    /// for every shift but [`Shift::Lsl`])
+    Register {
+        rm: u8,
+        shift: Shift,
+        amount: u8,
+    },
+}
+
+/// A decoded load or store instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub load: bool,
+    pub size: AccessSize,
+    /// Base register
+    pub base: u8,
+    pub offset: Offset,
+    /// The offset is subtracted from the base
+    pub subtract: bool,
+    /// The offset is applied before the access. Otherwise the base is used as is
+    pub pre_indexed: bool,
+}
+impl MemoryAccess {
+    /// First address accessed, given the registers before the instruction executed.
+    ///
+    /// `pc` is the value read from `r15` by the instruction: its address + 8 in ARM
+    /// state, or its address + 4 aligned to a word in Thumb state.
+    /// `carry` is the carry flag, used by `RRX` offsets.
+    pub fn address(&self, registers: &[u32; 16], pc: u32, carry: bool) -> u32 {
+        let read = |r: u8| {
+            if r == 15 {
+                pc
+            } else {
+                registers[r as usize & 15]
+            }
+        };
+        let base = read(self.base);
+        if !self.pre_indexed {
+            return base;
+        }
+        let offset = match self.offset {
+            Offset::Immediate(offset) => offset,
+            Offset::Register { rm, shift, amount } => shifted(read(rm), shift, amount, carry),
+        };
+        if self.subtract {
+            base.wrapping_sub(offset)
+        } else {
+            base.wrapping_add(offset)
+        }
+    }
+}
+
+/// Applies an immediate shift like the barrel shifter does
+const fn shifted(value: u32, shift: Shift, amount: u8, carry: bool) -> u32 {
+    let amount = amount as u32;
+    match (shift, amount) {
+        (Shift::Lsl, _) => value << amount,
+        (Shift::Lsr, 0) => 0,
+        (Shift::Lsr, _) => value >> amount,
+        (Shift::Asr, 0) => ((value as i32) >> 31) as u32,
+        (Shift::Asr, _) => ((value as i32) >> amount) as u32,
+        // RRX
+        (Shift::Ror, 0) => ((carry as u32) << 31) | (value >> 1),
+        (Shift::Ror, _) => value.rotate_right(amount),
+    }
+}
+
+const fn bit(opcode: u32, n: u32) -> bool {
+    opcode & (1 << n) != 0
+}
+
+const fn field(opcode: u32, low: u32, len: u32) -> u32 {
+    (opcode >> low) & ((1 << len) - 1)
+}
+
+/// Offset and direction of the first address of a block transfer of `count` registers
+const fn block_start(count: u32, increment: bool, before: bool) -> (u32, bool) {
+    match (increment, before) {
+        (true, false) => (0, false),
+        (true, true) => (4, false),
+        (false, false) => ((count * 4).saturating_sub(4), true),
+        (false, true) => (count * 4, true),
+    }
+}
+
+/// Decodes an ARM instruction. Returns `None` if it doesn't access memory
+pub fn decode_arm(opcode: u32) -> Option<MemoryAccess> {
+    let load = bit(opcode, 20);
+    let base = field(opcode, 16, 4) as u8;
+    let pre_indexed = bit(opcode, 24);
+    let subtract = !bit(opcode, 23);
+
+    // SWP and SWPB
+    if opcode & 0x0FB0_0FF0 == 0x0100_0090 {
+        let size = if bit(opcode, 22) {
+            AccessSize::Byte
+        } else {
+            AccessSize::Word
+        };
+        return Some(MemoryAccess {
+            load: true,
+            size,
+            base,
+            offset: Offset::Immediate(0),
+            subtract: false,
+            pre_indexed: true,
+        });
+    }
+
+    match field(opcode, 25, 3) {
+        // Halfword, signed and doubleword transfers
+        0b000 if bit(opcode, 7) && bit(opcode, 4) && field(opcode, 5, 2) != 0 => {
+            let (load, size) = match (field(opcode, 5, 2), load) {
+                (0b01, _) => (load, AccessSize::Halfword),
+                (0b10, true) => (true, AccessSize::Byte),
+                (0b11, true) => (true, AccessSize::Halfword),
+                // LDRD and STRD
+                (sh, false) => (sh == 0b10, AccessSize::Doubleword),
+                _ => unreachable!(),
+            };
+            let offset = if bit(opcode, 22) {
+                Offset::Immediate((field(opcode, 8, 4) << 4) | field(opcode, 0, 4))
+            } else {
+                Offset::Register {
+                    rm: field(opcode, 0, 4) as u8,
+                    shift: Shift::Lsl,
+                    amount: 0,
+                }
+            };
+            Some(MemoryAccess {
+                load,
+                size,
+                base,
+                offset,
+                subtract,
+                pre_indexed,
+            })
+        }
+        // Single data transfers
+        0b010 | 0b011 => {
+            let register = bit(opcode, 25);
+            if register && bit(opcode, 4) {
+                // Undefined instruction space
+                return None;
+            }
+            let offset = if register {
+                let shift = match field(opcode, 5, 2) {
+                    0 => Shift::Lsl,
+                    1 => Shift::Lsr,
+                    2 => Shift::Asr,
+                    _ => Shift::Ror,
+                };
+                Offset::Register {
+                    rm: field(opcode, 0, 4) as u8,
+                    shift,
+                    amount: field(opcode, 7, 5) as u8,
+                }
+            } else {
+                Offset::Immediate(field(opcode, 0, 12))
+            };
+            let size = if bit(opcode, 22) {
+                AccessSize::Byte
+            } else {
+                AccessSize::Word
+            };
+            Some(MemoryAccess {
+                load,
+                size,
+                base,
+                offset,
+                subtract,
+                pre_indexed,
+            })
+        }
+        // Block transfers
+        0b100 => {
+            let count = field(opcode, 0, 16).count_ones();
+            let (offset, subtract) = block_start(count, !subtract, pre_indexed);
+            Some(MemoryAccess {
+                load,
+                size: AccessSize::Multiple(count as u8),
+                base,
+                offset: Offset::Immediate(offset),
+                subtract,
+                pre_indexed: true,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Decodes a Thumb instruction. Returns `None` if it doesn't access memory
+pub fn decode_thumb(opcode: u16) -> Option<MemoryAccess> {
+    let opcode = opcode as u32;
+    let low_register = |n: u32| field(opcode, n, 3) as u8;
+    let immediate = |base: u8, offset: u32, load: bool, size: AccessSize| MemoryAccess {
+        load,
+        size,
+        base,
+        offset: Offset::Immediate(offset),
+        subtract: false,
+        pre_indexed: true,
+    };
+    let load = bit(opcode, 11);
+
+    if opcode & 0xF800 == 0x4800 {
+        // LDR Rd, [PC, #imm]
+        return Some(immediate(
+            15,
+            field(opcode, 0, 8) * 4,
+            true,
+            AccessSize::Word,
+        ));
+    }
+    match opcode & 0xF000 {
+        // Register offset
+        0x5000 => {
+            let (load, size) = match field(opcode, 9, 3) {
+                0b000 => (false, AccessSize::Word),
+                0b001 => (false, AccessSize::Halfword),
+                0b010 => (false, AccessSize::Byte),
+                0b011 => (true, AccessSize::Byte),
+                0b100 => (true, AccessSize::Word),
+                0b101 => (true, AccessSize::Halfword),
+                0b110 => (true, AccessSize::Byte),
+                _ => (true, AccessSize::Halfword),
+            };
+            Some(MemoryAccess {
+                load,
+                size,
+                base: low_register(3),
+                offset: Offset::Register {
+                    rm: low_register(6),
+                    shift: Shift::Lsl,
+                    amount: 0,
+                },
+                subtract: false,
+                pre_indexed: true,
+            })
+        }
+        // Word and byte immediate offset
+        0x6000 | 0x7000 => {
+            let (scale, size) = if bit(opcode, 12) {
+                (1, AccessSize::Byte)
+            } else {
+                (4, AccessSize::Word)
+            };
+            let offset = field(opcode, 6, 5) * scale;
+            Some(immediate(low_register(3), offset, load, size))
+        }
+        // Halfword immediate offset
+        0x8000 => {
+            let offset = field(opcode, 6, 5) * 2;
+            Some(immediate(
+                low_register(3),
+                offset,
+                load,
+                AccessSize::Halfword,
+            ))
+        }
+        // SP relative
+        0x9000 => {
+            let offset = field(opcode, 0, 8) * 4;
+            Some(immediate(13, offset, load, AccessSize::Word))
+        }
+        // PUSH and POP
+        0xB000 if opcode & 0x0600 == 0x0400 => {
+            let count = field(opcode, 0, 9).count_ones();
+            let size = AccessSize::Multiple(count as u8);
+            let mut access = immediate(13, 0, load, size);
+            if !load {
+                access.offset = Offset::Immediate(count * 4);
+                access.subtract = true;
+            }
+            Some(access)
+        }
+        // LDMIA and STMIA
+        0xC000 => {
+            let count = field(opcode, 0, 8).count_ones();
+            let size = AccessSize::Multiple(count as u8);
+            Some(immediate(low_register(8), 0, load, size))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> [u32; 16] {
+        let mut registers = [0; 16];
+        for (n, r) in registers.iter_mut().enumerate() {
+            *r = n as u32;
+        }
+        registers[1] = 0x0200_1000;
+        registers[13] = 0x0300_0100;
+        registers
+    }
+
+    #[test]
+    fn arm_ldr_immediate() {
+        // ldr r0, [r1, #4]
+        let access = decode_arm(0xE591_0004).unwrap();
+        assert_eq!(
+            access,
+            MemoryAccess {
+                load: true,
+                size: AccessSize::Word,
+                base: 1,
+                offset: Offset::Immediate(4),
+                subtract: false,
+                pre_indexed: true,
+            }
+        );
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_1004);
+
+        // ldr r0, [r1], #8
+        let access = decode_arm(0xE491_0008).unwrap();
+        assert!(!access.pre_indexed);
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_1000);
+
+        // ldr r0, [pc, #4]
+        let access = decode_arm(0xE59F_0004).unwrap();
+        assert_eq!(
+            access.address(&registers(), 0x0200_0008, false),
+            0x0200_000C
+        );
+    }
+
+    #[test]
+    fn arm_ldr_register() {
+        // ldr r0, [r1, -r2, lsl #2]
+        let access = decode_arm(0xE711_0102).unwrap();
+        assert_eq!(
+            access.offset,
+            Offset::Register {
+                rm: 2,
+                shift: Shift::Lsl,
+                amount: 2,
+            }
+        );
+        assert!(access.subtract);
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_0FF8);
+
+        // ldr r0, [r1, r2, rrx]
+        let access = decode_arm(0xE791_0062).unwrap();
+        assert_eq!(access.address(&registers(), 0, true), 0x8200_1001);
+    }
+
+    #[test]
+    fn arm_str() {
+        // strb r2, [r3, #1]
+        let access = decode_arm(0xE5C3_2001).unwrap();
+        assert!(!access.load);
+        assert_eq!(access.size, AccessSize::Byte);
+        assert_eq!(access.base, 3);
+        assert_eq!(access.address(&registers(), 0, false), 4);
+
+        // ldrh r0, [r1, #0x12]
+        let access = decode_arm(0xE1D0_01B2).unwrap();
+        assert!(access.load);
+        assert_eq!(access.size, AccessSize::Halfword);
+        assert_eq!(access.offset, Offset::Immediate(0x12));
+
+        // strd r2, [r0, #8]
+        let access = decode_arm(0xE1C0_20F8).unwrap();
+        assert!(!access.load);
+        assert_eq!(access.size, AccessSize::Doubleword);
+        assert_eq!(access.offset, Offset::Immediate(8));
+
+        // swp r0, r1, [r2]
+        let access = decode_arm(0xE102_0091).unwrap();
+        assert_eq!(access.size, AccessSize::Word);
+        assert_eq!(access.address(&registers(), 0, false), 2);
+    }
+
+    #[test]
+    fn arm_ldm_stm() {
+        // push {r4-r7, lr}
+        let access = decode_arm(0xE92D_40F0).unwrap();
+        assert!(!access.load);
+        assert_eq!(access.size, AccessSize::Multiple(5));
+        assert_eq!(access.address(&registers(), 0, false), 0x0300_0100 - 20);
+
+        // ldmia r1, {r1, r2}
+        let access = decode_arm(0xE891_0006).unwrap();
+        assert!(access.load);
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_1000);
+
+        // ldmib r1, {r1}
+        let access = decode_arm(0xE991_0002).unwrap();
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_1004);
+
+        // ldmda r1, {r1-r3}
+        let access = decode_arm(0xE811_000E).unwrap();
+        assert_eq!(access.size, AccessSize::Multiple(3));
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_0FF8);
+    }
+
+    #[test]
+    fn arm_other_instructions() {
+        // mov r0, r1
+        assert_eq!(decode_arm(0xE1A0_0001), None);
+        // mul r0, r1, r2
+        assert_eq!(decode_arm(0xE000_0291), None);
+        // b .
+        assert_eq!(decode_arm(0xEAFF_FFFE), None);
+    }
+
+    #[test]
+    fn thumb_single_transfers() {
+        // ldr r0, [pc, #8]
+        let access = decode_thumb(0x4802).unwrap();
+        assert_eq!(access.base, 15);
+        assert_eq!(
+            access.address(&registers(), 0x0200_0004, false),
+            0x0200_000C
+        );
+
+        // str r1, [r2, r3]
+        let access = decode_thumb(0x50D1).unwrap();
+        assert!(!access.load);
+        assert_eq!(access.size, AccessSize::Word);
+        assert_eq!(access.address(&registers(), 0, false), 5);
+
+        // ldrsh r0, [r1, r2]
+        let access = decode_thumb(0x5E88).unwrap();
+        assert!(access.load);
+        assert_eq!(access.size, AccessSize::Halfword);
+
+        // ldr r0, [r1, #4]
+        let access = decode_thumb(0x6848).unwrap();
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_1004);
+
+        // strb r0, [r1, #3]
+        let access = decode_thumb(0x70C8).unwrap();
+        assert!(!access.load);
+        assert_eq!(access.size, AccessSize::Byte);
+        assert_eq!(access.offset, Offset::Immediate(3));
+
+        // ldrh r0, [r1, #2]
+        let access = decode_thumb(0x8848).unwrap();
+        assert_eq!(access.size, AccessSize::Halfword);
+        assert_eq!(access.offset, Offset::Immediate(2));
+
+        // str r0, [sp, #8]
+        let access = decode_thumb(0x9002).unwrap();
+        assert_eq!(access.address(&registers(), 0, false), 0x0300_0108);
+    }
+
+    #[test]
+    fn thumb_block_transfers() {
+        // push {r4, lr}
+        let access = decode_thumb(0xB510).unwrap();
+        assert!(!access.load);
+        assert_eq!(access.size, AccessSize::Multiple(2));
+        assert_eq!(access.address(&registers(), 0, false), 0x0300_00F8);
+
+        // pop {r4, pc}
+        let access = decode_thumb(0xBD10).unwrap();
+        assert!(access.load);
+        assert_eq!(access.address(&registers(), 0, false), 0x0300_0100);
+
+        // ldmia r1!, {r1, r2}
+        let access = decode_thumb(0xC906).unwrap();
+        assert_eq!(access.size, AccessSize::Multiple(2));
+        assert_eq!(access.address(&registers(), 0, false), 0x0200_1000);
+    }
+
+    #[test]
+    fn thumb_other_instructions() {
+        // adds r0, r0, r1
+        assert_eq!(decode_thumb(0x1840), None);
+        // add sp, #0
+        assert_eq!(decode_thumb(0xB000), None);
+    }
+}
@@ -0,0 +1,90 @@
+//! A single-core async executor, driven by interrupts
+//!
+//! Instead of a game loop written as a state machine around
+//! [`swi_wait_for_v_blank`](crate::interrupts::swi_wait_for_v_blank), each part of the
+//! game can be an `async` task awaiting what it needs:
+//!
+//! - [`next_frame`] for the next VBlank,
+//! - [`timer::sleep`] for some time to pass,
+//! - [`keys::pressed`] for a key to be pressed,
+//! - a DMA [`Transfer`](crate::dma::Transfer) to finish,
+//! - a message from the ARM7 on a [`FifoChannel`](crate::fifo::FifoChannel).
+//!
+//! Tasks are only polled after being woken up by one of those, from their interrupts.
+//! When no task can make progress, the executor sleeps with `swiIntrWait` until the next
+//! interrupt.
+//!
+//! ```rust,no_run
+//! use nds_rs::executor::{keys, next_frame, timer, Executor};
+//!
+//! let mut executor = Executor::new();
+//! let spawner = executor.spawner();
+//! executor.block_on(async move {
+//!     spawner.spawn(async {
+//!         loop {
+//!             timer::sleep(Duration::from_millis(500)).await;
+//!             blink_cursor();
+//!         }
+//!     });
+//!     keys::pressed(KeypadBits::A).await;
+//!     for _ in 0..60 {
+//!         fade_out_step();
+//!         next_frame().await;
+//!     }
+//! });
+//! ```
+//!
+//! # Interrupt handlers
+//!
+//! Awaiting these futures installs handlers for their interrupts, replacing any
+//! previous handler: VBlank, keypad, the DMA channels and timers 2 and 3 (used by [`timer`]).
+//!
+//! # Without the hardware
+//!
+//! The scheduling is done by [`Executor`], which only depends on the hardware through
+//! an [`IrqSource`], so it can run on a host computer with a mock source.
+//! [`IrqWakers`] and [`Sleepers`] keep the wakers of the tasks waiting for interrupts,
+//! and can be used by a mock to simulate them.
+
+mod frame;
+pub mod irq;
+#[cfg(not(feature = "arm7"))]
+pub mod keys;
+mod scheduler;
+pub mod timer;
+mod wakers;
+pub use frame::*;
+pub use scheduler::*;
+pub use wakers::*;
+
+use core::future::Future;
+
+use crate::interrupts::{swi_intr_wait, Flags};
+
+/// Sleeps until the next interrupt with `swiIntrWait`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Interrupts;
+impl IrqSource for Interrupts {
+    fn wait(&mut self) {
+        // Returns right away if an interrupt happened since the previous call,
+        // so one that woke a task just before can't be missed
+        swi_intr_wait(Flags::all(), false);
+    }
+}
+
+impl Executor<Interrupts> {
+    /// Creates an executor that sleeps until the next interrupt when idle
+    pub fn new() -> Self {
+        Self::with_source(Interrupts)
+    }
+}
+impl Default for Executor<Interrupts> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs `future` to completion in a new [`Executor`]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    Executor::new().block_on(future)
+}
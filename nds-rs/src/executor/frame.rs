@@ -0,0 +1,65 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use nds_sys::interrupts::irqSet;
+use portable_atomic::{AtomicU32, Ordering};
+
+use super::irq;
+use crate::interrupts::{irq_enable, Flags};
+
+/// VBlanks counted by the handler installed by [`next_frame`]
+static FRAMES: AtomicU32 = AtomicU32::new(0);
+
+/// Amount of frames since a task first waited for one. Wraps around after about 2 years
+pub fn frame_count() -> u32 {
+    FRAMES.load(Ordering::Relaxed)
+}
+
+/// Resolves at the start of the next VBlank, returning the new [`frame_count`].
+///
+/// Installs a VBlank handler, replacing the previous one. With the `log` feature,
+/// the handler keeps counting the frames of the [logger](crate::logger).
+pub fn next_frame() -> NextFrame {
+    NextFrame { start: None }
+}
+
+/// Future returned by [`next_frame`]
+#[must_use = "futures do nothing unless awaited"]
+pub struct NextFrame {
+    start: Option<u32>,
+}
+impl Future for NextFrame {
+    type Output = u32;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let start = *self.start.get_or_insert_with(frame_count);
+        if frame_count() == start {
+            listen_vblank(cx.waker());
+        }
+        // The VBlank could have happened before the waker was registered
+        match frame_count() {
+            frame if frame != start => Poll::Ready(frame),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+/// Registers `waker` to be woken up at the next VBlank, installing the VBlank handler
+pub(super) fn listen_vblank(waker: &Waker) {
+    irq::register(Flags::VBLANK, waker);
+    // Installed every time, in case another handler replaced it
+    unsafe {
+        irqSet(Flags::VBLANK.bits(), Some(on_vblank));
+        irq_enable(Flags::VBLANK);
+    }
+}
+
+unsafe extern "C" fn on_vblank() {
+    FRAMES.fetch_add(1, Ordering::Relaxed);
+    #[cfg(all(feature = "log", not(feature = "arm7")))]
+    crate::logger::tick();
+    irq::wake(Flags::VBLANK);
+}
@@ -0,0 +1,54 @@
+//! Wakers of the tasks waiting for interrupts
+
+use core::{cell::RefCell, task::Waker};
+
+use critical_section::Mutex;
+use nds_sys::interrupts::irqSet;
+
+use super::IrqWakers;
+use crate::interrupts::{irq_enable, Flags};
+
+static WAKERS: Mutex<RefCell<IrqWakers>> = Mutex::new(RefCell::new(IrqWakers::new()));
+
+/// Registers `waker` to be woken up by any of the interrupts in `irqs`, without
+/// touching their handlers. The handlers must call [`wake`]
+pub fn register(irqs: Flags, waker: &Waker) {
+    critical_section::with(|cs| WAKERS.borrow_ref_mut(cs).register(irqs, waker));
+}
+
+/// Wakes up the tasks waiting for any of the interrupts in `irqs`
+pub fn wake(irqs: Flags) {
+    critical_section::with(|cs| WAKERS.borrow_ref_mut(cs).wake(irqs));
+}
+
+/// Registers `waker` to be woken up by any of the interrupts in `irqs`.
+///
+/// Installs a handler that wakes up the tasks for each of them and enables them,
+/// replacing the previous handlers.
+pub fn listen(irqs: Flags, waker: &Waker) {
+    register(irqs, waker);
+    for (line, handler) in HANDLERS.iter().enumerate() {
+        let irq = Flags::from_bits_retain(1 << line);
+        if irqs.contains(irq) {
+            unsafe {
+                irqSet(irq.bits(), Some(*handler));
+                irq_enable(irq);
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn on_irq<const LINE: u32>() {
+    wake(Flags::from_bits_retain(1 << LINE));
+}
+
+macro_rules! handlers {
+    ($($line:literal)*) => {
+        [$(on_irq::<$line>),*]
+    };
+}
+
+/// Handler installed by [`listen`] for each interrupt
+static HANDLERS: [unsafe extern "C" fn(); super::IRQ_LINES] = handlers!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31
+);
@@ -0,0 +1,99 @@
+//! Waiting for keys.
+//!
+//! Keys from A to L wake up tasks with the keypad interrupt. X, Y, the touch screen and the
+//! lid are read by the ARM7, so they are checked at every VBlank instead.
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use nds_sys::{
+    input::{keysCurrent, KeypadBits, KEYCNT_IRQ, REG_KEYCNT},
+    interrupts::irqSet,
+};
+
+use super::{frame::listen_vblank, irq};
+use crate::interrupts::{irq_enable, Flags};
+
+/// Keys that can fire the keypad interrupt
+const KEYCNT_KEYS: u32 = 0x3FF;
+
+/// Keys currently pressed, as [`KeypadBits`]
+pub fn current() -> u32 {
+    unsafe { keysCurrent() }
+}
+
+/// Resolves the next time `key` is pressed. If it's being held when first polled,
+/// it must be released first.
+///
+/// Keys can be combined by passing them to [`any_pressed`] instead.
+pub fn pressed(key: KeypadBits) -> Pressed {
+    any_pressed(key as u32)
+}
+
+/// Resolves the next time one of the keys in `keys` (a combination of [`KeypadBits`])
+/// is pressed, returning the keys pressed among them. Keys being held when first polled
+/// must be released first.
+pub fn any_pressed(keys: u32) -> Pressed {
+    Pressed {
+        keys,
+        released: false,
+    }
+}
+
+/// Future returned by [`pressed`] and [`any_pressed`]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Pressed {
+    keys: u32,
+    /// All the keys were released since the first poll
+    released: bool,
+}
+impl Future for Pressed {
+    type Output = u32;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let held = current() & self.keys;
+        if !self.released {
+            if held != 0 {
+                // Keep checking until they're released
+                listen_vblank(cx.waker());
+                return Poll::Pending;
+            }
+            self.released = true;
+        }
+        if held != 0 {
+            return Poll::Ready(held);
+        }
+        listen(self.keys, cx.waker());
+        // They could have been pressed before the waker was registered
+        match current() & self.keys {
+            0 => Poll::Pending,
+            held => Poll::Ready(held),
+        }
+    }
+}
+
+/// Registers `waker` to be woken up when any of `keys` could have been pressed
+fn listen(keys: u32, waker: &Waker) {
+    if keys & !KEYCNT_KEYS != 0 {
+        listen_vblank(waker);
+    }
+    if keys & KEYCNT_KEYS != 0 {
+        irq::register(Flags::KEYS, waker);
+        unsafe {
+            let keycnt = REG_KEYCNT.read_volatile();
+            REG_KEYCNT.write_volatile(keycnt | KEYCNT_IRQ | (keys & KEYCNT_KEYS) as u16);
+            irqSet(Flags::KEYS.bits(), Some(on_keys));
+            irq_enable(Flags::KEYS);
+        }
+    }
+}
+
+unsafe extern "C" fn on_keys() {
+    // The interrupt keeps firing while the keys are held, so it's disabled
+    // until a task waits for keys again
+    REG_KEYCNT.write_volatile(0);
+    irq::wake(Flags::KEYS);
+}
@@ -0,0 +1,398 @@
+//! Hardware independent part of the executor
+
+extern crate alloc;
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::{
+    cell::RefCell,
+    future::Future,
+    ops::Deref,
+    pin::{pin, Pin},
+    ptr::{self, NonNull},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use portable_atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/// Maximum amount of tasks spawned at once in an [`Executor`]
+pub const MAX_TASKS: usize = 32;
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Something that can make the CPU wait for an interrupt.
+///
+/// The executor calls [`wait`](IrqSource::wait) when none of its tasks can make progress.
+/// Any interrupt that may wake a task must make it return.
+pub trait IrqSource {
+    fn wait(&mut self);
+}
+
+/// A single-core executor polling tasks only after they're woken up,
+/// and sleeping in between. See the [module documentation](super).
+pub struct Executor<S> {
+    source: S,
+    tasks: Vec<Option<Task>>,
+    spawned: Rc<RefCell<Vec<Task>>>,
+    /// A flag per task, set by their wakers
+    woken: Vec<Flag>,
+    /// Flag of the future given to [`Executor::block_on`]
+    blocked_on: Flag,
+}
+impl<S: IrqSource> Executor<S> {
+    /// Creates an executor that waits for interrupts using `source`
+    pub fn with_source(source: S) -> Self {
+        Self {
+            source,
+            tasks: Vec::new(),
+            spawned: Rc::new(RefCell::new(Vec::new())),
+            woken: Vec::new(),
+            blocked_on: Flag::new(),
+        }
+    }
+
+    /// Returns a handle to spawn tasks from other tasks
+    pub fn spawner(&self) -> Spawner {
+        Spawner(self.spawned.clone())
+    }
+
+    /// Adds a task, which will be polled by [`run`](Executor::run) or
+    /// [`block_on`](Executor::block_on)
+    pub fn spawn(&mut self, task: impl Future<Output = ()> + 'static) {
+        self.spawned.borrow_mut().push(Box::pin(task));
+    }
+
+    /// Amount of tasks that haven't finished yet
+    pub fn tasks(&self) -> usize {
+        self.tasks.iter().flatten().count() + self.spawned.borrow().len()
+    }
+
+    /// The source of interrupts given to [`with_source`](Executor::with_source)
+    pub fn source(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Runs every task until all of them have finished
+    pub fn run(&mut self) {
+        loop {
+            let polled = self.poll_tasks();
+            if self.tasks() == 0 {
+                return;
+            }
+            if !polled {
+                self.idle();
+            }
+        }
+    }
+
+    /// Runs `future` and the spawned tasks until `future` completes, returning its output.
+    /// Tasks that haven't finished are kept for the next call.
+    pub fn block_on<F: Future>(&mut self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let flag = self.blocked_on.clone();
+        flag.store(true, Ordering::Relaxed);
+        loop {
+            if flag.swap(false, Ordering::Acquire) {
+                let waker = flag.waker();
+                if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker))
+                {
+                    return output;
+                }
+            }
+            if !self.poll_tasks() {
+                self.idle();
+            }
+        }
+    }
+
+    /// Waits for an interrupt, unless a task is ready to be polled
+    fn idle(&mut self) {
+        let woken = self
+            .woken
+            .iter()
+            .chain([&self.blocked_on])
+            .any(|flag| flag.load(Ordering::Acquire));
+        if !woken && self.spawned.borrow().is_empty() {
+            self.source.wait();
+        }
+    }
+
+    /// Moves the tasks created by [`Spawner`]s to free slots, marking them as woken
+    fn adopt_spawned(&mut self) {
+        let spawned = core::mem::take(&mut *self.spawned.borrow_mut());
+        for task in spawned {
+            let index = match self.tasks.iter().position(Option::is_none) {
+                Some(index) => index,
+                None if self.tasks.len() < MAX_TASKS => {
+                    self.tasks.push(None);
+                    self.woken.push(Flag::new());
+                    self.tasks.len() - 1
+                }
+                None => panic!("Can't run more than {MAX_TASKS} tasks at once"),
+            };
+            self.tasks[index] = Some(task);
+            self.woken[index].store(true, Ordering::Release);
+        }
+    }
+
+    /// Polls the tasks that were woken up. Returns `true` if any was polled
+    fn poll_tasks(&mut self) -> bool {
+        self.adopt_spawned();
+        let mut polled = false;
+        for (slot, flag) in self.tasks.iter_mut().zip(self.woken.iter()) {
+            let Some(task) = slot else {
+                continue;
+            };
+            if !flag.swap(false, Ordering::Acquire) {
+                continue;
+            }
+            polled = true;
+            let waker = flag.waker();
+            if task
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_ready()
+            {
+                *slot = None;
+            }
+        }
+        polled
+    }
+}
+
+/// Spawns tasks on an [`Executor`], see [`Executor::spawner`]
+#[derive(Clone)]
+pub struct Spawner(Rc<RefCell<Vec<Task>>>);
+impl Spawner {
+    /// Adds a task to the executor. It will be polled once the current task yields
+    pub fn spawn(&self, task: impl Future<Output = ()> + 'static) {
+        self.0.borrow_mut().push(Box::pin(task));
+    }
+}
+
+/// A flag set by wakers, shared with them by reference counting like an `Arc`.
+///
+/// Wakers may be dropped by interrupt handlers, which must not free memory: a flag whose
+/// last reference is dropped goes to a free list instead, to be reused by the next executors.
+struct Flag(NonNull<FlagInner>);
+
+struct FlagInner {
+    refs: AtomicUsize,
+    woken: AtomicBool,
+    /// Next flag of [`FREE_FLAGS`]
+    next: AtomicPtr<FlagInner>,
+}
+
+/// Flags without references left, linked by [`FlagInner::next`]
+static FREE_FLAGS: AtomicPtr<FlagInner> = AtomicPtr::new(ptr::null_mut());
+
+impl Flag {
+    fn new() -> Self {
+        let free = critical_section::with(|_| {
+            let head = FREE_FLAGS.load(Ordering::Acquire);
+            // SAFETY: flags in the free list are never freed
+            if let Some(flag) = unsafe { head.as_ref() } {
+                FREE_FLAGS.store(flag.next.load(Ordering::Relaxed), Ordering::Release);
+            }
+            head
+        });
+        let inner = match NonNull::new(free) {
+            Some(inner) => {
+                // SAFETY: nothing references the flags of the free list
+                let flag = unsafe { inner.as_ref() };
+                flag.refs.store(1, Ordering::Relaxed);
+                flag.woken.store(false, Ordering::Relaxed);
+                inner
+            }
+            None => NonNull::from(Box::leak(Box::new(FlagInner {
+                refs: AtomicUsize::new(1),
+                woken: AtomicBool::new(false),
+                next: AtomicPtr::new(ptr::null_mut()),
+            }))),
+        };
+        Self(inner)
+    }
+
+    /// Waker setting the flag. It can be woken and dropped from interrupts
+    fn waker(&self) -> Waker {
+        // SAFETY: the vtable functions keep the reference count of the flag
+        unsafe { Waker::from_raw(retain(self.0.as_ptr() as *const ())) }
+    }
+}
+impl Clone for Flag {
+    fn clone(&self) -> Self {
+        retain(self.0.as_ptr() as *const ());
+        Self(self.0)
+    }
+}
+impl Deref for Flag {
+    type Target = AtomicBool;
+
+    fn deref(&self) -> &AtomicBool {
+        // SAFETY: the flag stays allocated while referenced
+        unsafe { &self.0.as_ref().woken }
+    }
+}
+impl Drop for Flag {
+    fn drop(&mut self) {
+        release(self.0.as_ptr() as *const ());
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(retain, wake, wake_by_ref, release);
+
+/// Adds a reference to the flag at `data`
+fn retain(data: *const ()) -> RawWaker {
+    // SAFETY: wakers are only created from referenced flags
+    unsafe { &*(data as *const FlagInner) }
+        .refs
+        .fetch_add(1, Ordering::Relaxed);
+    RawWaker::new(data, &VTABLE)
+}
+
+fn wake(data: *const ()) {
+    wake_by_ref(data);
+    release(data);
+}
+
+fn wake_by_ref(data: *const ()) {
+    // SAFETY: the waker holds a reference to the flag
+    unsafe { &*(data as *const FlagInner) }
+        .woken
+        .store(true, Ordering::Release);
+}
+
+/// Removes a reference to the flag at `data`, moving it to the free list if it was the last
+fn release(data: *const ()) {
+    let flag = data as *mut FlagInner;
+    // SAFETY: the caller held a reference to the flag
+    if unsafe { &*flag }.refs.fetch_sub(1, Ordering::AcqRel) == 1 {
+        critical_section::with(|_| {
+            let head = FREE_FLAGS.load(Ordering::Acquire);
+            unsafe { &*flag }.next.store(head, Ordering::Relaxed);
+            FREE_FLAGS.store(flag, Ordering::Release);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use core::{cell::Cell, future::poll_fn};
+
+    use super::*;
+
+    /// Wakes the tasks waiting for an [`interrupt`] when the executor waits
+    #[derive(Default)]
+    struct MockSource {
+        waiting: Rc<RefCell<Vec<Waker>>>,
+        waits: usize,
+    }
+    impl IrqSource for MockSource {
+        fn wait(&mut self) {
+            self.waits += 1;
+            self.waiting.borrow_mut().drain(..).for_each(Waker::wake);
+        }
+    }
+
+    /// Completes after the next interrupt
+    async fn interrupt(waiting: Rc<RefCell<Vec<Waker>>>) {
+        let mut registered = false;
+        poll_fn(|cx| {
+            if registered {
+                return Poll::Ready(());
+            }
+            registered = true;
+            waiting.borrow_mut().push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    #[test]
+    fn block_on_ready_future_doesnt_wait() {
+        let mut executor = Executor::with_source(MockSource::default());
+        assert_eq!(executor.block_on(async { 42 }), 42);
+        assert_eq!(executor.source().waits, 0);
+    }
+
+    #[test]
+    fn block_on_waits_for_interrupts() {
+        let mut executor = Executor::with_source(MockSource::default());
+        let waiting = executor.source().waiting.clone();
+        let output = executor.block_on(async move {
+            interrupt(waiting.clone()).await;
+            interrupt(waiting).await;
+            "done"
+        });
+        assert_eq!(output, "done");
+        assert_eq!(executor.source().waits, 2);
+    }
+
+    #[test]
+    fn run_polls_woken_tasks() {
+        let mut executor = Executor::with_source(MockSource::default());
+        let finished = Rc::new(Cell::new(0));
+        for _ in 0..3 {
+            let waiting = executor.source().waiting.clone();
+            let finished = finished.clone();
+            executor.spawn(async move {
+                interrupt(waiting).await;
+                finished.set(finished.get() + 1);
+            });
+        }
+        assert_eq!(executor.tasks(), 3);
+        executor.run();
+        assert_eq!(finished.get(), 3);
+        assert_eq!(executor.tasks(), 0);
+        // They all waited for the same interrupt
+        assert_eq!(executor.source().waits, 1);
+    }
+
+    #[test]
+    fn block_on_keeps_unfinished_tasks() {
+        let mut executor = Executor::with_source(MockSource::default());
+        let spawner = executor.spawner();
+        let waiting = executor.source().waiting.clone();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let log = order.clone();
+        executor.block_on(async move {
+            let task_log = log.clone();
+            spawner.spawn(async move {
+                task_log.borrow_mut().push("task started");
+                interrupt(waiting).await;
+                task_log.borrow_mut().push("task finished");
+            });
+            log.borrow_mut().push("block_on");
+        });
+        assert_eq!(*order.borrow(), vec!["block_on"]);
+        assert_eq!(executor.tasks(), 1);
+
+        executor.run();
+        assert_eq!(
+            *order.borrow(),
+            vec!["block_on", "task started", "task finished"]
+        );
+    }
+
+    #[test]
+    fn wakers_outlive_executor() {
+        let mut executor = Executor::with_source(MockSource::default());
+        let waker = executor.block_on(poll_fn(|cx| Poll::Ready(cx.waker().clone())));
+        drop(executor);
+        waker.wake_by_ref();
+        drop(waker.clone());
+
+        let mut executor = Executor::with_source(MockSource::default());
+        assert_eq!(executor.block_on(async { 1 }), 1);
+        waker.wake();
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't run more than")]
+    fn too_many_tasks() {
+        let mut executor = Executor::with_source(MockSource::default());
+        for _ in 0..=MAX_TASKS {
+            executor.spawn(core::future::pending());
+        }
+        executor.run();
+    }
+}
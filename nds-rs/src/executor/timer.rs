@@ -0,0 +1,197 @@
+//! Time measured with the hardware timers, and sleeping tasks.
+//!
+//! Timer 3 counts time, and timer 2 fires when the next sleeping task must be woken up.
+//! Both are reserved once [`now`] or [`sleep`] has been called.
+
+use core::{
+    cell::RefCell,
+    future::Future,
+    ops::{Add, Sub},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use critical_section::{CriticalSection, Mutex};
+use nds_sys::{
+    interrupts::{irqSet, REG_IF},
+    timer::{timer_cr, timer_data, TimerFlags, BUS_CLOCK},
+};
+use portable_atomic::{AtomicBool, AtomicU32, Ordering};
+
+use super::Sleepers;
+use crate::interrupts::{irq_enable, Flags};
+
+/// Timer counting time
+const CLOCK: usize = 3;
+/// Timer firing when a sleep ends
+const ALARM: usize = 2;
+/// Divider of the bus clock used by both timers
+const DIVIDER: u32 = 1024;
+/// Frequency of the timers, about 32.7 kHz (a tick is about 30.5 µs)
+pub const TICKS_PER_SECOND: u32 = BUS_CLOCK / DIVIDER;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+/// Overflows of the clock timer, the upper bits of [`now`]
+static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+static SLEEPERS: Mutex<RefCell<Sleepers>> = Mutex::new(RefCell::new(Sleepers::new()));
+
+/// A point in time, measured in ticks of the timers since the clock was started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+impl Instant {
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Self(ticks)
+    }
+
+    pub const fn ticks(self) -> u64 {
+        self.0
+    }
+
+    /// Time since `earlier`, zero if `earlier` is later
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        ticks_to_duration(self.0.saturating_sub(earlier.0))
+    }
+
+    /// Time since this instant
+    pub fn elapsed(self) -> Duration {
+        now().duration_since(self)
+    }
+}
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Self(self.0.saturating_add(duration_to_ticks(rhs)))
+    }
+}
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Self::Output {
+        self.duration_since(rhs)
+    }
+}
+
+/// Ticks in `duration`, rounded up
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let ticks = (duration.as_nanos() * BUS_CLOCK as u128).div_ceil(DIVIDER as u128 * 1_000_000_000);
+    ticks.try_into().unwrap_or(u64::MAX)
+}
+
+/// Duration of `ticks`, rounded down
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    let nanos = ticks as u128 * DIVIDER as u128 * 1_000_000_000 / BUS_CLOCK as u128;
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
+
+/// The current time. Starts the clock the first time
+pub fn now() -> Instant {
+    start();
+    critical_section::with(now_cs)
+}
+
+fn now_cs(_: CriticalSection) -> Instant {
+    let mut overflows = OVERFLOWS.load(Ordering::Relaxed) as u64;
+    let mut counter = unsafe { timer_data(CLOCK).read_volatile() };
+    // An overflow happened, but its interrupt couldn't run yet
+    let pending = unsafe { REG_IF.read_volatile() } & Flags::TIMER3.bits() != 0;
+    if pending {
+        overflows += 1;
+        counter = unsafe { timer_data(CLOCK).read_volatile() };
+    }
+    Instant((overflows << 16) | counter as u64)
+}
+
+/// Starts the clock and installs the timer handlers, if it wasn't done yet
+fn start() {
+    if STARTED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let flags = TimerFlags::ENABLE | TimerFlags::IRQ | TimerFlags::DIV_1024;
+    unsafe {
+        irqSet(Flags::TIMER3.bits(), Some(on_clock_overflow));
+        irqSet(Flags::TIMER2.bits(), Some(on_alarm));
+        irq_enable(Flags::TIMER3 | Flags::TIMER2);
+        timer_cr(ALARM).write_volatile(0);
+        timer_cr(CLOCK).write_volatile(0);
+        timer_data(CLOCK).write_volatile(0);
+        timer_cr(CLOCK).write_volatile(flags.bits());
+    }
+}
+
+unsafe extern "C" fn on_clock_overflow() {
+    OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+    critical_section::with(wake_sleepers);
+}
+
+unsafe extern "C" fn on_alarm() {
+    timer_cr(ALARM).write_volatile(0);
+    critical_section::with(wake_sleepers);
+}
+
+/// Wakes up the tasks whose sleep ended, and sets the alarm for the next one
+fn wake_sleepers(cs: CriticalSection) {
+    let mut sleepers = SLEEPERS.borrow_ref_mut(cs);
+    sleepers.wake_expired(now_cs(cs).0);
+    if let Some(deadline) = sleepers.next_deadline() {
+        set_alarm(cs, deadline);
+    }
+}
+
+/// Makes the alarm fire at `deadline`, if it's close enough.
+/// Later deadlines are handled by the overflow interrupt of the clock.
+fn set_alarm(cs: CriticalSection, deadline: u64) {
+    let ticks = deadline.saturating_sub(now_cs(cs).0).max(1);
+    unsafe {
+        timer_cr(ALARM).write_volatile(0);
+        if ticks <= u16::MAX as u64 {
+            let flags = TimerFlags::ENABLE | TimerFlags::IRQ | TimerFlags::DIV_1024;
+            timer_data(ALARM).write_volatile((0x1_0000 - ticks) as u16);
+            timer_cr(ALARM).write_volatile(flags.bits());
+        }
+    }
+}
+
+/// Resolves once `duration` has passed
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(now() + duration)
+}
+
+/// Resolves at `deadline`
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep { deadline }
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Sleep {
+    deadline: Instant,
+}
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        start();
+        let deadline = self.deadline.0;
+        critical_section::with(|cs| {
+            if now_cs(cs).0 >= deadline {
+                return Poll::Ready(());
+            }
+            let mut sleepers = SLEEPERS.borrow_ref_mut(cs);
+            sleepers.insert(deadline, cx.waker());
+            if sleepers.next_deadline() == Some(deadline) {
+                set_alarm(cs, deadline);
+            }
+            Poll::Pending
+        })
+    }
+}
@@ -0,0 +1,111 @@
+//! Collections of wakers woken up from interrupts, independent of the hardware
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::task::Waker;
+
+use crate::interrupts::Flags;
+
+/// Interrupt lines that can have wakers, up to the highest bit of [`Flags`]
+pub const IRQ_LINES: usize = (u32::BITS - Flags::all().bits().leading_zeros()) as usize;
+
+/// Wakers of the tasks waiting for each interrupt
+pub struct IrqWakers {
+    lines: [Vec<Waker>; IRQ_LINES],
+}
+impl IrqWakers {
+    pub const fn new() -> Self {
+        Self {
+            lines: [const { Vec::new() }; IRQ_LINES],
+        }
+    }
+
+    /// Registers `waker` to be woken up by any of the interrupts in `irqs`
+    pub fn register(&mut self, irqs: Flags, waker: &Waker) {
+        for (line, wakers) in self.lines.iter_mut().enumerate() {
+            if irqs.bits() & (1 << line) == 0 {
+                continue;
+            }
+            if !wakers.iter().any(|w| w.will_wake(waker)) {
+                wakers.push(waker.clone());
+            }
+        }
+    }
+
+    /// Wakes up (and forgets) the wakers of the interrupts in `irqs`.
+    /// Doesn't allocate, so it can be called from interrupt handlers
+    pub fn wake(&mut self, irqs: Flags) {
+        for (line, wakers) in self.lines.iter_mut().enumerate() {
+            if irqs.bits() & (1 << line) != 0 {
+                wakers.drain(..).for_each(Waker::wake);
+            }
+        }
+    }
+
+    /// Interrupts with wakers waiting for them
+    pub fn pending(&self) -> Flags {
+        let bits = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, wakers)| !wakers.is_empty())
+            .fold(0, |bits, (line, _)| bits | (1 << line));
+        Flags::from_bits_retain(bits)
+    }
+}
+impl Default for IrqWakers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wakers of sleeping tasks, each with the time it must be woken up at
+pub struct Sleepers {
+    entries: Vec<(u64, Waker)>,
+}
+impl Sleepers {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Registers `waker` to be woken up at `deadline`.
+    /// A waker already registered for the same deadline isn't added again
+    pub fn insert(&mut self, deadline: u64, waker: &Waker) {
+        let registered = self
+            .entries
+            .iter()
+            .any(|(time, w)| *time == deadline && w.will_wake(waker));
+        if !registered {
+            self.entries.push((deadline, waker.clone()));
+        }
+    }
+
+    /// Wakes up and forgets the wakers whose deadline is not after `now`.
+    /// Doesn't allocate, so it can be called from interrupt handlers
+    pub fn wake_expired(&mut self, now: u64) {
+        let mut i = 0;
+        while i < self.entries.len() {
+            if self.entries[i].0 <= now {
+                self.entries.swap_remove(i).1.wake();
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// The earliest deadline
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.entries.iter().map(|(deadline, _)| *deadline).min()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+impl Default for Sleepers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
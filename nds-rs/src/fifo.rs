@@ -0,0 +1,258 @@
+//! Messages between the ARM9 and the ARM7, over the FIFO system of libnds
+//!
+//! libnds splits the IPC FIFO into 16 channels. Channels 0 to 7 are used by libnds and
+//! other libraries, channels [`USER_CHANNELS`] are free for the program, e.g. to talk to a
+//! custom service running on the ARM7.
+//!
+//! A [`FifoChannel`] sends and receives typed values, encoded with [`Serialize`]:
+//! ```rust,no_run
+//! let channel = FifoChannel::<(u8, u32)>::new(8)?;
+//! channel.send((SET_VOLUME, 100)).await?;
+//! let (status, value) = channel.recv().await?;
+//! ```
+//! The raw messages of libnds can also be sent with [`send_value32`] and [`send_datamsg`].
+
+use core::{
+    ffi::{c_int, c_void},
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    ops::RangeInclusive,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use nds_sys::fifo::*;
+use portable_atomic::{AtomicU16, Ordering};
+
+use crate::executor::{next_frame, NextFrame};
+
+mod message;
+pub use message::*;
+
+/// Channels free for the program
+pub const USER_CHANNELS: RangeInclusive<u8> = FIFO_USER_01 as u8..=FIFO_USER_08 as u8;
+
+/// Errors of the FIFO channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FifoError {
+    /// The channel isn't one of the [`USER_CHANNELS`]
+    Reserved,
+    /// There's already a [`FifoChannel`] for this channel
+    InUse,
+    /// The channel isn't one of the 16 channels of libnds
+    InvalidChannel,
+    /// libnds couldn't queue the message, the ARM7 isn't reading them fast enough
+    Full,
+    /// A data message to send is empty or longer than [`MAX_MESSAGE_SIZE`]
+    InvalidSize,
+    /// A received message is longer than [`MAX_MESSAGE_SIZE`], it was dropped
+    TooLong,
+    Encode(EncodeError),
+    Decode(DecodeError),
+}
+impl From<EncodeError> for FifoError {
+    fn from(e: EncodeError) -> Self {
+        Self::Encode(e)
+    }
+}
+impl From<DecodeError> for FifoError {
+    fn from(e: DecodeError) -> Self {
+        Self::Decode(e)
+    }
+}
+impl fmt::Display for FifoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reserved => f.write_str("channel reserved by libnds"),
+            Self::InUse => f.write_str("channel already in use"),
+            Self::InvalidChannel => f.write_str("invalid channel"),
+            Self::Full => f.write_str("FIFO full"),
+            Self::InvalidSize => f.write_str("data message empty or too long"),
+            Self::TooLong => f.write_str("message too long"),
+            Self::Encode(e) => write!(f, "couldn't encode message: {e:?}"),
+            Self::Decode(e) => write!(f, "couldn't decode message: {e:?}"),
+        }
+    }
+}
+
+/// Fails with [`FifoError::InvalidChannel`] if libnds doesn't have `channel`, so a
+/// failed send is always a full FIFO
+fn check_channel(channel: u8) -> Result<(), FifoError> {
+    match c_int::from(channel) < FIFO_NUM_CHANNELS {
+        true => Ok(()),
+        false => Err(FifoError::InvalidChannel),
+    }
+}
+
+/// Sends a 32-bit value on `channel`
+pub fn send_value32(channel: u8, value: u32) -> Result<(), FifoError> {
+    check_channel(channel)?;
+    match unsafe { fifoSendValue32(channel as c_int, value) } {
+        true => Ok(()),
+        false => Err(FifoError::Full),
+    }
+}
+
+/// Sends the address of some main RAM on `channel`
+///
+/// # Safety
+/// The memory must stay valid while the ARM7 uses it, and the data cache must be flushed
+/// for anything the ARM7 reads.
+pub unsafe fn send_address(channel: u8, address: *mut c_void) -> Result<(), FifoError> {
+    check_channel(channel)?;
+    match fifoSendAddress(channel as c_int, address) {
+        true => Ok(()),
+        false => Err(FifoError::Full),
+    }
+}
+
+/// Sends the bytes of a data message on `channel`.
+/// Fails with [`FifoError::InvalidSize`] if `bytes` is empty or longer than
+/// [`MAX_MESSAGE_SIZE`]
+pub fn send_datamsg(channel: u8, bytes: &[u8]) -> Result<(), FifoError> {
+    check_channel(channel)?;
+    if !is_valid_size(bytes.len()) {
+        return Err(FifoError::InvalidSize);
+    }
+    match unsafe { fifoSendDatamsg(channel as c_int, bytes.len() as c_int, bytes.as_ptr()) } {
+        true => Ok(()),
+        false => Err(FifoError::Full),
+    }
+}
+
+/// Reads the next 32-bit value received on `channel`, if the channel has no value handler
+pub fn receive_value32(channel: u8) -> Option<u32> {
+    unsafe { fifoCheckValue32(channel as c_int).then(|| fifoGetValue32(channel as c_int)) }
+}
+
+/// Channels with a [`FifoChannel`]
+static CLAIMED: AtomicU16 = AtomicU16::new(0);
+
+/// A user channel sending and receiving values of type `T` as data messages.
+///
+/// Sending waits for the next frame while libnds can't queue more messages, and receiving
+/// checks for a message from the ARM7 every frame. The channel has no handler, so messages
+/// wait in libnds' queue until they're received (a handler would have to read them).
+pub struct FifoChannel<T: Serialize> {
+    channel: u8,
+    _message: PhantomData<fn(T) -> T>,
+}
+impl<T: Serialize> FifoChannel<T> {
+    /// Takes one of the [`USER_CHANNELS`]
+    pub fn new(channel: u8) -> Result<Self, FifoError> {
+        if !USER_CHANNELS.contains(&channel) {
+            return Err(FifoError::Reserved);
+        }
+        let bit = 1 << channel;
+        if CLAIMED.fetch_or(bit, Ordering::Relaxed) & bit != 0 {
+            return Err(FifoError::InUse);
+        }
+        Ok(Self {
+            channel,
+            _message: PhantomData,
+        })
+    }
+
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Sends `value`, or fails with [`FifoError::Full`] if libnds can't queue it
+    pub fn try_send(&self, value: &T) -> Result<(), FifoError> {
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+        let len = encode_message(value, &mut buffer)?;
+        send_datamsg(self.channel, &buffer[..len])
+    }
+
+    /// Sends `value`, waiting a frame each time libnds can't queue it
+    pub fn send(&self, value: T) -> Send<'_, T> {
+        Send {
+            channel: self,
+            value,
+            frame: None,
+        }
+    }
+
+    /// Receives a value if there's a message
+    pub fn try_recv(&self) -> Result<Option<T>, FifoError> {
+        let channel = self.channel as c_int;
+        let len = unsafe { fifoCheckDatamsgLength(channel) };
+        if len < 0 {
+            return Ok(None);
+        }
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+        let read = unsafe { fifoGetDatamsg(channel, buffer.len() as c_int, buffer.as_mut_ptr()) };
+        if len as usize > buffer.len() {
+            return Err(FifoError::TooLong);
+        }
+        Ok(Some(decode_message(&buffer[..read.max(0) as usize])?))
+    }
+
+    /// Waits for a message and receives its value, checking every frame
+    pub fn recv(&self) -> Recv<'_, T> {
+        Recv {
+            channel: self,
+            frame: None,
+        }
+    }
+}
+impl<T: Serialize> Drop for FifoChannel<T> {
+    fn drop(&mut self) {
+        CLAIMED.fetch_and(!(1 << self.channel), Ordering::Relaxed);
+    }
+}
+
+/// Future returned by [`FifoChannel::send`]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Send<'c, T: Serialize> {
+    channel: &'c FifoChannel<T>,
+    value: T,
+    frame: Option<NextFrame>,
+}
+impl<T: Serialize> Future for Send<'_, T> {
+    type Output = Result<(), FifoError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: nothing is moved out, and `NextFrame` is `Unpin`
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            if let Some(frame) = &mut this.frame {
+                match Pin::new(frame).poll(cx) {
+                    Poll::Ready(_) => this.frame = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            match this.channel.try_send(&this.value) {
+                Err(FifoError::Full) => this.frame = Some(next_frame()),
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+}
+
+/// Future returned by [`FifoChannel::recv`]
+#[must_use = "futures do nothing unless awaited"]
+pub struct Recv<'c, T: Serialize> {
+    channel: &'c FifoChannel<T>,
+    frame: Option<NextFrame>,
+}
+impl<T: Serialize> Future for Recv<'_, T> {
+    type Output = Result<T, FifoError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            if let Some(frame) = &mut this.frame {
+                match Pin::new(frame).poll(cx) {
+                    Poll::Ready(_) => this.frame = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            match this.channel.try_recv().transpose() {
+                Some(value) => return Poll::Ready(value),
+                None => this.frame = Some(next_frame()),
+            }
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Encoding of the messages of a [`FifoChannel`](super::FifoChannel), independent of
+//! the hardware
+//!
+//! Values are encoded in little endian, without padding, so the ARM7 side can decode
+//! them with plain byte reads. Sequences are prefixed with their length as a `u16`.
+//!
+//! A message is the size of the encoded value as a byte, then the value. libnds can't
+//! send empty messages, this way values encoding to nothing like `()` can be sent too.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+/// Biggest message a [`FifoChannel`](super::FifoChannel) sends or receives, in bytes.
+/// libnds only sends messages shorter than `FIFO_MAX_DATA_BYTES` (128 bytes)
+pub const MAX_MESSAGE_SIZE: usize = 127;
+
+/// Biggest encoded value of a message, after the size byte
+pub const MAX_VALUE_SIZE: usize = MAX_MESSAGE_SIZE - 1;
+
+/// Errors when encoding a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The message doesn't fit in the buffer
+    TooLong,
+}
+
+/// Errors when decoding a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The message ended in the middle of a value
+    UnexpectedEnd,
+    /// The message continues after the value
+    TrailingBytes,
+    /// A value has an invalid encoding, e.g. a `bool` that isn't 0 or 1
+    Invalid,
+}
+
+/// A value that can be sent in a message
+pub trait Serialize: Sized {
+    fn serialize(&self, encoder: &mut Encoder<'_>) -> Result<(), EncodeError>;
+    fn deserialize(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError>;
+}
+
+/// Writes values to a buffer
+pub struct Encoder<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+}
+impl<'b> Encoder<'b> {
+    pub fn new(buffer: &'b mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        let end = self.len + bytes.len();
+        let dest = self
+            .buffer
+            .get_mut(self.len..end)
+            .ok_or(EncodeError::TooLong)?;
+        dest.copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Bytes written
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the encoded bytes
+    pub fn finish(self) -> &'b [u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+/// Reads values from a message
+pub struct Decoder<'b> {
+    bytes: &'b [u8],
+}
+impl<'b> Decoder<'b> {
+    pub fn new(bytes: &'b [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'b [u8], DecodeError> {
+        if len > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (read, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(read)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    /// Bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Checks that the whole message was read
+    pub fn finish(self) -> Result<(), DecodeError> {
+        match self.bytes {
+            [] => Ok(()),
+            _ => Err(DecodeError::TrailingBytes),
+        }
+    }
+}
+
+/// Encodes `value` to `buffer`, returning the amount of bytes written
+pub fn encode<T: Serialize>(value: &T, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(buffer);
+    value.serialize(&mut encoder)?;
+    Ok(encoder.len())
+}
+
+/// Decodes a value taking all of `bytes`
+pub fn decode<T: Serialize>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let mut decoder = Decoder::new(bytes);
+    let value = T::deserialize(&mut decoder)?;
+    decoder.finish()?;
+    Ok(value)
+}
+
+/// Encodes `value` as a message to `buffer`: its size, then the value.
+/// Returns the size of the message
+pub fn encode_message<T: Serialize>(
+    value: &T,
+    buffer: &mut [u8; MAX_MESSAGE_SIZE],
+) -> Result<usize, EncodeError> {
+    let (size, rest) = buffer.split_first_mut().unwrap();
+    let len = encode(value, rest)?;
+    *size = len as u8;
+    Ok(len + 1)
+}
+
+/// Decodes a message made by [`encode_message`]
+pub fn decode_message<T: Serialize>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let (&size, value) = bytes.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+    match (size as usize).cmp(&value.len()) {
+        core::cmp::Ordering::Greater => Err(DecodeError::UnexpectedEnd),
+        core::cmp::Ordering::Less => Err(DecodeError::TrailingBytes),
+        core::cmp::Ordering::Equal => decode(value),
+    }
+}
+
+/// Returns `true` if libnds can send a data message of `len` bytes
+pub fn is_valid_size(len: usize) -> bool {
+    (1..=MAX_MESSAGE_SIZE).contains(&len)
+}
+
+macro_rules! impl_serialize_int {
+    ($($int:ty)*) => {
+        $(
+            impl Serialize for $int {
+                fn serialize(&self, encoder: &mut Encoder<'_>) -> Result<(), EncodeError> {
+                    encoder.write_bytes(&self.to_le_bytes())
+                }
+
+                fn deserialize(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+                    Ok(Self::from_le_bytes(decoder.read_array()?))
+                }
+            }
+        )*
+    };
+}
+impl_serialize_int!(u8 u16 u32 u64 i8 i16 i32 i64);
+
+impl Serialize for bool {
+    fn serialize(&self, encoder: &mut Encoder<'_>) -> Result<(), EncodeError> {
+        (*self as u8).serialize(encoder)
+    }
+
+    fn deserialize(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        match u8::deserialize(decoder)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Invalid),
+        }
+    }
+}
+
+impl Serialize for () {
+    fn serialize(&self, _: &mut Encoder<'_>) -> Result<(), EncodeError> {
+        Ok(())
+    }
+
+    fn deserialize(_: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(())
+    }
+}
+
+/// A `bool` telling if there's a value, then the value
+impl<T: Serialize> Serialize for Option<T> {
+    fn serialize(&self, encoder: &mut Encoder<'_>) -> Result<(), EncodeError> {
+        self.is_some().serialize(encoder)?;
+        match self {
+            Some(value) => value.serialize(encoder),
+            None => Ok(()),
+        }
+    }
+
+    fn deserialize(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        match bool::deserialize(decoder)? {
+            true => T::deserialize(decoder).map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+/// The values one after the other, without a length
+impl<T: Serialize, const N: usize> Serialize for [T; N] {
+    fn serialize(&self, encoder: &mut Encoder<'_>) -> Result<(), EncodeError> {
+        self.iter().try_for_each(|value| value.serialize(encoder))
+    }
+
+    fn deserialize(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        let values = (0..N)
+            .map(|_| T::deserialize(decoder))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(values.try_into().ok().unwrap())
+    }
+}
+
+impl<T: Serialize> Serialize for Vec<T> {
+    fn serialize(&self, encoder: &mut Encoder<'_>) -> Result<(), EncodeError> {
+        let len = u16::try_from(self.len()).map_err(|_| EncodeError::TooLong)?;
+        len.serialize(encoder)?;
+        self.iter().try_for_each(|value| value.serialize(encoder))
+    }
+
+    fn deserialize(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        let len = u16::deserialize(decoder)? as usize;
+        // Every value takes at least a byte, except for zero sized ones
+        if len > decoder.remaining() && core::mem::size_of::<T>() != 0 {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        (0..len).map(|_| T::deserialize(decoder)).collect()
+    }
+}
+
+macro_rules! impl_serialize_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: Serialize),+> Serialize for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn serialize(&self, encoder: &mut Encoder<'_>) -> Result<(), EncodeError> {
+                let ($($name,)+) = self;
+                $($name.serialize(encoder)?;)+
+                Ok(())
+            }
+
+            fn deserialize(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+                Ok(($($name::deserialize(decoder)?,)+))
+            }
+        }
+    };
+}
+impl_serialize_tuple!(A);
+impl_serialize_tuple!(A B);
+impl_serialize_tuple!(A B C);
+impl_serialize_tuple!(A B C D);
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+    use core::fmt::Debug;
+
+    use super::*;
+
+    /// Encodes `value`, checks the bytes and decodes them back
+    fn round_trip<T: Serialize + PartialEq + Debug>(value: T, bytes: &[u8]) {
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+        let len = encode(&value, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], bytes);
+        assert_eq!(decode::<T>(bytes), Ok(value));
+    }
+
+    #[test]
+    fn ints_are_little_endian() {
+        round_trip(0xABu8, &[0xAB]);
+        round_trip(0x1234u16, &[0x34, 0x12]);
+        round_trip(0x1234_5678u32, &[0x78, 0x56, 0x34, 0x12]);
+        round_trip(
+            u64::MAX - 1,
+            &[0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+        );
+        round_trip(-2i8, &[0xFE]);
+        round_trip(-2i16, &[0xFE, 0xFF]);
+        round_trip(i32::MIN, &[0, 0, 0, 0x80]);
+        round_trip(-1i64, &[0xFF; 8]);
+    }
+
+    #[test]
+    fn bools_are_0_or_1() {
+        round_trip(false, &[0]);
+        round_trip(true, &[1]);
+        assert_eq!(decode::<bool>(&[2]), Err(DecodeError::Invalid));
+        assert_eq!(decode::<Option<u8>>(&[0xFF, 1]), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn options_start_with_a_bool() {
+        round_trip(None::<u16>, &[0]);
+        round_trip(Some(0x0102u16), &[1, 0x02, 0x01]);
+        round_trip(Some(None::<u8>), &[1, 0]);
+    }
+
+    #[test]
+    fn arrays_and_tuples_have_no_length() {
+        round_trip([1u16, 2, 3], &[1, 0, 2, 0, 3, 0]);
+        round_trip([0u8; 0], &[]);
+        round_trip((7u8, true, -1i16), &[7, 1, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn vecs_start_with_their_length() {
+        round_trip(Vec::<u8>::new(), &[0, 0]);
+        round_trip(vec![0x11u16, 0x22], &[2, 0, 0x11, 0, 0x22, 0]);
+        round_trip(vec![(), (), ()], &[3, 0]);
+        // Longer than the message
+        assert_eq!(
+            decode::<Vec<u8>>(&[0xFF, 0xFF, 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        let too_long = vec![(); usize::from(u16::MAX) + 1];
+        assert_eq!(encode(&too_long, &mut [0; 4]), Err(EncodeError::TooLong));
+    }
+
+    #[test]
+    fn whole_message_is_decoded() {
+        assert_eq!(decode::<u16>(&[1, 2, 3]), Err(DecodeError::TrailingBytes));
+        assert_eq!(decode::<u32>(&[1, 2, 3]), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(decode::<(u8, u8)>(&[1]), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(decode::<()>(&[0]), Err(DecodeError::TrailingBytes));
+    }
+
+    #[test]
+    fn encoding_fails_when_full() {
+        assert_eq!(encode(&0u32, &mut [0; 3]), Err(EncodeError::TooLong));
+        assert_eq!(encode(&[1u8; 4], &mut [0; 4]), Ok(4));
+    }
+
+    #[test]
+    fn messages_start_with_their_size() {
+        let mut buffer = [0xFF; MAX_MESSAGE_SIZE];
+        // Values encoding to nothing still make a message
+        assert_eq!(encode_message(&(), &mut buffer), Ok(1));
+        assert_eq!(buffer[0], 0);
+        assert_eq!(decode_message::<()>(&buffer[..1]), Ok(()));
+
+        assert_eq!(encode_message(&0x0102u16, &mut buffer), Ok(3));
+        assert_eq!(buffer[..3], [2, 0x02, 0x01]);
+        assert_eq!(decode_message::<u16>(&buffer[..3]), Ok(0x0102));
+
+        assert_eq!(decode_message::<()>(&[]), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(
+            decode_message::<u8>(&[2, 1]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            decode_message::<u8>(&[1, 1, 2]),
+            Err(DecodeError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn messages_fit_in_libnds() {
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+        // The biggest value makes a message of 127 bytes
+        let biggest = [7u8; MAX_VALUE_SIZE];
+        assert_eq!(encode_message(&biggest, &mut buffer), Ok(127));
+        assert_eq!(buffer[0], 126);
+        assert_eq!(decode_message::<[u8; MAX_VALUE_SIZE]>(&buffer), Ok(biggest));
+        // A message of 128 bytes can't be sent
+        let too_big = [7u8; MAX_VALUE_SIZE + 1];
+        assert_eq!(
+            encode_message(&too_big, &mut buffer),
+            Err(EncodeError::TooLong)
+        );
+
+        assert!(!is_valid_size(0));
+        assert!(is_valid_size(1));
+        assert!(is_valid_size(127));
+        assert!(!is_valid_size(128));
+    }
+}
@@ -0,0 +1,295 @@
+//! Variable width bitmap fonts, converted at build time by
+//! [`include_font!`](crate::include_font) from BMFont or BDF files
+//!
+//! ```rust,no_run
+//! static FONT: Font = include_font!("fonts/ui.fnt");
+//!
+//! let mut framebuffer = mode.layer3_framebuffer();
+//! let options = TextOptions::new().with_width(200).with_alignment(Alignment::Center);
+//! FONT.draw(&mut framebuffer, "Hello, wörld!", 28, 80, &options, 0x7FFF);
+//! ```
+//!
+//! Text can be drawn on anything implementing [`TextTarget`]: a bitmap, a
+//! [`TileCanvas`] on a tiled background or a [`SpriteCanvas`]. With the
+//! `embedded-graphics` feature, [`FontStyle`] lets the text of `embedded-graphics` use
+//! the fonts.
+
+use crate::background::RenderTargetBitmap;
+
+mod canvas;
+#[cfg(feature = "embedded-graphics")]
+mod style;
+pub use canvas::*;
+#[cfg(feature = "embedded-graphics")]
+pub use style::*;
+
+/// A character of a [`Font`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyph {
+    pub char: char,
+    /// Size of the bitmap in pixels
+    pub width: u8,
+    pub height: u8,
+    /// Position of the bitmap relative to the pen, on the top of the line
+    pub x_offset: i8,
+    pub y_offset: i8,
+    /// Distance to the next character
+    pub advance: u8,
+    /// Start of the bitmap in [`Font::bitmaps`]
+    pub offset: u32,
+}
+
+/// A pair of characters closer or further apart than their advance says
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Kerning {
+    pub first: char,
+    pub second: char,
+    pub amount: i8,
+}
+
+/// A bitmap font, with one bit per pixel
+#[derive(Debug, Clone, Copy)]
+pub struct Font {
+    /// Distance between two lines in pixels
+    pub line_height: u8,
+    /// Distance from the top of the line to the baseline
+    pub baseline: u8,
+    /// Sorted by character
+    pub glyphs: &'static [Glyph],
+    /// Sorted by pair of characters
+    pub kerning: &'static [Kerning],
+    /// Bitmaps of the glyphs, line by line, each line starting on a byte with the
+    /// leftmost pixel in the highest bit
+    pub bitmaps: &'static [u8],
+}
+
+/// How lines are placed horizontally
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Layout of a text: wrapping and alignment
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TextOptions {
+    width: Option<u32>,
+    alignment: Alignment,
+}
+impl TextOptions {
+    /// No wrapping, aligned to the left
+    pub const fn new() -> Self {
+        Self {
+            width: None,
+            alignment: Alignment::Left,
+        }
+    }
+
+    /// Wraps the lines wider than `width` pixels, between words if possible. The lines
+    /// are aligned in a box of this width
+    pub const fn with_width(self, width: u32) -> Self {
+        Self {
+            width: Some(width),
+            ..self
+        }
+    }
+
+    /// Aligns the lines in the box given by [`with_width`](Self::with_width), or
+    /// around the position of the text without it
+    pub const fn with_alignment(self, alignment: Alignment) -> Self {
+        Self { alignment, ..self }
+    }
+}
+
+/// Something text can be drawn on, pixel by pixel
+pub trait TextTarget {
+    type Color: Copy;
+
+    /// Sets a pixel. Pixels out of the target are ignored
+    fn set_pixel(&mut self, x: i32, y: i32, color: Self::Color);
+}
+
+impl TextTarget for RenderTargetBitmap {
+    /// BGR555
+    type Color = u16;
+
+    fn set_pixel(&mut self, x: i32, y: i32, color: u16) {
+        if 0 <= x && (x as u32) < self.width() && 0 <= y {
+            self.put_pixel(x as u32, y as u32, color | 0x8000);
+        }
+    }
+}
+
+impl Font {
+    /// The glyph of `c`, or of `?` if the font doesn't have it
+    pub fn glyph(&self, c: char) -> Option<&'static Glyph> {
+        let glyphs = self.glyphs;
+        let find = |c| glyphs.binary_search_by_key(&c, |glyph| glyph.char).ok();
+        find(c).or_else(|| find('?')).map(|i| &glyphs[i])
+    }
+
+    /// The adjustment of the distance between `first` and `second`
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kerning
+            .binary_search_by_key(&(first, second), |kerning| (kerning.first, kerning.second))
+            .map_or(0, |i| self.kerning[i].amount as i32)
+    }
+
+    /// The pixels of a glyph drawn with the pen at (`x`, `y`), at the top of the line
+    pub fn pixels(&self, glyph: &Glyph, x: i32, y: i32) -> impl Iterator<Item = (i32, i32)> {
+        let (width, height) = (glyph.width as usize, glyph.height as usize);
+        let bytes_per_line = width.div_ceil(8);
+        let start = glyph.offset as usize;
+        let bitmap = &self.bitmaps[start..start + bytes_per_line * height];
+        let (x, y) = (x + glyph.x_offset as i32, y + glyph.y_offset as i32);
+        (0..height).flat_map(move |line| {
+            let bytes = &bitmap[line * bytes_per_line..];
+            (0..width)
+                .filter(move |column| bytes[column / 8] & (0x80 >> (column % 8)) != 0)
+                .map(move |column| (x + column as i32, y + line as i32))
+        })
+    }
+
+    /// Width in pixels of a line of text. Line breaks aren't handled
+    pub fn line_width(&self, line: &str) -> u32 {
+        let mut width = 0;
+        let mut previous = None;
+        for c in line.chars() {
+            if let Some(previous) = previous {
+                width += self.kerning(previous, c);
+            }
+            width += self.glyph(c).map_or(0, |glyph| glyph.advance as i32);
+            previous = Some(c);
+        }
+        width.max(0) as u32
+    }
+
+    /// The lines of `text`, split on line breaks and wrapped as `options` says
+    pub fn lines<'a>(&self, text: &'a str, options: &TextOptions) -> Lines<'a> {
+        Lines {
+            font: *self,
+            rest: Some(text),
+            width: options.width,
+        }
+    }
+
+    /// Width and height in pixels of `text` laid out with `options`
+    pub fn measure(&self, text: &str, options: &TextOptions) -> (u32, u32) {
+        let (mut width, mut lines) = (0, 0);
+        for line in self.lines(text, options) {
+            width = width.max(self.line_width(line));
+            lines += 1;
+        }
+        (width, lines * self.line_height as u32)
+    }
+
+    /// Draws a line of text with the pen at (`x`, `y`), the top of the line. Line breaks
+    /// aren't handled. Returns the position of the pen after the line
+    pub fn draw_line<T: TextTarget + ?Sized>(
+        &self,
+        target: &mut T,
+        line: &str,
+        mut x: i32,
+        y: i32,
+        color: T::Color,
+    ) -> i32 {
+        let mut previous = None;
+        for c in line.chars() {
+            if let Some(previous) = previous {
+                x += self.kerning(previous, c);
+            }
+            previous = Some(c);
+            let Some(glyph) = self.glyph(c) else {
+                continue;
+            };
+            for (px, py) in self.pixels(glyph, x, y) {
+                target.set_pixel(px, py, color);
+            }
+            x += glyph.advance as i32;
+        }
+        x
+    }
+
+    /// Draws `text` laid out with `options`, from (`x`, `y`), the top left corner of its
+    /// box. Returns the position of the line after the text
+    pub fn draw<T: TextTarget + ?Sized>(
+        &self,
+        target: &mut T,
+        text: &str,
+        x: i32,
+        mut y: i32,
+        options: &TextOptions,
+        color: T::Color,
+    ) -> i32 {
+        let width = options.width.unwrap_or(0) as i32;
+        for line in self.lines(text, options) {
+            let free = width - self.line_width(line) as i32;
+            let x = match options.alignment {
+                Alignment::Left => x,
+                Alignment::Center => x + free / 2,
+                Alignment::Right => x + free,
+            };
+            self.draw_line(target, line, x, y, color);
+            y += self.line_height as i32;
+        }
+        y
+    }
+}
+
+/// Iterator over the lines of a text, wrapped to a width.
+/// See [`Font::lines`]
+#[derive(Debug, Clone)]
+pub struct Lines<'a> {
+    font: Font,
+    /// `None` once the last line was returned
+    rest: Option<&'a str>,
+    width: Option<u32>,
+}
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let text = self.rest?;
+        let (line, after) = match text.split_once('\n') {
+            Some((line, after)) => (line, Some(after)),
+            None => (text, None),
+        };
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let Some(width) = self.width else {
+            self.rest = after;
+            return Some(line);
+        };
+
+        // The longest start of the line that fits, preferably ending before a space
+        let mut fits = 0;
+        let mut last_space = None;
+        let mut word = false;
+        let mut pen = 0;
+        let mut previous = None;
+        for (i, c) in line.char_indices() {
+            if let Some(previous) = previous {
+                pen += self.font.kerning(previous, c);
+            }
+            previous = Some(c);
+            let advance = self.font.glyph(c).map_or(0, |glyph| glyph.advance as i32);
+            if c == ' ' {
+                if word {
+                    last_space = Some(i);
+                }
+                word = false;
+            } else if pen + advance > width as i32 && i > 0 {
+                let end = last_space.unwrap_or(fits);
+                self.rest = Some(text[end..].trim_start_matches(' '));
+                return Some(line[..end].trim_end_matches(' '));
+            } else {
+                word = true;
+            }
+            pen += advance;
+            fits = i + c.len_utf8();
+        }
+        self.rest = after;
+        Some(line)
+    }
+}
@@ -0,0 +1,364 @@
+//! Text drawn into tiles of backgrounds and sprites
+
+use nds_sys::{
+    sprite::{Attr0, X_COORD_MASK, Y_COORD_MASK},
+    video::{BG_GFX, BG_GFX_SUB, SPRITE_GFX, SPRITE_GFX_SUB},
+};
+
+use super::TextTarget;
+#[cfg(feature = "embedded-graphics-core")]
+use crate::embedded_graphics::{clip, PaletteIndex};
+use crate::{
+    gfx::{Bpp, MAP_BASE_SIZE, TILE_BASE_SIZE},
+    sprite::{dimensions, Obj, Shape, Size},
+    video::Engine,
+};
+
+/// Sets the pixel (`x`, `y`) of the tile at `tile` to the palette index `color`
+///
+/// # Safety
+/// `tile` must point to a tile of VRAM of this depth
+unsafe fn write_pixel(tile: *mut u16, bpp: Bpp, x: usize, y: usize, color: u8) {
+    let (halfword, shift, mask) = match bpp {
+        Bpp::Four => (tile.add(y * 2 + x / 4), (x % 4) * 4, 0xF),
+        Bpp::Eight => (tile.add(y * 4 + x / 2), (x % 2) * 8, 0xFF),
+    };
+    let bits = (halfword.read_volatile() & !(mask << shift)) | ((color as u16 & mask) << shift);
+    halfword.write_volatile(bits);
+}
+
+/// Clears `count` halfwords of VRAM
+///
+/// # Safety
+/// They must be in VRAM
+unsafe fn clear(start: *mut u16, count: usize) {
+    for i in 0..count {
+        start.add(i).write_volatile(0);
+    }
+}
+
+/// An area of a text background whose tiles are allocated as text is drawn, for
+/// variable width text.
+///
+/// The cells of the area start on the tile 0 of the tile base, which must be blank.
+/// When a glyph reaches a blank cell, the next free tile is cleared and given to the
+/// cell. Once all the tiles are used, the pixels of new cells are dropped.
+///
+/// The colors are palette indices, of the palette given by
+/// [`with_palette`](Self::with_palette) with 4 bits per pixel.
+/// With the `embedded-graphics-core` feature, it's also a `DrawTarget` of
+/// [`PaletteIndex`](crate::embedded_graphics::PaletteIndex) filling whole cells at once.
+#[derive(Debug)]
+pub struct TileCanvas {
+    tiles: *mut u16,
+    map: *mut u16,
+    bpp: Bpp,
+    palette: u16,
+    first_tile: u16,
+    tile_count: u16,
+    next_tile: u16,
+    /// Area of the map, in tiles
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+}
+impl TileCanvas {
+    /// A canvas over the whole screen, on a 32x32 map at the map base `map_base`
+    /// (2 KiB each) using the tiles 1 to 1023 of the tile base `tile_base` (16 KiB each)
+    pub fn new(engine: Engine, map_base: usize, tile_base: usize, bpp: Bpp) -> Self {
+        assert!(map_base < 32 && tile_base < 16, "invalid base");
+        let gfx = match engine {
+            Engine::Main => BG_GFX,
+            Engine::Sub => BG_GFX_SUB,
+        };
+        unsafe {
+            Self {
+                tiles: gfx.byte_add(tile_base * TILE_BASE_SIZE),
+                map: gfx.byte_add(map_base * MAP_BASE_SIZE),
+                bpp,
+                palette: 0,
+                first_tile: 1,
+                tile_count: 1023,
+                next_tile: 1,
+                x: 0,
+                y: 0,
+                width: 32,
+                height: 24,
+            }
+        }
+    }
+
+    /// Allocates the tiles `first_tile` to `first_tile + count - 1`
+    pub fn with_tiles(self, first_tile: u16, count: u16) -> Self {
+        assert!(first_tile > 0, "the tile 0 is the blank tile");
+        assert!(
+            first_tile as usize + count as usize <= 1024,
+            "invalid tiles"
+        );
+        Self {
+            first_tile,
+            tile_count: count,
+            next_tile: first_tile,
+            ..self
+        }
+    }
+
+    /// Draws on `width`x`height` tiles from the tile (`x`, `y`) of the map
+    pub fn with_area(self, x: u16, y: u16, width: u16, height: u16) -> Self {
+        assert!(
+            x + width <= 32 && y + height <= 32,
+            "the area is out of the map"
+        );
+        Self {
+            x,
+            y,
+            width,
+            height,
+            ..self
+        }
+    }
+
+    /// Uses the palette `palette` of 16 colors for 4 bits per pixel
+    pub fn with_palette(self, palette: u16) -> Self {
+        assert!(palette < 16, "palette out of range");
+        Self { palette, ..self }
+    }
+
+    /// Size of the area in pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.width as u32 * 8, self.height as u32 * 8)
+    }
+
+    /// Number of tiles given to cells since the last [`clear`](Self::clear)
+    pub fn used_tiles(&self) -> u16 {
+        self.next_tile - self.first_tile
+    }
+
+    /// Resets the cells of the area to the blank tile, and frees their tiles
+    pub fn clear(&mut self) {
+        for y in self.y..self.y + self.height {
+            for x in self.x..self.x + self.width {
+                unsafe { self.map.add(y as usize * 32 + x as usize).write_volatile(0) }
+            }
+        }
+        self.next_tile = self.first_tile;
+    }
+
+    /// The tile of a cell, allocated if needed
+    fn cell_tile(&mut self, cell: *mut u16) -> Option<*mut u16> {
+        let words_per_tile = self.bpp.tile_size() / 2;
+        let mut tile = unsafe { cell.read_volatile() } & 0x3FF;
+        if tile == 0 {
+            if self.used_tiles() >= self.tile_count {
+                return None;
+            }
+            tile = self.next_tile;
+            self.next_tile += 1;
+            unsafe {
+                clear(
+                    self.tiles.add(tile as usize * words_per_tile),
+                    words_per_tile,
+                );
+                cell.write_volatile(tile | (self.palette << 12));
+            }
+        }
+        Some(unsafe { self.tiles.add(tile as usize * words_per_tile) })
+    }
+}
+impl TextTarget for TileCanvas {
+    /// Palette index
+    type Color = u8;
+
+    fn set_pixel(&mut self, x: i32, y: i32, color: u8) {
+        let (width, height) = self.size();
+        if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let cell = (self.y as usize + y / 8) * 32 + self.x as usize + x / 8;
+        if let Some(tile) = self.cell_tile(unsafe { self.map.add(cell) }) {
+            unsafe { write_pixel(tile, self.bpp, x % 8, y % 8, color) }
+        }
+    }
+}
+
+#[cfg(feature = "embedded-graphics-core")]
+impl embedded_graphics_core::geometry::OriginDimensions for TileCanvas {
+    fn size(&self) -> embedded_graphics_core::geometry::Size {
+        let (width, height) = TileCanvas::size(self);
+        embedded_graphics_core::geometry::Size::new(width, height)
+    }
+}
+#[cfg(feature = "embedded-graphics-core")]
+impl embedded_graphics_core::draw_target::DrawTarget for TileCanvas {
+    type Color = PaletteIndex;
+
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = embedded_graphics_core::Pixel<Self::Color>>,
+    {
+        for embedded_graphics_core::Pixel(pos, color) in pixels {
+            self.set_pixel(pos.x, pos.y, color.0);
+        }
+        Ok(())
+    }
+
+    fn fill_solid(
+        &mut self,
+        area: &embedded_graphics_core::primitives::Rectangle,
+        color: Self::Color,
+    ) -> Result<(), Self::Error> {
+        use embedded_graphics_core::geometry::OriginDimensions;
+
+        let Some((x, y, width, height)) = clip(area, OriginDimensions::size(self)) else {
+            return Ok(());
+        };
+        let words_per_tile = self.bpp.tile_size() / 2;
+        let pattern = match self.bpp {
+            Bpp::Four => (color.0 as u16 & 0xF) * 0x1111,
+            Bpp::Eight => color.0 as u16 * 0x0101,
+        };
+        for cell_y in y / 8..(y + height).div_ceil(8) {
+            for cell_x in x / 8..(x + width).div_ceil(8) {
+                let cell = unsafe {
+                    let offset = (self.y as usize + cell_y) * 32 + self.x as usize + cell_x;
+                    self.map.add(offset)
+                };
+                // Blank cells are already filled with the color 0
+                if color.0 == 0 && unsafe { cell.read_volatile() } & 0x3FF == 0 {
+                    continue;
+                }
+                // Part of the cell covered by the area
+                let left = x.max(cell_x * 8);
+                let right = (x + width).min(cell_x * 8 + 8);
+                let top = y.max(cell_y * 8);
+                let bottom = (y + height).min(cell_y * 8 + 8);
+                let Some(tile) = self.cell_tile(cell) else {
+                    continue;
+                };
+                if right - left == 8 && bottom - top == 8 {
+                    for i in 0..words_per_tile {
+                        unsafe { tile.add(i).write_volatile(pattern) }
+                    }
+                    continue;
+                }
+                for py in top..bottom {
+                    for px in left..right {
+                        unsafe { write_pixel(tile, self.bpp, px % 8, py % 8, color.0) }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        use embedded_graphics_core::geometry::Dimensions;
+
+        TileCanvas::clear(self);
+        if color.0 != 0 {
+            self.fill_solid(&self.bounding_box(), color)?;
+        }
+        Ok(())
+    }
+}
+
+/// A row of OBJs of the same size showing text, whose tiles follow each other with the
+/// 1D mapping with a boundary of 32 bytes.
+///
+/// The colors are palette indices.
+#[derive(Debug)]
+pub struct SpriteCanvas {
+    gfx: *mut u16,
+    first_tile: u16,
+    shape: Shape,
+    size: Size,
+    count: u16,
+    bpp: Bpp,
+}
+impl SpriteCanvas {
+    /// A canvas of `count` OBJs of this shape and size, using the tiles of the sprites
+    /// from `first_tile`, in units of 32 bytes
+    pub fn new(
+        engine: Engine,
+        first_tile: u16,
+        shape: Shape,
+        size: Size,
+        count: u16,
+        bpp: Bpp,
+    ) -> Self {
+        let (gfx, vram_size) = match engine {
+            Engine::Main => (SPRITE_GFX, 256 * 1024),
+            Engine::Sub => (SPRITE_GFX_SUB, 128 * 1024),
+        };
+        let canvas = Self {
+            gfx: unsafe { gfx.byte_add(first_tile as usize * 32) },
+            first_tile,
+            shape,
+            size,
+            count,
+            bpp,
+        };
+        let end = first_tile as usize * 32 + canvas.obj_size() * count as usize;
+        assert!(end <= vram_size.min(32 * 1024), "the tiles don't fit");
+        canvas
+    }
+
+    /// Size of the tiles of an OBJ in bytes
+    fn obj_size(&self) -> usize {
+        let (width, height) = dimensions(self.shape, self.size);
+        width as usize / 8 * (height as usize / 8) * self.bpp.tile_size()
+    }
+
+    /// Size of the canvas in pixels
+    pub fn size(&self) -> (u32, u32) {
+        let (width, height) = dimensions(self.shape, self.size);
+        (width as u32 * self.count as u32, height as u32)
+    }
+
+    /// Tile index of the OBJ `index` for [`Obj::set_tile`]
+    pub fn tile(&self, index: u16) -> u16 {
+        self.first_tile + (index as usize * self.obj_size() / 32) as u16
+    }
+
+    /// Clears the tiles
+    pub fn clear(&mut self) {
+        unsafe { clear(self.gfx, self.obj_size() * self.count as usize / 2) }
+    }
+
+    /// Sets up an OBJ per OBJ of the canvas, showing it with its top left corner at
+    /// (`x`, `y`) on the screen. The other attributes of the OBJs are kept
+    pub fn place(&self, objs: &mut [Obj], x: i32, y: i32) {
+        assert!(objs.len() >= self.count as usize, "not enough OBJs");
+        let (width, _) = dimensions(self.shape, self.size);
+        for (i, obj) in objs.iter_mut().take(self.count as usize).enumerate() {
+            obj.set_shape(self.shape);
+            obj.set_size(self.size);
+            obj.set_x((x + i as i32 * width as i32) as u16 & X_COORD_MASK);
+            obj.set_y(y as u16 & Y_COORD_MASK);
+            obj.attr0.set(Attr0::COLOR_256, self.bpp == Bpp::Eight);
+            obj.show();
+            obj.set_tile(self.tile(i as u16));
+        }
+    }
+}
+impl TextTarget for SpriteCanvas {
+    /// Palette index
+    type Color = u8;
+
+    fn set_pixel(&mut self, x: i32, y: i32, color: u8) {
+        let (width, height) = self.size();
+        if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+            return;
+        }
+        let (obj_width, _) = dimensions(self.shape, self.size);
+        let (x, y) = (x as usize, y as usize);
+        let (obj, x) = (x / obj_width as usize, x % obj_width as usize);
+        let tile = (y / 8) * (obj_width as usize / 8) + x / 8;
+        let offset = obj * self.obj_size() + tile * self.bpp.tile_size();
+        unsafe { write_pixel(self.gfx.byte_add(offset), self.bpp, x % 8, y % 8, color) }
+    }
+}
@@ -0,0 +1,131 @@
+//! Text of `embedded-graphics` drawn with [`Font`]s
+
+use embedded_graphics::{
+    prelude::*,
+    primitives::Rectangle,
+    text::{
+        renderer::{CharacterStyle, TextMetrics, TextRenderer},
+        Baseline,
+    },
+};
+
+use super::Font;
+
+/// A character style of `embedded-graphics` using a [`Font`].
+///
+/// ```rust,no_run
+/// let style = FontStyle::new(&FONT, Bgr555::WHITE);
+/// Text::new("Hello!", Point::new(8, 20), style).draw(&mut framebuffer)?;
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FontStyle<C> {
+    pub font: &'static Font,
+    /// Color of the glyphs, which aren't drawn if `None`
+    pub text_color: Option<C>,
+    /// Color behind the glyphs, transparent if `None`
+    pub background_color: Option<C>,
+}
+impl<C> FontStyle<C> {
+    pub const fn new(font: &'static Font, text_color: C) -> Self {
+        Self {
+            font,
+            text_color: Some(text_color),
+            background_color: None,
+        }
+    }
+
+    /// Distance from the top of the line to the position of the text
+    fn baseline_offset(&self, baseline: Baseline) -> i32 {
+        let line_height = self.font.line_height as i32;
+        match baseline {
+            Baseline::Top => 0,
+            Baseline::Bottom => line_height - 1,
+            Baseline::Middle => (line_height - 1) / 2,
+            Baseline::Alphabetic => self.font.baseline as i32,
+        }
+    }
+}
+
+impl<C: PixelColor> CharacterStyle for FontStyle<C> {
+    type Color = C;
+
+    fn set_text_color(&mut self, text_color: Option<C>) {
+        self.text_color = text_color;
+    }
+
+    fn set_background_color(&mut self, background_color: Option<C>) {
+        self.background_color = background_color;
+    }
+}
+
+impl<C: PixelColor> TextRenderer for FontStyle<C> {
+    type Color = C;
+
+    fn draw_string<D>(
+        &self,
+        text: &str,
+        position: Point,
+        baseline: Baseline,
+        target: &mut D,
+    ) -> Result<Point, D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        let width = self.font.line_width(text);
+        let next = self.draw_whitespace(width, position, baseline, target)?;
+        let Some(color) = self.text_color else {
+            return Ok(next);
+        };
+        let top = position.y - self.baseline_offset(baseline);
+        let mut x = position.x;
+        let mut previous = None;
+        for c in text.chars() {
+            if let Some(previous) = previous {
+                x += self.font.kerning(previous, c);
+            }
+            previous = Some(c);
+            let Some(glyph) = self.font.glyph(c) else {
+                continue;
+            };
+            let pixels = self.font.pixels(glyph, x, top);
+            target.draw_iter(pixels.map(|(x, y)| Pixel(Point::new(x, y), color)))?;
+            x += glyph.advance as i32;
+        }
+        Ok(next)
+    }
+
+    fn draw_whitespace<D>(
+        &self,
+        width: u32,
+        position: Point,
+        baseline: Baseline,
+        target: &mut D,
+    ) -> Result<Point, D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        if let Some(color) = self.background_color {
+            let top = Point::new(position.x, position.y - self.baseline_offset(baseline));
+            let size = Size::new(width, self.line_height());
+            target.fill_solid(&Rectangle::new(top, size), color)?;
+        }
+        Ok(position + Point::new(width as i32, 0))
+    }
+
+    fn measure_string(&self, text: &str, position: Point, baseline: Baseline) -> TextMetrics {
+        let width = self.font.line_width(text);
+        let top = Point::new(position.x, position.y - self.baseline_offset(baseline));
+        let size = match width {
+            0 => Size::zero(),
+            _ => Size::new(width, self.line_height()),
+        };
+        TextMetrics {
+            bounding_box: Rectangle::new(top, size),
+            next_position: position + Point::new(width as i32, 0),
+        }
+    }
+
+    fn line_height(&self) -> u32 {
+        self.font.line_height as u32
+    }
+}
@@ -0,0 +1,359 @@
+//! Graphics converted at build time by [`include_image!`](crate::include_image)
+//!
+//! ```rust,no_run
+//! static LEVEL: TiledImage = include_image!("gfx/level.png", tiles);
+//! static HERO: SpriteSheet = include_image!("gfx/hero.png", sprite(16, 16));
+//!
+//! LEVEL.tiles.load_bg(Engine::Main, 1, 0);
+//! LEVEL.map.load_bg(Engine::Main, 0);
+//! LEVEL.palette.load_bg(Engine::Main, 0);
+//!
+//! HERO.tiles.load_sprite(Engine::Main, 0);
+//! HERO.palette.load_sprite(Engine::Main, 0);
+//! obj.set_tile(HERO.frame_tile(0, 2));
+//! ```
+//!
+//! Maps made with Tiled are converted by [`include_tiled!`](crate::include_tiled) to a
+//! [`TiledMap`] and tables of [`Spawn`]s, and sprites made with Aseprite by
+//! [`include_sprite!`](crate::include_sprite) to a
+//! [`MetaSprite`](crate::sprite::MetaSprite) and its animations.
+
+use nds_sys::{
+    background::TileMapEntry16,
+    video::{BG_GFX, BG_GFX_SUB, BG_PALETTE, BG_PALETTE_SUB, SPRITE_GFX, SPRITE_GFX_SUB},
+    video::{SPRITE_PALETTE, SPRITE_PALETTE_SUB},
+};
+
+use crate::{background::RenderTargetBitmap, video::Engine};
+
+mod tiled;
+pub use tiled::*;
+
+/// Size of a tile base of the backgrounds
+pub(crate) const TILE_BASE_SIZE: usize = 16 * 1024;
+/// Size of a map base of the backgrounds
+pub(crate) const MAP_BASE_SIZE: usize = 2 * 1024;
+
+/// Copies `src` to VRAM or palette RAM, which can't be written byte by byte
+unsafe fn copy_volatile<T: Copy>(src: &[T], dst: *mut T) {
+    for (i, &value) in src.iter().enumerate() {
+        dst.add(i).write_volatile(value);
+    }
+}
+
+/// Colors in BGR555. Index 0 is transparent
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    colors: &'static [u16],
+}
+impl Palette {
+    pub const fn new(colors: &'static [u16]) -> Self {
+        Self { colors }
+    }
+
+    pub const fn colors(&self) -> &'static [u16] {
+        self.colors
+    }
+
+    pub const fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// Copies the colors to the palette of the backgrounds, from color `start`.
+    /// The palette `n` of 16 colors starts at color `n * 16`
+    pub fn load_bg(&self, engine: Engine, start: usize) {
+        let palette = match engine {
+            Engine::Main => BG_PALETTE,
+            Engine::Sub => BG_PALETTE_SUB,
+        };
+        self.load(palette, start);
+    }
+
+    /// Copies the colors to the palette of the sprites, from color `start`.
+    /// The palette `n` of 16 colors starts at color `n * 16`
+    pub fn load_sprite(&self, engine: Engine, start: usize) {
+        let palette = match engine {
+            Engine::Main => SPRITE_PALETTE,
+            Engine::Sub => SPRITE_PALETTE_SUB,
+        };
+        self.load(palette, start);
+    }
+
+    fn load(&self, palette: *mut u16, start: usize) {
+        assert!(start + self.len() <= 256, "the palette doesn't fit");
+        unsafe { copy_volatile(self.colors, palette.add(start)) }
+    }
+}
+
+/// Bits per pixel of tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bpp {
+    /// 16 colors, from one of the 16 palettes
+    Four = 4,
+    /// 256 colors
+    Eight = 8,
+}
+impl Bpp {
+    /// Size of a tile in bytes
+    pub const fn tile_size(self) -> usize {
+        8 * self as usize
+    }
+}
+
+/// 8x8 tiles, packed in words
+#[derive(Debug, Clone, Copy)]
+pub struct Tiles {
+    bpp: Bpp,
+    data: &'static [u32],
+}
+impl Tiles {
+    pub const fn new(bpp: Bpp, data: &'static [u32]) -> Self {
+        Self { bpp, data }
+    }
+
+    pub const fn bpp(&self) -> Bpp {
+        self.bpp
+    }
+
+    pub const fn data(&self) -> &'static [u32] {
+        self.data
+    }
+
+    /// Size of the tiles in bytes
+    pub const fn size(&self) -> usize {
+        self.data.len() * 4
+    }
+
+    pub const fn count(&self) -> usize {
+        self.size() / self.bpp.tile_size()
+    }
+
+    /// The words of a tile
+    pub fn tile(&self, index: usize) -> &'static [u32] {
+        let words = self.bpp.tile_size() / 4;
+        &self.data[index * words..(index + 1) * words]
+    }
+
+    /// Copies the tiles to the tile base `tile_base` of the backgrounds (16 KiB each),
+    /// from tile `first_tile`. The maps must then add `first_tile` to their indices
+    pub fn load_bg(&self, engine: Engine, tile_base: usize, first_tile: usize) {
+        let (gfx, vram_size) = match engine {
+            Engine::Main => (BG_GFX, 512 * 1024),
+            Engine::Sub => (BG_GFX_SUB, 128 * 1024),
+        };
+        let offset = tile_base * TILE_BASE_SIZE + first_tile * self.bpp.tile_size();
+        assert!(offset + self.size() <= vram_size, "the tiles don't fit");
+        unsafe { copy_volatile(self.data, gfx.byte_add(offset).cast()) }
+    }
+
+    /// Copies the tiles to the tiles of the sprites, from the tile `first_tile` in units
+    /// of 32 bytes (The tile index of [`Obj::set_tile`](crate::sprite::Obj::set_tile)
+    /// with the 1D mapping)
+    pub fn load_sprite(&self, engine: Engine, first_tile: usize) {
+        let (gfx, vram_size) = match engine {
+            Engine::Main => (SPRITE_GFX, 256 * 1024),
+            Engine::Sub => (SPRITE_GFX_SUB, 128 * 1024),
+        };
+        let offset = first_tile * 32;
+        assert!(offset + self.size() <= vram_size, "the tiles don't fit");
+        unsafe { copy_volatile(self.data, gfx.byte_add(offset).cast()) }
+    }
+}
+
+/// A map of `width`x`height` tiles, line by line
+#[derive(Debug, Clone, Copy)]
+pub struct TileMap {
+    width: u16,
+    height: u16,
+    entries: &'static [TileMapEntry16],
+}
+impl TileMap {
+    pub const fn new(width: u16, height: u16, entries: &'static [TileMapEntry16]) -> Self {
+        Self {
+            width,
+            height,
+            entries,
+        }
+    }
+
+    /// Width in tiles
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Height in tiles
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub const fn entries(&self) -> &'static [TileMapEntry16] {
+        self.entries
+    }
+
+    pub fn entry(&self, x: u16, y: u16) -> Option<TileMapEntry16> {
+        if x >= self.width {
+            return None;
+        }
+        self.entries
+            .get(y as usize * self.width as usize + x as usize)
+            .copied()
+    }
+
+    /// Copies the map to the map base `map_base` of the backgrounds (2 KiB each).
+    ///
+    /// The hardware splits maps in blocks of 32x32 tiles, one per map base, placed
+    /// line by line: a 64x64 map uses 4 blocks, the top left one first. Maps narrower
+    /// than 32 tiles are placed in the top left corner of the block.
+    pub fn load_bg(&self, engine: Engine, map_base: usize) {
+        let gfx = match engine {
+            Engine::Main => BG_GFX,
+            Engine::Sub => BG_GFX_SUB,
+        };
+        let blocks_wide = (self.width as usize).div_ceil(32);
+        let blocks_high = (self.height as usize).div_ceil(32);
+        assert!(
+            map_base + blocks_wide * blocks_high <= 32,
+            "the map doesn't fit"
+        );
+        let base: *mut u16 = unsafe { gfx.byte_add(map_base * MAP_BASE_SIZE) };
+        let lines = self.entries.chunks_exact(self.width as usize);
+        for (y, line) in lines.enumerate() {
+            for (block_x, part) in line.chunks(32).enumerate() {
+                let block = (y / 32) * blocks_wide + block_x;
+                let offset = block * 32 * 32 + (y % 32) * 32;
+                for (x, entry) in part.iter().enumerate() {
+                    unsafe { base.add(offset + x).write_volatile(entry.bits()) }
+                }
+            }
+        }
+    }
+}
+
+/// A bitmap of palette indices, two pixels per halfword
+#[derive(Debug, Clone, Copy)]
+pub struct Bitmap8 {
+    width: u16,
+    height: u16,
+    data: &'static [u16],
+}
+impl Bitmap8 {
+    pub const fn new(width: u16, height: u16, data: &'static [u16]) -> Self {
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub const fn data(&self) -> &'static [u16] {
+        self.data
+    }
+
+    /// The palette index of a pixel
+    pub fn index(&self, x: u16, y: u16) -> Option<u8> {
+        if x >= self.width {
+            return None;
+        }
+        let offset = y as usize * self.width as usize + x as usize;
+        let pair = self.data.get(offset / 2)?;
+        Some((pair >> ((offset % 2) * 8)) as u8)
+    }
+}
+
+/// A bitmap in BGR555, with the alpha bit set on opaque pixels
+#[derive(Debug, Clone, Copy)]
+pub struct Bitmap16 {
+    width: u16,
+    height: u16,
+    pixels: &'static [u16],
+}
+impl Bitmap16 {
+    pub const fn new(width: u16, height: u16, pixels: &'static [u16]) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub const fn pixels(&self) -> &'static [u16] {
+        self.pixels
+    }
+
+    /// Copies the bitmap to the top left corner of `target`, cropped to its size
+    pub fn copy_to(&self, target: &mut RenderTargetBitmap) {
+        let target_width = target.width() as usize;
+        let width = (self.width as usize).min(target_width);
+        let lines = self.pixels.chunks_exact(self.width as usize);
+        for (src, dst) in lines.zip(target.framebuffer().chunks_exact_mut(target_width)) {
+            dst[..width].copy_from_slice(&src[..width]);
+        }
+    }
+}
+
+/// A tiled background: its tiles, map and palette
+#[derive(Debug, Clone, Copy)]
+pub struct TiledImage {
+    pub tiles: Tiles,
+    pub map: TileMap,
+    pub palette: Palette,
+}
+
+/// An 8 bits bitmap and its palette
+#[derive(Debug, Clone, Copy)]
+pub struct BitmapImage8 {
+    pub bitmap: Bitmap8,
+    pub palette: Palette,
+}
+
+/// Frames of a sprite, each one stored as the 1D mapping expects
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteSheet {
+    pub tiles: Tiles,
+    pub palette: Palette,
+    /// Width of the frames in pixels
+    pub frame_width: u16,
+    /// Height of the frames in pixels
+    pub frame_height: u16,
+    pub frames: u16,
+}
+impl SpriteSheet {
+    /// Number of 8x8 tiles of a frame
+    pub const fn tiles_per_frame(&self) -> usize {
+        (self.frame_width as usize / 8) * (self.frame_height as usize / 8)
+    }
+
+    /// The words of a frame
+    pub fn frame(&self, frame: u16) -> &'static [u32] {
+        let words = self.tiles_per_frame() * self.tiles.bpp().tile_size() / 4;
+        let start = frame as usize * words;
+        &self.tiles.data()[start..start + words]
+    }
+
+    /// Tile index of a frame for [`Obj::set_tile`](crate::sprite::Obj::set_tile), when
+    /// the sheet was loaded at `first_tile` with [`Tiles::load_sprite`]
+    pub const fn frame_tile(&self, first_tile: u16, frame: u16) -> u16 {
+        let size = self.tiles_per_frame() * self.tiles.bpp().tile_size();
+        first_tile + (frame as usize * size / 32) as u16
+    }
+}
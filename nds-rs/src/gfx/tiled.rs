@@ -0,0 +1,103 @@
+//! Maps and objects converted by [`include_tiled!`](crate::include_tiled)
+
+use nds_sys::{
+    background::{BgSize, Layer, TileMapEntry16},
+    video::{BG_GFX, BG_GFX_SUB},
+};
+
+use super::{copy_volatile, Palette, Tiles, MAP_BASE_SIZE};
+use crate::video::Engine;
+
+/// A map made with Tiled: the tiles and palette shared by its layers
+#[derive(Debug, Clone, Copy)]
+pub struct TiledMap {
+    pub tiles: Tiles,
+    pub palette: Palette,
+    /// Width in 8x8 tiles
+    pub width: u16,
+    /// Height in 8x8 tiles
+    pub height: u16,
+    pub layers: &'static [MapLayer],
+}
+impl TiledMap {
+    /// The layer shown on the background `bg`
+    pub fn layer(&self, bg: Layer) -> Option<&'static MapLayer> {
+        self.layers.iter().find(|layer| layer.bg == bg)
+    }
+
+    /// The layer named `name` in Tiled
+    pub fn layer_named(&self, name: &str) -> Option<&'static MapLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+}
+
+/// A tile layer of a [`TiledMap`], laid out in screen blocks of 32x32 tiles
+#[derive(Debug, Clone, Copy)]
+pub struct MapLayer {
+    pub name: &'static str,
+    /// The background showing the layer
+    pub bg: Layer,
+    /// Size of the background, one of the text sizes
+    pub size: BgSize,
+    /// Entries of the screen blocks, one after the other
+    pub entries: &'static [TileMapEntry16],
+}
+impl MapLayer {
+    /// Number of screen blocks, and so of map bases, used by the layer
+    pub const fn blocks(&self) -> usize {
+        self.entries.len() / (32 * 32)
+    }
+
+    /// Copies the screen blocks to the map base `map_base` of the backgrounds
+    /// (2 KiB each) and the following ones
+    pub fn load_bg(&self, engine: Engine, map_base: usize) {
+        let gfx = match engine {
+            Engine::Main => BG_GFX,
+            Engine::Sub => BG_GFX_SUB,
+        };
+        assert!(map_base + self.blocks() <= 32, "the map doesn't fit");
+        unsafe { copy_volatile(self.entries, gfx.byte_add(map_base * MAP_BASE_SIZE).cast()) }
+    }
+}
+
+/// An object of an object layer, placed with Tiled
+#[derive(Debug, Clone, Copy)]
+pub struct Spawn<K: 'static> {
+    /// Unique ID of the object in the map
+    pub id: u32,
+    pub name: &'static str,
+    pub class: Option<K>,
+    /// Position in pixels. Tiled places objects with a tile at their bottom left corner
+    pub x: i32,
+    pub y: i32,
+    /// Size in pixels
+    pub width: u32,
+    pub height: u32,
+    pub properties: &'static [Property],
+}
+impl<K> Spawn<K> {
+    /// The value of the property `name`
+    pub fn property(&self, name: &str) -> Option<PropertyValue> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+            .map(|property| property.value)
+    }
+}
+
+/// A custom property of an object
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Property {
+    pub name: &'static str,
+    pub value: PropertyValue,
+}
+
+/// The value of a [`Property`]. Colors, files and other types are strings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    /// Also used for the references to objects, by their ID
+    Int(i32),
+    Float(f32),
+    String(&'static str),
+}
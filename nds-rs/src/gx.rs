@@ -0,0 +1,332 @@
+//! Display lists for the 3D geometry engine
+//!
+//! The geometry engine is fed through a FIFO ([`GXFIFO`]) that accepts "packed" commands:
+//! a word holding up to four opcodes (first command in the lowest byte),
+//! followed by the parameters of those commands, in order.
+//! [`DisplayList`] builds such a stream in memory, so it can be stored, replayed
+//! and sent to the hardware with a single DMA transfer in
+//! [`GEO_CMD_FIFO`](Flags::GEO_CMD_FIFO) mode.
+//!
+//! Encoding is done entirely in software and doesn't touch the hardware, only
+//! [`DisplayList::submit`], [`DisplayList::submit_cpu`], [`submit_words`] and their
+//! `start_` variants do.
+//!
+//! # Example
+//! ```rust,no_run
+//! let mut list = DisplayList::new();
+//! list.mtx_identity()
+//!     .begin(Primitive::Triangles)
+//!     .color(0x001F)
+//!     .vertex16(0, 1 << 12, 0)
+//!     .vertex16(-(1 << 12), -(1 << 12), 0)
+//!     .vertex16(1 << 12, -(1 << 12), 0)
+//!     .end();
+//! list.submit(&mut hw.dma.ch0);
+//! ```
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use nds_sys::dma::Flags;
+pub use nds_sys::gx::{Command, MatrixMode, Primitive, GXFIFO};
+
+use crate::{
+    cache::dc_flush_slice,
+    dma::{count_flags, DmaChannel, Transfer},
+};
+
+/// A stream of packed geometry commands.
+///
+/// Every method that adds a command returns `&mut Self`, so they can be chained.
+#[derive(Clone)]
+pub struct DisplayList {
+    words: Vec<u32>,
+    /// Index of the last command word in `words`
+    header: usize,
+    /// Amount of opcodes already stored in the last command word
+    slots: u8,
+}
+impl DisplayList {
+    /// Creates an empty display list
+    pub const fn new() -> Self {
+        Self {
+            words: Vec::new(),
+            header: 0,
+            slots: 4,
+        }
+    }
+
+    /// Appends `command` and its parameters to the list.
+    ///
+    /// Panics if `params` doesn't have exactly [`Command::param_count`] elements.
+    pub fn push(&mut self, command: Command, params: &[u32]) -> &mut Self {
+        assert_eq!(
+            params.len(),
+            command.param_count(),
+            "Wrong amount of parameters for {command:?}"
+        );
+        if self.slots == 4 {
+            self.header = self.words.len();
+            self.words.push(0);
+            self.slots = 0;
+        }
+        self.words[self.header] |= (command as u32) << (8 * self.slots);
+        self.slots += 1;
+        self.words.extend_from_slice(params);
+        self
+    }
+
+    /// Returns the packed stream, ready to be written to [`GXFIFO`].
+    /// Unused opcodes in the last command word are [`Command::Nop`].
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    /// Returns the packed stream in the format expected by libnds' `glCallList`:
+    /// the length (in words) followed by the stream itself.
+    pub fn to_call_list(&self) -> Vec<u32> {
+        let mut list = Vec::with_capacity(self.words.len() + 1);
+        list.push(self.words.len() as u32);
+        list.extend_from_slice(&self.words);
+        list
+    }
+
+    /// Returns `true` if no command has been added
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Removes all commands, keeping the allocated memory
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.header = 0;
+        self.slots = 4;
+    }
+
+    /// Sends the list to the geometry engine using DMA, and waits until it's done.
+    /// See [`submit_words`].
+    pub fn submit(&self, dma: &mut DmaChannel) {
+        submit_words(&self.words, dma)
+    }
+
+    /// Starts sending the list to the geometry engine using DMA.
+    /// See [`start_submit_words`].
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked, see [`DmaChannel::start_copy`]
+    pub unsafe fn start_submit<'t>(&'t self, dma: &'t mut DmaChannel) -> Transfer<'t> {
+        start_submit_words(&self.words, dma)
+    }
+
+    /// Sends the list to the geometry engine using the CPU.
+    /// Slower than [`submit`](Self::submit), but doesn't need a DMA channel.
+    pub fn submit_cpu(&self) {
+        for &word in &self.words {
+            unsafe {
+                GXFIFO.write_volatile(word);
+            }
+        }
+    }
+
+    pub fn mtx_mode(&mut self, mode: MatrixMode) -> &mut Self {
+        self.push(Command::MtxMode, &[mode as u32])
+    }
+    pub fn mtx_push(&mut self) -> &mut Self {
+        self.push(Command::MtxPush, &[])
+    }
+    /// Pops `amount` matrices from the stack. Only the lowest 6 bits are used.
+    pub fn mtx_pop(&mut self, amount: i8) -> &mut Self {
+        self.push(Command::MtxPop, &[amount as u32 & 0b111111])
+    }
+    pub fn mtx_store(&mut self, index: u8) -> &mut Self {
+        self.push(Command::MtxStore, &[index as u32 & 0b11111])
+    }
+    pub fn mtx_restore(&mut self, index: u8) -> &mut Self {
+        self.push(Command::MtxRestore, &[index as u32 & 0b11111])
+    }
+    pub fn mtx_identity(&mut self) -> &mut Self {
+        self.push(Command::MtxIdentity, &[])
+    }
+    /// Loads a 4x4 matrix of 20.12 fixed point values, in column-major order
+    pub fn mtx_load_4x4(&mut self, m: &[i32; 16]) -> &mut Self {
+        self.push(Command::MtxLoad4x4, &m.map(|v| v as u32))
+    }
+    /// Loads a 4x3 matrix of 20.12 fixed point values, in column-major order
+    pub fn mtx_load_4x3(&mut self, m: &[i32; 12]) -> &mut Self {
+        self.push(Command::MtxLoad4x3, &m.map(|v| v as u32))
+    }
+    pub fn mtx_mult_4x4(&mut self, m: &[i32; 16]) -> &mut Self {
+        self.push(Command::MtxMult4x4, &m.map(|v| v as u32))
+    }
+    pub fn mtx_mult_4x3(&mut self, m: &[i32; 12]) -> &mut Self {
+        self.push(Command::MtxMult4x3, &m.map(|v| v as u32))
+    }
+    pub fn mtx_mult_3x3(&mut self, m: &[i32; 9]) -> &mut Self {
+        self.push(Command::MtxMult3x3, &m.map(|v| v as u32))
+    }
+    /// Scales the current matrix. Values are 20.12 fixed point
+    pub fn mtx_scale(&mut self, x: i32, y: i32, z: i32) -> &mut Self {
+        self.push(Command::MtxScale, &[x as u32, y as u32, z as u32])
+    }
+    /// Translates the current matrix. Values are 20.12 fixed point
+    pub fn mtx_translate(&mut self, x: i32, y: i32, z: i32) -> &mut Self {
+        self.push(Command::MtxTrans, &[x as u32, y as u32, z as u32])
+    }
+
+    /// Sets the vertex color, in BGR555 format
+    pub fn color(&mut self, color: u16) -> &mut Self {
+        self.push(Command::Color, &[color as u32])
+    }
+    /// Sets the normal vector. Values are 1.0.9 fixed point (10 bits)
+    pub fn normal(&mut self, x: i16, y: i16, z: i16) -> &mut Self {
+        self.push(Command::Normal, &[pack_10(x, y, z)])
+    }
+    /// Sets the texture coordinates. Values are 12.4 fixed point
+    pub fn tex_coord(&mut self, s: i16, t: i16) -> &mut Self {
+        self.push(Command::TexCoord, &[pack_16(s, t)])
+    }
+    /// Adds a vertex. Values are 4.12 fixed point
+    pub fn vertex16(&mut self, x: i16, y: i16, z: i16) -> &mut Self {
+        self.push(Command::Vtx16, &[pack_16(x, y), z as u16 as u32])
+    }
+    /// Adds a vertex. Values are 4.6 fixed point (10 bits)
+    pub fn vertex10(&mut self, x: i16, y: i16, z: i16) -> &mut Self {
+        self.push(Command::Vtx10, &[pack_10(x, y, z)])
+    }
+    /// Adds a vertex, reusing the Z coordinate of the previous one
+    pub fn vertex_xy(&mut self, x: i16, y: i16) -> &mut Self {
+        self.push(Command::VtxXY, &[pack_16(x, y)])
+    }
+    /// Adds a vertex, reusing the Y coordinate of the previous one
+    pub fn vertex_xz(&mut self, x: i16, z: i16) -> &mut Self {
+        self.push(Command::VtxXZ, &[pack_16(x, z)])
+    }
+    /// Adds a vertex, reusing the X coordinate of the previous one
+    pub fn vertex_yz(&mut self, y: i16, z: i16) -> &mut Self {
+        self.push(Command::VtxYZ, &[pack_16(y, z)])
+    }
+    /// Adds a vertex relative to the previous one. Values are 0.10 fixed point (10 bits)
+    pub fn vertex_diff(&mut self, x: i16, y: i16, z: i16) -> &mut Self {
+        self.push(Command::VtxDiff, &[pack_10(x, y, z)])
+    }
+    pub fn polygon_attr(&mut self, attr: u32) -> &mut Self {
+        self.push(Command::PolygonAttr, &[attr])
+    }
+    pub fn tex_image_param(&mut self, param: u32) -> &mut Self {
+        self.push(Command::TexImageParam, &[param])
+    }
+    pub fn palette_base(&mut self, base: u32) -> &mut Self {
+        self.push(Command::PlttBase, &[base])
+    }
+    pub fn begin(&mut self, primitive: Primitive) -> &mut Self {
+        self.push(Command::BeginVtxs, &[primitive as u32])
+    }
+    pub fn end(&mut self) -> &mut Self {
+        self.push(Command::EndVtxs, &[])
+    }
+    pub fn swap_buffers(&mut self, flags: u32) -> &mut Self {
+        self.push(Command::SwapBuffers, &[flags])
+    }
+    /// Sets the viewport. `(x1, y1)` is the bottom left corner, `(x2, y2)` the top right one
+    pub fn viewport(&mut self, x1: u8, y1: u8, x2: u8, y2: u8) -> &mut Self {
+        let viewport = u32::from_le_bytes([x1, y1, x2, y2]);
+        self.push(Command::Viewport, &[viewport])
+    }
+}
+
+impl Default for DisplayList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Packs two 16 bit values, `low` in bits 0-15 and `high` in bits 16-31
+const fn pack_16(low: i16, high: i16) -> u32 {
+    (low as u16 as u32) | ((high as u16 as u32) << 16)
+}
+
+/// Packs three 10 bit values, `x` in bits 0-9, `y` in 10-19 and `z` in 20-29
+const fn pack_10(x: i16, y: i16, z: i16) -> u32 {
+    const MASK: u32 = 0b11_11111111;
+    (x as u32 & MASK) | ((y as u32 & MASK) << 10) | ((z as u32 & MASK) << 20)
+}
+
+/// Sends a packed command stream (such as one created by [`DisplayList`])
+/// to the geometry engine using DMA, and waits until it's done.
+///
+/// Flushes `words` from the cache before starting. The transfer runs in
+/// [`GEO_CMD_FIFO`](Flags::GEO_CMD_FIFO) mode, so the DMA only moves data while
+/// the FIFO is less than half full.
+pub fn submit_words(words: &[u32], dma: &mut DmaChannel) {
+    unsafe { start_submit_words(words, dma) }.wait()
+}
+
+/// Starts sending a packed command stream to the geometry engine using DMA.
+/// See [`submit_words`].
+///
+/// # Safety
+/// The returned [`Transfer`] must not be leaked, see [`DmaChannel::start_copy`]
+pub unsafe fn start_submit_words<'t>(words: &'t [u32], dma: &'t mut DmaChannel) -> Transfer<'t> {
+    let flags = Flags::ENABLE
+        | Flags::GEO_CMD_FIFO
+        | Flags::WORDS
+        | Flags::INC_SRC
+        | Flags::FIX_DST
+        | Flags::INT_REQ;
+    if words.is_empty() {
+        return dma.start_noop();
+    }
+    let flags = flags | count_flags(words.len());
+    dc_flush_slice(words);
+    dma.start(words.as_ptr() as _, GXFIFO as _, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_four_commands_per_header() {
+        let mut list = DisplayList::new();
+        list.mtx_mode(MatrixMode::Position)
+            .mtx_identity()
+            .color(0x001F)
+            .begin(Primitive::Triangles);
+        assert_eq!(list.words(), &[0x40_20_15_10, 1, 0x001F, 0]);
+    }
+
+    #[test]
+    fn pads_last_header_with_nop() {
+        let mut list = DisplayList::new();
+        list.mtx_push()
+            .mtx_translate(1 << 12, -1, 0)
+            .mtx_identity()
+            .begin(Primitive::Quads)
+            .vertex16(1, -1, 2)
+            .end();
+        #[rustfmt::skip]
+        let expected = [
+            0x40_15_1C_11, 1 << 12, 0xFFFF_FFFF, 0, 1,
+            0x00_00_41_23, 0xFFFF_0001, 2,
+        ];
+        assert_eq!(list.words(), &expected);
+    }
+
+    #[test]
+    fn call_list_starts_with_length() {
+        let mut list = DisplayList::new();
+        list.viewport(0, 0, 255, 191).end();
+        assert_eq!(list.to_call_list(), [2, 0x00_00_41_60, 0xBF_FF_00_00]);
+    }
+
+    #[test]
+    fn default_is_empty_list() {
+        let mut list = DisplayList::default();
+        assert!(list.is_empty());
+        list.mtx_identity();
+        assert_eq!(list.words(), &[0x15]);
+        list.clear();
+        list.end();
+        assert_eq!(list.words(), &[0x41]);
+    }
+}
@@ -2,22 +2,70 @@ use core::ffi::CStr;
 
 pub const HEADER_START: *const u8 = 0x027FFE00 as _;
 
+/// Location of a table or a binary in the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct RomSpan {
+    /// Offset in the ROM
+    pub offset: u32,
+    /// Size in bytes
+    pub size: u32,
+}
+
+/// Binary of one of the CPUs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct CpuBinary {
+    pub rom_offset: u32,
+    pub entry_address: u32,
+    pub ram_address: u32,
+    pub size: u32,
+}
+
+/// Start of the header of a `.nds` file, up to the overlay tables
+#[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct NdsHeader {
     game_title: [u8; 12],
     game_code: [u8; 4],
     maker_code: [u8; 2],
     unit_code: u8,
+    seed_select: u8,
+    capacity: u8,
+    _reserved: [u8; 8],
+    region: u8,
+    version: u8,
+    autostart: u8,
+    /// 0x20
+    pub arm9: CpuBinary,
+    /// 0x30
+    pub arm7: CpuBinary,
+    /// 0x40, file name table of the file system
+    pub fnt: RomSpan,
+    /// 0x48, file allocation table of the file system. See [`crate::overlay::FatEntry`]
+    pub fat: RomSpan,
+    /// 0x50, overlay table of the ARM9. See [`crate::overlay::OverlayEntry`]
+    pub arm9_overlays: RomSpan,
+    /// 0x58, overlay table of the ARM7
+    pub arm7_overlays: RomSpan,
     // TODO
 }
 impl NdsHeader {
     /// Returns the header of the currently running ROM.
     pub fn running() -> &'static Self {
-        unsafe { *HEADER_START.cast() }
+        unsafe { &*HEADER_START.cast() }
     }
 
     pub fn title(&self) -> Option<&str> {
         let title = CStr::from_bytes_until_nul(&self.game_title);
         title.ok().and_then(|t| t.to_str().ok())
     }
+
+    pub fn game_code(&self) -> [u8; 4] {
+        self.game_code
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
 }
@@ -1,17 +1,73 @@
+#[cfg(not(feature = "arm7"))]
 pub use nds_sys::input::TouchPosition;
 use nds_sys::input::*;
 
+#[cfg(not(feature = "arm7"))]
 pub fn scan_keys() {
     unsafe {
         scanKeys();
     }
 }
+#[cfg(not(feature = "arm7"))]
 pub fn keys_down() -> u32 {
     unsafe { keysDown() }
 }
+#[cfg(not(feature = "arm7"))]
 pub fn keys_held() -> u32 {
     unsafe { keysHeld() }
 }
+
+#[cfg(feature = "arm7")]
+pub use arm7::*;
+
+/// libnds only tracks the keys on the ARM9, the ARM7 reads them from the registers
+#[cfg(feature = "arm7")]
+mod arm7 {
+    use nds_sys::{
+        arm7::{KeyXyBits, REG_KEYXY},
+        input::{KeypadBits, REG_KEYINPUT},
+    };
+    use portable_atomic::{AtomicU32, Ordering};
+
+    /// Keys held at the last [`scan_keys`]
+    static HELD: AtomicU32 = AtomicU32::new(0);
+    /// Keys held at the last [`scan_keys`] that weren't held at the one before
+    static DOWN: AtomicU32 = AtomicU32::new(0);
+
+    /// Keys currently pressed, as [`KeypadBits`]
+    pub fn keys_current() -> u32 {
+        let input = unsafe { REG_KEYINPUT.read_volatile() };
+        let xy = KeyXyBits::from_bits_retain(unsafe { REG_KEYXY.read_volatile() });
+        let mut keys = !input as u32 & 0x3FF;
+        if !xy.contains(KeyXyBits::X) {
+            keys |= KeypadBits::X as u32;
+        }
+        if !xy.contains(KeyXyBits::Y) {
+            keys |= KeypadBits::Y as u32;
+        }
+        if !xy.contains(KeyXyBits::PEN_UP) {
+            keys |= KeypadBits::Touch as u32;
+        }
+        if xy.contains(KeyXyBits::LID_CLOSED) {
+            keys |= KeypadBits::Lid as u32;
+        }
+        keys
+    }
+
+    pub fn scan_keys() {
+        let held = keys_current();
+        let previous = HELD.swap(held, Ordering::Relaxed);
+        DOWN.store(held & !previous, Ordering::Relaxed);
+    }
+    pub fn keys_down() -> u32 {
+        DOWN.load(Ordering::Relaxed)
+    }
+    pub fn keys_held() -> u32 {
+        HELD.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(not(feature = "arm7"))]
 pub fn touch_read(data: &mut TouchPosition) {
     let data_ptr: *mut TouchPosition = &mut *data;
     unsafe {
@@ -27,7 +27,7 @@ pub fn swi_intr_wait(flags: Flags, wait_for_next: bool) {
 /// OR different flags to enable many interrupts at once.
 /// # Safety
 /// This function should not be called since changing the state of interrupts
-/// can (will!) break other code (for example: [`wait_for`](crate::dma::wait_for))
+/// can (will!) break other code (for example: awaiting a [`Transfer`](crate::dma::Transfer))
 pub unsafe fn irq_enable(irq: Flags) {
     irqEnable(irq.bits());
 }
@@ -36,7 +36,7 @@ pub unsafe fn irq_enable(irq: Flags) {
 /// OR different flags to disable many interrupts at once.
 /// # Safety
 /// This function should not be called since changing the state of interrupts
-/// can (will!) break other code (for example: [`wait_for`](crate::dma::wait_for))
+/// can (will!) break other code (for example: awaiting a [`Transfer`](crate::dma::Transfer))
 pub unsafe fn irq_disable(irq: Flags) {
     irqDisable(irq.bits());
 }
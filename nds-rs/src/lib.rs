@@ -1,33 +1,82 @@
 #![no_std]
 #![feature(alloc_error_handler)]
+#![feature(allocator_api)]
 #![feature(adt_const_params)]
+#![cfg_attr(not(feature = "arm7"), feature(fn_traits, tuple_trait, unboxed_closures))]
 #![allow(unused_parens, dead_code)]
 
 // Reexport internal crates
 pub use nds_sys as sys;
 #[macro_use]
 pub extern crate nds_proc_macros;
+pub use nds_proc_macros::{
+    dtcm, entry, include_font, include_image, include_sprite, include_tiled, itcm, overlay,
+};
 
 #[macro_use]
 pub mod debug;
+#[cfg(feature = "arm7")]
+pub mod arm7;
+#[cfg(not(feature = "arm7"))]
 pub mod background;
+pub mod backtrace;
+#[cfg(not(feature = "arm7"))]
 pub mod cache;
+#[cfg(not(feature = "arm7"))]
+pub mod camera;
+#[cfg(not(feature = "arm7"))]
+pub mod console;
 pub mod dma;
-#[cfg(feature = "embedded-graphics-core")]
+pub mod dsi;
+#[cfg(not(feature = "arm7"))]
+pub mod effects;
+#[cfg(all(feature = "embedded-graphics-core", not(feature = "arm7")))]
 pub mod embedded_graphics;
+#[cfg(not(feature = "arm7"))]
+pub mod exception;
+pub mod executor;
+pub mod fifo;
+#[cfg(not(feature = "arm7"))]
+pub mod font;
+#[cfg(not(feature = "arm7"))]
+pub mod gfx;
+#[cfg(not(feature = "arm7"))]
+pub mod gx;
 pub mod input;
 pub mod interrupts;
+#[cfg(all(feature = "log", not(feature = "arm7")))]
+pub mod logger;
 pub mod macros;
-mod memalloc;
+pub mod memalloc;
+#[cfg(not(feature = "arm7"))]
+pub mod mpu;
+#[cfg(not(feature = "arm7"))]
+pub mod ndma;
+#[cfg(not(feature = "arm7"))]
+pub mod overlay;
+#[cfg(not(feature = "arm7"))]
+pub mod panic_screen;
+#[cfg(not(feature = "arm7"))]
 mod peripherals;
+#[cfg(not(feature = "arm7"))]
 pub mod sprite;
+#[cfg(not(feature = "arm7"))]
 pub mod system;
+#[cfg(not(feature = "arm7"))]
+pub mod tcm;
+#[cfg(not(feature = "arm7"))]
 pub mod video;
+#[cfg(not(feature = "arm7"))]
+pub mod window;
+#[cfg(feature = "arm7")]
+pub use arm7::Hw;
+#[cfg(not(feature = "arm7"))]
 pub use peripherals::Hw;
 pub mod header;
 pub mod runtime;
 
 #[doc(hidden)]
+#[cfg(not(feature = "arm7"))]
 mod private {
     /// This trait is sealed and cannot be implemented outside of this crate.
     /// It is used to prevent users from implementing special traits for marker
@@ -37,4 +86,6 @@ mod private {
     impl<L2, L3, R> Sealed for crate::background::GraphicsMode<L2, L3, R> {}
     impl Sealed for crate::background::MainGraphicsModeSettings {}
     impl Sealed for crate::background::SubGraphicsModeSettings {}
+    impl Sealed for crate::background::RenderTargetBitmap {}
+    impl Sealed for crate::background::RenderTargetBitmap8 {}
 }
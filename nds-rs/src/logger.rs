@@ -0,0 +1,402 @@
+//! Backend for the [`log`] crate
+//!
+//! Records go through a [`Filter`] (a default level and per-module levels, both of
+//! which can be changed at runtime) and are then sent to every [`Sink`] of the
+//! [`Logger`] whose own level allows it.
+//!
+//! Sinks are provided for the NO$GBA debugger ([`NoCash`]), text consoles ([`Console`]
+//! and [`Tty`]), an in-memory ring buffer that is printed on panic ([`RingSink`]) and
+//! files ([`FileSink`]). Every entry is stamped with the amount of frames since
+//! [`init`] was called.
+//!
+//! The filtering and fan-out logic doesn't touch the hardware, only the sinks do.
+//!
+//! # Example
+//! ```rust,no_run
+//! logger::init(
+//!     Logger::new(LevelFilter::Info)
+//!         .with_sink(LevelFilter::Trace, NoCash)
+//!         .with_sink(LevelFilter::Warn, Tty)
+//!         .with_sink(LevelFilter::Debug, RingSink::new(4096)),
+//! )
+//! .unwrap();
+//! logger::set_module_level("my_game::physics", LevelFilter::Off);
+//! log::info!("Loaded {} levels", levels.len());
+//! ```
+
+extern crate alloc;
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt;
+
+pub use log::{Level, LevelFilter, SetLoggerError};
+use nds_sys::interrupts::irqSet;
+use portable_atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+
+use crate::interrupts::{irq_enable, Flags};
+pub use crate::{
+    console::Console,
+    debug::{NoCash, Tty},
+};
+
+mod sinks;
+pub use sinks::*;
+
+/// A record being logged, as seen by the sinks
+pub struct Entry<'a> {
+    pub level: Level,
+    /// Usually the path of the module that emitted the record
+    pub target: &'a str,
+    /// Value of [`frames`] when the record was emitted
+    pub frame: u32,
+    pub args: fmt::Arguments<'a>,
+}
+/// Formats the entry as `[frame LEVEL target] message`
+impl fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:>6} {:<5} {}] {}",
+            self.frame, self.level, self.target, self.args
+        )
+    }
+}
+
+/// A destination for log entries
+pub trait Sink: Send {
+    /// Writes `entry`, which already passed the filters
+    fn write(&mut self, entry: &Entry);
+
+    /// Makes sure everything written so far is stored
+    fn flush(&mut self) {}
+}
+
+/// Decides which records are logged, from their level and target
+#[derive(Debug, Clone)]
+pub struct Filter {
+    level: LevelFilter,
+    /// Module paths and their levels
+    modules: Vec<(String, LevelFilter)>,
+}
+impl Filter {
+    /// A filter allowing records up to `level` for every module
+    pub const fn new(level: LevelFilter) -> Self {
+        Self {
+            level,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Level used for the modules without their own level
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    pub fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+    }
+
+    /// Sets the level of `module` and its submodules, replacing any previous one
+    pub fn set_module_level(&mut self, module: &str, level: LevelFilter) {
+        match self.modules.iter_mut().find(|(m, _)| m == module) {
+            Some((_, old)) => *old = level,
+            None => self.modules.push((module.into(), level)),
+        }
+    }
+
+    /// Removes the level of `module`, so it uses the default one again
+    pub fn clear_module_level(&mut self, module: &str) {
+        self.modules.retain(|(m, _)| m != module);
+    }
+
+    /// Level for `target`: the one of the most specific module containing it,
+    /// or the default level
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| is_within(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |&(_, level)| level)
+    }
+
+    /// Returns `true` if a record of `level` from `target` should be logged
+    pub fn enabled(&self, level: Level, target: &str) -> bool {
+        level <= self.level_for(target)
+    }
+
+    /// Most verbose level allowed by any module
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.level, Ord::max)
+    }
+}
+
+/// Returns `true` if `target` is `module` or one of its submodules
+fn is_within(target: &str, module: &str) -> bool {
+    match target.strip_prefix(module) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// A [`Filter`] and the sinks receiving the records that pass it
+pub struct Logger {
+    filter: Filter,
+    sinks: Vec<(LevelFilter, Box<dyn Sink>)>,
+}
+impl Logger {
+    /// A logger without sinks, allowing records up to `level`
+    pub fn new(level: LevelFilter) -> Self {
+        Self {
+            filter: Filter::new(level),
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Adds a sink receiving the records up to `level` that pass the filter
+    pub fn with_sink(mut self, level: LevelFilter, sink: impl Sink + 'static) -> Self {
+        self.add_sink(level, sink);
+        self
+    }
+
+    pub fn add_sink(&mut self, level: LevelFilter, sink: impl Sink + 'static) {
+        self.sinks.push((level, Box::new(sink)));
+    }
+
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    pub fn filter_mut(&mut self) -> &mut Filter {
+        &mut self.filter
+    }
+
+    /// Most verbose level any sink can receive
+    pub fn max_level(&self) -> LevelFilter {
+        let sinks = self
+            .sinks
+            .iter()
+            .map(|&(level, _)| level)
+            .fold(LevelFilter::Off, Ord::max);
+        self.filter.max_level().min(sinks)
+    }
+
+    /// Sends `entry` to every sink that accepts it.
+    /// Returns the amount of sinks that received it.
+    pub fn log(&mut self, entry: &Entry) -> usize {
+        if !self.filter.enabled(entry.level, entry.target) {
+            return 0;
+        }
+        let mut count = 0;
+        for (level, sink) in &mut self.sinks {
+            if entry.level <= *level {
+                sink.write(entry);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    pub fn flush(&mut self) {
+        for (_, sink) in &mut self.sinks {
+            sink.flush();
+        }
+    }
+}
+
+/// The logger used by the `log` macros
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+/// Frames since [`init`]
+static FRAMES: AtomicU32 = AtomicU32::new(0);
+
+struct Facade;
+impl log::Log for Facade {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        match LOGGER.try_lock() {
+            Some(logger) => logger
+                .as_ref()
+                .is_some_and(|l| l.filter.enabled(metadata.level(), metadata.target())),
+            None => false,
+        }
+    }
+
+    fn log(&self, record: &log::Record) {
+        // Records emitted while logging (e.g. from a sink) are dropped
+        let Some(mut logger) = LOGGER.try_lock() else {
+            return;
+        };
+        if let Some(logger) = logger.as_mut() {
+            logger.log(&Entry {
+                level: record.level(),
+                target: record.target(),
+                frame: frames(),
+                args: *record.args(),
+            });
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = LOGGER.lock().as_mut() {
+            logger.flush();
+        }
+    }
+}
+
+/// Installs `logger` as the backend of the `log` crate.
+///
+/// Also installs a VBlank interrupt handler counting frames for the timestamps.
+/// If another VBlank handler is installed later, it should call [`tick`].
+///
+/// Fails if a backend was already installed, in which case `logger` is dropped.
+pub fn init(logger: Logger) -> Result<(), SetLoggerError> {
+    critical_section::with(|_| {
+        // SAFETY: no other thread can call `set_logger_racy` nor log while interrupts
+        // are disabled
+        unsafe { log::set_logger_racy(&Facade)? };
+        let max_level = logger.max_level();
+        *LOGGER.lock() = Some(logger);
+        unsafe {
+            log::set_max_level_racy(max_level);
+        }
+        Ok(())
+    })?;
+    unsafe {
+        irqSet(Flags::VBLANK.bits(), Some(on_vblank));
+        irq_enable(Flags::VBLANK);
+    }
+    Ok(())
+}
+
+/// Gives access to the installed logger, e.g. to add sinks.
+/// Returns `None` if [`init`] wasn't called.
+pub fn with_logger<R>(f: impl FnOnce(&mut Logger) -> R) -> Option<R> {
+    let mut lock = LOGGER.lock();
+    let result = lock.as_mut().map(f);
+    if let Some(logger) = lock.as_ref() {
+        let max_level = logger.max_level();
+        // SAFETY: single core, and the logger lock is held
+        unsafe {
+            log::set_max_level_racy(max_level);
+        }
+    }
+    result
+}
+
+/// Changes the default level of the installed logger
+pub fn set_level(level: LevelFilter) {
+    with_logger(|logger| logger.filter_mut().set_level(level));
+}
+
+/// Changes the level of `module` and its submodules in the installed logger
+pub fn set_module_level(module: &str, level: LevelFilter) {
+    with_logger(|logger| logger.filter_mut().set_module_level(module, level));
+}
+
+/// Frames since [`init`] was called
+pub fn frames() -> u32 {
+    FRAMES.load(Ordering::Relaxed)
+}
+
+/// Counts a frame. Only needed when the VBlank handler installed by [`init`] was replaced:
+/// call it from the new handler.
+pub fn tick() {
+    FRAMES.fetch_add(1, Ordering::Relaxed);
+}
+
+unsafe extern "C" fn on_vblank() {
+    tick();
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::ToString, sync::Arc};
+
+    use super::*;
+
+    /// Keeps the messages it receives, shared with the test
+    struct Collect(Arc<Mutex<Vec<String>>>);
+    impl Sink for Collect {
+        fn write(&mut self, entry: &Entry) {
+            self.0
+                .lock()
+                .push(format!("{} {}", entry.level, entry.args));
+        }
+    }
+
+    fn collect(logger: &mut Logger, level: LevelFilter) -> Arc<Mutex<Vec<String>>> {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        logger.add_sink(level, Collect(messages.clone()));
+        messages
+    }
+
+    fn log(logger: &mut Logger, level: Level, target: &str, message: &str) -> usize {
+        logger.log(&Entry {
+            level,
+            target,
+            frame: 0,
+            args: format_args!("{message}"),
+        })
+    }
+
+    #[test]
+    fn sinks_receive_their_levels() {
+        let mut logger = Logger::new(LevelFilter::Trace);
+        let all = collect(&mut logger, LevelFilter::Trace);
+        let warnings = collect(&mut logger, LevelFilter::Warn);
+        let none = collect(&mut logger, LevelFilter::Off);
+
+        assert_eq!(log(&mut logger, Level::Error, "game", "a"), 2);
+        assert_eq!(log(&mut logger, Level::Info, "game", "b"), 1);
+        assert_eq!(log(&mut logger, Level::Trace, "game", "c"), 1);
+
+        assert_eq!(*all.lock(), ["ERROR a", "INFO b", "TRACE c"]);
+        assert_eq!(*warnings.lock(), ["ERROR a"]);
+        assert!(none.lock().is_empty());
+        assert_eq!(logger.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn filter_applies_before_sinks() {
+        let mut logger = Logger::new(LevelFilter::Info);
+        let all = collect(&mut logger, LevelFilter::Trace);
+        logger
+            .filter_mut()
+            .set_module_level("game::physics", LevelFilter::Off);
+        logger
+            .filter_mut()
+            .set_module_level("game::physics::debug", LevelFilter::Debug);
+
+        log(&mut logger, Level::Debug, "game", "a");
+        log(&mut logger, Level::Error, "game::physics", "b");
+        log(&mut logger, Level::Error, "game::physics::solver", "c");
+        log(&mut logger, Level::Debug, "game::physics::debug", "d");
+        log(&mut logger, Level::Info, "game::physicsx", "e");
+        assert_eq!(*all.lock(), ["DEBUG d", "INFO e"]);
+        assert_eq!(logger.max_level(), LevelFilter::Debug);
+
+        logger.filter_mut().clear_module_level("game::physics");
+        log(&mut logger, Level::Error, "game::physics::solver", "f");
+        assert_eq!(all.lock().last().unwrap(), "ERROR f");
+    }
+
+    #[test]
+    fn max_level_is_limited_by_sinks() {
+        let mut logger = Logger::new(LevelFilter::Trace);
+        assert_eq!(logger.max_level(), LevelFilter::Off);
+        collect(&mut logger, LevelFilter::Warn);
+        assert_eq!(logger.max_level(), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn entry_format() {
+        let entry = Entry {
+            level: Level::Warn,
+            target: "game",
+            frame: 42,
+            args: format_args!("hi"),
+        };
+        assert_eq!(entry.to_string(), "[    42 WARN  game] hi");
+    }
+}
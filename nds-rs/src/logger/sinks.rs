@@ -0,0 +1,271 @@
+extern crate alloc;
+use alloc::{format, vec, vec::Vec};
+use core::{
+    ffi::CStr,
+    fmt::{self, Write},
+};
+
+use log::Level;
+use spin::Mutex;
+
+use super::{Entry, Sink};
+use crate::{
+    console::Console,
+    debug::{NoCash, Tty},
+};
+
+impl Sink for NoCash {
+    fn write(&mut self, entry: &Entry) {
+        let _ = writeln!(self, "{entry}");
+    }
+}
+
+impl Sink for Tty {
+    fn write(&mut self, entry: &Entry) {
+        let _ = writeln!(self, "{entry}");
+    }
+}
+
+/// Entries are colored according to their level
+impl Sink for Console {
+    fn write(&mut self, entry: &Entry) {
+        let color = match entry.level {
+            Level::Error => "\x1b[91m",
+            Level::Warn => "\x1b[93m",
+            Level::Info => "\x1b[39m",
+            Level::Debug => "\x1b[36m",
+            Level::Trace => "\x1b[90m",
+        };
+        let _ = writeln!(self, "{color}{entry}\x1b[0m");
+    }
+}
+
+/// A fixed size buffer keeping the most recent text written to it
+#[derive(Debug, Clone)]
+pub struct RingBuffer {
+    data: Vec<u8>,
+    /// Index of the oldest byte
+    start: usize,
+    len: usize,
+    overflowed: bool,
+}
+impl RingBuffer {
+    /// Creates a buffer keeping the last `capacity` bytes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: vec![0; capacity],
+            start: 0,
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` once older text has been overwritten
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        self.overflowed = false;
+    }
+
+    /// Appends `bytes`, overwriting the oldest ones if needed
+    pub fn push(&mut self, bytes: &[u8]) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+        // Only the end of `bytes` can be kept
+        let skipped = bytes.len().saturating_sub(capacity);
+        self.overflowed |= skipped > 0;
+        let bytes = &bytes[skipped..];
+        for &byte in bytes {
+            let end = (self.start + self.len) % capacity;
+            self.data[end] = byte;
+            if self.len == capacity {
+                self.start = (self.start + 1) % capacity;
+                self.overflowed = true;
+            } else {
+                self.len += 1;
+            }
+        }
+    }
+
+    /// The contents, oldest first, split in two where the buffer wraps around
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let end = self.start + self.len;
+        if end <= self.capacity() {
+            (&self.data[self.start..end], &[])
+        } else {
+            let (head, tail) = self.data.split_at(self.start);
+            (tail, &head[..end - self.capacity()])
+        }
+    }
+}
+impl fmt::Write for RingBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+/// Writes the contents. If older text was overwritten, the first (partial) line is skipped
+impl fmt::Display for RingBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (first, second) = self.as_slices();
+        let mut skip_line = self.overflowed;
+        for part in [first, second] {
+            let mut part = part;
+            if skip_line {
+                match part.iter().position(|&b| b == b'\n') {
+                    Some(newline) => {
+                        part = &part[newline + 1..];
+                        skip_line = false;
+                    }
+                    None => continue,
+                }
+            }
+            for chunk in part.utf8_chunks() {
+                f.write_str(chunk.valid())?;
+                if !chunk.invalid().is_empty() {
+                    f.write_char(char::REPLACEMENT_CHARACTER)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Buffer shared by every [`RingSink`]
+static RING: Mutex<Option<RingBuffer>> = Mutex::new(None);
+
+/// Keeps the most recent entries in memory, so they can be printed when panicking
+/// (See [`ring_contents`]).
+///
+/// Every `RingSink` shares the same buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct RingSink;
+impl RingSink {
+    /// Creates the shared buffer with room for `capacity` bytes,
+    /// replacing any previous one
+    pub fn new(capacity: usize) -> Self {
+        *RING.lock() = Some(RingBuffer::new(capacity));
+        Self
+    }
+}
+impl Sink for RingSink {
+    fn write(&mut self, entry: &Entry) {
+        if let Some(ring) = RING.lock().as_mut() {
+            let _ = writeln!(ring, "{entry}");
+        }
+    }
+}
+
+/// Calls `f` with the buffer of the [`RingSink`]s, if there's one.
+///
+/// Doesn't wait if the buffer is in use (e.g. when panicking while logging), returns `None` instead.
+pub fn ring_contents<R>(f: impl FnOnce(&RingBuffer) -> R) -> Option<R> {
+    RING.try_lock()?.as_ref().map(f)
+}
+
+/// Appends the entries to a file, e.g. on the SD card.
+///
+/// The filesystem must be initialized first (`fatInitDefault` with libnds).
+/// Entries are buffered by the C library: they are written when the buffer is full
+/// or the logger is flushed (See [`log::logger`]).
+pub struct FileSink {
+    file: *mut libc::FILE,
+}
+// SAFETY: the C library doesn't tie files to threads
+unsafe impl Send for FileSink {}
+impl FileSink {
+    /// Opens `path` for appending, creating it if needed. Returns `None` if it can't be opened
+    pub fn append(path: &CStr) -> Option<Self> {
+        let file = unsafe { libc::fopen(path.as_ptr(), c"a".as_ptr()) };
+        if file.is_null() {
+            None
+        } else {
+            Some(Self { file })
+        }
+    }
+}
+impl Sink for FileSink {
+    fn write(&mut self, entry: &Entry) {
+        let line = format!("{entry}\n");
+        unsafe {
+            libc::fwrite(line.as_ptr() as _, 1, line.len(), self.file);
+        }
+    }
+
+    fn flush(&mut self) {
+        unsafe {
+            libc::fflush(self.file);
+        }
+    }
+}
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        unsafe {
+            libc::fclose(self.file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn ring_buffer_wraps_around() {
+        let mut ring = RingBuffer::new(8);
+        ring.push(b"abcde");
+        assert_eq!(ring.as_slices(), (&b"abcde"[..], &b""[..]));
+        assert!(!ring.overflowed());
+
+        ring.push(b"fghij");
+        assert_eq!(ring.len(), 8);
+        assert!(ring.overflowed());
+        assert_eq!(ring.as_slices(), (&b"cdefgh"[..], &b"ij"[..]));
+
+        ring.push(b"0123456789");
+        assert_eq!(ring.as_slices(), (&b"234567"[..], &b"89"[..]));
+
+        ring.clear();
+        assert!(ring.is_empty() && !ring.overflowed());
+        assert_eq!(ring.as_slices(), (&b""[..], &b""[..]));
+    }
+
+    #[test]
+    fn ring_buffer_skips_partial_line() {
+        let mut ring = RingBuffer::new(12);
+        ring.push(b"first\nsecond\n");
+        assert_eq!(ring.to_string(), "second\n");
+
+        let mut ring = RingBuffer::new(12);
+        ring.push(b"one\ntwo\n");
+        assert_eq!(ring.to_string(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn empty_ring_buffer_keeps_nothing() {
+        let mut ring = RingBuffer::new(0);
+        ring.push(b"abc");
+        assert!(ring.is_empty());
+        assert_eq!(ring.to_string(), "");
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg_attr(feature = "arm7", allow(unused_macros))]
 macro_rules! bit {
     ($shift: literal) => {
         (1 << $shift)
@@ -60,7 +61,7 @@ macro_rules! include_bytes_aligned {
 /// In the above example, if the file is 10 bytes long, the array will be 2 elements
 /// long, leaving the last 2 bytes unreacheable.
 ///
-/// This macro is useful for including data that can be directly used by the hardware, such as palettes or bitmaps, or copied using [DMA](crate::dma::copy()).
+/// This macro is useful for including data that can be directly used by the hardware, such as palettes or bitmaps, or copied using [DMA](crate::dma::DmaChannel::copy()).
 #[macro_export]
 macro_rules! include_bytes_as {
     ($type:ty, $file:expr) => {{
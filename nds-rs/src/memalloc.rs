@@ -1,47 +1,249 @@
-extern crate alloc;
+//! Memory allocation
+//!
+//! By default, the global allocator forwards to the `malloc` of the C library.
+//! With the `tlsf` feature, it's a [TLSF](Tlsf) heap written in Rust instead, getting
+//! its memory with `sbrk`, which allocates in constant time.
+//!
+//! Other allocators can be used for some allocations:
+//! - [`Arena`], a bump allocator for short-lived allocations (e.g. during a frame),
+//! - [`Heap`], a TLSF heap on other [regions] of memory (DTCM, shared WRAM, VRAM...).
+//!
+//! [`stats`] shows how the global heap is used.
 
-use core::alloc::{GlobalAlloc, Layout};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    ffi::c_void,
+    fmt,
+};
 
-type CPtr = *mut core::ffi::c_void;
+use portable_atomic::{AtomicBool, AtomicUsize, Ordering};
 
+mod arena;
+mod heap;
+mod tlsf;
+pub use arena::*;
+pub use heap::*;
+pub use tlsf::*;
+
+/// Usage of a heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeapStats {
+    /// Bytes allocated
+    pub used: usize,
+    /// Bytes that can be allocated
+    pub free: usize,
+    /// Biggest allocation that could succeed
+    pub largest_free: usize,
+    /// Highest amount of bytes that were allocated at once
+    pub high_watermark: usize,
+    /// Amount of allocations that weren't freed
+    pub allocations: usize,
+}
+impl HeapStats {
+    /// Percentage of the free memory that isn't in the biggest free block
+    pub fn fragmentation(&self) -> u8 {
+        match self.free {
+            0 => 0,
+            free => (100 - self.largest_free.min(free) * 100 / free) as u8,
+        }
+    }
+}
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "used {} (peak {}) free {} (largest {}, {}% fragmented)",
+            self.used,
+            self.high_watermark,
+            self.free,
+            self.largest_free,
+            self.fragmentation()
+        )
+    }
+}
+
+/// Usage of the global heap.
+///
+/// Without the `tlsf` feature, `free` and `largest_free` come from `mallinfo`: they don't
+/// count the memory `malloc` hasn't claimed yet, and `largest_free` is the size of the
+/// free memory at the end of the heap.
+pub fn stats() -> HeapStats {
+    ALLOC.stats()
+}
+
+#[cfg(not(feature = "tlsf"))]
 #[global_allocator]
-static ALLOC: MallocAlloc = MallocAlloc;
+static ALLOC: MallocAlloc = MallocAlloc::new();
 
-struct MallocAlloc;
-unsafe impl GlobalAlloc for MallocAlloc {
-    unsafe fn alloc(&self, layout: core::alloc::Layout) -> *mut u8 {
-        let ptr = libc::malloc(layout.size());
-        if ptr.is_null() {
-            handle_alloc_error(layout);
+#[cfg(feature = "tlsf")]
+#[global_allocator]
+static ALLOC: Heap = Heap::growing(grow_with_sbrk);
+
+/// Memory requested at once by the global TLSF heap
+#[cfg(feature = "tlsf")]
+const GROW_STEP: usize = 64 * 1024;
+
+#[cfg(feature = "tlsf")]
+fn grow_with_sbrk(min: usize) -> Option<(*mut u8, usize)> {
+    let len = min.max(GROW_STEP).next_multiple_of(8);
+    let ptr = unsafe { sbrk(len as isize) };
+    if ptr as isize == -1 {
+        None
+    } else {
+        Some((ptr as *mut u8, len))
+    }
+}
+
+// Newlib functions not in the `libc` crate
+extern "C" {
+    fn sbrk(increment: isize) -> *mut c_void;
+    fn mallinfo() -> Mallinfo;
+}
+
+#[repr(C)]
+struct Mallinfo {
+    arena: usize,
+    ordblks: usize,
+    smblks: usize,
+    hblks: usize,
+    hblkhd: usize,
+    usmblks: usize,
+    fsmblks: usize,
+    uordblks: usize,
+    fordblks: usize,
+    keepcost: usize,
+}
+
+/// Alignment of the memory returned by `malloc`
+const MALLOC_ALIGN: usize = 8;
+
+/// Forwards to `malloc`, or `memalign` for alignments above 8 bytes
+struct MallocAlloc {
+    used: AtomicUsize,
+    high_watermark: AtomicUsize,
+    allocations: AtomicUsize,
+}
+impl MallocAlloc {
+    const fn new() -> Self {
+        Self {
+            used: AtomicUsize::new(0),
+            high_watermark: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
+        }
+    }
+
+    fn count(&self, ptr: *mut u8, size: usize) -> *mut u8 {
+        if !ptr.is_null() {
+            let used = self.used.fetch_add(size, Ordering::Relaxed) + size;
+            self.high_watermark.fetch_max(used, Ordering::Relaxed);
+            self.allocations.fetch_add(1, Ordering::Relaxed);
         }
-        ptr as *mut u8
+        ptr
+    }
+
+    fn stats(&self) -> HeapStats {
+        let info = unsafe { mallinfo() };
+        HeapStats {
+            used: self.used.load(Ordering::Relaxed),
+            free: info.fordblks,
+            largest_free: info.keepcost,
+            high_watermark: self.high_watermark.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+        }
+    }
+}
+unsafe impl GlobalAlloc for MallocAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = if layout.align() <= MALLOC_ALIGN {
+            libc::malloc(layout.size())
+        } else {
+            libc::memalign(layout.align(), layout.size())
+        };
+        self.count(ptr as *mut u8, layout.size())
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _: core::alloc::Layout) {
-        libc::free(ptr as CPtr);
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        libc::free(ptr as *mut c_void);
+        self.used.fetch_sub(layout.size(), Ordering::Relaxed);
+        self.allocations.fetch_sub(1, Ordering::Relaxed);
     }
 
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
-        let ptr = libc::calloc(1, layout.size());
-        if ptr.is_null() {
-            handle_alloc_error(layout);
+        if layout.align() <= MALLOC_ALIGN {
+            let ptr = libc::calloc(1, layout.size());
+            self.count(ptr as *mut u8, layout.size())
+        } else {
+            let ptr = self.alloc(layout);
+            if !ptr.is_null() {
+                ptr.write_bytes(0, layout.size());
+            }
+            ptr
         }
-        ptr as *mut u8
     }
 
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
-        let ptr = libc::realloc(ptr as CPtr, new_size);
-        if ptr.is_null() {
-            handle_alloc_error(layout);
+        if layout.align() > MALLOC_ALIGN {
+            // `realloc` doesn't keep the alignment
+            let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+            let new_ptr = self.alloc(new_layout);
+            if !new_ptr.is_null() {
+                new_ptr.copy_from_nonoverlapping(ptr, layout.size().min(new_size));
+                self.dealloc(ptr, layout);
+            }
+            return new_ptr;
         }
-        ptr as *mut u8
+        let new_ptr = libc::realloc(ptr as *mut c_void, new_size) as *mut u8;
+        if !new_ptr.is_null() {
+            self.used.fetch_sub(layout.size(), Ordering::Relaxed);
+            self.allocations.fetch_sub(1, Ordering::Relaxed);
+            self.count(new_ptr, new_size);
+        }
+        new_ptr
     }
 }
 
+/// Set while the panic screen is being shown for a failed allocation
+static OUT_OF_MEMORY: AtomicBool = AtomicBool::new(false);
+
 #[alloc_error_handler]
-pub fn handle_alloc_error(_: Layout) -> ! {
-    println!("Out of memory!\0");
-    loop {
-        crate::interrupts::swi_wait_for_v_blank();
+pub fn handle_alloc_error(layout: Layout) -> ! {
+    // The panic screen allocates too. If that fails, there's nothing else to do
+    if OUT_OF_MEMORY.swap(true, Ordering::Relaxed) {
+        loop {
+            crate::interrupts::swi_wait_for_v_blank();
+        }
+    }
+    panic!(
+        "memory allocation of {} bytes (align {}) failed\n{}",
+        layout.size(),
+        layout.align(),
+        stats()
+    );
+}
+
+/// Xorshift generator for the randomized tests of the allocators
+#[cfg(test)]
+struct XorShift(u32);
+#[cfg(test)]
+impl XorShift {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// A number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        self.next() as usize % n
+    }
+
+    /// A layout of a few bytes, or of up to 4 KiB once in a while
+    fn layout(&mut self) -> Layout {
+        let size = match self.below(8) {
+            0 => 1 + self.below(4096),
+            _ => 1 + self.below(256),
+        };
+        Layout::from_size_align(size, 1 << self.below(7)).unwrap()
     }
 }
@@ -0,0 +1,176 @@
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::Cell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr::NonNull,
+};
+
+/// A bump allocator over a fixed buffer, for short-lived allocations such as the ones
+/// made during a frame.
+///
+/// Allocating just moves an offset forward, and nothing is freed until the arena is
+/// [reset](Arena::reset) or a [scope](Arena::scope) ends. Values are never dropped.
+///
+/// It can also be used with the collections of `alloc`, e.g. `Vec::new_in(&arena)`.
+pub struct Arena<'b> {
+    start: NonNull<u8>,
+    len: usize,
+    offset: Cell<usize>,
+    high_watermark: Cell<usize>,
+    _buffer: PhantomData<&'b mut [u8]>,
+}
+impl<'b> Arena<'b> {
+    pub fn new(buffer: &'b mut [MaybeUninit<u8>]) -> Self {
+        Self {
+            len: buffer.len(),
+            start: NonNull::from(buffer).cast(),
+            offset: Cell::new(0),
+            high_watermark: Cell::new(0),
+            _buffer: PhantomData,
+        }
+    }
+
+    /// Allocates memory for `layout`, or returns `None` if the arena is full
+    pub fn alloc_layout(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let base = self.start.as_ptr() as usize;
+        let start = (base + self.offset.get()).checked_next_multiple_of(layout.align())? - base;
+        let end = start.checked_add(layout.size())?;
+        if end > self.len {
+            return None;
+        }
+        self.offset.set(end);
+        self.high_watermark.set(self.high_watermark.get().max(end));
+        // SAFETY: `start` is within the buffer
+        Some(unsafe { self.start.add(start) })
+    }
+
+    /// Moves `value` to the arena. Returns it back if the arena is full
+    #[allow(clippy::mut_from_ref)] // Every allocation is a different part of the buffer
+    pub fn alloc<T>(&self, value: T) -> Result<&mut T, T> {
+        match self.alloc_layout(Layout::new::<T>()) {
+            Some(ptr) => {
+                let ptr = ptr.cast::<T>().as_ptr();
+                // SAFETY: the memory is unused, aligned and big enough
+                unsafe {
+                    ptr.write(value);
+                    Ok(&mut *ptr)
+                }
+            }
+            None => Err(value),
+        }
+    }
+
+    /// Copies `values` to the arena, or returns `None` if the arena is full
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, values: &[T]) -> Option<&mut [T]> {
+        let ptr = self.alloc_layout(Layout::for_value(values))?.cast::<T>();
+        // SAFETY: the memory is unused, aligned and big enough
+        unsafe {
+            let slice = core::slice::from_raw_parts_mut(ptr.as_ptr(), values.len());
+            slice.copy_from_slice(values);
+            Some(slice)
+        }
+    }
+
+    /// Copies `s` to the arena, or returns `None` if the arena is full
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_str(&self, s: &str) -> Option<&mut str> {
+        let bytes = self.alloc_slice_copy(s.as_bytes())?;
+        // SAFETY: the bytes were copied from a `str`
+        Some(unsafe { core::str::from_utf8_unchecked_mut(bytes) })
+    }
+
+    /// Frees everything allocated in the arena
+    pub fn reset(&mut self) {
+        self.offset.set(0);
+    }
+
+    /// Runs `f`, freeing everything it allocated in the arena when it returns
+    pub fn scope<R>(&mut self, f: impl FnOnce(&Arena<'b>) -> R) -> R {
+        let offset = self.offset.get();
+        let result = f(self);
+        self.offset.set(offset);
+        result
+    }
+
+    /// Bytes allocated, including the padding needed for alignment
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.len
+    }
+
+    /// Highest amount of bytes that were used at once
+    pub fn high_watermark(&self) -> usize {
+        self.high_watermark.get()
+    }
+}
+/// Freeing does nothing, the memory is only reclaimed when the arena is reset
+unsafe impl Allocator for Arena<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.alloc_layout(layout).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::memalloc::XorShift;
+
+    #[test]
+    fn random_allocations_stay_in_the_buffer() {
+        let mut buffer = [MaybeUninit::uninit(); 4096];
+        let start = buffer.as_ptr() as usize;
+        let mut arena = Arena::new(&mut buffer);
+        let mut rng = XorShift(0x9E37_79B9);
+
+        for round in 0..100 {
+            let used = arena.used();
+            arena.scope(|arena| {
+                let mut end = used;
+                loop {
+                    let layout = rng.layout();
+                    let Some(ptr) = arena.alloc_layout(layout) else {
+                        let aligned = (start + end).next_multiple_of(layout.align()) - start;
+                        assert!(aligned + layout.size() > arena.capacity());
+                        break;
+                    };
+                    let offset = ptr.as_ptr() as usize - start;
+                    assert_eq!(ptr.as_ptr() as usize % layout.align(), 0);
+                    assert!(offset >= end, "overlapping allocations");
+                    end = offset + layout.size();
+                    assert_eq!(arena.used(), end);
+                    assert!(arena.used() <= arena.capacity());
+                    assert!(arena.high_watermark() >= arena.used());
+                    unsafe { ptr.as_ptr().write_bytes(round as u8, layout.size()) };
+                }
+            });
+            assert_eq!(arena.used(), used);
+            if round % 10 == 0 {
+                arena.reset();
+            } else {
+                // Kept across scopes until the next reset
+                arena.alloc(round).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn collections_grow_in_the_arena() {
+        let mut buffer = [MaybeUninit::uninit(); 1024];
+        let arena = Arena::new(&mut buffer);
+        let mut values = Vec::new_in(&arena);
+        values.extend(0..100u16);
+        assert_eq!(values.iter().copied().sum::<u16>(), 4950);
+        assert!(arena.used() >= 200);
+        assert!(arena.used() <= arena.high_watermark());
+    }
+}
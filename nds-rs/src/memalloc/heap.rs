@@ -0,0 +1,182 @@
+use core::{
+    alloc::{AllocError, Allocator, GlobalAlloc, Layout},
+    cell::RefCell,
+    ptr::{self, NonNull},
+};
+
+use critical_section::Mutex;
+#[cfg(not(feature = "arm7"))]
+use nds_sys::video::VRAM_ENABLE;
+
+use super::{HeapStats, Tlsf};
+
+/// Gets more memory for a [`Heap`], at least the given amount of bytes.
+/// Returns the start and length of the new memory
+pub type Grow = fn(usize) -> Option<(*mut u8, usize)>;
+
+/// A [TLSF](super::Tlsf) heap that can be shared, e.g. in a `static`.
+///
+/// It can be the global allocator, or be used with the collections of `alloc`
+/// (`Box::new_in(value, &HEAP)`) to allocate from other regions of memory,
+/// such as the ones in [`regions`](super::regions).
+/// Interrupts are disabled while it's in use, so it can be used from interrupt handlers.
+pub struct Heap {
+    tlsf: Mutex<RefCell<Tlsf>>,
+    grow: Option<Grow>,
+}
+impl Heap {
+    /// Creates a heap without memory, see [`add_region`](Heap::add_region)
+    pub const fn new() -> Self {
+        Self {
+            tlsf: Mutex::new(RefCell::new(Tlsf::new())),
+            grow: None,
+        }
+    }
+
+    /// Creates a heap calling `grow` when it runs out of memory
+    pub const fn growing(grow: Grow) -> Self {
+        Self {
+            tlsf: Mutex::new(RefCell::new(Tlsf::new())),
+            grow: Some(grow),
+        }
+    }
+
+    /// Gives `len` bytes starting at `start` to the heap.
+    /// Returns `false` if they couldn't be used, see [`Tlsf::add_pool`]
+    ///
+    /// # Safety
+    /// The memory must be valid for reads and writes, and not used by anything else.
+    pub unsafe fn add_region(&self, start: *mut u8, len: usize) -> bool {
+        critical_section::with(|cs| self.tlsf.borrow_ref_mut(cs).add_pool(start, len))
+    }
+
+    /// Allocates memory for `layout`, growing the heap if needed
+    pub fn alloc(&self, layout: Layout) -> Option<NonNull<u8>> {
+        critical_section::with(|cs| {
+            let mut tlsf = self.tlsf.borrow_ref_mut(cs);
+            if let Some(ptr) = tlsf.allocate(layout) {
+                return Some(ptr);
+            }
+            let (start, len) = (self.grow?)(Tlsf::pool_size_for(layout))?;
+            // SAFETY: `grow` gives memory that isn't used
+            if unsafe { tlsf.add_pool(start, len) } {
+                tlsf.allocate(layout)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Frees memory returned by [`alloc`](Heap::alloc)
+    ///
+    /// # Safety
+    /// `ptr` must have been allocated by this heap, and not freed yet.
+    pub unsafe fn dealloc(&self, ptr: NonNull<u8>) {
+        critical_section::with(|cs| self.tlsf.borrow_ref_mut(cs).deallocate(ptr));
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        critical_section::with(|cs| self.tlsf.borrow_ref(cs).stats())
+    }
+
+    /// See [`Tlsf::validate`]
+    pub fn validate(&self) -> Result<(), &'static str> {
+        critical_section::with(|cs| self.tlsf.borrow_ref(cs).validate())
+    }
+}
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Heap::alloc(self, layout).map_or(ptr::null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _: Layout) {
+        if let Some(ptr) = NonNull::new(ptr) {
+            Heap::dealloc(self, ptr);
+        }
+    }
+}
+unsafe impl Allocator for Heap {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = Heap::alloc(self, layout).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: Layout) {
+        Heap::dealloc(self, ptr);
+    }
+}
+
+/// Regions of memory that can hold a [`Heap`] when the program doesn't use them
+#[cfg(not(feature = "arm7"))]
+pub mod regions {
+    use core::ops::Range;
+
+    use super::*;
+
+    /// Data TCM: 16 KiB of fast memory, where the runtime mapped it (See
+    /// [`tcm::dtcm`](crate::tcm::dtcm)). The stacks and the `.dtcm` section are at its end
+    /// and its start respectively, only the space between them can be used.
+    pub fn dtcm() -> Range<usize> {
+        crate::tcm::dtcm_range()
+    }
+
+    /// Shared WRAM, when both banks are given to the ARM9 (`WRAMCNT` = 0).
+    /// The ARM7 program must not be using it.
+    pub const SHARED_WRAM: Range<usize> = 0x0300_0000..0x0300_8000;
+
+    /// VRAM banks, mapped to the LCD controller to be used as memory.
+    ///
+    /// VRAM ignores 8-bit writes, so it can only hold values written 16 or 32 bits at a time
+    /// (e.g. arrays of `u16`/`u32`, or data copied with the DMA).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum VramBank {
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+    }
+    impl VramBank {
+        /// Addresses of the bank when mapped to the LCD controller
+        pub const fn lcdc(self) -> Range<usize> {
+            let (start, len) = match self {
+                Self::A => (0x0680_0000, 128 * 1024),
+                Self::B => (0x0682_0000, 128 * 1024),
+                Self::C => (0x0684_0000, 128 * 1024),
+                Self::D => (0x0686_0000, 128 * 1024),
+                Self::E => (0x0688_0000, 64 * 1024),
+                Self::F => (0x0689_0000, 16 * 1024),
+                Self::G => (0x0689_4000, 16 * 1024),
+                Self::H => (0x0689_8000, 32 * 1024),
+                Self::I => (0x068A_0000, 16 * 1024),
+            };
+            start..start + len
+        }
+
+        const fn control(self) -> *mut u8 {
+            let offset = match self {
+                Self::H => 8,
+                Self::I => 9,
+                bank => bank as usize,
+            };
+            (0x0400_0240 + offset) as _
+        }
+
+        /// Maps the bank to the LCD controller, so it can be used as memory
+        ///
+        /// # Safety
+        /// The bank must not be in use by the graphics engines.
+        pub unsafe fn map_lcdc(self) {
+            self.control().write_volatile(VRAM_ENABLE);
+        }
+    }
+}
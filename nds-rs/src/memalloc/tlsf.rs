@@ -0,0 +1,504 @@
+//! Two-Level Segregated Fit allocator, allocating and freeing in constant time.
+//!
+//! Free blocks are kept in lists by size class: the first level splits sizes by powers
+//! of two, the second one splits each power of two in [`SL_COUNT`] ranges. Two bitmaps
+//! tell which lists have blocks, so a suitable block is found with a couple of bit scans.
+//! Freed blocks are merged with their free neighbours right away.
+//!
+//! Every block starts with a header of two words: the previous block in memory (only valid
+//! when that block is free) and the size of the block, whose lower bits hold flags.
+
+use core::{
+    alloc::Layout,
+    mem::size_of,
+    ptr::{self, NonNull},
+};
+
+use super::HeapStats;
+
+const WORD: usize = size_of::<usize>();
+/// Size of the header of the blocks
+const HEADER: usize = 2 * WORD;
+/// Sizes of the blocks are multiples of this, so they can hold the links of the free lists
+const GRANULE: usize = 2 * WORD;
+const GRANULE_LOG2: u32 = GRANULE.trailing_zeros();
+const SL_LOG2: u32 = 4;
+/// Lists per power of two
+pub const SL_COUNT: usize = 1 << SL_LOG2;
+/// Blocks smaller than `1 << FL_SHIFT` are in the first level, with a list per granule
+const FL_SHIFT: u32 = SL_LOG2 + GRANULE_LOG2;
+const FL_COUNT: usize = (usize::BITS - FL_SHIFT + 1) as usize;
+/// Maximum amount of memory regions given to [`Tlsf::add_pool`] that aren't contiguous
+pub const MAX_POOLS: usize = 8;
+
+/// The block is free
+const FREE: usize = 1;
+/// The previous block in memory is free
+const PREV_FREE: usize = 2;
+const FLAGS: usize = FREE | PREV_FREE;
+
+#[repr(C)]
+struct Block {
+    /// Previous block in memory, only valid when it's free
+    prev_phys: *mut Block,
+    /// Size of the payload, and flags
+    size: usize,
+    /// Links of the free list, in the payload of free blocks
+    next_free: *mut Block,
+    prev_free: *mut Block,
+}
+
+unsafe fn block_size(block: *mut Block) -> usize {
+    (*block).size & !FLAGS
+}
+
+unsafe fn is_free(block: *mut Block) -> bool {
+    (*block).size & FREE != 0
+}
+
+unsafe fn is_prev_free(block: *mut Block) -> bool {
+    (*block).size & PREV_FREE != 0
+}
+
+fn payload(block: *mut Block) -> *mut u8 {
+    block.cast::<u8>().wrapping_add(HEADER)
+}
+
+unsafe fn next_phys(block: *mut Block) -> *mut Block {
+    payload(block).add(block_size(block)).cast()
+}
+
+const fn align_up(value: usize, align: usize) -> Option<usize> {
+    match value.checked_add(align - 1) {
+        Some(value) => Some(value & !(align - 1)),
+        None => None,
+    }
+}
+
+/// Size class of a block of `size` bytes
+const fn mapping(size: usize) -> (usize, usize) {
+    if size < 1 << FL_SHIFT {
+        (0, size >> GRANULE_LOG2)
+    } else {
+        let fl = usize::BITS - 1 - size.leading_zeros();
+        let sl = (size >> (fl - SL_LOG2)) ^ SL_COUNT;
+        ((fl - FL_SHIFT + 1) as usize, sl)
+    }
+}
+
+/// Smallest size class where every block holds `size` bytes
+const fn mapping_search(size: usize) -> Option<(usize, usize)> {
+    if size < 1 << FL_SHIFT {
+        return Some(mapping(size));
+    }
+    let fl = usize::BITS - 1 - size.leading_zeros();
+    match size.checked_add((1 << (fl - SL_LOG2)) - 1) {
+        Some(size) => Some(mapping(size)),
+        None => None,
+    }
+}
+
+/// A TLSF heap, managing up to [`MAX_POOLS`] regions of memory
+pub struct Tlsf {
+    fl_bitmap: usize,
+    sl_bitmaps: [u32; FL_COUNT],
+    heads: [[*mut Block; SL_COUNT]; FL_COUNT],
+    /// Start and end of each pool
+    pools: [(usize, usize); MAX_POOLS],
+    pool_count: usize,
+    free: usize,
+    used: usize,
+    high_watermark: usize,
+    allocations: usize,
+}
+// SAFETY: the pools are owned by the heap
+unsafe impl Send for Tlsf {}
+impl Tlsf {
+    /// Creates a heap without any memory
+    pub const fn new() -> Self {
+        Self {
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL_COUNT],
+            heads: [[ptr::null_mut(); SL_COUNT]; FL_COUNT],
+            pools: [(0, 0); MAX_POOLS],
+            pool_count: 0,
+            free: 0,
+            used: 0,
+            high_watermark: 0,
+            allocations: 0,
+        }
+    }
+
+    /// Smallest pool that can hold an allocation of `layout`
+    pub const fn pool_size_for(layout: Layout) -> usize {
+        layout
+            .size()
+            .saturating_add(layout.align())
+            .saturating_add(4 * HEADER + 2 * GRANULE)
+    }
+
+    /// Gives `len` bytes starting at `start` to the heap. Returns `false` if the region
+    /// is too small, or if there are already [`MAX_POOLS`] pools and it doesn't
+    /// directly follow one of them.
+    ///
+    /// # Safety
+    /// The memory must be valid for reads and writes, not used by anything else,
+    /// and live as long as the heap is used.
+    pub unsafe fn add_pool(&mut self, start: *mut u8, len: usize) -> bool {
+        let (Some(begin), Some(end)) = (
+            align_up(start as usize, GRANULE),
+            (start as usize).checked_add(len),
+        ) else {
+            return false;
+        };
+        let end = end & !(GRANULE - 1);
+        if end < begin || end - begin < 2 * HEADER + GRANULE {
+            return false;
+        }
+        if let Some(pool) = self.pools[..self.pool_count]
+            .iter_mut()
+            .find(|(_, e)| *e == begin)
+        {
+            // The old sentinel becomes a block covering the new memory. It's "freed",
+            // which merges it with the free block before it.
+            let block = (begin - HEADER) as *mut Block;
+            let sentinel = (end - HEADER) as *mut Block;
+            let size = end - begin - HEADER;
+            (*block).size = size | ((*block).size & PREV_FREE);
+            (*sentinel).prev_phys = ptr::null_mut();
+            (*sentinel).size = 0;
+            pool.1 = end;
+            self.used += size;
+            self.allocations += 1;
+            self.deallocate(NonNull::new_unchecked(payload(block)));
+            return true;
+        }
+        if self.pool_count == MAX_POOLS {
+            return false;
+        }
+        let block = begin as *mut Block;
+        (*block).prev_phys = ptr::null_mut();
+        (*block).size = (end - begin - 2 * HEADER) | FREE;
+        let sentinel = next_phys(block);
+        (*sentinel).prev_phys = block;
+        (*sentinel).size = PREV_FREE;
+        self.insert(block);
+        self.pools[self.pool_count] = (begin, end);
+        self.pool_count += 1;
+        true
+    }
+
+    unsafe fn insert(&mut self, block: *mut Block) {
+        let (fl, sl) = mapping(block_size(block));
+        let head = self.heads[fl][sl];
+        (*block).next_free = head;
+        (*block).prev_free = ptr::null_mut();
+        if !head.is_null() {
+            (*head).prev_free = block;
+        }
+        self.heads[fl][sl] = block;
+        self.sl_bitmaps[fl] |= 1 << sl;
+        self.fl_bitmap |= 1 << fl;
+        self.free += block_size(block);
+    }
+
+    unsafe fn remove(&mut self, block: *mut Block) {
+        let (fl, sl) = mapping(block_size(block));
+        let (next, prev) = ((*block).next_free, (*block).prev_free);
+        if !next.is_null() {
+            (*next).prev_free = prev;
+        }
+        if prev.is_null() {
+            self.heads[fl][sl] = next;
+            if next.is_null() {
+                self.sl_bitmaps[fl] &= !(1 << sl);
+                if self.sl_bitmaps[fl] == 0 {
+                    self.fl_bitmap &= !(1 << fl);
+                }
+            }
+        } else {
+            (*prev).next_free = next;
+        }
+        self.free -= block_size(block);
+    }
+
+    /// A free block of at least `size` bytes
+    fn find(&self, size: usize) -> Option<*mut Block> {
+        let (mut fl, sl) = mapping_search(size)?;
+        if fl >= FL_COUNT {
+            return None;
+        }
+        let mut sl_map = self.sl_bitmaps[fl] & (!0 << sl);
+        if sl_map == 0 {
+            let fl_map = self.fl_bitmap & (!0usize).checked_shl(fl as u32 + 1).unwrap_or(0);
+            if fl_map == 0 {
+                return None;
+            }
+            fl = fl_map.trailing_zeros() as usize;
+            sl_map = self.sl_bitmaps[fl];
+        }
+        Some(self.heads[fl][sl_map.trailing_zeros() as usize])
+    }
+
+    /// Allocates memory for `layout`. Returns `None` if there isn't a big enough free block
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let size = align_up(layout.size().max(1), GRANULE)?;
+        let align = layout.align();
+        let search = if align <= GRANULE {
+            size
+        } else {
+            size.checked_add(align + HEADER + GRANULE)?
+        };
+        let mut block = self.find(search)?;
+        unsafe {
+            self.remove(block);
+            if align > GRANULE {
+                block = self.split_front(block, align);
+            }
+            let total = block_size(block);
+            let next = next_phys(block);
+            if total >= size + HEADER + GRANULE {
+                let rest = payload(block).add(size).cast::<Block>();
+                (*rest).prev_phys = block;
+                (*rest).size = (total - size - HEADER) | FREE;
+                (*next).prev_phys = rest;
+                self.insert(rest);
+                (*block).size = size | ((*block).size & PREV_FREE);
+            } else {
+                (*next).size &= !PREV_FREE;
+            }
+            (*block).size &= !FREE;
+            self.used += block_size(block);
+            self.high_watermark = self.high_watermark.max(self.used);
+            self.allocations += 1;
+            Some(NonNull::new_unchecked(payload(block)))
+        }
+    }
+
+    /// Splits the beginning of a removed free block so its payload is aligned to `align`,
+    /// freeing the first part. Returns the aligned part, which isn't in the free lists.
+    unsafe fn split_front(&mut self, block: *mut Block, align: usize) -> *mut Block {
+        let start = payload(block) as usize;
+        let mut aligned = start.next_multiple_of(align);
+        if aligned == start {
+            return block;
+        }
+        // The first part must be big enough to be a block
+        if aligned - start < HEADER + GRANULE {
+            aligned = (start + HEADER + GRANULE).next_multiple_of(align);
+        }
+        let gap = aligned - start;
+        let rest = (aligned - HEADER) as *mut Block;
+        (*rest).prev_phys = block;
+        (*rest).size = (block_size(block) - gap) | PREV_FREE;
+        // The block was free, so the one before it isn't
+        (*block).size = (gap - HEADER) | FREE;
+        self.insert(block);
+        rest
+    }
+
+    /// Frees memory returned by [`allocate`](Tlsf::allocate).
+    ///
+    /// # Safety
+    /// `ptr` must have been allocated by this heap, and not freed yet.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>) {
+        let mut block = ptr.as_ptr().sub(HEADER).cast::<Block>();
+        debug_assert!(!is_free(block), "double free");
+        self.used -= block_size(block);
+        self.allocations -= 1;
+        (*block).size |= FREE;
+        let mut next = next_phys(block);
+        if is_free(next) {
+            self.remove(next);
+            (*block).size += block_size(next) + HEADER;
+            next = next_phys(block);
+        }
+        if is_prev_free(block) {
+            let prev = (*block).prev_phys;
+            self.remove(prev);
+            (*prev).size += block_size(block) + HEADER;
+            block = prev;
+        }
+        (*next).prev_phys = block;
+        (*next).size |= PREV_FREE;
+        self.insert(block);
+    }
+
+    /// Size of the biggest free block
+    pub fn largest_free(&self) -> usize {
+        if self.fl_bitmap == 0 {
+            return 0;
+        }
+        let fl = (usize::BITS - 1 - self.fl_bitmap.leading_zeros()) as usize;
+        let sl = (u32::BITS - 1 - self.sl_bitmaps[fl].leading_zeros()) as usize;
+        let mut largest = 0;
+        let mut block = self.heads[fl][sl];
+        while !block.is_null() {
+            unsafe {
+                largest = largest.max(block_size(block));
+                block = (*block).next_free;
+            }
+        }
+        largest
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            used: self.used,
+            free: self.free,
+            largest_free: self.largest_free(),
+            high_watermark: self.high_watermark,
+            allocations: self.allocations,
+        }
+    }
+
+    /// Walks every block of every pool, checking the structure of the heap.
+    /// Useful to find memory corruption.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let mut free = 0;
+        let mut used = 0;
+        for &(begin, end) in &self.pools[..self.pool_count] {
+            let mut block = begin as *mut Block;
+            let mut prev: *mut Block = ptr::null_mut();
+            loop {
+                unsafe {
+                    let prev_free = !prev.is_null() && is_free(prev);
+                    if is_prev_free(block) != prev_free {
+                        return Err("wrong previous block flag");
+                    }
+                    if prev_free && (*block).prev_phys != prev {
+                        return Err("wrong previous block");
+                    }
+                    if block as usize == end - HEADER {
+                        if block_size(block) != 0 || is_free(block) {
+                            return Err("corrupted pool end");
+                        }
+                        break;
+                    }
+                    if block_size(block) < GRANULE || block_size(block) & (GRANULE - 1) != 0 {
+                        return Err("wrong block size");
+                    }
+                    if is_free(block) {
+                        if prev_free {
+                            return Err("contiguous free blocks");
+                        }
+                        let (fl, sl) = mapping(block_size(block));
+                        if self.sl_bitmaps[fl] & (1 << sl) == 0 || self.fl_bitmap & (1 << fl) == 0 {
+                            return Err("free block in an empty list");
+                        }
+                        free += block_size(block);
+                    } else {
+                        used += block_size(block);
+                    }
+                    prev = block;
+                    block = next_phys(block);
+                    if block as usize >= end {
+                        return Err("block past the end of its pool");
+                    }
+                }
+            }
+        }
+        if free != self.free || used != self.used {
+            return Err("wrong statistics");
+        }
+        Ok(())
+    }
+}
+impl Default for Tlsf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::mem::size_of_val;
+
+    use super::*;
+    use crate::memalloc::XorShift;
+
+    /// Checks that an allocation still holds the byte it was filled with
+    unsafe fn check(ptr: NonNull<u8>, len: usize, fill: u8) {
+        let bytes = core::slice::from_raw_parts(ptr.as_ptr(), len);
+        assert!(bytes.iter().all(|&b| b == fill), "overwritten allocation");
+    }
+
+    #[test]
+    fn random_operations_keep_the_heap_consistent() {
+        let mut buffer = [0u128; 2048];
+        let (start, len) = (buffer.as_mut_ptr().cast::<u8>(), size_of_val(&buffer));
+        let mut tlsf = Tlsf::new();
+        assert!(unsafe { tlsf.add_pool(start, len) });
+        let capacity = tlsf.stats().free;
+        let mut rng = XorShift(0x1234_5678);
+        // Pointer, layout and filling of the allocations that weren't freed
+        let mut live: Vec<(NonNull<u8>, Layout, u8)> = Vec::new();
+
+        for step in 0..10_000 {
+            let fill = step as u8;
+            match rng.below(3) {
+                // Allocate
+                0 => {
+                    let layout = rng.layout();
+                    if let Some(ptr) = tlsf.allocate(layout) {
+                        assert_eq!(ptr.as_ptr() as usize % layout.align(), 0);
+                        let offset = ptr.as_ptr() as usize - start as usize;
+                        assert!(offset + layout.size() <= len);
+                        unsafe { ptr.as_ptr().write_bytes(fill, layout.size()) };
+                        live.push((ptr, layout, fill));
+                    }
+                }
+                // Reallocate, by moving to a new allocation like `GlobalAlloc::realloc`
+                1 if !live.is_empty() => {
+                    let index = rng.below(live.len());
+                    let (old, old_layout, old_fill) = live[index];
+                    let size = rng.layout().size();
+                    let layout = Layout::from_size_align(size, old_layout.align()).unwrap();
+                    if let Some(ptr) = tlsf.allocate(layout) {
+                        unsafe {
+                            let kept = size.min(old_layout.size());
+                            old.as_ptr().copy_to_nonoverlapping(ptr.as_ptr(), kept);
+                            check(old, old_layout.size(), old_fill);
+                            tlsf.deallocate(old);
+                            check(ptr, kept, old_fill);
+                            ptr.as_ptr().write_bytes(fill, size);
+                        }
+                        live[index] = (ptr, layout, fill);
+                    }
+                }
+                // Free
+                _ if !live.is_empty() => {
+                    let (ptr, layout, fill) = live.swap_remove(rng.below(live.len()));
+                    unsafe {
+                        check(ptr, layout.size(), fill);
+                        tlsf.deallocate(ptr);
+                    }
+                }
+                _ => {}
+            }
+
+            if let Err(error) = tlsf.validate() {
+                panic!("step {step}: {error}");
+            }
+            let stats = tlsf.stats();
+            let requested: usize = live.iter().map(|(_, layout, _)| layout.size()).sum();
+            assert_eq!(stats.allocations, live.len());
+            assert!(stats.used >= requested);
+            assert!(stats.used + stats.free <= capacity);
+            assert!(stats.largest_free <= stats.free);
+            assert!(stats.high_watermark >= stats.used);
+        }
+
+        for (ptr, layout, fill) in live {
+            unsafe {
+                check(ptr, layout.size(), fill);
+                tlsf.deallocate(ptr);
+            }
+        }
+        let stats = tlsf.stats();
+        assert_eq!((stats.used, stats.allocations), (0, 0));
+        assert_eq!((stats.free, stats.largest_free), (capacity, capacity));
+        assert_eq!(tlsf.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,131 @@
+//! Protection regions of the ARM9 memory protection unit (MPU)
+//!
+//! The MPU has 8 regions, each one covering a power of two sized, aligned block of memory.
+//! When regions overlap, the one with the highest number wins. Accessing memory outside
+//! of every region causes an abort (See [`crate::exception`]).
+//!
+//! The regions are set up by the runtime before `main`; this module only allows
+//! inspecting them and making small changes such as [`protect_null_page`].
+
+use core::arch::asm;
+
+/// Amount of protection regions
+pub const REGIONS: u8 = 8;
+
+/// Base and size of a protection region (CP15 register 6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub enabled: bool,
+    /// Start address, aligned to the size
+    pub base: u32,
+    /// The region covers `2 << size_log2` bytes, `size_log2` goes from 11 (4 KiB)
+    /// to 31 (4 GiB)
+    pub size_log2: u8,
+}
+impl Region {
+    pub const fn from_bits(bits: u32) -> Self {
+        Self {
+            enabled: bits & 1 != 0,
+            base: bits & 0xFFFF_F000,
+            size_log2: ((bits >> 1) & 0b11111) as u8,
+        }
+    }
+
+    pub const fn bits(&self) -> u32 {
+        (self.base & 0xFFFF_F000) | ((self.size_log2 as u32 & 0b11111) << 1) | self.enabled as u32
+    }
+
+    /// Size of the region in bytes, saturated to `u32::MAX` for the 4 GiB region
+    pub const fn size(&self) -> u32 {
+        match 2u32.checked_shl(self.size_log2 as u32) {
+            Some(size) if self.size_log2 < 31 => size,
+            _ => u32::MAX,
+        }
+    }
+
+    /// Returns `true` if `address` is in the region (enabled or not)
+    pub const fn contains(&self, address: u32) -> bool {
+        let size = self.size();
+        address.wrapping_sub(self.base) < size || size == u32::MAX
+    }
+}
+
+macro_rules! region_access {
+    ($index:literal) => {{
+        let bits: u32;
+        asm!(concat!("mrc p15, 0, {}, c6, c", $index, ", 0"), out(reg) bits, options(nomem, nostack));
+        bits
+    }};
+    ($index:literal, $bits:expr) => {{
+        asm!(concat!("mcr p15, 0, {}, c6, c", $index, ", 0"), in(reg) $bits, options(nostack));
+    }};
+}
+
+/// Reads protection region `n`. Panics if `n` isn't lower than [`REGIONS`]
+pub fn region(n: u8) -> Region {
+    let bits = unsafe {
+        match n {
+            0 => region_access!("0"),
+            1 => region_access!("1"),
+            2 => region_access!("2"),
+            3 => region_access!("3"),
+            4 => region_access!("4"),
+            5 => region_access!("5"),
+            6 => region_access!("6"),
+            7 => region_access!("7"),
+            _ => panic!("There are only {REGIONS} protection regions"),
+        }
+    };
+    Region::from_bits(bits)
+}
+
+/// Changes protection region `n`, keeping its access permissions and cache settings.
+/// Panics if `n` isn't lower than [`REGIONS`].
+///
+/// # Safety
+/// Code and data in use must stay accessible.
+pub unsafe fn set_region(n: u8, region: Region) {
+    let bits = region.bits();
+    match n {
+        0 => region_access!("0", bits),
+        1 => region_access!("1", bits),
+        2 => region_access!("2", bits),
+        3 => region_access!("3", bits),
+        4 => region_access!("4", bits),
+        5 => region_access!("5", bits),
+        6 => region_access!("6", bits),
+        7 => region_access!("7", bits),
+        _ => panic!("There are only {REGIONS} protection regions"),
+    }
+}
+
+/// Makes accesses to the first 16 MiB of memory (null pointers included) fault.
+///
+/// The runtime usually covers the instruction TCM with a region starting at address 0,
+/// where the TCM is mirrored. That region is shrunk to its part starting at `0x01000000`,
+/// where code placed in ITCM actually runs.
+///
+/// Returns `false` if no such region was found, in which case nothing is changed.
+/// Aborts are only reported once an exception handler is installed
+/// (See [`crate::exception::install`]).
+pub fn protect_null_page() -> bool {
+    const ITCM: u32 = 0x0100_0000;
+    const ITCM_SIZE_LOG2: u8 = 23; // 16 MiB
+    for n in (0..REGIONS).rev() {
+        let current = region(n);
+        // Bigger regions could be covering main RAM too
+        let itcm_only = current.size() <= 0x0200_0000;
+        if current.enabled && current.base == 0 && current.contains(ITCM) && itcm_only {
+            let shrunk = Region {
+                enabled: true,
+                base: ITCM,
+                size_log2: ITCM_SIZE_LOG2,
+            };
+            unsafe {
+                set_region(n, shrunk);
+            }
+            return true;
+        }
+    }
+    false
+}
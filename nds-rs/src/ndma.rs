@@ -0,0 +1,490 @@
+//! API to control the new DMA of the DSi
+//!
+//! In DSi mode, four NDMA channels are available besides the [legacy ones](crate::dma).
+//! They always transfer words, and can move up to 16M words at once, fill memory
+//! without a source, and start on more events (e.g. the [cameras](Flags::CAMERA)).
+//!
+//! [`Ndma::channels`] returns `Err(Unsupported)` outside of DSi mode. To use the NDMA
+//! when it's available and fall back to the legacy DMA otherwise, use
+//! [`Ndma::channel_or`], which returns an [`AnyChannel`]:
+//! ```rust,no_run
+//! let mut channel = hw.ndma.channel_or(Channel::Ch0, &mut hw.dma.ch3);
+//! channel.copy(&src, &mut dst);
+//! unsafe { channel.start_fill(0u32, &mut dst) }.await;
+//! ```
+//! Both engines implement [`DmaEngine`], to write code generic over them.
+//!
+//! The warnings of the [`dma`](crate::dma) module apply here too:
+//! [`copy`](NdmaChannel::copy), [`fill`](NdmaChannel::fill) and
+//! [`copy_within`](NdmaChannel::copy_within) return once the operation is done, while the
+//! `start_` variants return a [`Transfer`] borrowing the channel and the buffers until the
+//! operation is done. Leaking it releases them while the NDMA may still be running, so
+//! those are `unsafe`.
+
+use core::{
+    future::Future,
+    marker::PhantomData,
+    mem::{align_of_val, size_of, size_of_val},
+    ops::Range,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use nds_sys::{
+    interrupts,
+    ndma::{calc_registers, MAX_BLOCK_WORDS},
+};
+
+use crate::{
+    cache::dc_flush_slice,
+    dma::{self, DmaChannel, DmaEngine, DmaTransfer},
+    dsi::{self, Capabilities, Unsupported},
+    executor::irq,
+};
+
+pub use nds_sys::ndma::{Channel, Flags};
+
+/// Checks if the specified [`Channel`] is busy
+pub fn is_busy(ch: Channel) -> bool {
+    let cr = calc_registers(ch).cr;
+    let flags = Flags::from_bits_retain(unsafe { cr.read_volatile() });
+    flags.contains(Flags::ENABLED)
+}
+
+/// Hangs until the specified [`Channel`] becomes available.
+/// Polls the channel, so it doesn't depend on interrupts being enabled.
+pub fn wait_for(ch: Channel) {
+    while is_busy(ch) {}
+}
+
+const fn irq_of(ch: Channel) -> interrupts::Flags {
+    match ch {
+        Channel::Ch0 => interrupts::Flags::NDMA0,
+        Channel::Ch1 => interrupts::Flags::NDMA1,
+        Channel::Ch2 => interrupts::Flags::NDMA2,
+        Channel::Ch3 => interrupts::Flags::NDMA3,
+    }
+}
+
+/// The NDMA channels. Part of [`Hw`](crate::Hw), usable in DSi mode only
+pub struct Ndma {
+    channels: NdmaChannels,
+}
+impl Ndma {
+    pub(crate) const unsafe fn new() -> Self {
+        Self {
+            channels: NdmaChannels {
+                ch0: NdmaChannel::new(Channel::Ch0),
+                ch1: NdmaChannel::new(Channel::Ch1),
+                ch2: NdmaChannel::new(Channel::Ch2),
+                ch3: NdmaChannel::new(Channel::Ch3),
+            },
+        }
+    }
+
+    /// Returns the four channels, or `Err(Unsupported)` outside of DSi mode
+    pub fn channels(&mut self) -> Result<&mut NdmaChannels, Unsupported> {
+        dsi::require(Capabilities::NDMA)?;
+        Ok(&mut self.channels)
+    }
+
+    /// Returns the NDMA channel `ch` if available, or else `fallback`
+    pub fn channel_or<'c>(
+        &'c mut self,
+        ch: Channel,
+        fallback: &'c mut DmaChannel,
+    ) -> AnyChannel<'c> {
+        match self.channels() {
+            Ok(channels) => AnyChannel::Ndma(channels.get(ch)),
+            Err(Unsupported) => AnyChannel::Dma(fallback),
+        }
+    }
+}
+
+/// All four NDMA channels
+#[non_exhaustive]
+pub struct NdmaChannels {
+    /// Highest priority
+    pub ch0: NdmaChannel,
+    pub ch1: NdmaChannel,
+    pub ch2: NdmaChannel,
+    /// Lowest priority
+    pub ch3: NdmaChannel,
+}
+impl NdmaChannels {
+    pub fn get(&mut self, ch: Channel) -> &mut NdmaChannel {
+        match ch {
+            Channel::Ch0 => &mut self.ch0,
+            Channel::Ch1 => &mut self.ch1,
+            Channel::Ch2 => &mut self.ch2,
+            Channel::Ch3 => &mut self.ch3,
+        }
+    }
+}
+
+/// Exclusive handle to an NDMA [`Channel`].
+///
+/// Only one [`Transfer`] can be running on a channel at any time,
+/// since starting one borrows the channel mutably.
+pub struct NdmaChannel {
+    ch: Channel,
+}
+impl NdmaChannel {
+    const unsafe fn new(ch: Channel) -> Self {
+        Self { ch }
+    }
+
+    /// The channel this handle controls
+    pub fn channel(&self) -> Channel {
+        self.ch
+    }
+
+    /// Checks if the channel is busy.
+    /// Only returns `true` if a [`Transfer`] was leaked or if the channel
+    /// was programmed through [`start`](Self::start).
+    pub fn is_busy(&self) -> bool {
+        is_busy(self.ch)
+    }
+
+    /// Programs the channel to move `total_len` words from `src` to `dst`, in blocks
+    /// of `block_len` words, using `flags` (which must include [`Flags::ENABLE`]).
+    ///
+    /// # Safety
+    /// The caller must make sure that the transfer only reads from and writes to
+    /// memory that outlives the returned [`Transfer`], and that the memory has been
+    /// flushed from the cache if needed.
+    pub unsafe fn start<'t>(
+        &'t mut self,
+        src: *const u32,
+        dst: *mut u32,
+        total_len: u32,
+        block_len: u32,
+        flags: Flags,
+    ) -> Transfer<'t> {
+        wait_for(self.ch);
+        let regs = calc_registers(self.ch);
+        regs.src.write_volatile(src as u32);
+        regs.dst.write_volatile(dst as u32);
+        regs.total_len.write_volatile(total_len);
+        regs.block_len.write_volatile(block_len);
+        regs.interval.write_volatile(0);
+        regs.cr.write_volatile(flags.bits());
+        Transfer {
+            channel: self,
+            _buffers: PhantomData,
+        }
+    }
+
+    /// Starts an immediate transfer of `words` words
+    unsafe fn start_now<'t>(
+        &'t mut self,
+        src: *const u32,
+        dst: *mut u32,
+        words: usize,
+        flags: Flags,
+    ) -> Transfer<'t> {
+        if words == 0 {
+            return self.start_noop();
+        }
+        assert!(
+            words <= MAX_BLOCK_WORDS,
+            "NDMA transfers are limited to {MAX_BLOCK_WORDS} words"
+        );
+        let flags = flags | Flags::ENABLE | Flags::START_IMM | Flags::INT_REQ;
+        self.start(src, dst, words as u32, words as u32, flags)
+    }
+
+    /// Copies `src` into `dst`, and waits until it's done.
+    /// Panics if the slices aren't aligned to 4 bytes, or if their size isn't a multiple
+    /// of 4 bytes.
+    /// In case `src.len() != dst.len()` then only `min(src.len(), dst.len())` elements will be copied.
+    pub fn copy<T>(&mut self, src: &[T], dst: &mut [T])
+    where
+        T: Sized + Copy,
+    {
+        unsafe { self.start_copy(src, dst) }.wait()
+    }
+
+    /// Starts copying `src` into `dst`, see [`copy`](Self::copy).
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked, directly or through a future
+    /// awaiting it: it has to be dropped, waited for or awaited until done.
+    pub unsafe fn start_copy<'t, T>(&'t mut self, src: &'t [T], dst: &'t mut [T]) -> Transfer<'t>
+    where
+        T: Sized + Copy,
+    {
+        let len = src.len().min(dst.len());
+        let (src, dst) = (&src[..len], &mut dst[..len]);
+        let words = words_of(src, "copy");
+        check_aligned(dst, "copy");
+        dc_flush_slice(src);
+        dc_flush_slice(dst);
+        let flags = Flags::INC_SRC | Flags::INC_DST;
+        self.start_now(src.as_ptr() as _, dst.as_mut_ptr() as _, words, flags)
+    }
+
+    /// Copies the elements of `buffer` in range `src` to `buffer`, starting at `dest`,
+    /// and waits until it's done.
+    /// Works like [`slice::copy_within`], overlapping ranges included.
+    /// Panics if the ranges are out of bounds, aren't aligned to 4 bytes, or if their
+    /// size isn't a multiple of 4 bytes.
+    pub fn copy_within<T>(&mut self, buffer: &mut [T], src: Range<usize>, dest: usize)
+    where
+        T: Sized + Copy,
+    {
+        unsafe { self.start_copy_within(buffer, src, dest) }.wait()
+    }
+
+    /// Starts copying the elements of `buffer` in range `src` to `dest`, see
+    /// [`copy_within`](Self::copy_within).
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked, see [`start_copy`](Self::start_copy)
+    pub unsafe fn start_copy_within<'t, T>(
+        &'t mut self,
+        buffer: &'t mut [T],
+        src: Range<usize>,
+        dest: usize,
+    ) -> Transfer<'t>
+    where
+        T: Sized + Copy,
+    {
+        assert!(src.start <= src.end, "src start is greater than src end");
+        assert!(src.end <= buffer.len(), "src is out of bounds");
+        let len = src.end - src.start;
+        assert!(dest <= buffer.len() - len, "dest is out of bounds");
+        let words = words_of(&buffer[src.clone()], "copy_within");
+        check_aligned(&buffer[dest..dest + len], "copy_within");
+        let ptr = buffer.as_mut_ptr();
+        dc_flush_slice(buffer);
+        // When the destination is after the source the ranges may overlap,
+        // so copy backwards, starting from the last word
+        if dest > src.start {
+            let last = (words.max(1) - 1) * 4;
+            let from = ptr.add(src.start).cast::<u8>().add(last);
+            let to = ptr.add(dest).cast::<u8>().add(last);
+            let flags = Flags::DEC_SRC | Flags::DEC_DST;
+            self.start_now(from as _, to as _, words, flags)
+        } else {
+            let flags = Flags::INC_SRC | Flags::INC_DST;
+            self.start_now(ptr.add(src.start) as _, ptr.add(dest) as _, words, flags)
+        }
+    }
+
+    /// Fills `dst` by copying `value`, and waits until it's done.
+    /// Panics if `size_of::<T>()` isn't 1, 2 or 4, if `dst` isn't aligned to 4 bytes,
+    /// or if its size isn't a multiple of 4 bytes.
+    pub fn fill<T>(&mut self, value: T, dst: &mut [T])
+    where
+        T: Sized + Copy,
+    {
+        unsafe { self.start_fill(value, dst) }.wait()
+    }
+
+    /// Starts filling `dst` with `value`, see [`fill`](Self::fill).
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked, see [`start_copy`](Self::start_copy)
+    pub unsafe fn start_fill<'t, T>(&'t mut self, value: T, dst: &'t mut [T]) -> Transfer<'t>
+    where
+        T: Sized + Copy,
+    {
+        let value = fill_word(value);
+        let words = words_of(dst, "fill");
+        let fill = calc_registers(self.ch).fill;
+        dc_flush_slice(dst);
+        wait_for(self.ch);
+        fill.write_volatile(value);
+        let flags = Flags::FILL_SRC | Flags::INC_DST;
+        self.start_now(core::ptr::null(), dst.as_mut_ptr() as _, words, flags)
+    }
+
+    /// Returns a [`Transfer`] that is already done, for empty operations
+    unsafe fn start_noop(&mut self) -> Transfer<'_> {
+        Transfer {
+            channel: self,
+            _buffers: PhantomData,
+        }
+    }
+}
+
+/// Returns the length of `slice` in words.
+/// Panics if it isn't aligned to 4 bytes, or if its size isn't a multiple of 4 bytes.
+fn words_of<T>(slice: &[T], op: &str) -> usize {
+    check_aligned(slice, op);
+    let size = size_of_val(slice);
+    assert!(
+        size.is_multiple_of(4),
+        "Can only run {op}() on a multiple of 4 bytes"
+    );
+    size / 4
+}
+
+fn check_aligned<T>(slice: &[T], op: &str) {
+    let aligned = align_of_val(slice) >= 4 || (slice.as_ptr() as usize).is_multiple_of(4);
+    assert!(aligned, "Can only run {op}() on memory aligned to 4 bytes");
+}
+
+/// Repeats `value` to fill a word.
+/// Panics if `size_of::<T>()` isn't 1, 2 or 4.
+pub fn fill_word<T: Copy>(value: T) -> u32 {
+    match size_of::<T>() {
+        4 => unsafe { core::mem::transmute_copy::<_, u32>(&value) },
+        2 => (unsafe { core::mem::transmute_copy::<_, u16>(&value) } as u32) * 0x0001_0001,
+        1 => (unsafe { core::mem::transmute_copy::<_, u8>(&value) } as u32) * 0x0101_0101,
+        _ => panic!("Can only run fill<T>() if T is 1, 2 or 4 bytes"),
+    }
+}
+
+/// An operation running on an [`NdmaChannel`].
+///
+/// Borrows the channel and the buffers involved until the operation is done.
+/// Dropping it waits until the channel is available.
+#[must_use = "dropping a `Transfer` blocks until it's done"]
+pub struct Transfer<'t> {
+    channel: &'t mut NdmaChannel,
+    _buffers: PhantomData<&'t mut [u8]>,
+}
+impl Transfer<'_> {
+    /// Returns `true` once the operation has finished
+    pub fn is_done(&self) -> bool {
+        !self.channel.is_busy()
+    }
+
+    /// Blocks until the operation has finished
+    pub fn wait(self) {
+        // Dropping waits
+    }
+
+    /// The channel running this operation
+    pub fn channel(&self) -> Channel {
+        self.channel.ch
+    }
+}
+impl Drop for Transfer<'_> {
+    fn drop(&mut self) {
+        wait_for(self.channel.ch);
+    }
+}
+/// Resolves once the operation has finished. Uses the NDMA interrupt of the channel
+/// to wake the task, installing the handler and enabling the interrupt when polled.
+impl Future for Transfer<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_done() {
+            return Poll::Ready(());
+        }
+        irq::listen(irq_of(self.channel.ch), cx.waker());
+        // The transfer could have finished before the waker was registered
+        if self.is_done() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl DmaEngine for NdmaChannel {
+    type Transfer<'t> = Transfer<'t>;
+
+    unsafe fn start_copy<'t, T: Copy>(
+        &'t mut self,
+        src: &'t [T],
+        dst: &'t mut [T],
+    ) -> Transfer<'t> {
+        NdmaChannel::start_copy(self, src, dst)
+    }
+
+    unsafe fn start_copy_within<'t, T: Copy>(
+        &'t mut self,
+        buffer: &'t mut [T],
+        src: Range<usize>,
+        dest: usize,
+    ) -> Transfer<'t> {
+        NdmaChannel::start_copy_within(self, buffer, src, dest)
+    }
+
+    unsafe fn start_fill<'t, T: Copy>(&'t mut self, value: T, dst: &'t mut [T]) -> Transfer<'t> {
+        NdmaChannel::start_fill(self, value, dst)
+    }
+}
+
+impl DmaTransfer for Transfer<'_> {
+    fn is_done(&self) -> bool {
+        Transfer::is_done(self)
+    }
+}
+
+/// An NDMA channel in DSi mode, or a legacy one otherwise.
+/// See [`Ndma::channel_or`]
+pub enum AnyChannel<'c> {
+    Ndma(&'c mut NdmaChannel),
+    Dma(&'c mut DmaChannel),
+}
+
+/// A [`Transfer`] of an [`AnyChannel`]
+#[must_use = "dropping a `Transfer` blocks until it's done"]
+pub enum AnyTransfer<'t> {
+    Ndma(Transfer<'t>),
+    Dma(dma::Transfer<'t>),
+}
+
+impl DmaEngine for AnyChannel<'_> {
+    type Transfer<'t>
+        = AnyTransfer<'t>
+    where
+        Self: 't;
+
+    unsafe fn start_copy<'t, T: Copy>(
+        &'t mut self,
+        src: &'t [T],
+        dst: &'t mut [T],
+    ) -> AnyTransfer<'t> {
+        match self {
+            Self::Ndma(ch) => AnyTransfer::Ndma(ch.start_copy(src, dst)),
+            Self::Dma(ch) => AnyTransfer::Dma(ch.start_copy(src, dst)),
+        }
+    }
+
+    unsafe fn start_copy_within<'t, T: Copy>(
+        &'t mut self,
+        buffer: &'t mut [T],
+        src: Range<usize>,
+        dest: usize,
+    ) -> AnyTransfer<'t> {
+        match self {
+            Self::Ndma(ch) => AnyTransfer::Ndma(ch.start_copy_within(buffer, src, dest)),
+            Self::Dma(ch) => AnyTransfer::Dma(ch.start_copy_within(buffer, src, dest)),
+        }
+    }
+
+    unsafe fn start_fill<'t, T: Copy>(&'t mut self, value: T, dst: &'t mut [T]) -> AnyTransfer<'t> {
+        match self {
+            Self::Ndma(ch) => AnyTransfer::Ndma(ch.start_fill(value, dst)),
+            Self::Dma(ch) => AnyTransfer::Dma(ch.start_fill(value, dst)),
+        }
+    }
+}
+
+impl Future for AnyTransfer<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Both transfers are Unpin
+        match self.get_mut() {
+            Self::Ndma(transfer) => Pin::new(transfer).poll(cx),
+            Self::Dma(transfer) => Pin::new(transfer).poll(cx),
+        }
+    }
+}
+
+impl DmaTransfer for AnyTransfer<'_> {
+    fn is_done(&self) -> bool {
+        match self {
+            Self::Ndma(transfer) => transfer.is_done(),
+            Self::Dma(transfer) => transfer.is_done(),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Overlays: code and data loaded from the ROM when needed
+//!
+//! Programs too big for main RAM can put some of their code and data in overlays. Each
+//! overlay is linked to run at a given address, and several overlays can share the same
+//! addresses (a region) when they're never needed at the same time.
+//!
+//! Items are placed in overlay `N` with [`#[overlay(N)]`](macro@crate::overlay), which wraps
+//! statics in an [`OverlayItem`] and functions in an [`OverlayFn`]. They can only be used
+//! through the [`Overlay`] handle returned by [`Overlay::load`], and don't outlive it, so
+//! they can't be used while the overlay isn't loaded:
+//! ```rust,no_run
+//! #[overlay(2)]
+//! fn boss_fight(hw: &mut Hw) -> Outcome { /* ... */ }
+//!
+//! let mut rom = FileRom::running()?;
+//! // SAFETY: the ROM is the running program, with its overlay tables
+//! let overlay = unsafe { Overlay::load(2, &mut rom)? };
+//! let outcome = boss_fight.get(&overlay)(&mut hw);
+//! drop(overlay); // The region can now be used by other overlays
+//! ```
+//!
+//! The overlays are described by the overlay table and the file allocation table of the
+//! ROM (See [`NdsHeader`]). The BlocksDS linker script doesn't lay out overlays: the
+//! `.overlayN.*` sections must be placed with a custom linker script, which also defines
+//! `__overlay_region_start` and `__overlay_region_end` around the addresses of all the
+//! overlays: [`Overlay::load`] refuses to load anything outside of them.
+//! The tables are built on the host with the `nds-overlay-builder` crate, and given to
+//! `ndstool`.
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::{
+    cell::RefCell,
+    ffi::CStr,
+    fmt,
+    marker::{PhantomData, Tuple},
+    ops::Range,
+};
+
+use critical_section::Mutex;
+use nds_sys::system::{ARGV_MAGIC, SYSTEM_ARGV};
+
+use crate::{cache, header::NdsHeader};
+
+mod table;
+pub use table::*;
+
+/// Errors when loading an overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayError {
+    /// There's no overlay with this id
+    NotFound(u32),
+    /// The overlay or file allocation table is invalid
+    Table(TableError),
+    /// Compressed overlays aren't supported
+    Compressed,
+    /// The overlay with this id is loaded in the same addresses
+    InUse(u32),
+    /// Reading the ROM failed
+    Read,
+}
+impl From<TableError> for OverlayError {
+    fn from(e: TableError) -> Self {
+        Self::Table(e)
+    }
+}
+impl fmt::Display for OverlayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(id) => write!(f, "overlay {id} not found"),
+            Self::Table(e) => write!(f, "invalid overlay table: {e:?}"),
+            Self::Compressed => f.write_str("compressed overlays aren't supported"),
+            Self::InUse(id) => write!(f, "overlay {id} is loaded in the same addresses"),
+            Self::Read => f.write_str("couldn't read the ROM"),
+        }
+    }
+}
+
+/// Somewhere the ROM can be read from
+pub trait RomSource {
+    /// Fills `buffer` with the bytes of the ROM starting at `offset`
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<(), OverlayError>;
+}
+
+/// A ROM in memory
+impl RomSource for &[u8] {
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<(), OverlayError> {
+        let start = offset as usize;
+        let bytes = start
+            .checked_add(buffer.len())
+            .and_then(|end| self.get(start..end))
+            .ok_or(OverlayError::Read)?;
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// A `.nds` file, read with the C library
+pub struct FileRom {
+    file: *mut libc::FILE,
+}
+impl FileRom {
+    /// Opens the `.nds` file at `path`. The file system must have been initialized
+    pub fn open(path: &CStr) -> Result<Self, OverlayError> {
+        let file = unsafe { libc::fopen(path.as_ptr(), c"rb".as_ptr()) };
+        if file.is_null() {
+            Err(OverlayError::Read)
+        } else {
+            Ok(Self { file })
+        }
+    }
+
+    /// Opens the `.nds` file of the running program, given by the loader in `argv[0]`
+    pub fn running() -> Result<Self, OverlayError> {
+        let argv = unsafe { &*SYSTEM_ARGV };
+        if argv.magic != ARGV_MAGIC || argv.argc < 1 || argv.argv.is_null() {
+            return Err(OverlayError::Read);
+        }
+        let path = unsafe { *argv.argv };
+        if path.is_null() {
+            return Err(OverlayError::Read);
+        }
+        Self::open(unsafe { CStr::from_ptr(path) })
+    }
+}
+impl RomSource for FileRom {
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<(), OverlayError> {
+        unsafe {
+            if libc::fseek(self.file, offset as _, libc::SEEK_SET) != 0 {
+                return Err(OverlayError::Read);
+            }
+            let read = libc::fread(buffer.as_mut_ptr() as _, 1, buffer.len(), self.file);
+            if read != buffer.len() {
+                return Err(OverlayError::Read);
+            }
+        }
+        Ok(())
+    }
+}
+impl Drop for FileRom {
+    fn drop(&mut self) {
+        unsafe {
+            libc::fclose(self.file);
+        }
+    }
+}
+
+extern "C" {
+    /// Bounds of the addresses overlays are linked at, defined by the linker script
+    static __overlay_region_start: u8;
+    static __overlay_region_end: u8;
+}
+
+/// Addresses overlays can be loaded at
+fn overlay_region() -> Range<u32> {
+    let start = core::ptr::addr_of!(__overlay_region_start) as u32;
+    let end = core::ptr::addr_of!(__overlay_region_end) as u32;
+    start..end
+}
+
+/// Ids and addresses of the loaded overlays
+type Loaded = Vec<(u32, Range<u32>)>;
+static LOADED: Mutex<RefCell<Loaded>> = Mutex::new(RefCell::new(Vec::new()));
+
+/// Reads the entry of overlay `id` and the location of its file
+fn find(
+    header: &NdsHeader,
+    id: u32,
+    rom: &mut impl RomSource,
+) -> Result<(OverlayEntry, FatEntry), OverlayError> {
+    let offset = id as usize * OVERLAY_ENTRY_SIZE;
+    if offset + OVERLAY_ENTRY_SIZE > header.arm9_overlays.size as usize {
+        return Err(OverlayError::NotFound(id));
+    }
+    let mut bytes = [0; OVERLAY_ENTRY_SIZE];
+    rom.read(header.arm9_overlays.offset + offset as u32, &mut bytes)?;
+    let entry = OverlayEntry::from_bytes(&bytes);
+    if entry.id != id {
+        return Err(TableError::UnorderedId(id as usize).into());
+    }
+    entry.validate()?;
+
+    let offset = entry.file_id as usize * FAT_ENTRY_SIZE;
+    if offset + FAT_ENTRY_SIZE > header.fat.size as usize {
+        return Err(TableError::InvalidEntry(id).into());
+    }
+    let mut bytes = [0; FAT_ENTRY_SIZE];
+    rom.read(header.fat.offset + offset as u32, &mut bytes)?;
+    let file = FatEntry::from_bytes(&bytes);
+    if entry.is_compressed() {
+        return Err(OverlayError::Compressed);
+    }
+    if file.len() < entry.ram_size {
+        return Err(TableError::InvalidEntry(id).into());
+    }
+    Ok((entry, file))
+}
+
+/// A loaded overlay. Its region is freed when it's dropped
+pub struct Overlay {
+    entry: OverlayEntry,
+}
+impl Overlay {
+    /// Loads ARM9 overlay `id` from `rom`: reads its code and data, clears its BSS, and
+    /// runs its static initializers.
+    ///
+    /// Fails if another overlay using the same addresses is loaded, or if the overlay
+    /// isn't inside the region of the linker script.
+    ///
+    /// # Safety
+    /// `rom` must be the ROM of the running program, with the overlay table built for
+    /// its overlays: the entry of the overlay gives the addresses that are overwritten,
+    /// and the static initializers that are called.
+    pub unsafe fn load(id: u32, rom: &mut impl RomSource) -> Result<Self, OverlayError> {
+        let (entry, file) = find(NdsHeader::running(), id, rom)?;
+        let range = entry.ram_range();
+        let region = overlay_region();
+        if range.start < region.start || range.end > region.end {
+            return Err(TableError::InvalidEntry(id).into());
+        }
+        critical_section::with(|cs| {
+            let mut loaded = LOADED.borrow_ref_mut(cs);
+            if let Some((other, _)) = loaded.iter().find(|(_, r)| overlaps(r, &range)) {
+                return Err(OverlayError::InUse(*other));
+            }
+            loaded.push((id, range.clone()));
+            Ok(())
+        })?;
+        // From here, dropping the handle frees the region
+        let overlay = Self { entry };
+
+        // SAFETY: the addresses are in the overlay region and reserved for this overlay,
+        // and its items can only be used through the handle
+        let memory = unsafe {
+            core::slice::from_raw_parts_mut(
+                range.start as *mut u8,
+                (range.end - range.start) as usize,
+            )
+        };
+        let (data, bss) = memory.split_at_mut(entry.ram_size as usize);
+        rom.read(file.start, data)?;
+        bss.fill(0);
+        unsafe {
+            cache::dc_flush_slice(memory);
+            cache::ic_invalidate_slice(memory);
+        }
+
+        for address in (entry.sinit_start..entry.sinit_end).step_by(4) {
+            // SAFETY: the linker fills the array with pointers to `extern "C" fn()`, and
+            // the caller guarantees the table comes from the linker
+            unsafe {
+                let init = (address as *const Option<extern "C" fn()>).read();
+                if let Some(init) = init {
+                    init();
+                }
+            }
+        }
+        Ok(overlay)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.entry.id
+    }
+
+    pub fn entry(&self) -> &OverlayEntry {
+        &self.entry
+    }
+
+    /// Returns `true` if overlay `id` is loaded
+    pub fn is_loaded(id: u32) -> bool {
+        critical_section::with(|cs| LOADED.borrow_ref(cs).iter().any(|(i, _)| *i == id))
+    }
+}
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        let id = self.entry.id;
+        critical_section::with(|cs| LOADED.borrow_ref_mut(cs).retain(|(i, _)| *i != id));
+    }
+}
+
+/// A `static` placed in an overlay by [`#[overlay(N)]`](macro@crate::overlay).
+///
+/// The item itself is in the main program: only the value it points to is in the overlay.
+pub struct OverlayItem<T: ?Sized + 'static> {
+    id: u32,
+    value: &'static T,
+}
+impl<T: ?Sized> OverlayItem<T> {
+    #[doc(hidden)]
+    pub const fn new(id: u32, value: &'static T) -> Self {
+        Self { id, value }
+    }
+
+    /// Overlay the item is in
+    pub const fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the value, which can be used as long as the overlay stays loaded.
+    /// Panics if `overlay` isn't the overlay of the item
+    pub fn get<'o>(&self, overlay: &'o Overlay) -> &'o T {
+        match self.try_get(overlay) {
+            Some(value) => value,
+            None => panic!(
+                "item of overlay {} used with overlay {}",
+                self.id,
+                overlay.id()
+            ),
+        }
+    }
+
+    /// Returns the value, or `None` if `overlay` isn't the overlay of the item
+    pub fn try_get<'o>(&self, overlay: &'o Overlay) -> Option<&'o T> {
+        (overlay.id() == self.id).then_some(self.value)
+    }
+}
+
+/// A function placed in an overlay by [`#[overlay(N)]`](macro@crate::overlay), as a pointer
+/// of type `F`, e.g. `OverlayFn<fn(u32) -> u32>`.
+///
+/// The pointer isn't given out: it can only be called through the [`OverlayFnRef`]
+/// returned by [`get`](Self::get), which borrows the overlay.
+pub struct OverlayFn<F> {
+    id: u32,
+    function: F,
+}
+impl<F: Copy> OverlayFn<F> {
+    #[doc(hidden)]
+    pub const fn new(id: u32, function: F) -> Self {
+        Self { id, function }
+    }
+
+    /// Overlay the function is in
+    pub const fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the function, which can be called as long as the overlay stays loaded.
+    /// Panics if `overlay` isn't the overlay of the function
+    pub fn get<'o>(&self, overlay: &'o Overlay) -> OverlayFnRef<'o, F> {
+        match self.try_get(overlay) {
+            Some(function) => function,
+            None => panic!(
+                "function of overlay {} used with overlay {}",
+                self.id,
+                overlay.id()
+            ),
+        }
+    }
+
+    /// Returns the function, or `None` if `overlay` isn't the overlay of the function
+    pub fn try_get<'o>(&self, overlay: &'o Overlay) -> Option<OverlayFnRef<'o, F>> {
+        (overlay.id() == self.id).then_some(OverlayFnRef {
+            function: self.function,
+            _overlay: PhantomData,
+        })
+    }
+}
+
+/// A function of a loaded overlay, called like the function itself:
+/// `spawn_enemies.get(&overlay)(level)`
+pub struct OverlayFnRef<'o, F> {
+    function: F,
+    _overlay: PhantomData<&'o Overlay>,
+}
+impl<F: Fn<Args>, Args: Tuple> FnOnce<Args> for OverlayFnRef<'_, F> {
+    type Output = F::Output;
+
+    extern "rust-call" fn call_once(self, args: Args) -> F::Output {
+        self.function.call(args)
+    }
+}
+impl<F: Fn<Args>, Args: Tuple> FnMut<Args> for OverlayFnRef<'_, F> {
+    extern "rust-call" fn call_mut(&mut self, args: Args) -> F::Output {
+        self.function.call(args)
+    }
+}
+impl<F: Fn<Args>, Args: Tuple> Fn<Args> for OverlayFnRef<'_, F> {
+    extern "rust-call" fn call(&self, args: Args) -> F::Output {
+        self.function.call(args)
+    }
+}
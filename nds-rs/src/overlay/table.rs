@@ -0,0 +1,277 @@
+//! Overlay and file allocation tables of the `.nds` format, independent of the hardware.
+//!
+//! Only the parsing is here: the tables are built on the host with the
+//! `nds-overlay-builder` crate.
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Size in bytes of an [`OverlayEntry`] in the overlay table
+pub const OVERLAY_ENTRY_SIZE: usize = 32;
+
+/// Size in bytes of a [`FatEntry`] in the file allocation table
+pub const FAT_ENTRY_SIZE: usize = 8;
+
+/// Set in [`OverlayEntry::flags`] when the overlay is compressed
+pub const OVERLAY_COMPRESSED: u32 = 1 << 24;
+
+/// An entry of the overlay table (`y9.bin`), describing where an overlay goes in RAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OverlayEntry {
+    pub id: u32,
+    /// Where the overlay is loaded
+    pub ram_address: u32,
+    /// Size of the code and data, read from the ROM
+    pub ram_size: u32,
+    /// Size of the zeroed data, after the code and data
+    pub bss_size: u32,
+    /// Start of the array of static initializers, called after loading
+    pub sinit_start: u32,
+    /// End of the array of static initializers
+    pub sinit_end: u32,
+    /// File of the overlay in the [file allocation table](FatEntry)
+    pub file_id: u32,
+    /// Compressed size in the lower 24 bits, and [`OVERLAY_COMPRESSED`]
+    pub flags: u32,
+}
+impl OverlayEntry {
+    pub fn from_bytes(bytes: &[u8; OVERLAY_ENTRY_SIZE]) -> Self {
+        let word =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        Self {
+            id: word(0),
+            ram_address: word(4),
+            ram_size: word(8),
+            bss_size: word(12),
+            sinit_start: word(16),
+            sinit_end: word(20),
+            file_id: word(24),
+            flags: word(28),
+        }
+    }
+
+    /// Addresses used by the overlay in RAM, BSS included
+    pub fn ram_range(&self) -> Range<u32> {
+        let end = self
+            .ram_address
+            .saturating_add(self.ram_size)
+            .saturating_add(self.bss_size);
+        self.ram_address..end
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.flags & OVERLAY_COMPRESSED != 0
+    }
+
+    /// Checks that the entry is consistent with itself
+    pub fn validate(&self) -> Result<(), TableError> {
+        let range = self.ram_range();
+        if self
+            .ram_address
+            .checked_add(self.ram_size)
+            .and_then(|end| end.checked_add(self.bss_size))
+            .is_none()
+        {
+            return Err(TableError::InvalidEntry(self.id));
+        }
+        let sinit_aligned = (self.sinit_start | self.sinit_end).is_multiple_of(4);
+        let sinit_inside = self.sinit_start == self.sinit_end
+            || (range.contains(&self.sinit_start) && self.sinit_end <= range.end);
+        if !sinit_aligned || self.sinit_start > self.sinit_end || !sinit_inside {
+            return Err(TableError::InvalidEntry(self.id));
+        }
+        Ok(())
+    }
+}
+
+/// An entry of the file allocation table, the location of a file in the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FatEntry {
+    pub start: u32,
+    pub end: u32,
+}
+impl FatEntry {
+    pub fn from_bytes(bytes: &[u8; FAT_ENTRY_SIZE]) -> Self {
+        let [a, b, c, d, e, f, g, h] = *bytes;
+        Self {
+            start: u32::from_le_bytes([a, b, c, d]),
+            end: u32::from_le_bytes([e, f, g, h]),
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Errors in overlay tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableError {
+    /// The table size isn't a multiple of the entry size
+    InvalidSize,
+    /// The entry of this overlay isn't consistent
+    InvalidEntry(u32),
+    /// The entry at this index doesn't have the index as its id
+    UnorderedId(usize),
+}
+
+/// Parses an overlay table, checking that the ids are the indices and that the entries
+/// are valid
+pub fn parse_overlay_table(bytes: &[u8]) -> Result<Vec<OverlayEntry>, TableError> {
+    if !bytes.len().is_multiple_of(OVERLAY_ENTRY_SIZE) {
+        return Err(TableError::InvalidSize);
+    }
+    bytes
+        .chunks_exact(OVERLAY_ENTRY_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let entry = OverlayEntry::from_bytes(chunk.try_into().unwrap());
+            if entry.id as usize != i {
+                return Err(TableError::UnorderedId(i));
+            }
+            entry.validate()?;
+            Ok(entry)
+        })
+        .collect()
+}
+
+/// Parses a file allocation table
+pub fn parse_fat(bytes: &[u8]) -> Result<Vec<FatEntry>, TableError> {
+    if !bytes.len().is_multiple_of(FAT_ENTRY_SIZE) {
+        return Err(TableError::InvalidSize);
+    }
+    let entries = bytes.chunks_exact(FAT_ENTRY_SIZE);
+    Ok(entries
+        .map(|chunk| FatEntry::from_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+/// Returns `true` if the ranges share at least one address
+pub fn overlaps(a: &Range<u32>, b: &Range<u32>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    /// Encodes the entries as `ndstool` expects them
+    fn encode(entries: &[OverlayEntry]) -> Vec<u8> {
+        let words = |e: &OverlayEntry| {
+            [
+                e.id,
+                e.ram_address,
+                e.ram_size,
+                e.bss_size,
+                e.sinit_start,
+                e.sinit_end,
+                e.file_id,
+                e.flags,
+            ]
+        };
+        entries
+            .iter()
+            .flat_map(words)
+            .flat_map(u32::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn overlay_table_parsing() {
+        let entries = vec![
+            OverlayEntry {
+                id: 0,
+                ram_address: 0x0230_0000,
+                ram_size: 0x1000,
+                bss_size: 0x100,
+                sinit_start: 0x0230_0F00,
+                sinit_end: 0x0230_0F08,
+                file_id: 3,
+                flags: 0,
+            },
+            OverlayEntry {
+                id: 1,
+                ram_address: 0x0230_0000,
+                ram_size: 0x800,
+                file_id: 4,
+                flags: OVERLAY_COMPRESSED | 0x600,
+                ..Default::default()
+            },
+        ];
+        let bytes = encode(&entries);
+        assert_eq!(bytes.len(), 2 * OVERLAY_ENTRY_SIZE);
+        assert_eq!(parse_overlay_table(&bytes), Ok(entries.clone()));
+        assert_eq!(entries[0].ram_range(), 0x0230_0000..0x0230_1100);
+        assert!(entries[1].is_compressed());
+        assert_eq!(parse_overlay_table(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn overlay_table_errors() {
+        let entry = OverlayEntry {
+            ram_address: 0x0230_0000,
+            ram_size: 0x100,
+            ..Default::default()
+        };
+        let bytes = encode(&[entry]);
+        assert_eq!(
+            parse_overlay_table(&bytes[..OVERLAY_ENTRY_SIZE - 1]),
+            Err(TableError::InvalidSize)
+        );
+
+        let bytes = encode(&[entry, entry]);
+        assert_eq!(parse_overlay_table(&bytes), Err(TableError::UnorderedId(1)));
+
+        // Static initializers outside of the overlay
+        let invalid = OverlayEntry {
+            sinit_start: 0x0200_0000,
+            sinit_end: 0x0200_0004,
+            ..entry
+        };
+        assert_eq!(
+            parse_overlay_table(&encode(&[invalid])),
+            Err(TableError::InvalidEntry(0))
+        );
+
+        // Past the end of the address space
+        let invalid = OverlayEntry {
+            ram_address: 0xFFFF_FF00,
+            ..entry
+        };
+        assert_eq!(
+            parse_overlay_table(&encode(&[invalid])),
+            Err(TableError::InvalidEntry(0))
+        );
+    }
+
+    #[test]
+    fn fat_parsing() {
+        let bytes = [
+            0x00, 0x40, 0, 0, 0x00, 0x48, 0, 0, 0x00, 0x4A, 0, 0, 0x00, 0x4A, 0, 0,
+        ];
+        let entries = parse_fat(&bytes).unwrap();
+        assert_eq!(
+            entries,
+            [
+                FatEntry {
+                    start: 0x4000,
+                    end: 0x4800,
+                },
+                FatEntry {
+                    start: 0x4A00,
+                    end: 0x4A00,
+                },
+            ]
+        );
+        assert_eq!(entries[0].len(), 0x800);
+        assert!(entries[1].is_empty());
+        assert_eq!(parse_fat(&bytes[..12]), Err(TableError::InvalidSize));
+    }
+}
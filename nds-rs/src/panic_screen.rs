@@ -0,0 +1,222 @@
+//! The screen shown when the program panics or crashes
+//!
+//! [`show`] takes over the sub engine (usually the bottom screen) with a text
+//! [`Console`] and prints a [`Report`]: what happened, where, the registers for CPU
+//! exceptions, a [backtrace](crate::backtrace) and, with the `log` feature, the
+//! last log entries kept by [`RingSink`](crate::logger::RingSink).
+//!
+//! The report is also sent to the NO$GBA debugger when running in an emulator,
+//! and written to [`REPORT_PATH`] if a filesystem is available
+//! (`fatInitDefault` must have been called). CPU exceptions use
+//! [`show_without_file`] instead, since they can happen inside the C library.
+//!
+//! Then it waits for a key: A exits to the loader (or powers off if there's none),
+//! B powers off.
+//!
+//! Nothing is allocated on the way, so it also works when the heap is broken. If the
+//! screen itself panics, the nested report skips the file, and a third one only
+//! waits for a key.
+
+use core::{
+    ffi::CStr,
+    fmt::{self, Display, Write},
+    mem::MaybeUninit,
+    panic::Location,
+    ptr::addr_of_mut,
+};
+
+use nds_sys::{
+    console::PrintConsole,
+    effects::{DB_BLDCNT, DB_MASTER_BRIGHT},
+    input::{KeypadBits, REG_KEYINPUT},
+    interrupts::REG_IME,
+    system::{registers::POWCNT, systemShutDown, PowerFlags},
+};
+use portable_atomic::{AtomicU8, Ordering};
+
+use crate::{backtrace, console::Console, debug::NoCash, header::NdsHeader};
+
+/// File where the report is written, on the default drive
+pub const REPORT_PATH: &CStr = c"/panic.txt";
+
+/// Times [`show`] was entered, to detect panics while showing a report
+static ENTERED: AtomicU8 = AtomicU8::new(0);
+/// State of the console of the screen, so it's not allocated
+static mut CONSOLE: MaybeUninit<PrintConsole> = MaybeUninit::uninit();
+
+/// General purpose registers and status register of the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    /// `r0` to `r15`. `r13` is `sp`, `r14` is `lr` and `r15` is `pc`
+    pub r: [u32; 16],
+    pub cpsr: u32,
+}
+/// Two registers per line, so it fits a 32 columns console
+impl Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [&str; 16] = [
+            "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp",
+            "lr", "pc",
+        ];
+        for (pair, names) in self.r.chunks(2).zip(NAMES.chunks(2)) {
+            writeln!(
+                f,
+                "{:>4} {:08X}  {:>4} {:08X}",
+                names[0], pair[0], names[1], pair[1]
+            )?;
+        }
+        writeln!(f, "cpsr {:08X}", self.cpsr)
+    }
+}
+
+/// Everything shown by [`show`]
+pub struct Report<'a> {
+    /// What happened, e.g. `"panicked"` or `"data abort"`
+    pub kind: &'a str,
+    pub message: Option<&'a dyn Display>,
+    pub location: Option<&'a Location<'a>>,
+    pub registers: Option<&'a Registers>,
+    /// Frame pointer to start the backtrace from, 0 to skip it
+    pub frame_pointer: usize,
+}
+impl Report<'_> {
+    /// Writes the report as text
+    pub fn write(&self, w: &mut dyn Write) -> fmt::Result {
+        let title = NdsHeader::running().title().unwrap_or("Unknown");
+        writeln!(w, "'{title}' {}", self.kind)?;
+        if let Some(location) = self.location {
+            writeln!(
+                w,
+                "at {}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            )?;
+        }
+        if let Some(message) = self.message {
+            writeln!(w, "{message}")?;
+        }
+        if let Some(registers) = self.registers {
+            writeln!(w)?;
+            write!(w, "{registers}")?;
+        }
+        if self.frame_pointer != 0 {
+            writeln!(w, "\nBacktrace:")?;
+            let frames = backtrace::walk(self.frame_pointer, backtrace::read_stack_word);
+            for (i, address) in frames.enumerate() {
+                writeln!(w, "{i:>3}: {address:08X}")?;
+            }
+        }
+        #[cfg(feature = "log")]
+        crate::logger::ring_contents(|ring| {
+            if ring.is_empty() {
+                Ok(())
+            } else {
+                write!(w, "\nLast log entries:\n{ring}")
+            }
+        })
+        .unwrap_or(Ok(()))?;
+        Ok(())
+    }
+}
+
+/// Writes to every output of the panic screen at once
+struct Outputs {
+    console: Console,
+    file: *mut libc::FILE,
+    nocash: bool,
+}
+impl Write for Outputs {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.console.write_str(s)?;
+        if !self.file.is_null() {
+            unsafe {
+                libc::fwrite(s.as_ptr() as _, 1, s.len(), self.file);
+            }
+        }
+        if self.nocash {
+            NoCash.write_str(s)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows `report` on the bottom screen and waits for a key to exit or power off.
+///
+/// Interrupts are disabled, and the sub engine is reset to a text mode.
+pub fn show(report: &Report) -> ! {
+    show_report(report, true)
+}
+
+/// Same as [`show`], without writing the report to [`REPORT_PATH`].
+///
+/// Meant for CPU exceptions: if one happened inside `malloc` or the C library, opening the
+/// file would wait for the lock held by the faulting code forever.
+pub fn show_without_file(report: &Report) -> ! {
+    show_report(report, false)
+}
+
+fn show_report(report: &Report, write_file: bool) -> ! {
+    let entered = ENTERED.fetch_add(1, Ordering::Relaxed);
+    if entered >= 2 {
+        // Showing the report keeps panicking
+        wait_and_exit();
+    }
+    unsafe {
+        REG_IME.write_volatile(0);
+        let power = PowerFlags::from_bits_retain(POWCNT.read_volatile())
+            | PowerFlags::POWER_LCD
+            | PowerFlags::SUB_ENGINE;
+        POWCNT.write_volatile(power.bits());
+        DB_MASTER_BRIGHT.write_volatile(0);
+        DB_BLDCNT.write_volatile(0);
+    }
+    // SAFETY: nothing else is going to use the screen anymore, and the console storage
+    // was only used by the report that panicked, if any
+    let console = unsafe { Console::demo_in(&mut *addr_of_mut!(CONSOLE)) };
+    // The C library may be what panicked
+    let file = if entered == 0 && write_file {
+        unsafe { libc::fopen(REPORT_PATH.as_ptr(), c"w".as_ptr()) }
+    } else {
+        core::ptr::null_mut()
+    };
+    let mut outputs = Outputs {
+        console,
+        file,
+        nocash: NoCash::get_emu_id().is_some(),
+    };
+    let _ = report.write(&mut outputs);
+    if !file.is_null() {
+        unsafe {
+            libc::fclose(file);
+        }
+    }
+    outputs.file = core::ptr::null_mut();
+    outputs.nocash = false;
+    let _ = write!(outputs, "\n\x1b[93mA: exit  B: power off\x1b[39m");
+    wait_and_exit()
+}
+
+/// Exits on A, powers off on B
+fn wait_and_exit() -> ! {
+    loop {
+        match wait_for_key(KeypadBits::A as u16 | KeypadBits::B as u16) {
+            k if k & KeypadBits::A as u16 != 0 => unsafe { libc::exit(1) },
+            _ => unsafe { systemShutDown() },
+        }
+    }
+}
+
+/// Polls the keypad until one of `keys` is pressed, after all of them are released.
+/// Works with interrupts disabled. Returns the pressed keys
+fn wait_for_key(keys: u16) -> u16 {
+    // Bits are cleared while the key is pressed
+    let pressed = || !unsafe { REG_KEYINPUT.read_volatile() } & keys;
+    while pressed() != 0 {}
+    loop {
+        let keys = pressed();
+        if keys != 0 {
+            return keys;
+        }
+    }
+}
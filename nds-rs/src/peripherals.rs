@@ -1,6 +1,6 @@
 use spin::Mutex;
 
-use crate::{system::System, video::Video};
+use crate::{dma::Dma, ndma::Ndma, system::System, video::Video};
 
 #[no_mangle]
 pub static __HW: Mutex<Option<Hw>> = Mutex::new(Some(unsafe { Hw::new() }));
@@ -27,7 +27,10 @@ pub static __HW: Mutex<Option<Hw>> = Mutex::new(Some(unsafe { Hw::new() }));
 #[non_exhaustive]
 pub struct Hw {
     pub video: Video,
-    pub system: System
+    pub system: System,
+    pub dma: Dma,
+    /// Only usable in DSi mode
+    pub ndma: Ndma,
 }
 impl Drop for Hw {
     fn drop(&mut self) {
@@ -40,7 +43,9 @@ impl Hw {
     pub(crate) const unsafe fn new() -> Self {
         Self {
             video: Video::new(),
-            system: System::new()
+            system: System::new(),
+            dma: Dma::new(),
+            ndma: Ndma::new(),
         }
     }
 
@@ -1,4 +1,9 @@
-use crate::{header::NdsHeader, Hw};
+use crate::Hw;
+#[cfg(not(feature = "arm7"))]
+use crate::{
+    backtrace,
+    panic_screen::{self, Report},
+};
 
 /// Entry point called from the C runtime
 ///
@@ -12,6 +17,10 @@ pub unsafe extern "C" fn main() {
         fn main(hw: Hw) -> ();
     }
 
+    // Unlike the ARM9, the ARM7 program has to set up libnds itself
+    #[cfg(feature = "arm7")]
+    crate::arm7::init();
+
     let peripherals = Hw::take().unwrap_unchecked();
     unsafe {
         main(peripherals);
@@ -19,17 +28,26 @@ pub unsafe extern "C" fn main() {
 }
 
 #[panic_handler]
+#[cfg(not(feature = "arm7"))]
 pub unsafe fn panic(info: &core::panic::PanicInfo) -> ! {
-    let game_title = NdsHeader::running().title().unwrap_or("Unknown");
-    println!("'{game_title}' panicked");
-    if let Some(location) = info.location() {
-        println!(
-            "{}:{}:{}",
-            location.file(),
-            location.line(),
-            location.column()
-        );
+    let message = info.message();
+    panic_screen::show(&Report {
+        kind: "panicked",
+        message: Some(&message),
+        location: info.location(),
+        registers: None,
+        frame_pointer: backtrace::frame_pointer(),
+    })
+}
+
+// The ARM7 has no screen: the message goes to the emulator debugger, and the ARM7 stops
+#[panic_handler]
+#[cfg(feature = "arm7")]
+pub fn panic(info: &core::panic::PanicInfo) -> ! {
+    use core::fmt::Write;
+    let _ = writeln!(crate::debug::NoCash, "ARM7 {info}");
+    crate::debug::NoCash::breakpoint();
+    loop {
+        crate::interrupts::swi_wait_for_v_blank();
     }
-    println!("{}", info.message());
-    loop {}
 }
@@ -1,11 +1,18 @@
 use nds_sys::sprite;
 
+mod animation;
+mod metasprite;
+pub use animation::*;
+pub use metasprite::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Shape {
     ShapeSquare = sprite::Attr0::SHAPE_SQUARE.bits() as isize,
     ShapeWide = sprite::Attr0::SHAPE_WIDE.bits() as isize,
     ShapeTall = sprite::Attr0::SHAPE_TALL.bits() as isize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Size {
     SizeSmall = sprite::Attr1::SIZE_SMALL.bits() as isize,
     SizeMed = sprite::Attr1::SIZE_MED.bits() as isize,
@@ -13,6 +20,21 @@ pub enum Size {
     SizeMax = sprite::Attr1::SIZE_MAX.bits() as isize,
 }
 
+/// Width and height in pixels of OBJs of this shape and size
+pub const fn dimensions(shape: Shape, size: Size) -> (u16, u16) {
+    let side = match size {
+        Size::SizeSmall => 0,
+        Size::SizeMed => 1,
+        Size::SizeBig => 2,
+        Size::SizeMax => 3,
+    };
+    match shape {
+        Shape::ShapeSquare => (8 << side, 8 << side),
+        Shape::ShapeWide => ([16, 32, 32, 64][side], [8, 8, 16, 32][side]),
+        Shape::ShapeTall => ([8, 8, 16, 32][side], [16, 32, 32, 64][side]),
+    }
+}
+
 // TODO: Maybe the `attr`s should be each one their own struct?
 #[repr(align(64))]
 #[derive(Clone, Copy)]
@@ -41,6 +63,32 @@ impl Obj {
         let bits = self.attr0.bits() & !sprite::Y_COORD_MASK;
         self.attr0 = sprite::Attr0::from_bits_retain(bits | y);
     }
+    /// Sets the first tile of the sprite, in units of 32 bytes with the 1D mapping
+    pub fn set_tile(&mut self, tile: u16) {
+        debug_assert!(tile <= sprite::ID_MASK, "tile index out of range");
+        self.attr2 = (self.attr2 & !sprite::ID_MASK) | tile;
+    }
+    pub fn tile(&self) -> u16 {
+        self.attr2 & sprite::ID_MASK
+    }
+    /// Sets the palette of 16 colors sprites
+    pub fn set_palette(&mut self, palette: u16) {
+        debug_assert!(palette < 16, "palette out of range");
+        self.attr2 = (self.attr2 & !sprite::COLOR) | (palette << 12);
+    }
+    pub fn set_shape(&mut self, shape: Shape) {
+        let bits = self.attr0.bits() & !(0b11 << 14);
+        self.attr0 = sprite::Attr0::from_bits_retain(bits | shape as u16);
+    }
+    pub fn set_size(&mut self, size: Size) {
+        let bits = self.attr1.bits() & !(0b11 << 14);
+        self.attr1 = sprite::Attr1::from_bits_retain(bits | size as u16);
+    }
+    /// Shows the OBJ, without transformation
+    pub fn show(&mut self) {
+        self.attr0
+            .remove(sprite::Attr0::AFFINE_ENABLE | sprite::Attr0::DOUBLE_SIZE);
+    }
     pub fn hide(&mut self) {
         self.attr0.remove(sprite::Attr0::AFFINE_ENABLE);
         self.attr0.insert(sprite::Attr0::DOUBLE_SIZE);
@@ -0,0 +1,121 @@
+//! Animations of [`MetaSprite`]s
+
+use super::{MetaSprite, Obj};
+
+/// A frame of an [`Animation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFrame {
+    /// Index of the frame in the [`MetaSprite`]
+    pub frame: u16,
+    /// Duration in video frames, at least 1
+    pub duration: u16,
+}
+
+/// A timeline of frames, like a tag of Aseprite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Animation {
+    pub name: &'static str,
+    pub frames: &'static [AnimationFrame],
+    /// Number of times the animation plays, 0 to loop forever
+    pub repeat: u16,
+}
+
+/// An [`Animation`] being played by a [`MetaSprite`]
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteAnimation {
+    sprite: &'static MetaSprite,
+    animation: &'static Animation,
+    base_tile: u16,
+    index: usize,
+    timer: u16,
+    loops: u16,
+    finished: bool,
+}
+impl SpriteAnimation {
+    /// Plays `animation` from its start, with the tiles of `sprite` loaded at `base_tile`
+    /// with [`Tiles::load_sprite`](crate::gfx::Tiles::load_sprite)
+    pub fn new(sprite: &'static MetaSprite, animation: &'static Animation, base_tile: u16) -> Self {
+        assert!(!animation.frames.is_empty(), "the animation has no frames");
+        Self {
+            sprite,
+            animation,
+            base_tile,
+            index: 0,
+            timer: 0,
+            loops: 0,
+            finished: false,
+        }
+    }
+
+    /// Switches to `animation` and restarts it, unless it's already playing
+    pub fn play(&mut self, animation: &'static Animation) {
+        if !core::ptr::eq(self.animation, animation) {
+            *self = Self::new(self.sprite, animation, self.base_tile);
+        }
+    }
+
+    /// Restarts the animation
+    pub fn restart(&mut self) {
+        *self = Self::new(self.sprite, self.animation, self.base_tile);
+    }
+
+    /// Advances the animation by one video frame. Returns `true` if the frame changed
+    pub fn tick(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+        self.timer += 1;
+        if self.timer < self.animation.frames[self.index].duration {
+            return false;
+        }
+        self.timer = 0;
+        if self.index + 1 < self.animation.frames.len() {
+            self.index += 1;
+            return true;
+        }
+        self.loops = self.loops.saturating_add(1);
+        if self.animation.repeat != 0 && self.loops >= self.animation.repeat {
+            self.finished = true;
+            return false;
+        }
+        self.index = 0;
+        self.animation.frames.len() > 1
+    }
+
+    pub const fn sprite(&self) -> &'static MetaSprite {
+        self.sprite
+    }
+
+    pub const fn animation(&self) -> &'static Animation {
+        self.animation
+    }
+
+    /// The current frame of the [`MetaSprite`]
+    pub const fn frame(&self) -> u16 {
+        self.animation.frames[self.index].frame
+    }
+
+    /// Whether the animation played as many times as it repeats. It then stays on
+    /// its last frame
+    pub const fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Tile index of a piece for the current frame
+    pub fn tile(&self, piece: usize) -> u16 {
+        self.sprite.tile(self.base_tile, self.frame(), piece)
+    }
+
+    /// Updates the tile indices of the OBJs placed by [`MetaSprite::place`] to show the
+    /// current frame
+    pub fn apply(&self, objs: &mut [Obj]) {
+        for (piece, obj) in objs.iter_mut().take(self.sprite.pieces.len()).enumerate() {
+            obj.set_tile(self.tile(piece));
+        }
+    }
+
+    /// Places the OBJs with [`MetaSprite::place`] on the current frame
+    pub fn place(&self, objs: &mut [Obj], x: i32, y: i32) {
+        self.sprite.place(objs, x, y, self.frame(), self.base_tile);
+    }
+}
@@ -0,0 +1,88 @@
+//! Sprites made of several OBJs
+
+use nds_sys::{
+    sprite::{Attr0, X_COORD_MASK, Y_COORD_MASK},
+    video::{SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+use super::{dimensions, Animation, Obj, Shape, Size};
+use crate::gfx::{Bpp, Palette, Tiles};
+
+/// An OBJ of a [`MetaSprite`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Piece {
+    /// Position relative to the top left corner of the metasprite
+    pub x: i16,
+    pub y: i16,
+    pub shape: Shape,
+    pub size: Size,
+    /// First tile of the OBJ relative to the frame, in units of 32 bytes
+    pub tile: u16,
+}
+impl Piece {
+    /// Width and height in pixels
+    pub const fn dimensions(&self) -> (u16, u16) {
+        dimensions(self.shape, self.size)
+    }
+}
+
+/// A sprite bigger than an OBJ, or made of OBJs of different sizes, with its frames.
+///
+/// All the frames use the same [`Piece`]s. Their tiles are stored frame after frame,
+/// for the 1D mapping with a boundary of 32 bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct MetaSprite {
+    pub tiles: Tiles,
+    pub palette: Palette,
+    /// Size of the frames in pixels
+    pub width: u16,
+    pub height: u16,
+    pub pieces: &'static [Piece],
+    /// First tile of each frame relative to the tiles, in units of 32 bytes.
+    /// Identical frames share their tiles
+    pub frames: &'static [u16],
+    pub animations: &'static [&'static Animation],
+}
+impl MetaSprite {
+    /// The animation named `name`
+    pub fn animation(&self, name: &str) -> Option<&'static Animation> {
+        self.animations
+            .iter()
+            .copied()
+            .find(|animation| animation.name == name)
+    }
+
+    /// Tile index of a piece of a frame for [`Obj::set_tile`], when the tiles were
+    /// loaded at `base_tile` with [`Tiles::load_sprite`]
+    pub fn tile(&self, base_tile: u16, frame: u16, piece: usize) -> u16 {
+        base_tile + self.frames[frame as usize] + self.pieces[piece].tile
+    }
+
+    /// Sets up an OBJ per piece, to show the frame `frame` with its top left corner at
+    /// (`x`, `y`) on the screen. Pieces out of the screen are hidden.
+    ///
+    /// The other attributes of the OBJs (palette, priority, mode...) are kept.
+    pub fn place(&self, objs: &mut [Obj], x: i32, y: i32, frame: u16, base_tile: u16) {
+        assert!(objs.len() >= self.pieces.len(), "not enough OBJs");
+        for (i, (obj, piece)) in objs.iter_mut().zip(self.pieces).enumerate() {
+            let (width, height) = piece.dimensions();
+            let (x, y) = (x + piece.x as i32, y + piece.y as i32);
+            if x + width as i32 <= 0
+                || x >= SCREEN_WIDTH as i32
+                || y + height as i32 <= 0
+                || y >= SCREEN_HEIGHT as i32
+            {
+                obj.hide();
+                continue;
+            }
+            obj.set_shape(piece.shape);
+            obj.set_size(piece.size);
+            obj.set_x(x as u16 & X_COORD_MASK);
+            obj.set_y(y as u16 & Y_COORD_MASK);
+            obj.attr0
+                .set(Attr0::COLOR_256, self.tiles.bpp() == Bpp::Eight);
+            obj.show();
+            obj.set_tile(self.tile(base_tile, frame, i));
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use nds_sys::system::PowerFlags;
 
+use crate::dsi::{self, Capabilities, CpuSpeed, Model, Unsupported};
+
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum Screen {
     Top,
@@ -22,6 +24,40 @@ impl System {
         unsafe { nds_sys::system::getBatteryLevel() }
     }
 
+    /// The console running the program, see [`dsi::model`]
+    pub fn model() -> Model {
+        dsi::model()
+    }
+
+    /// Returns `true` when running in DSi mode
+    pub fn is_dsi_mode() -> bool {
+        dsi::is_dsi_mode()
+    }
+
+    /// The hardware available to the program, see [`dsi::capabilities`]
+    pub fn capabilities() -> Capabilities {
+        dsi::capabilities()
+    }
+
+    pub fn cpu_speed(&self) -> CpuSpeed {
+        match dsi::scfg_clock() {
+            Some(clock) if clock.contains(dsi::ScfgClock::ARM9_133MHZ) => CpuSpeed::Mhz133,
+            _ => CpuSpeed::Mhz67,
+        }
+    }
+
+    /// Switches the speed of the ARM9, returning the previous one.
+    ///
+    /// Needs DSi mode and unlocked SCFG registers, otherwise returns `Err(Unsupported)`.
+    pub fn set_cpu_speed(&mut self, speed: CpuSpeed) -> Result<CpuSpeed, Unsupported> {
+        dsi::require(Capabilities::SCFG)?;
+        let previous = unsafe { nds_sys::dsi::setCpuClock(speed == CpuSpeed::Mhz133) };
+        Ok(match previous {
+            true => CpuSpeed::Mhz133,
+            false => CpuSpeed::Mhz67,
+        })
+    }
+
     /// Controls weather the main engine should output to the top or bottom screen
     pub fn main_engine_on(&mut self, wanted: Screen) {
         let powercnt = unsafe { nds_sys::system::registers::POWCNT.read_volatile() };
@@ -0,0 +1,124 @@
+//! Tightly coupled memories (TCM) of the ARM9
+//!
+//! The ARM9 has two small memories that it accesses in a single cycle, without going
+//! through the bus or the cache:
+//! - the instruction TCM (ITCM), 32 KiB for code. Use [`#[itcm]`](crate::itcm) to place
+//!   functions there,
+//! - the data TCM (DTCM), 16 KiB that also holds the stacks. Use [`#[dtcm]`](crate::dtcm)
+//!   to place statics there, and [`DtcmCell`] for the ones that must be mutable.
+//!
+//! Only the ARM9 can access them: the DMA and the ARM7 can't read or write TCM.
+
+use core::{arch::asm, cell::RefCell, ops::Range};
+
+use critical_section::Mutex;
+
+/// Location of a TCM (CP15 register 9)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcmRegion {
+    /// Start address, aligned to the size
+    pub base: u32,
+    /// The TCM covers `512 << size_log2` bytes. The memory is mirrored
+    /// when that's bigger than the TCM
+    pub size_log2: u8,
+}
+impl TcmRegion {
+    pub const fn from_bits(bits: u32) -> Self {
+        Self {
+            base: bits & 0xFFFF_F000,
+            size_log2: ((bits >> 1) & 0b11111) as u8,
+        }
+    }
+
+    /// Size of the region in bytes, saturated to `u32::MAX`
+    pub const fn size(&self) -> u32 {
+        match 512u32.checked_shl(self.size_log2 as u32) {
+            Some(size) if self.size_log2 < 23 => size,
+            _ => u32::MAX,
+        }
+    }
+
+    /// Returns `true` if `address` is in the region
+    pub const fn contains(&self, address: u32) -> bool {
+        let size = self.size();
+        address.wrapping_sub(self.base) < size || size == u32::MAX
+    }
+}
+
+/// Where the instruction TCM is mapped.
+///
+/// The runtime maps it at address 0 with a 32 MiB size, so it's mirrored up to `0x01FFFFFF`.
+/// Code placed with [`#[itcm]`](crate::itcm) runs from the last mirror, at `0x01FF8000`.
+pub fn itcm() -> TcmRegion {
+    let bits: u32;
+    unsafe {
+        asm!("mrc p15, 0, {}, c9, c1, 1", out(reg) bits, options(nomem, nostack));
+    }
+    TcmRegion::from_bits(bits)
+}
+
+/// Where the data TCM is mapped
+pub fn dtcm() -> TcmRegion {
+    let bits: u32;
+    unsafe {
+        asm!("mrc p15, 0, {}, c9, c1, 0", out(reg) bits, options(nomem, nostack));
+    }
+    TcmRegion::from_bits(bits)
+}
+
+/// Size of the data TCM in bytes
+pub const DTCM_SIZE: u32 = 16 * 1024;
+
+/// Addresses of the data TCM where the runtime mapped it, without its mirrors
+pub fn dtcm_range() -> Range<usize> {
+    let dtcm = dtcm();
+    let size = dtcm.size().min(DTCM_SIZE);
+    dtcm.base as usize..(dtcm.base + size) as usize
+}
+
+/// Returns `true` if `ptr` points to the data TCM, which the DMA can't access
+pub fn is_in_dtcm<T: ?Sized>(ptr: *const T) -> bool {
+    dtcm().contains(ptr as *const u8 as u32)
+}
+
+/// A value that can be changed from a `static`, meant to be placed in the data TCM.
+///
+/// Interrupts are disabled while the value is in use, so it can be shared
+/// with interrupt handlers.
+///
+/// # Example:
+/// ```rust,no_run
+/// #[dtcm]
+/// static PARTICLES: DtcmCell<[Particle; 64]> = DtcmCell::new([Particle::ZERO; 64]);
+///
+/// PARTICLES.with(|particles| particles.iter_mut().for_each(Particle::update));
+/// ```
+pub struct DtcmCell<T> {
+    value: Mutex<RefCell<T>>,
+}
+impl<T> DtcmCell<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: Mutex::new(RefCell::new(value)),
+        }
+    }
+
+    /// Runs `f` with the value. Panics if called from `f`
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.value.borrow_ref_mut(cs)))
+    }
+
+    /// Replaces the value, returning the old one
+    pub fn replace(&self, value: T) -> T {
+        critical_section::with(|cs| self.value.borrow(cs).replace(value))
+    }
+}
+impl<T: Copy> DtcmCell<T> {
+    pub fn get(&self) -> T {
+        critical_section::with(|cs| *self.value.borrow_ref(cs))
+    }
+
+    pub fn set(&self, value: T) {
+        self.replace(value);
+    }
+}
@@ -1,5 +1,12 @@
 use crate::background::{MainGraphicsMode, SubGraphicsMode, ValidGraphicsMode};
 
+/// The two 2D graphics engines
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Engine {
+    Main,
+    Sub,
+}
+
 pub struct Video {}
 impl Video {
     pub const fn new() -> Self {
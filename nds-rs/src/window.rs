@@ -0,0 +1,245 @@
+//! Windows of the 2D engines
+//!
+//! Each engine has two rectangular windows ([`WindowId::Win0`] has priority over [`WindowId::Win1`]),
+//! an object window (the opaque pixels of the sprites in [`WINDOW`](nds_sys::sprite::Attr0::WINDOW) mode),
+//! and the area outside all of them. Each of these regions selects which layers are shown
+//! and whether color effects apply.
+//!
+//! Windows are configured with [`Windows`], either as part of a [`GraphicsMode`](crate::background::GraphicsMode)
+//! (See [`MainGraphicsModeSettings::with_windows`](crate::background::MainGraphicsModeSettings::with_windows))
+//! or at any time using [`Video::set_windows`].
+//!
+//! Non rectangular shapes can be made by changing the horizontal bounds on every scanline,
+//! see [`hblank_bounds`].
+
+use nds_sys::{
+    video::{DispCntFlags, REG_DISPCNT, REG_DISPCNT_SUB},
+    window::*,
+};
+
+pub use nds_sys::window::WindowLayers;
+
+use crate::{
+    dma::{DmaChannel, HBlankDma, ScanlineTable},
+    video::{Engine, Video},
+};
+
+/// One of the two rectangular windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowId {
+    Win0,
+    Win1,
+}
+
+/// A rectangle in screen coordinates. `right` and `bottom` are exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub left: u8,
+    pub top: u8,
+    pub right: u8,
+    pub bottom: u8,
+}
+impl Rect {
+    pub const fn new(left: u8, top: u8, right: u8, bottom: u8) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// Value of the horizontal bounds register (`WINxH`)
+    pub const fn h_bits(&self) -> u16 {
+        span(self.left, self.right)
+    }
+
+    /// Value of the vertical bounds register (`WINxV`)
+    pub const fn v_bits(&self) -> u16 {
+        span(self.top, self.bottom)
+    }
+}
+
+/// Packs the bounds of a window along one axis, as expected by the bounds registers.
+/// `end` is exclusive.
+///
+/// Used to build the tables for [`hblank_bounds`].
+pub const fn span(start: u8, end: u8) -> u16 {
+    ((start as u16) << 8) | end as u16
+}
+
+/// A rectangular window and the layers shown inside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub bounds: Rect,
+    pub layers: WindowLayers,
+}
+
+/// Window configuration of an engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Windows {
+    /// `None` disables window 0
+    pub win0: Option<Window>,
+    /// `None` disables window 1
+    pub win1: Option<Window>,
+    /// Layers shown inside the object window, `None` disables it
+    pub obj_window: Option<WindowLayers>,
+    /// Layers shown outside every enabled window
+    pub outside: WindowLayers,
+}
+impl Windows {
+    /// All windows disabled
+    pub const fn new() -> Self {
+        Self {
+            win0: None,
+            win1: None,
+            obj_window: None,
+            outside: WindowLayers::all(),
+        }
+    }
+
+    pub const fn with_window(mut self, id: WindowId, window: Window) -> Self {
+        match id {
+            WindowId::Win0 => self.win0 = Some(window),
+            WindowId::Win1 => self.win1 = Some(window),
+        }
+        self
+    }
+
+    pub const fn with_obj_window(mut self, layers: WindowLayers) -> Self {
+        self.obj_window = Some(layers);
+        self
+    }
+
+    pub const fn with_outside(mut self, layers: WindowLayers) -> Self {
+        self.outside = layers;
+        self
+    }
+
+    /// Returns `true` if any window is enabled
+    pub const fn any_enabled(&self) -> bool {
+        self.win0.is_some() || self.win1.is_some() || self.obj_window.is_some()
+    }
+
+    /// Bits of the display control register that enable each window
+    pub const fn display_flags(&self) -> DispCntFlags {
+        let mut flags = DispCntFlags::empty();
+        if self.win0.is_some() {
+            flags = flags.union(DispCntFlags::WIN0);
+        }
+        if self.win1.is_some() {
+            flags = flags.union(DispCntFlags::WIN1);
+        }
+        if self.obj_window.is_some() {
+            flags = flags.union(DispCntFlags::OBJ_WINDOW);
+        }
+        flags
+    }
+
+    /// Value of `WININ`
+    pub const fn inside_bits(&self) -> u16 {
+        let win0 = match self.win0 {
+            Some(w) => w.layers.bits(),
+            None => 0,
+        };
+        let win1 = match self.win1 {
+            Some(w) => w.layers.bits(),
+            None => 0,
+        };
+        u16::from_le_bytes([win0, win1])
+    }
+
+    /// Value of `WINOUT`
+    pub const fn outside_bits(&self) -> u16 {
+        let obj = match self.obj_window {
+            Some(layers) => layers.bits(),
+            None => 0,
+        };
+        u16::from_le_bytes([self.outside.bits(), obj])
+    }
+
+    /// Writes the bounds and layers of every window of `engine`.
+    /// Doesn't touch the enable bits of the display control register.
+    pub(crate) fn write_registers(&self, engine: Engine) {
+        let [h0, v0, h1, v1, inside, outside] = registers(engine);
+        unsafe {
+            if let Some(w) = self.win0 {
+                h0.write_volatile(w.bounds.h_bits());
+                v0.write_volatile(w.bounds.v_bits());
+            }
+            if let Some(w) = self.win1 {
+                h1.write_volatile(w.bounds.h_bits());
+                v1.write_volatile(w.bounds.v_bits());
+            }
+            inside.write_volatile(self.inside_bits());
+            outside.write_volatile(self.outside_bits());
+        }
+    }
+}
+impl Default for Windows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `[WIN0H, WIN0V, WIN1H, WIN1V, WININ, WINOUT]` for `engine`
+const fn registers(engine: Engine) -> [*mut u16; 6] {
+    match engine {
+        Engine::Main => [WIN0H, WIN0V, WIN1H, WIN1V, WININ, WINOUT],
+        Engine::Sub => [DB_WIN0H, DB_WIN0V, DB_WIN1H, DB_WIN1V, DB_WININ, DB_WINOUT],
+    }
+}
+
+/// Horizontal bounds register of window `id` of `engine`
+pub const fn h_register(engine: Engine, id: WindowId) -> *mut u16 {
+    let [h0, _, h1, ..] = registers(engine);
+    match id {
+        WindowId::Win0 => h0,
+        WindowId::Win1 => h1,
+    }
+}
+
+impl Video {
+    /// Configures the windows of `engine`, enabling or disabling them as needed.
+    pub fn set_windows(&mut self, engine: Engine, windows: &Windows) {
+        windows.write_registers(engine);
+        let dispcnt = match engine {
+            Engine::Main => REG_DISPCNT,
+            Engine::Sub => REG_DISPCNT_SUB,
+        };
+        unsafe {
+            let flags = DispCntFlags::from_bits_retain(dispcnt.read_volatile())
+                .difference(DispCntFlags::WINDOWS_MASK)
+                .union(windows.display_flags());
+            dispcnt.write_volatile(flags.bits());
+        }
+    }
+}
+
+/// Changes the horizontal bounds of window `id` on every scanline, using HBlank DMA.
+/// Each entry of `table` is made with [`span`]. The vertical bounds still apply,
+/// so they should cover the lines where the shape is visible.
+///
+/// # Example
+/// ```rust,no_run
+/// // A circle of radius 64 in the middle of the screen
+/// let mut table = ScanlineTable::from_fn(|line| {
+///     let dy = line as i32 - 96;
+///     let dx = (64 * 64 - dy * dy).max(0).isqrt();
+///     span((128 - dx) as u8, (128 + dx) as u8)
+/// });
+/// let mut hdma = hblank_bounds(&mut hw.dma.ch0, &mut table, Engine::Main, WindowId::Win0);
+/// loop {
+///     swi_wait_for_v_blank();
+///     hdma.vblank();
+/// }
+/// ```
+pub fn hblank_bounds<'t>(
+    dma: &'t mut DmaChannel,
+    table: &'t mut ScanlineTable<u16>,
+    engine: Engine,
+    id: WindowId,
+) -> HBlankDma<'t, u16> {
+    // SAFETY: any value is valid for a bounds register
+    unsafe { dma.hblank(table, h_register(engine, id)) }
+}
@@ -45,9 +45,13 @@ fn main() {
         .unwrap_or_else(|_| wonderful_toolchain.join("thirdparty/blocksds/core"));
     let gcc_dir = wonderful_toolchain.join("toolchain/gcc-arm-none-eabi");
 
-    let libnds_path = blocksds.join("libs/libnds/lib/libnds9.a");
+    // The ARM7 binary links libnds7 and can't use the headers of the ARM9 hardware
+    let arm7 = env::var_os("CARGO_FEATURE_ARM7").is_some();
+    let lib_name = if arm7 { "nds7" } else { "nds9" };
+
+    let libnds_path = blocksds.join(format!("libs/libnds/lib/lib{lib_name}.a"));
     if !libnds_path.exists() {
-        panic!("libnds9.a not found at {libnds_path:?}");
+        panic!("lib{lib_name}.a not found at {libnds_path:?}");
     }
 
     println!(
@@ -57,10 +61,14 @@ fn main() {
     println!(
         "cargo:rustc-link-lib=static={}",
         match std::env::var("PROFILE").unwrap().as_str() {
-            "debug" => "nds9d",
-            _ => "nds9",
+            "debug" => format!("{lib_name}d"),
+            _ => lib_name.to_string(),
         }
     );
+    println!("cargo:rerun-if-changed=build.rs");
+    if arm7 {
+        return;
+    }
 
     let system_flags = [
         "-mthumb",
@@ -115,5 +123,4 @@ fn main() {
         .expect("Unable to generate bindings")
         .write_to_file(bindings_folder.join("backgrounds.rs"))
         .expect("Couldn't write bindings!");
-    println!("cargo:rerun-if-changed=build.rs");
 }
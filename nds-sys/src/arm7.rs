@@ -0,0 +1,43 @@
+//! Hardware only the ARM7 can access, and the libnds7 functions to set it up.
+//!
+//! The hardware shared by both CPUs (timers, DMA, interrupts, keys, IPC FIFO) is in the
+//! other modules.
+
+pub mod power;
+pub mod rtc;
+pub mod sound;
+pub mod spi;
+pub mod touch;
+
+extern "C" {
+    /// Initializes the interrupt dispatcher of libnds
+    pub fn irqInit();
+    /// Initializes the FIFO system, to talk to the ARM9
+    pub fn fifoInit();
+    /// Answers the requests libnds9 makes to the ARM7 (power, sleep, SD card...)
+    pub fn installSystemFIFO();
+    /// Plays the sounds requested with the sound functions of libnds9
+    pub fn installSoundFIFO();
+    /// Copies the user settings from the firmware to `__DSi_PersonalData`
+    pub fn readUserSettings();
+    /// Reads the touch screen calibration from the user settings
+    pub fn touchInit();
+    /// Waits for about `duration * 4` cycles
+    pub fn swiDelay(duration: u32);
+}
+
+/// Keys that aren't in `REG_KEYINPUT`. Cleared bits are pressed keys
+pub const REG_KEYXY: *const u16 = 0x04000136 as _;
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct KeyXyBits: u16 {
+        const X = bit!(0);
+        const Y = bit!(1);
+        const DEBUG = bit!(3);
+        /// Cleared while the touch screen is touched
+        const PEN_UP = bit!(6);
+        /// Set while the lid is closed
+        const LID_CLOSED = bit!(7);
+    }
+}
@@ -0,0 +1,45 @@
+//! Registers of the power management chip, accessed through the SPI bus.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#dspowermanagementdevice).
+
+/// Set in the register index to read it instead of writing it
+pub const PM_READ: u8 = bit!(7);
+
+/// See [`PmControl`]
+pub const PM_CONTROL_REG: u8 = 0;
+/// Bit 0 is set when the battery is low
+pub const PM_BATTERY_REG: u8 = 1;
+/// Bit 0 enables the microphone amplifier
+pub const PM_AMPLIFIER_REG: u8 = 2;
+/// Bits 0-1 are the gain of the microphone amplifier
+pub const PM_GAIN_REG: u8 = 3;
+/// Backlight brightness of the DS Lite, bits 0-1
+pub const PM_BACKLIGHT_LEVEL_REG: u8 = 4;
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct PmControl: u8 {
+        const SOUND_AMP = bit!(0);
+        const SOUND_MUTE = bit!(1);
+        const BACKLIGHT_BOTTOM = bit!(2);
+        const BACKLIGHT_TOP = bit!(3);
+        /// Makes the power LED blink
+        const LED_BLINK = bit!(4);
+        /// Makes the LED blink fast, with [`LED_BLINK`](Self::LED_BLINK)
+        const LED_FAST = bit!(5);
+        /// Turns the console off
+        const SYSTEM_POWER_OFF = bit!(6);
+    }
+}
+
+pub mod registers {
+    /// Power of the ARM7 hardware. See [`PowerFlags2`](super::PowerFlags2)
+    pub const POWCNT2: *mut u16 = 0x04000304 as _;
+}
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct PowerFlags2: u16 {
+        const SOUND = bit!(0);
+        const WIFI = bit!(1);
+    }
+}
@@ -0,0 +1,36 @@
+//! Real time clock, driven by toggling the bits of [`REG_RTC`](registers::REG_RTC).
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#dsrealtimeclockrtc).
+
+pub mod registers {
+    /// See [`RtcBits`](super::RtcBits)
+    pub const REG_RTC: *mut u8 = 0x04000138 as _;
+}
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct RtcBits: u8 {
+        const DATA = bit!(0);
+        const CLOCK = bit!(1);
+        const SELECT = bit!(2);
+        /// Set when the ARM7 writes the data bit, cleared when it reads it
+        const DATA_OUT = bit!(4);
+        const CLOCK_OUT = bit!(5);
+        const SELECT_OUT = bit!(6);
+    }
+}
+
+/// Commands are `0110 CCC R`, sent most significant bit first
+pub const RTC_COMMAND: u8 = 0x60;
+/// Set in a command to read the register
+pub const RTC_READ: u8 = 1;
+pub const RTC_CMD_STATUS_1: u8 = 0 << 1;
+pub const RTC_CMD_STATUS_2: u8 = 1 << 1;
+/// 7 bytes: year, month, day, weekday, hour, minute, second, in BCD
+pub const RTC_CMD_DATE_TIME: u8 = 2 << 1;
+/// 3 bytes: hour, minute, second, in BCD
+pub const RTC_CMD_TIME: u8 = 3 << 1;
+
+/// In `STATUS_1`, set for the 24 hours mode
+pub const RTC_STATUS_24H: u8 = bit!(1);
+/// In the hour byte of the 12 hours mode, set after noon
+pub const RTC_HOUR_PM: u8 = bit!(6);
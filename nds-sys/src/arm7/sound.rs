@@ -0,0 +1,74 @@
+//! The 16 sound channels and the sound mixer.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#dssound).
+
+/// Amount of sound channels
+pub const SOUND_CHANNELS: usize = 16;
+
+/// Clock of the sound timers, half the bus clock
+pub const SOUND_CLOCK: u32 = crate::timer::BUS_CLOCK / 2;
+
+pub mod registers {
+    /// Master control, see [`SoundControl`](super::SoundControl)
+    pub const REG_SOUNDCNT: *mut u16 = 0x04000500 as _;
+    /// Output level with no sound, usually `0x200`
+    pub const REG_SOUNDBIAS: *mut u16 = 0x04000504 as _;
+}
+
+/// Control of channel `n`. See [`SoundFlags`]
+pub const fn sound_cr(n: usize) -> *mut u32 {
+    (0x04000400 + n * 0x10) as _
+}
+
+/// Source address of channel `n`
+pub const fn sound_sad(n: usize) -> *mut u32 {
+    (0x04000404 + n * 0x10) as _
+}
+
+/// Timer of channel `n`: the sample rate is `SOUND_CLOCK / (0x10000 - timer)`
+pub const fn sound_tmr(n: usize) -> *mut u16 {
+    (0x04000408 + n * 0x10) as _
+}
+
+/// Loop start of channel `n`, in words
+pub const fn sound_pnt(n: usize) -> *mut u16 {
+    (0x0400040A + n * 0x10) as _
+}
+
+/// Length of channel `n` after the loop start, in words
+pub const fn sound_len(n: usize) -> *mut u32 {
+    (0x0400040C + n * 0x10) as _
+}
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct SoundFlags: u32 {
+        /// Volume from 0 to 127, bits 0-6
+        const VOLUME_MASK = 0x7F;
+        /// Divides the volume by 1, 2, 4 or 16, bits 8-9
+        const VOLUME_DIV_MASK = 3 << 8;
+        /// Keeps the last sample after a one-shot sound ends
+        const HOLD = bit!(15);
+        /// Panning from 0 (left) to 127 (right), bits 16-22
+        const PAN_MASK = 0x7F << 16;
+        /// Duty of the PSG channels 8-13, bits 24-26
+        const DUTY_MASK = 7 << 24;
+        const REPEAT = 1 << 27;
+        const ONE_SHOT = 2 << 27;
+        const FORMAT_PCM8 = 0 << 29;
+        const FORMAT_PCM16 = 1 << 29;
+        const FORMAT_ADPCM = 2 << 29;
+        /// Square wave (channels 8-13) or noise (channels 14-15)
+        const FORMAT_PSG = 3 << 29;
+        /// Set to play, cleared by the hardware when a one-shot sound ends
+        const ENABLE = bit!(31);
+    }
+}
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct SoundControl: u16 {
+        /// Master volume from 0 to 127, bits 0-6
+        const VOLUME_MASK = 0x7F;
+        const ENABLE = bit!(15);
+    }
+}
@@ -0,0 +1,28 @@
+//! Serial bus to the power management chip, the firmware flash and the touch controller.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#dsserialperipheralinterfacebusspi).
+
+pub mod registers {
+    /// See [`SpiFlags`](super::SpiFlags)
+    pub const REG_SPICNT: *mut u16 = 0x040001C0 as _;
+    /// Writing starts the transfer of a byte, reading gets the byte received
+    pub const REG_SPIDATA: *mut u16 = 0x040001C2 as _;
+}
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct SpiFlags: u16 {
+        const BAUD_4MHZ = 0;
+        const BAUD_2MHZ = 1;
+        const BAUD_1MHZ = 2;
+        const BAUD_512KHZ = 3;
+        /// Set while a byte is being transferred
+        const BUSY = bit!(7);
+        const DEVICE_POWER = 0 << 8;
+        const DEVICE_FIRMWARE = 1 << 8;
+        const DEVICE_TOUCH = 2 << 8;
+        /// Keeps the device selected after the byte, for the next bytes of a command
+        const HOLD = bit!(11);
+        const IRQ = bit!(14);
+        const ENABLE = bit!(15);
+    }
+}
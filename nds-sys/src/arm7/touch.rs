@@ -0,0 +1,15 @@
+//! Touch screen controller (TSC2046), accessed through the SPI bus.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#dstouchscreencontrollertsc).
+
+/// Start bit of every command
+pub const TSC_START: u8 = bit!(7);
+pub const TSC_MEASURE_TEMP1: u8 = 0x84;
+pub const TSC_MEASURE_Y: u8 = 0x90;
+pub const TSC_MEASURE_BATTERY: u8 = 0xA4;
+pub const TSC_MEASURE_Z1: u8 = 0xB0;
+pub const TSC_MEASURE_Z2: u8 = 0xC0;
+pub const TSC_MEASURE_X: u8 = 0xD0;
+pub const TSC_MEASURE_AUX: u8 = 0xE4;
+pub const TSC_MEASURE_TEMP2: u8 = 0xF4;
+/// Set in a command for 8-bit results instead of 12-bit
+pub const TSC_8BIT: u8 = bit!(3);
@@ -50,7 +50,7 @@ pub struct TileMapEntry8 {
 ///
 /// `PPPPVHII_IIIIIIII`
 #[repr(C, packed)]
-#[derive(Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct TileMapEntry16 {
     data: u16,
 }
@@ -64,6 +64,14 @@ impl TileMapEntry16 {
         Self { data: 0 }
     }
 
+    /// Creates an entry from its raw `PPPPVHII_IIIIIIII` bits
+    pub const fn from_bits(data: u16) -> Self {
+        Self { data }
+    }
+    pub const fn bits(&self) -> u16 {
+        self.data
+    }
+
     pub fn set_index(&mut self, index: u16) {
         self.data = (self.data & !Self::INDEX_MASK) | (index & Self::INDEX_MASK);
     }
@@ -407,7 +415,7 @@ pub const SCREEN_OFFSET_MASK: u16 = 0b11111 << 8;
 pub const SCREEN_BASE_OFFSET: u16 = 8;
 pub const CHARACTER_BASE_OFFSET: u16 = 2;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[repr(C)]
 pub enum Layer {
     Layer0 = 0,
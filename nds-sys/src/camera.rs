@@ -0,0 +1,56 @@
+//! The cameras of the DSi, only available in DSi mode
+//!
+//! The cameras are configured by the ARM7 (through I2C), libnds sends it the
+//! commands. The ARM9 receives the pixels from [`REG_CAM_DATA`](registers::REG_CAM_DATA),
+//! usually with an NDMA channel started by the camera.
+
+/// `Camera` of libnds
+pub const CAMERA_NONE: u32 = 0;
+pub const CAMERA_INNER: u32 = 1;
+pub const CAMERA_OUTER: u32 = 2;
+
+/// Capture modes of [`cameraStartTransfer`]: 256x192 preview, or 640x480 capture
+pub const MCUREG_APT_SEQ_CMD_PREVIEW: u8 = 1;
+pub const MCUREG_APT_SEQ_CMD_CAPTURE: u8 = 2;
+
+extern "C" {
+    /// Powers the cameras on and initializes them. Returns `false` on failure
+    pub fn cameraInit() -> bool;
+    pub fn cameraDeinit() -> bool;
+    /// Activates one of the cameras (`CAMERA_*`). Returns `false` on failure
+    pub fn cameraSelect(camera: u32) -> bool;
+    pub fn cameraGetActive() -> u32;
+    /// Starts receiving frames in `buffer` with NDMA channel `ndma_id`
+    pub fn cameraStartTransfer(buffer: *mut u16, capture_mode: u8, ndma_id: u8);
+    pub fn cameraStopTransfer();
+    pub fn cameraTransferActive() -> bool;
+}
+
+pub mod registers {
+    pub const REG_CAM_MCNT: *mut u16 = 0x04004200 as _;
+    pub const REG_CAM_CNT: *mut u16 = 0x04004202 as _;
+    /// Pixels received from the camera, two per word
+    pub const REG_CAM_DATA: *mut u32 = 0x04004204 as _;
+    /// First pixel kept when trimming
+    pub const REG_CAM_SOFS: *mut u32 = 0x04004210 as _;
+    /// Last pixel kept when trimming
+    pub const REG_CAM_EOFS: *mut u32 = 0x04004214 as _;
+}
+
+bitflags! {
+    /// `REG_CAM_CNT`
+    #[derive(PartialEq, Eq, Copy, Clone)]
+    pub struct CameraControl: u16 {
+        /// Scanlines per NDMA request, minus 1
+        const LINES_MASK = 0xF;
+        const FIFO_EMPTY = bit!(4);
+        /// Read: pixels were lost. Write 1: clear the FIFO
+        const FIFO_OVERRUN = bit!(5);
+        const IRQ = bit!(11);
+        /// Convert the pixels to RGB555 instead of sending YUV422
+        const FORMAT_RGB = bit!(13);
+        /// Only keep the pixels between `REG_CAM_SOFS` and `REG_CAM_EOFS`
+        const TRIMMING = bit!(14);
+        const TRANSFER = bit!(15);
+    }
+}
@@ -0,0 +1,70 @@
+//! DSi (TWL) specific hardware
+//!
+//! The SCFG registers only exist in DSi mode, and only while the loader kept them
+//! unlocked ([`ScfgExt::SCFG_ACCESS`]). Otherwise they read as 0.
+
+use super::bitflags::bitflags;
+
+extern "C" {
+    /// `true` when running in DSi mode
+    pub static __dsimode: bool;
+    /// Switches the ARM9 to 133 MHz (`true`) or 67 MHz (`false`), returning the
+    /// previous speed
+    pub fn setCpuClock(speed: bool) -> bool;
+    /// Reads `length` bytes of the firmware at `address` (through the ARM7 on the ARM9)
+    pub fn readFirmware(address: u32, buffer: *mut core::ffi::c_void, length: u32) -> i32;
+}
+
+pub mod registers {
+    pub const REG_SCFG_ROM: *mut u16 = 0x04004000 as _;
+    pub const REG_SCFG_CLK: *mut u16 = 0x04004004 as _;
+    pub const REG_SCFG_EXT: *mut u32 = 0x04004008 as _;
+}
+
+/// Offset of the console type in the firmware header
+pub const FIRMWARE_CONSOLE_TYPE: u32 = 0x1D;
+
+/// Values of the console type of the firmware header
+pub const CONSOLE_TYPE_DS: u8 = 0xFF;
+pub const CONSOLE_TYPE_DS_LITE: u8 = 0x20;
+pub const CONSOLE_TYPE_DSI: u8 = 0x57;
+pub const CONSOLE_TYPE_IQUE_DS: u8 = 0x43;
+pub const CONSOLE_TYPE_IQUE_DS_LITE: u8 = 0x63;
+
+bitflags! {
+    /// `REG_SCFG_CLK`, as seen by the ARM9
+    pub struct ScfgClock: u16 {
+        /// The ARM9 runs at 133 MHz instead of 67 MHz
+        const ARM9_133MHZ = bit!(0);
+        const DSP = bit!(1);
+        const CAMERA = bit!(2);
+        const NEW_WRAM = bit!(7);
+        /// Clock output to the cameras
+        const CAMERA_EXTERNAL = bit!(8);
+    }
+}
+
+bitflags! {
+    /// `REG_SCFG_EXT`, as seen by the ARM9. The bits shared with the ARM7 are
+    /// `NDMA`, `RAM_LIMIT`, `NEW_WRAM` and `SCFG_ACCESS`
+    pub struct ScfgExt: u32 {
+        const REVISED_DMA = bit!(0);
+        const REVISED_GEOMETRY = bit!(1);
+        const REVISED_RENDERER = bit!(2);
+        const REVISED_2D = bit!(3);
+        const REVISED_DIVIDER = bit!(4);
+        const REVISED_CARD = bit!(7);
+        const EXTENDED_INTERRUPTS = bit!(8);
+        const EXTENDED_LCD = bit!(12);
+        const EXTENDED_VRAM = bit!(13);
+        /// Main RAM size: 0 or 1 for 4 MB, 2 for 16 MB, 3 for 32 MB
+        const RAM_LIMIT = 0b11 << 14;
+        const NDMA = bit!(16);
+        const CAMERA = bit!(17);
+        const DSP = bit!(18);
+        /// Access to the new shared WRAM registers (`MBK`)
+        const NEW_WRAM = bit!(25);
+        /// The SCFG registers can be read and written
+        const SCFG_ACCESS = bit!(31);
+    }
+}
@@ -0,0 +1,60 @@
+//! Color special effects, master brightness and mosaic registers of the 2D engines.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#lcdiocolorspecialeffects).
+
+/// Mosaic sizes (Main). BG size in bits 0-7, OBJ size in bits 8-15. Each size is `size - 1` in 4 bits, horizontal first
+pub const MOSAIC: *mut u16 = 0x0400004C as _;
+/// Color special effects selection (Main). See [`BlendTargets`] and [`BlendMode`]
+pub const BLDCNT: *mut u16 = 0x04000050 as _;
+/// Alpha blending coefficients (Main). First target in bits 0-4, second target in bits 8-12. `0..=16`
+pub const BLDALPHA: *mut u16 = 0x04000052 as _;
+/// Brightness coefficient (Main). Bits 0-4, `0..=16`
+pub const BLDY: *mut u16 = 0x04000054 as _;
+/// Master brightness (Main). Factor in bits 0-4 (`0..=16`), [`MasterBrightMode`] in bits 14-15
+pub const MASTER_BRIGHT: *mut u16 = 0x0400006C as _;
+
+/// Mosaic sizes (Sub). See [`MOSAIC`]
+pub const DB_MOSAIC: *mut u16 = 0x0400104C as _;
+/// Color special effects selection (Sub). See [`BLDCNT`]
+pub const DB_BLDCNT: *mut u16 = 0x04001050 as _;
+/// Alpha blending coefficients (Sub). See [`BLDALPHA`]
+pub const DB_BLDALPHA: *mut u16 = 0x04001052 as _;
+/// Brightness coefficient (Sub). See [`BLDY`]
+pub const DB_BLDY: *mut u16 = 0x04001054 as _;
+/// Master brightness (Sub). See [`MASTER_BRIGHT`]
+pub const DB_MASTER_BRIGHT: *mut u16 = 0x0400106C as _;
+
+bitflags! {
+    /// Layers that take part in a color special effect.
+    /// First targets use bits 0-5 of [`BLDCNT`], second targets bits 8-13
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct BlendTargets: u8 {
+        const BG0 = bit!(0);
+        const BG1 = bit!(1);
+        const BG2 = bit!(2);
+        const BG3 = bit!(3);
+        const OBJECTS = bit!(4);
+        const BACKDROP = bit!(5);
+    }
+}
+
+/// Color special effect, bits 6-7 of [`BLDCNT`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum BlendMode {
+    None = 0b00 << 6,
+    /// Blends the first and second targets, weighted by [`BLDALPHA`]
+    Alpha = 0b01 << 6,
+    /// Increases the brightness of the first targets by [`BLDY`]
+    Brighten = 0b10 << 6,
+    /// Decreases the brightness of the first targets by [`BLDY`]
+    Darken = 0b11 << 6,
+}
+
+/// Master brightness mode, bits 14-15 of [`MASTER_BRIGHT`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum MasterBrightMode {
+    Disabled = 0b00 << 14,
+    Up = 0b01 << 14,
+    Down = 0b10 << 14,
+}
@@ -0,0 +1,18 @@
+//! CPU exception handling of libnds
+
+extern "C" {
+    /// Sets the function called when the CPU raises an exception (aborts and undefined
+    /// instructions). It runs in the mode of the exception, on a small dedicated stack.
+    pub fn setExceptionHandler(handler: Option<unsafe extern "C" fn()>);
+
+    /// Handler showing the libnds exception screen
+    pub fn defaultExceptionHandler();
+
+    /// `r0` to `r15` at the time of the exception. `r15` holds the return address
+    /// of the exception (`lr` of the exception mode)
+    pub static exceptionRegisters: [u32; 16];
+}
+
+/// Saved program status register of the code that caused the exception,
+/// stored by the BIOS before calling the handler
+pub const EXCEPTION_SPSR: *const u32 = 0x02FFFD90 as _;
@@ -0,0 +1,76 @@
+//! FIFO messaging between the ARM9 and the ARM7, built by libnds on the IPC FIFO.
+//!
+//! Messages are sent on one of 16 channels, as 32-bit values, addresses of main RAM,
+//! or data messages of several bytes. Received messages are queued by libnds until
+//! they're read, or given to the handler of their channel.
+
+use core::ffi::{c_int, c_void};
+
+/// Power management
+pub const FIFO_PM: c_int = 0;
+pub const FIFO_SOUND: c_int = 1;
+/// Used by libnds for its own messages
+pub const FIFO_SYSTEM: c_int = 2;
+pub const FIFO_MAXMOD: c_int = 3;
+pub const FIFO_DSWIFI: c_int = 4;
+/// SD card of the DSi
+pub const FIFO_STORAGE: c_int = 5;
+pub const FIFO_FIRMWARE: c_int = 6;
+pub const FIFO_CAMERA: c_int = 7;
+/// First channel free for the program. The user channels go up to [`FIFO_USER_08`]
+pub const FIFO_USER_01: c_int = 8;
+pub const FIFO_USER_08: c_int = 15;
+/// Amount of channels
+pub const FIFO_NUM_CHANNELS: c_int = 16;
+/// Data messages must be shorter than this, in bytes
+pub const FIFO_MAX_DATA_BYTES: c_int = 128;
+
+/// Called with each 32-bit value received on a channel. The value is removed from the queue
+pub type FifoValue32HandlerFunc = Option<unsafe extern "C" fn(value32: u32, userdata: *mut c_void)>;
+/// Called with each address received on a channel. The address is removed from the queue
+pub type FifoAddressHandlerFunc =
+    Option<unsafe extern "C" fn(address: *mut c_void, userdata: *mut c_void)>;
+/// Called with the size of each data message received on a channel. The handler must read
+/// the message with [`fifoGetDatamsg`]: libnds discards it when the handler returns
+pub type FifoDatamsgHandlerFunc =
+    Option<unsafe extern "C" fn(num_bytes: c_int, userdata: *mut c_void)>;
+
+extern "C" {
+    /// Returns `false` if the message couldn't be queued
+    pub fn fifoSendAddress(channel: c_int, address: *mut c_void) -> bool;
+    /// Returns `false` if the message couldn't be queued
+    pub fn fifoSendValue32(channel: c_int, value32: u32) -> bool;
+    /// Returns `false` if the message couldn't be queued, but also if the channel is
+    /// invalid, or if `num_bytes` is 0 or at least [`FIFO_MAX_DATA_BYTES`]
+    pub fn fifoSendDatamsg(channel: c_int, num_bytes: c_int, data_array: *const u8) -> bool;
+
+    pub fn fifoSetAddressHandler(
+        channel: c_int,
+        newhandler: FifoAddressHandlerFunc,
+        userdata: *mut c_void,
+    ) -> bool;
+    pub fn fifoSetValue32Handler(
+        channel: c_int,
+        newhandler: FifoValue32HandlerFunc,
+        userdata: *mut c_void,
+    ) -> bool;
+    pub fn fifoSetDatamsgHandler(
+        channel: c_int,
+        newhandler: FifoDatamsgHandlerFunc,
+        userdata: *mut c_void,
+    ) -> bool;
+
+    pub fn fifoCheckAddress(channel: c_int) -> bool;
+    pub fn fifoCheckValue32(channel: c_int) -> bool;
+    pub fn fifoCheckDatamsg(channel: c_int) -> bool;
+    /// Size of the next data message, or -1 if there's none
+    pub fn fifoCheckDatamsgLength(channel: c_int) -> c_int;
+
+    /// Returns null if there's no address
+    pub fn fifoGetAddress(channel: c_int) -> *mut c_void;
+    /// Returns 0 if there's no value
+    pub fn fifoGetValue32(channel: c_int) -> u32;
+    /// Copies the next data message to `destbuffer`, returning its size, or -1 if
+    /// there's none. The rest of the message is lost if the buffer is too small
+    pub fn fifoGetDatamsg(channel: c_int, buffersize: c_int, destbuffer: *mut u8) -> c_int;
+}
@@ -0,0 +1,112 @@
+//! 3D geometry engine registers and command opcodes.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#ds3dgeometrycommands) for the meaning of each command.
+
+/// Geometry command FIFO. Accepts both packed and unpacked commands
+pub const GXFIFO: *mut u32 = 0x04000400 as _;
+/// Geometry engine status register
+pub const GXSTAT: *mut u32 = 0x04000600 as _;
+
+bitflags! {
+    /// Bits of [`GXSTAT`]
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct GxStatus: u32 {
+        /// Set while a box, position or vector test is in progress
+        const TEST_BUSY = bit!(0);
+        /// Result of the last box test
+        const BOX_TEST_RESULT = bit!(1);
+        /// Amount of entries in the command FIFO
+        const FIFO_COUNT_MASK = 0b1_11111111 << 16;
+        /// Set when the command FIFO is full
+        const FIFO_FULL = bit!(24);
+        /// Set when the command FIFO is less than half full
+        const FIFO_LESS_HALF = bit!(25);
+        /// Set when the command FIFO is empty
+        const FIFO_EMPTY = bit!(26);
+        /// Set while the geometry engine is executing commands
+        const BUSY = bit!(27);
+        /// Selects when the GXFIFO IRQ fires
+        const IRQ_MASK = 0b11 << 30;
+
+        // all bits have meaning
+        const _ = 0xFFFF_FFFFu32;
+    }
+}
+
+/// Geometry commands opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Command {
+    Nop = 0x00,
+    MtxMode = 0x10,
+    MtxPush = 0x11,
+    MtxPop = 0x12,
+    MtxStore = 0x13,
+    MtxRestore = 0x14,
+    MtxIdentity = 0x15,
+    MtxLoad4x4 = 0x16,
+    MtxLoad4x3 = 0x17,
+    MtxMult4x4 = 0x18,
+    MtxMult4x3 = 0x19,
+    MtxMult3x3 = 0x1A,
+    MtxScale = 0x1B,
+    MtxTrans = 0x1C,
+    Color = 0x20,
+    Normal = 0x21,
+    TexCoord = 0x22,
+    Vtx16 = 0x23,
+    Vtx10 = 0x24,
+    VtxXY = 0x25,
+    VtxXZ = 0x26,
+    VtxYZ = 0x27,
+    VtxDiff = 0x28,
+    PolygonAttr = 0x29,
+    TexImageParam = 0x2A,
+    PlttBase = 0x2B,
+    DifAmb = 0x30,
+    SpeEmi = 0x31,
+    LightVector = 0x32,
+    LightColor = 0x33,
+    Shininess = 0x34,
+    BeginVtxs = 0x40,
+    EndVtxs = 0x41,
+    SwapBuffers = 0x50,
+    Viewport = 0x60,
+    BoxTest = 0x70,
+    PosTest = 0x71,
+    VecTest = 0x72,
+}
+impl Command {
+    /// Amount of 32 bit parameters this command takes
+    pub const fn param_count(self) -> usize {
+        match self {
+            Command::Nop | Command::MtxPush | Command::MtxIdentity | Command::EndVtxs => 0,
+            Command::MtxLoad4x4 | Command::MtxMult4x4 => 16,
+            Command::MtxLoad4x3 | Command::MtxMult4x3 => 12,
+            Command::MtxMult3x3 => 9,
+            Command::MtxScale | Command::MtxTrans | Command::BoxTest => 3,
+            Command::Vtx16 | Command::PosTest => 2,
+            Command::Shininess => 32,
+            _ => 1,
+        }
+    }
+}
+
+/// Primitive types accepted by [`Command::BeginVtxs`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Primitive {
+    Triangles = 0,
+    Quads = 1,
+    TriangleStrip = 2,
+    QuadStrip = 3,
+}
+
+/// Matrix modes accepted by [`Command::MtxMode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MatrixMode {
+    Projection = 0,
+    Position = 1,
+    PositionVector = 2,
+    Texture = 3,
+}
@@ -2,9 +2,21 @@ extern "C" {
     pub fn scanKeys();
     pub fn keysDown() -> u32;
     pub fn keysHeld() -> u32;
+    pub fn keysCurrent() -> u32;
     pub fn touchRead(data: *mut TouchPosition);
 }
 
+/// State of the keys readable by the ARM9 (all of [`KeypadBits`] up to `L`).
+/// A bit is cleared while its key is pressed
+pub const REG_KEYINPUT: *const u16 = 0x04000130 as _;
+/// Keys that fire the keypad interrupt (bits 0-9, as in [`REG_KEYINPUT`]),
+/// with [`KEYCNT_IRQ`] and [`KEYCNT_AND`]
+pub const REG_KEYCNT: *mut u16 = 0x04000132 as _;
+/// Enables the keypad interrupt
+pub const KEYCNT_IRQ: u16 = bit!(14);
+/// Fire the interrupt when all the keys are pressed, instead of any of them
+pub const KEYCNT_AND: u16 = bit!(15);
+
 #[repr(u32)]
 pub enum KeypadBits {
     A = bit!(0),
@@ -3,10 +3,13 @@ extern "C" {
     pub fn swiIntrWait(waitForSet: u32, flags: u32);
     pub fn irqEnable(irq: u32);
     pub fn irqDisable(irq: u32);
+    pub fn irqSet(irq: u32, handler: Option<unsafe extern "C" fn()>);
 }
 
 pub static mut REG_IE: *mut u32 = 0x04000210 as *mut _;
 pub static mut REG_IME: *mut u32 = 0x04000208 as *mut _;
+/// Interrupts waiting to be handled. Writing a bit acknowledges it
+pub static mut REG_IF: *mut u32 = 0x04000214 as *mut _;
 
 bitflags! {
     #[derive(PartialEq, Eq, Copy, Clone)]
@@ -35,5 +38,10 @@ bitflags! {
         const SPI = bit!(23);
         const WIFI = bit!(24);
         const ALL = bit!(25) - 1;
+        /// New DMA channels, DSi mode only
+        const NDMA0 = bit!(28);
+        const NDMA1 = bit!(29);
+        const NDMA2 = bit!(30);
+        const NDMA3 = bit!(31);
     }
 }
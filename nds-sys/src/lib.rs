@@ -1,5 +1,5 @@
 #![no_std]
-#![feature(adt_const_params)]
+#![cfg_attr(not(feature = "arm7"), feature(adt_const_params))]
 #![allow(clippy::unusual_byte_groupings)]
 
 #[macro_use]
@@ -11,12 +11,32 @@ macro_rules! bit {
     };
 }
 
+#[cfg(feature = "arm7")]
+pub mod arm7;
+#[cfg(not(feature = "arm7"))]
 pub mod background;
+#[cfg(not(feature = "arm7"))]
+pub mod camera;
+#[cfg(not(feature = "arm7"))]
 pub mod console;
 pub mod debug;
 pub mod dma;
+pub mod dsi;
+#[cfg(not(feature = "arm7"))]
+pub mod effects;
+#[cfg(not(feature = "arm7"))]
+pub mod exception;
+pub mod fifo;
+#[cfg(not(feature = "arm7"))]
+pub mod gx;
 pub mod input;
 pub mod interrupts;
+pub mod ndma;
+#[cfg(not(feature = "arm7"))]
 pub mod sprite;
 pub mod system;
+pub mod timer;
+#[cfg(not(feature = "arm7"))]
 pub mod video;
+#[cfg(not(feature = "arm7"))]
+pub mod window;
@@ -0,0 +1,100 @@
+//! The new DMA of the DSi, only available in DSi mode
+//!
+//! Each channel has 7 registers: source, destination, total length, block length,
+//! block interval, fill value and control. Lengths are in words.
+
+#![allow(clippy::unusual_byte_groupings)]
+
+/// Global control of the four channels (arbitration)
+pub const REG_NDMAGCNT: *mut u32 = 0x04004100 as _;
+
+const NDMA_BASE: usize = 0x04004104;
+const NDMA_STRIDE: usize = 0x1C;
+
+bitflags! {
+    /// `NDMAxCNT`
+    #[derive(PartialEq, Eq, Copy, Clone)]
+    pub struct Flags: u32 {
+        const INC_DST = 0 << 10;
+        const DEC_DST = 1 << 10;
+        const FIX_DST = 2 << 10;
+        const DST_MASK = 3 << 10;
+        /// Reload the destination address after each repetition
+        const RELOAD_DST = bit!(12);
+
+        const INC_SRC = 0 << 13;
+        const DEC_SRC = 1 << 13;
+        const FIX_SRC = 2 << 13;
+        /// Read the fill register instead of the source
+        const FILL_SRC = 3 << 13;
+        const SRC_MASK = 3 << 13;
+        /// Reload the source address after each repetition
+        const RELOAD_SRC = bit!(15);
+
+        /// Words per block, as a power of 2
+        const BLOCK_SIZE_MASK = 0xF << 16;
+
+        const START_TIMER0 = 0x00 << 24;
+        const START_TIMER1 = 0x01 << 24;
+        const START_TIMER2 = 0x02 << 24;
+        const START_TIMER3 = 0x03 << 24;
+        const START_CARD = 0x04 << 24;
+        const START_AT_VBLANK = 0x06 << 24;
+        const START_AT_HBLANK = 0x07 << 24;
+        const SYNC_WITH_DISPLAY = 0x08 << 24;
+        const MAIN_MEM = 0x09 << 24;
+        const GEO_CMD_FIFO = 0x0A << 24;
+        const CAMERA = 0x0B << 24;
+        const START_IMM = 0x10 << 24;
+        const START_MASK = 0x1F << 24;
+
+        /// Repeat until stopped instead of stopping after the total length
+        const REPEAT = bit!(29);
+        const INT_REQ = bit!(30);
+        const ENABLE = bit!(31);
+        const ENABLED = bit!(31);
+    }
+}
+
+/// Largest block length, in words
+pub const MAX_BLOCK_WORDS: usize = 0xFF_FFFF;
+
+/// The four NDMA channels. Like the legacy DMA, lower channels have a higher priority
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Channel {
+    /// Highest priority
+    Ch0 = 0,
+    Ch1 = 1,
+    Ch2 = 2,
+    /// Lowest priority
+    Ch3 = 3,
+}
+
+/// Registers of a channel
+pub struct Registers {
+    pub src: *mut u32,
+    pub dst: *mut u32,
+    /// Total length in words, 0 for no limit when repeating
+    pub total_len: *mut u32,
+    /// Block length in words, 0 for the maximum
+    pub block_len: *mut u32,
+    /// Timer between blocks
+    pub interval: *mut u32,
+    pub fill: *mut u32,
+    pub cr: *mut u32,
+}
+
+/// Returns the addresses of the registers of `ch`
+pub const fn calc_registers(ch: Channel) -> Registers {
+    let base = NDMA_BASE + ch as usize * NDMA_STRIDE;
+    Registers {
+        src: base as _,
+        dst: (base + 0x04) as _,
+        total_len: (base + 0x08) as _,
+        block_len: (base + 0x0C) as _,
+        interval: (base + 0x10) as _,
+        fill: (base + 0x14) as _,
+        cr: (base + 0x18) as _,
+    }
+}
@@ -18,3 +18,21 @@ bitflags! {
         const POWER_LCD = bit!(0);
     }
 }
+
+/// Value of [`Argv::magic`] when the loader passed arguments
+pub const ARGV_MAGIC: i32 = 0x5f617267;
+
+/// Arguments passed to the program by the loader (`struct __argv`)
+#[repr(C)]
+pub struct Argv {
+    pub magic: i32,
+    pub command_line: *mut core::ffi::c_char,
+    pub length: i32,
+    pub argc: i32,
+    pub argv: *mut *mut core::ffi::c_char,
+    pub dummy: i32,
+    pub host: u32,
+}
+
+/// Where the loader puts the [`Argv`] (`__system_argv`)
+pub const SYSTEM_ARGV: *const Argv = 0x02FFFE70 as _;
@@ -0,0 +1,35 @@
+//! Hardware timers. Each of the 4 timers counts up from a reload value and fires
+//! an interrupt when it overflows.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#dstimers).
+
+/// Frequency of the bus clock driving the timers, in Hz
+pub const BUS_CLOCK: u32 = 33_513_982;
+
+/// Counter of timer `n` when read, reload value when written
+pub const fn timer_data(n: usize) -> *mut u16 {
+    (0x04000100 + n * 4) as _
+}
+
+/// Control register of timer `n`. See [`TimerFlags`]
+pub const fn timer_cr(n: usize) -> *mut u16 {
+    (0x04000102 + n * 4) as _
+}
+
+bitflags! {
+    #[derive(PartialEq, Eq, Copy, Clone, Debug)]
+    pub struct TimerFlags: u16 {
+        /// Counts at the bus clock
+        const DIV_1 = 0;
+        /// Counts every 64 cycles of the bus clock
+        const DIV_64 = 1;
+        /// Counts every 256 cycles of the bus clock
+        const DIV_256 = 2;
+        /// Counts every 1024 cycles of the bus clock
+        const DIV_1024 = 3;
+        /// Counts when the previous timer overflows, ignoring the divider
+        const CASCADE = bit!(2);
+        /// Fires an interrupt on overflow
+        const IRQ = bit!(6);
+        const ENABLE = bit!(7);
+    }
+}
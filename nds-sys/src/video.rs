@@ -48,6 +48,15 @@ pub const VRAM_D: *mut u16 = 0x6860000 as _;
 pub const BG_PALETTE: *mut u16 = 0x05000000 as _;
 /// Background palette (Sub)
 pub const BG_PALETTE_SUB: *mut u16 = 0x05000400 as _;
+/// Sprite palette (Main)
+pub const SPRITE_PALETTE: *mut u16 = 0x05000200 as _;
+/// Sprite palette (Sub)
+pub const SPRITE_PALETTE_SUB: *mut u16 = 0x05000600 as _;
+
+/// Sprite tiles (Main)
+pub const SPRITE_GFX: *mut u16 = 0x06400000 as _;
+/// Sprite tiles (Sub)
+pub const SPRITE_GFX_SUB: *mut u16 = 0x06600000 as _;
 
 pub enum DisplayMode {
     Off = 0b00_00_0000000000000000,
@@ -76,6 +85,13 @@ bitflags! {
         /// MAIN ONLY: 64kB offset for tile data
         const TILE_BASE_MASK = 0b111 << 24;
         const DISPLAY_SRC_MASK = 0b11_11 << 16;
+        /// Set to enable the object window
+        const OBJ_WINDOW = bit!(15);
+        /// Set to enable window 1
+        const WIN1 = bit!(14);
+        /// Set to enable window 0
+        const WIN0 = bit!(13);
+        const WINDOWS_MASK = 0b111 << 13;
         /// Set to show objects
         const OBJECTS = bit!(12);
         /// Set to show background 3
@@ -0,0 +1,42 @@
+//! Window registers of the 2D engines.
+//! See [gbatek](http://problemkaputt.de/gbatek.htm#lcdiowindowfeature).
+
+/// Window 0 horizontal bounds (Main). Left edge in bits 8-15, right edge (exclusive) in bits 0-7
+pub const WIN0H: *mut u16 = 0x04000040 as _;
+/// Window 1 horizontal bounds (Main). Left edge in bits 8-15, right edge (exclusive) in bits 0-7
+pub const WIN1H: *mut u16 = 0x04000042 as _;
+/// Window 0 vertical bounds (Main). Top edge in bits 8-15, bottom edge (exclusive) in bits 0-7
+pub const WIN0V: *mut u16 = 0x04000044 as _;
+/// Window 1 vertical bounds (Main). Top edge in bits 8-15, bottom edge (exclusive) in bits 0-7
+pub const WIN1V: *mut u16 = 0x04000046 as _;
+/// Layers shown inside window 0 (bits 0-7) and window 1 (bits 8-15) (Main)
+pub const WININ: *mut u16 = 0x04000048 as _;
+/// Layers shown outside every window (bits 0-7) and inside the object window (bits 8-15) (Main)
+pub const WINOUT: *mut u16 = 0x0400004A as _;
+
+/// Window 0 horizontal bounds (Sub). See [`WIN0H`]
+pub const DB_WIN0H: *mut u16 = 0x04001040 as _;
+/// Window 1 horizontal bounds (Sub). See [`WIN1H`]
+pub const DB_WIN1H: *mut u16 = 0x04001042 as _;
+/// Window 0 vertical bounds (Sub). See [`WIN0V`]
+pub const DB_WIN0V: *mut u16 = 0x04001044 as _;
+/// Window 1 vertical bounds (Sub). See [`WIN1V`]
+pub const DB_WIN1V: *mut u16 = 0x04001046 as _;
+/// Layers shown inside the windows (Sub). See [`WININ`]
+pub const DB_WININ: *mut u16 = 0x04001048 as _;
+/// Layers shown outside the windows (Sub). See [`WINOUT`]
+pub const DB_WINOUT: *mut u16 = 0x0400104A as _;
+
+bitflags! {
+    /// Layers enabled in a region of the screen. Each window uses one byte of [`WININ`] or [`WINOUT`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WindowLayers: u8 {
+        const BG0 = bit!(0);
+        const BG1 = bit!(1);
+        const BG2 = bit!(2);
+        const BG3 = bit!(3);
+        const OBJECTS = bit!(4);
+        /// Set to allow color special effects (blending, fading)
+        const EFFECTS = bit!(5);
+    }
+}
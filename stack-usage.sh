@@ -0,0 +1,29 @@
+#!/bin/sh
+# Report the stack usage of each function of a program,
+# biggest first.
+# The stacks are in DTCM (16 KiB shared with `#[dtcm]` statics),
+# so big frames are worth knowing about.
+#
+# Usage: ./stack-usage.sh <binary name> [limit]
+# Functions using more than `limit` bytes (default 1024) are marked with `!`.
+# Needs `llvm-readobj` (from LLVM, or `rustup component add llvm-tools`).
+
+[ -n "$1" ] || { echo "Usage: $0 <binary name> [limit]"; exit 1; }
+bin="$1"
+limit="${2:-1024}"
+
+READOBJ=$(command -v llvm-readobj || find "$(rustc --print sysroot)" -name llvm-readobj | head -n 1)
+[ -n "$READOBJ" ] || { echo "llvm-readobj not found"; exit 1; }
+
+# The stack sizes are only emitted into the object files, the linker discards them
+RUSTFLAGS="$RUSTFLAGS -Z emit-stack-sizes --emit=obj" \
+    cargo build --release --bin "$bin" || exit 1
+
+find target -path "*release/deps/*" -name "$(echo "$bin" | tr - _)-*.o" |
+xargs "$READOBJ" --stack-sizes --demangle 2>/dev/null |
+awk -v limit="$limit" '
+    /Functions:/ { sub(/.*Functions: \[/, ""); sub(/\]$/, ""); name = $0 }
+    /Size:/      { print $2, name }
+' |
+sort -n -r -u |
+awk -v limit="$limit" '{ printf "%s %6d %s\n", ($1 > limit ? "!" : " "), $1, substr($0, index($0, " ") + 1) }'